@@ -0,0 +1,117 @@
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+// Dependencies whose versions are reported by the version sub-command
+const REPORTED_DEPENDENCIES: [&str; 4] =
+    ["quadio-core", "hound", "cpal", "rubato"];
+
+fn main() {
+    let manifest_dir =
+        PathBuf::from(env::var_os("CARGO_MANIFEST_DIR").unwrap());
+
+    println!(
+        "cargo:rustc-env=QUADIO_GIT_COMMIT={}",
+        git_commit(&manifest_dir)
+    );
+    println!("cargo:rustc-env=QUADIO_FEATURES={}", features().join(","));
+    println!(
+        "cargo:rustc-env=QUADIO_DEPENDENCIES={}",
+        dependency_versions(&manifest_dir).join(",")
+    );
+}
+
+// Commit from $QUADIO_GIT_COMMIT if set, as when building from a source
+// tarball, otherwise from git
+fn git_commit(manifest_dir: &Path) -> String {
+    println!("cargo:rerun-if-env-changed=QUADIO_GIT_COMMIT");
+
+    if let Some(commit) = env::var("QUADIO_GIT_COMMIT")
+        .ok()
+        .filter(|commit| !commit.is_empty())
+    {
+        return commit;
+    }
+
+    let git = |args: &[&str]| {
+        Command::new("git")
+            .args(args)
+            .current_dir(manifest_dir)
+            .output()
+            .ok()
+            .filter(|output| output.status.success())
+            .and_then(|output| String::from_utf8(output.stdout).ok())
+            .map(|stdout| String::from(stdout.trim()))
+    };
+
+    for path in ["HEAD", "index"] {
+        if let Some(path) = git(&["rev-parse", "--git-path", path]) {
+            println!(
+                "cargo:rerun-if-changed={}",
+                manifest_dir.join(path).display()
+            );
+        }
+    }
+
+    git(&["rev-parse", "--short=12", "HEAD"]).unwrap_or(String::from("unknown"))
+}
+
+fn features() -> Vec<String> {
+    let mut features = env::vars()
+        .filter_map(|(key, _)| {
+            key.strip_prefix("CARGO_FEATURE_")
+                .map(|feature| feature.to_lowercase().replace('_', "-"))
+        })
+        .collect::<Vec<_>>();
+    features.sort();
+    features
+}
+
+// "name version" of each reported dependency found in the workspace's
+// Cargo.lock, or the crate's own if built alone
+fn dependency_versions(manifest_dir: &Path) -> Vec<String> {
+    let lock = [
+        manifest_dir.join("../Cargo.lock"),
+        manifest_dir.join("Cargo.lock"),
+    ]
+    .into_iter()
+    .find(|path| path.exists());
+
+    let Some(lock) = lock else {
+        return vec![];
+    };
+
+    println!("cargo:rerun-if-changed={}", lock.display());
+    let text = fs::read_to_string(lock).unwrap_or_default();
+    let mut versions = vec![];
+
+    for package in text.split("[[package]]") {
+        let field = |key: &str| {
+            package.lines().find_map(|line| {
+                line.trim()
+                    .strip_prefix(key)
+                    .and_then(|rest| rest.trim().strip_prefix('='))
+                    .map(|value| String::from(value.trim().trim_matches('"')))
+            })
+        };
+
+        if let (Some(name), Some(version)) = (field("name"), field("version")) {
+            if REPORTED_DEPENDENCIES.contains(&&name[..])
+                && !versions.iter().any(|(known, _)| known == &name)
+            {
+                versions.push((name, version));
+            }
+        }
+    }
+
+    REPORTED_DEPENDENCIES
+        .iter()
+        .filter_map(|&name| {
+            versions
+                .iter()
+                .find(|(known, _)| known == name)
+                .map(|(name, version)| format!("{} {}", name, version))
+        })
+        .collect()
+}
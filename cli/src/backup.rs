@@ -0,0 +1,63 @@
+use std::ffi::OsString;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Name of the `index`th backup of `path`: `<name>.bak` when only one is
+/// kept, otherwise `<name>.bak1` (newest) through `<name>.bak<N>`
+pub fn backup_path(path: &Path, index: Option<usize>) -> PathBuf {
+    let mut name = OsString::from(path.as_os_str());
+    name.push(".bak");
+
+    if let Some(index) = index {
+        name.push(index.to_string());
+    }
+
+    PathBuf::from(name)
+}
+
+/// Move an existing file at `path` out of the way before it is overwritten,
+/// keeping up to `keep` previous versions.  With `keep` of 1 the backup is
+/// `<name>.bak`; otherwise older backups are shifted along the numbered series
+/// and the oldest is removed.  Returns where the file was moved, or None if
+/// there was nothing to back up.
+pub fn backup(path: &Path, keep: usize) -> Result<Option<PathBuf>, String> {
+    if keep == 0 || !path.exists() {
+        return Ok(None);
+    }
+
+    let newest = if keep == 1 {
+        backup_path(path, None)
+    } else {
+        let oldest = backup_path(path, Some(keep));
+
+        if oldest.exists() {
+            fs::remove_file(&oldest)
+                .map_err(|e| format!("{}: {}", oldest.display(), e))?;
+        }
+
+        for index in (1..keep).rev() {
+            let from = backup_path(path, Some(index));
+
+            if from.exists() {
+                move_file(&from, &backup_path(path, Some(index + 1)))?;
+            }
+        }
+
+        backup_path(path, Some(1))
+    };
+
+    move_file(path, &newest)?;
+    Ok(Some(newest))
+}
+
+// Rename, falling back to copy and delete where a rename isn't possible, e.g.
+// across devices
+fn move_file(from: &Path, to: &Path) -> Result<(), String> {
+    if fs::rename(from, to).is_ok() {
+        return Ok(());
+    }
+
+    fs::copy(from, to)
+        .and_then(|_| fs::remove_file(from))
+        .map_err(|e| format!("{}: {}", from.display(), e))
+}
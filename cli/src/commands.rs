@@ -0,0 +1,1009 @@
+use crate::{CommandKind, FLAGS, OPTIONAL_VALUES};
+use crate::{PLAYBACK_ARGUMENTS, WRITE_ARGUMENTS};
+
+// Column usage text is wrapped before
+const USAGE_WIDTH: usize = 79;
+
+// Arguments given as times, or ranges or plans of them
+const TIME_ARGUMENTS: [&str; 14] = [
+    "start",
+    "end",
+    "duration",
+    "at",
+    "end-marker",
+    "snap",
+    "min-length",
+    "range",
+    "before",
+    "after",
+    "blend",
+    "blend-preview",
+    "seam",
+    "plan",
+];
+
+// How times are given, following a list of the arguments taking them
+const TIME_GRAMMAR: &str = "are given in non-zero integer numbers of \
+    samples.  A suffix can be provided to use rational-valued times in the \
+    desired unit, seconds or milliseconds, e.g. '0.5s' for seconds or \
+    '111.1ms' for milliseconds, or '%' for a percentage of the file's \
+    length, e.g. '25%'.  END, or LAST, is the end of the file, and \
+    END-<TIME> counts back from it, e.g. 'END-1.5s'; START+<TIME> is the \
+    same as <TIME>.  Files and times are limited to 4294967295 samples, the \
+    most a cue point can address.";
+
+/// A sub-command as both parsed and described, so the arguments its help
+/// lists are the ones it accepts
+pub struct CommandSpec {
+    pub kind: CommandKind,
+    pub name: &'static str,
+
+    /// Forms the sub-command is given in, each starting with its name
+    pub usage: &'static [&'static str],
+    pub description: &'static str,
+
+    /// Arguments only some sub-commands take, besides inputs and outputs
+    pub arguments: &'static [&'static str],
+
+    /// Lists of arguments taken by every sub-command of a kind, such as
+    /// those that write files
+    pub shared: &'static [&'static [&'static str]],
+
+    /// Arguments following the program name, each a complete invocation
+    pub examples: &'static [&'static str],
+}
+
+impl CommandSpec {
+    // Arguments the sub-command accepts, besides inputs and outputs, in the
+    // order they are declared
+    fn accepted(&self) -> impl Iterator<Item = &'static str> + '_ {
+        self.arguments
+            .iter()
+            .chain(self.shared.iter().flat_map(|arguments| arguments.iter()))
+            .copied()
+            .filter(|arg| self.kind.accepts(arg))
+    }
+
+    // What an argument's value is written as in the sub-command's usage,
+    // e.g. <TIME> for -start, or <VALUE> where its usage doesn't say
+    fn value_of(&self, arg: &str) -> &'static str {
+        let named = [format!("-{}=", arg), format!("-{}[=", arg)];
+
+        self.usage
+            .iter()
+            .find_map(|form| {
+                named.iter().find_map(|named| {
+                    form.match_indices(&named[..])
+                        .find(|&(at, _)| {
+                            at == 0 || form[..at].ends_with([' ', '[', '|'])
+                        })
+                        .map(|(at, _)| &form[at + named.len()..])
+                })
+            })
+            .and_then(|value| value.split([' ', ']']).next())
+            .filter(|value| !value.is_empty())
+            .unwrap_or("<VALUE>")
+    }
+}
+
+pub const COMMANDS: [CommandSpec; 35] = [
+    CommandSpec {
+        kind: CommandKind::Help,
+        name: "help",
+        usage: &["help [<sub-command>]"],
+        description:
+            "Print usage, or with a sub-command, its usage, the arguments \
+            it takes, and examples",
+        arguments: &[],
+        shared: &[],
+        examples: &["help set-loop"],
+    },
+    CommandSpec {
+        kind: CommandKind::Info,
+        name: "info",
+        usage: &["info [-format=text|json] [-pak=<PAK>] <input>"],
+        description:
+            "Print information about WAV file.  For looped files, this \
+            includes a seam artifact score: how much energy the spectrum \
+            across the loop seam holds beyond that of its surroundings, in \
+            dB.  Clicks score high, and seamless loops near -120 dB.  The \
+            peak and RMS levels follow in dBFS, with the loudest RMS over \
+            400ms windows and the RMS of the loop.  With -format=json, \
+            prints one object with the rate, length, format, loop, end \
+            marker, levels, and other cue points, with null for any that \
+            are absent, and nothing else",
+        arguments: &["format", "pak"],
+        shared: &[],
+        examples: &["info music.wav", "info -format=json music.wav"],
+    },
+    CommandSpec {
+        kind: CommandKind::Markers,
+        name: "markers",
+        usage: &["markers [-format=text|json|csv] <input>"],
+        description: "Lists the cue points other than the loop's and the end \
+            marker's in a table of their IDs, positions in samples and \
+            seconds, the lengths of the regions given by their labeled \
+            texts, if any, and labels.  Markers are kept by write commands \
+            as long as the file keeps a loop in a cue point, as Quake \
+            would take the first of them for the loop start otherwise; \
+            region lengths are not kept, for the same reason",
+        arguments: &["format"],
+        shared: &[],
+        examples: &["markers -format=csv music.wav"],
+    },
+    CommandSpec {
+        kind: CommandKind::Play,
+        name: "play",
+        usage: &["play [-start=<TIME>] [-engine-accurate] [-pak=<PAK>] \
+            [<level>...] [--] <input>..."],
+        description: "Play file from start to end, ignoring loops.  Playback \
+            continues past the loop end to the end marker, or the end of \
+            the file if there is none, unless -engine-accurate is given, \
+            in which case it stops at the loop end as the engine does.  \
+            With -start, playback begins there rather than at the first \
+            sample.  Given up to 9 inputs, plays them all at once with only \
+            the first heard; the number keys switch which one is heard, \
+            carrying on from the same moment",
+        arguments: &["start", "engine-accurate", "pak"],
+        shared: &[&PLAYBACK_ARGUMENTS],
+        examples: &[
+            "play music.wav",
+            "play -start=END-3s -engine-accurate music.wav",
+        ],
+    },
+    CommandSpec {
+        kind: CommandKind::PlayLooped,
+        name: "loop",
+        usage: &["loop [-plan=<PLAN> | -count=<N>] [-start=<TIME> | \
+            -seam[=<TIME>]] [-pak=<PAK>] [<level>...] [--] <input>..."],
+        description:
+            "Play file with loops.  If file contains no loops, loop from \
+            file start to end.  With -plan, plays a sequence of \
+            ';'-separated segments instead, each <TIME>..<TIME>, \"intro\" \
+            (the start of the file up to the loop), or \"loop\", optionally \
+            followed by *N to play it N times or *inf to repeat it \
+            forever, e.g. 'intro;loop*2;1s..2s*inf'.  With -count, plays \
+            the loop N times, then on to the end marker or the end of the \
+            file.  With -start, playback begins there, e.g. just before \
+            the loop end to hear the seam; a start past the loop end \
+            begins at the point in the loop that wrapping would reach.  \
+            With -seam, playback begins the time given, or 2 seconds, \
+            before the loop end, and a line is printed each time the loop \
+            wraps.  Given several inputs, switches between them as play \
+            does, e.g. to compare two blends of the same loop",
+        arguments: &["plan", "count", "start", "seam", "pak"],
+        shared: &[&PLAYBACK_ARGUMENTS],
+        examples: &[
+            "loop -seam music.wav",
+            "loop -plan='intro;loop*2' music.wav",
+            "loop -seam short_blend.wav long_blend.wav",
+        ],
+    },
+    CommandSpec {
+        kind: CommandKind::SetLoop,
+        name: "set-loop",
+        usage: &[
+            "set-loop -start=<TIME> [-end=<TIME>] [-end-marker=<TIME>] \
+            [-snap=<TIME>] [-strict] [-loop-chunks=cue|smpl|both] \
+            [-repair] [--] <input> <output>",
+            "set-loop -from-markers=<LABEL>,<LABEL> [...] [--] <input> \
+            <output>",
+            "set-loop ... -preview [--] <input>",
+        ],
+        description:
+            "Set loop point, ranging from start to end, or from the first \
+            marker labeled as given to the second.  If end is not \
+            provided, the last sample in the file is chosen.  Points in \
+            time are 0-based (0 refers to the first sample).  With -snap, \
+            each end of the loop moves to the nearest rising zero crossing \
+            within the time given, or stays put with a warning if there is \
+            none.  With -end-marker, also sets the end marker as set-end \
+            does.  -loop-chunks chooses the chunks the loop is written to: a \
+            cue point as Quake reads, a smpl chunk as many samplers read, \
+            or both; by default those it was read from, or a cue point for \
+            files without a loop",
+        arguments: &[
+            "start",
+            "end",
+            "end-marker",
+            "snap",
+            "strict",
+            "loop-chunks",
+            "from-markers",
+            "preview",
+        ],
+        shared: &[&WRITE_ARGUMENTS, &PLAYBACK_ARGUMENTS],
+        examples: &[
+            "set-loop -start=1.5s -end=12s in.wav out.wav",
+            "set-loop -start=25% -snap=5ms -preview in.wav",
+        ],
+    },
+    CommandSpec {
+        kind: CommandKind::SetEnd,
+        name: "set-end",
+        usage: &["set-end -at=<TIME> [-repair] [--] <input> <output>"],
+        description:
+            "Set the end marker, where playing without looping stops short \
+            of the end of the file.  Written as a second cue point labeled \
+            \"END\" after the loop's, so the file must have a loop",
+        arguments: &["at"],
+        shared: &[&WRITE_ARGUMENTS],
+        examples: &["set-end -at=END-1s in.wav out.wav"],
+    },
+    CommandSpec {
+        kind: CommandKind::Strip,
+        name: "strip",
+        usage: &["strip [-strict] [-repair] [--] <input> <output>"],
+        description:
+            "Strips loop and end marker (CUE and length markers) from file, \
+            keeping any other chunks",
+        arguments: &["strict"],
+        shared: &[&WRITE_ARGUMENTS],
+        examples: &["strip in.wav out.wav"],
+    },
+    CommandSpec {
+        kind: CommandKind::Clean,
+        name: "clean",
+        usage: &["clean [-repair] [--] <input> <output>"],
+        description:
+            "Rewrites file keeping only the fmt and data chunks plus the \
+            loop, and reports any other chunks or trailing bytes that were \
+            discarded.  The -clean flag does the same for any other write \
+            command",
+        arguments: &[],
+        shared: &[&WRITE_ARGUMENTS],
+        examples: &["clean -in-place music.wav"],
+    },
+    CommandSpec {
+        kind: CommandKind::Blend,
+        name: "blend",
+        usage: &[
+            "blend [-duration=<TIME>] [-clamp] [-entry] [-symmetric] \
+            [-curve=<CURVE>] [-max-impact=<PERCENT>] [-yes] [-strict] \
+            [-repair] [--] <input> <output>",
+            "blend ... -preview [--] <input>",
+        ],
+        description:
+            "Blends samples from a *duration* window before the loop starts \
+            with samples a *duration* window before the loop ends.  Loop \
+            must start after *duration* and be at least as long as \
+            *duration*.  If the duration is not provided, two periods of \
+            the lowest frequency found near the end of the loop are used, \
+            at least 5ms and at most what fits, which should eliminate \
+            clicks and pops in playback; the size used is printed.  With \
+            -clamp, the duration is shrunk to fit the loop and the lead \
+            before it, and the size used is printed.\n\
+            With -entry, the start of the loop is also blended in from the \
+            lead before it, smoothing the first entry into the loop as well \
+            as the wrap.\n\
+            With -symmetric, the *duration* after the loop start is also \
+            blended with the samples after the loop end, so the fade \
+            straddles the wrap; the file must continue *duration* past the \
+            loop end.\n\
+            -curve weights the fade: smooth (smoothstep, the default), \
+            linear, or equal-power, which avoids a dip in level mid-window \
+            on sustained tones but may clip.\n\
+            Asks for confirmation before rewriting more than 25% of the \
+            loop, or the percentage given by -max-impact; -yes skips the \
+            question",
+        arguments: &[
+            "duration",
+            "clamp",
+            "entry",
+            "symmetric",
+            "curve",
+            "max-impact",
+            "yes",
+            "strict",
+            "preview",
+        ],
+        shared: &[&WRITE_ARGUMENTS, &PLAYBACK_ARGUMENTS],
+        examples: &[
+            "blend in.wav out.wav",
+            "blend -duration=40ms -entry -preview in.wav",
+        ],
+    },
+    CommandSpec {
+        kind: CommandKind::Scan,
+        name: "scan",
+        usage: &["scan [-format=text|json|csv] \
+            [-filter=looped|unlooped|warnings] [-sort=<COLUMN>] [--] \
+            <directory>"],
+        description:
+            "Prints a summary row for every WAV file found under directory: \
+            name, sample rate, duration, whether it loops, bit depth, and \
+            a count of warnings (header/data size mismatch, loop past end \
+            of file, trailing bytes).  Rows are sorted by name unless \
+            another column is given",
+        arguments: &["format", "filter", "sort"],
+        shared: &[],
+        examples: &["scan -filter=warnings -sort=rate sound"],
+    },
+    CommandSpec {
+        kind: CommandKind::Trim,
+        name: "trim",
+        usage: &["trim [-start=<TIME>] [-end=<TIME>] [-repair] [--] <input> \
+            <output>"],
+        description:
+            "Crops the file to the samples from start to end, by default \
+            from the first sample to the loop end, which is as far as \
+            Quake plays.  The loop and end marker move with the samples \
+            they mark",
+        arguments: &["start", "end"],
+        shared: &[&WRITE_ARGUMENTS],
+        examples: &["trim -start=100ms in.wav out.wav"],
+    },
+    CommandSpec {
+        kind: CommandKind::FindLoop,
+        name: "find-loop",
+        usage: &[
+            "find-loop [-min-length=<TIME>] [-range=<TIME>..<TIME>] [--] \
+            <input>",
+            "find-loop -apply [-min-length=<TIME>] [-range=<TIME>..<TIME>] \
+            [-repair] [--] <input> <output>",
+        ],
+        description:
+            "Suggests up to five loops at least min-length long (a second \
+            unless given) within the range, or the whole file, best first.  \
+            Each is scored by how closely the samples leading to its end \
+            match those leading to its start, 1 being a perfect match.  \
+            With -apply, the best is set as the loop and the file written \
+            as set-loop does",
+        arguments: &["min-length", "range", "apply"],
+        shared: &[&WRITE_ARGUMENTS],
+        examples: &[
+            "find-loop -min-length=2s music.wav",
+            "find-loop -apply -range=25%..END in.wav out.wav",
+        ],
+    },
+    CommandSpec {
+        kind: CommandKind::Unroll,
+        name: "unroll",
+        usage: &["unroll -count=<N> [-tail] [-repair] [--] <input> <output>"],
+        description:
+            "Bakes the loop into the file for players that ignore loop \
+            points: the lead before the loop, then the loop N times, then \
+            with -tail whatever follows it.  The loop and end marker are \
+            removed.  Blend first so the repeats join seamlessly",
+        arguments: &["count", "tail"],
+        shared: &[&WRITE_ARGUMENTS],
+        examples: &["unroll -count=3 -tail in.wav out.wav"],
+    },
+    CommandSpec {
+        kind: CommandKind::Extract,
+        name: "extract",
+        usage: &["extract [-with-blend] [-repair] [--] <input> <output>"],
+        description:
+            "Writes only the loop, looped from its first sample to its last, \
+            for layering in an editor or for players that loop whole files. \
+            Markers within the loop are kept.  With -with-blend, the loop is \
+            first blended over the default window, as blend does, so the \
+            file loops cleanly on its own",
+        arguments: &["with-blend"],
+        shared: &[&WRITE_ARGUMENTS],
+        examples: &["extract -with-blend in.wav loop_only.wav"],
+    },
+    CommandSpec {
+        kind: CommandKind::Normalize,
+        name: "normalize",
+        usage: &["normalize -peak=<DB> | -rms=<DB> [-repair] [--] <input> \
+            <output>"],
+        description:
+            "Applies the gain that brings the highest sample, or the RMS \
+            level of the whole file, to the level given in dBFS, e.g. \
+            -peak=-1dB or -rms=-18dB, and prints the gain.  Samples \
+            clipped on the way are counted.  Silent files are left as they \
+            are",
+        arguments: &["peak", "rms"],
+        shared: &[&WRITE_ARGUMENTS],
+        examples: &["normalize -peak=-1dB in.wav out.wav"],
+    },
+    CommandSpec {
+        kind: CommandKind::MatchLoudness,
+        name: "match-loudness",
+        usage: &["match-loudness -reference=<WAV> [-repair] [--] <input> \
+            <output>"],
+        description:
+            "Applies the gain that brings the RMS level of the loop, or of \
+            the whole file without one, to that of the reference's loop, or \
+            the whole reference without one, so ambiences swapped in for \
+            each other play at the same level.  The gain and any clipped \
+            samples are printed",
+        arguments: &["reference"],
+        shared: &[&WRITE_ARGUMENTS],
+        examples: &["match-loudness -reference=wind1.wav wind2.wav out.wav"],
+    },
+    CommandSpec {
+        kind: CommandKind::Pad,
+        name: "pad",
+        usage: &[
+            "pad [-before=<TIME>] [-after=<TIME>] [-repair] [--] <input> \
+            <output>",
+        ],
+        description:
+            "Adds silence at the start, the end, or both.  Loop points and \
+            the end marker move with the audio, so padding the start gives \
+            blend the lead it needs before a loop starting near the \
+            beginning",
+        arguments: &["before", "after"],
+        shared: &[&WRITE_ARGUMENTS],
+        examples: &["pad -before=50ms in.wav out.wav"],
+    },
+    CommandSpec {
+        kind: CommandKind::Concat,
+        name: "concat",
+        usage: &["concat [-loop-second] [-repair] [--] <input> <appended> \
+            <output>"],
+        description:
+            "Joins two files of the same sample rate and channel count.  \
+            The first file's loop is kept, or failing that the second's, \
+            and the second's end marker.  With -loop-second, the loop \
+            covers exactly the second file, e.g. an intro followed by the \
+            body to loop",
+        arguments: &["loop-second"],
+        shared: &[&WRITE_ARGUMENTS],
+        examples: &["concat -loop-second intro.wav body.wav out.wav"],
+    },
+    CommandSpec {
+        kind: CommandKind::DcOffset,
+        name: "dc-offset",
+        usage: &["dc-offset [-repair] [--] <input> <output>"],
+        description:
+            "Centers each channel on zero by subtracting the mean of its \
+            samples, and prints the offset removed from each, in 16-bit \
+            sample values",
+        arguments: &[],
+        shared: &[&WRITE_ARGUMENTS],
+        examples: &["dc-offset in.wav out.wav"],
+    },
+    CommandSpec {
+        kind: CommandKind::RetagRate,
+        name: "retag-rate",
+        usage: &["retag-rate -rate=<HZ> [-repair] [--] <input> <output>"],
+        description:
+            "Changes the sample rate in the header without resampling, so \
+            the file plays slower and lower, or faster and higher.  Loop \
+            points stay on the same samples.  Prints the resulting change \
+            in duration",
+        arguments: &["rate"],
+        shared: &[&WRITE_ARGUMENTS],
+        examples: &["retag-rate -rate=11025 in.wav out.wav"],
+    },
+    CommandSpec {
+        kind: CommandKind::Convert,
+        name: "convert",
+        usage: &[
+            "convert [-rate=<HZ>] [-bits=8|16] [-blend[=<TIME>]] [-yes] \
+            [-repair] [--] <input> <output>",
+        ],
+        description:
+            "Resamples to the given rate, keeping pitch and speed, and \
+            writes the given bit depth, with dither when narrowing to 8 \
+            bits.  The loop, end marker, and other markers move to the \
+            same times.  With -blend, the loop is then blended as blend \
+            does, over the default window unless a duration is given.  \
+            Prints the rate, length, and loop before and after.  \
+            Upsampling a file written as 8 bits is refused unless -yes is \
+            given, as it only makes the file larger",
+        arguments: &["rate", "blend", "yes", "max-impact", "strict"],
+        shared: &[&WRITE_ARGUMENTS],
+        examples: &[
+            "convert -rate=22050 -bits=8 in.wav out.wav",
+            "convert -rate=11025 -blend=20ms in.wav out.wav",
+        ],
+    },
+    CommandSpec {
+        kind: CommandKind::Waveform,
+        name: "waveform",
+        usage: &["waveform -width=<PX> -height=<PX> [-range=<TIME>..<TIME>] \
+            [<color>...] [-clips[=<COLOR>]] [-blend-preview=<TIME>] [--] \
+            <input> <output>"],
+        description:
+            "Renders the waveform to a PNG image, with lines marking the \
+            loop. The range defaults to the whole file.  Colors are given \
+            as RRGGBB hex values with -background, -peak-color, \
+            -rms-color, and -marker-color. With -clips, columns reaching \
+            full scale are marked at the top or bottom edge, and the \
+            number of clipped samples in range is printed. With \
+            -blend-preview=<TIME>, the window a blend of that duration \
+            would rewrite is highlighted, with the blended waveform drawn \
+            over it",
+        arguments: &[
+            "width",
+            "height",
+            "range",
+            "background",
+            "peak-color",
+            "rms-color",
+            "marker-color",
+            "clips",
+            "blend-preview",
+        ],
+        shared: &[],
+        examples: &[
+            "waveform -width=800 -height=200 music.wav music.png",
+            "waveform -width=800 -height=200 -clips in.wav out.png",
+        ],
+    },
+    CommandSpec {
+        kind: CommandKind::Soak,
+        name: "soak",
+        usage: &["soak -iterations=<N> [-seed=<SEED>] [--] <input>"],
+        description:
+            "Runs looped playback without an audio device until the loop \
+            has wrapped N times, feeding the player buffers of random \
+            lengths and checking every buffer and the playhead afterward.  \
+            The stream is restarted now and then, checking that the old \
+            one falls silent.  Prints the seed so a failing run can be \
+            repeated, and the buffer lengths that led to a failure",
+        arguments: &["iterations", "seed"],
+        shared: &[],
+        examples: &["soak -iterations=1000 -seed=42 music.wav"],
+    },
+    CommandSpec {
+        kind: CommandKind::Hash,
+        name: "hash",
+        usage: &["hash [-format=text|json] [--] <input>"],
+        description:
+            "Prints SHA-256 digests of the sample data alone, which edits \
+            to the loop or other metadata leave unchanged, and of the \
+            whole file",
+        arguments: &["format"],
+        shared: &[],
+        examples: &["hash -format=json music.wav"],
+    },
+    CommandSpec {
+        kind: CommandKind::Diff,
+        name: "diff",
+        usage: &["diff [-tolerance=<N>] [-duration=<TIME>] \
+            [-format=text|json] [--] <a> <b>"],
+        description:
+            "Compares two WAV files sample by sample, a chunk at a time, \
+            reporting differences in format, length, loop, end marker, and \
+            cue points, which samples differ and by how much, and how far \
+            before the loop end of <a> they start.  With -duration, also \
+            reports whether they lie within a blend window that long.  The \
+            loop metadata of both is printed side by side.  Exits with 1 \
+            if the files differ, or with -tolerance, if any sample differs \
+            by more than N, and with 2 if they can't be compared",
+        arguments: &["tolerance", "duration", "format"],
+        shared: &[],
+        examples: &[
+            "diff -tolerance=1 a.wav b.wav",
+            "diff -duration=50ms source.wav blended.wav",
+        ],
+    },
+    CommandSpec {
+        kind: CommandKind::ApplyTemplate,
+        name: "apply-template",
+        usage: &["apply-template -template=<SPEC>|<reference> \
+            -out-dir=<DIRECTORY> [-duration=<TIME>] [--] <input>..."],
+        description:
+            "Sets the same loop in every input and writes each to the \
+            output directory under its own name.  SPEC is comma-separated \
+            settings, e.g. 'start=25%,end=LAST,blend=40ms', with times \
+            resolved against each file's own length; end defaults to LAST, \
+            and blend, or -duration, blends the loop afterward.  Given a \
+            reference WAV instead, its loop is copied at the same \
+            fractions of each file's length.  Prints a row for every \
+            input, and fails if any of them could not be written",
+        arguments: &["template", "out-dir", "duration", "reencode", "sync"],
+        shared: &[],
+        examples: &[
+            "apply-template -template=ref.wav -out-dir=build a.wav b.wav",
+        ],
+    },
+    CommandSpec {
+        kind: CommandKind::CheckSeam,
+        name: "check-seam",
+        usage: &["check-seam [-max-seam=<DB>] [-format=text|json] \
+            [-pak=<PAK>] <input>"],
+        description:
+            "Measures how sharply the loop wraps: the step from the last \
+            sample of the loop to the first, the step in slope across the \
+            wrap, both in sample units and dBFS, and the seam artifact \
+            score printed by info.  Fails if the score is above -30 dB or \
+            the score given by -max-seam.  blend prints the same \
+            measurements before and after blending",
+        arguments: &["max-seam", "format", "pak"],
+        shared: &[],
+        examples: &["check-seam -max-seam=-40dB music.wav"],
+    },
+    CommandSpec {
+        kind: CommandKind::Verify,
+        name: "verify",
+        usage: &["verify [-rate=<HZ>] [-max-seam=<DB>] \
+            [-target=quake1|quakespasm|fte] [-roundtrip] [-report=<PATH>] \
+            [--] <input>..."],
+        description:
+            "Checks that every input has a loop within the file whose seam \
+            artifact score, as printed by info, is at most -30 dB or the \
+            score given by -max-seam, and with -rate, the given sample \
+            rate.  With -target, also checks that the port plays the file \
+            as it is: its channels, sample format (never float), sample \
+            rate and size, printing PASS or FAIL for each check.  With \
+            -roundtrip, also writes each file as the write commands would, \
+            twice, checking that both writes give the same bytes and that \
+            reading the output back gives the same samples and metadata.  \
+            Prints \
+            PASS or FAIL for each file, with any other warnings beneath \
+            it, and fails if any file does.  With -report, also writes a \
+            JUnit XML report, with a test case per file",
+        arguments: &["rate", "max-seam", "target", "roundtrip", "report"],
+        shared: &[],
+        examples: &["verify -target=quake1 sound/ambience/*.wav"],
+    },
+    CommandSpec {
+        kind: CommandKind::Package,
+        name: "package",
+        usage: &[
+            "package -target=quake1|quakespasm|fte -out-dir=<DIRECTORY> \
+            [-max-seam=<DB>] [-format=text|json] [--] <input>...",
+        ],
+        description:
+            "Fits every input to what the target port plays and writes it \
+            under the output directory: resampled to a rate the port \
+            plays, converted to a bit depth it reads (with dither when \
+            narrowing to 8 bits), and for quake1, cut at the loop end, as \
+            it loops to the end of the file. Files keep their path from a \
+            sound or music directory, or go in sound by name.  Inputs \
+            failing the checks of verify, apart from lacking a loop, are \
+            not written.  Prints a manifest of the changes made to each \
+            file, and fails if any could not be packaged",
+        arguments: &["target", "out-dir", "max-seam", "format"],
+        shared: &[],
+        examples: &["package -target=quakespasm -out-dir=build music/*.wav"],
+    },
+    CommandSpec {
+        kind: CommandKind::ExportLoopMeta,
+        name: "export-loop-meta",
+        usage: &["export-loop-meta [--] <input> <output>"],
+        description:
+            "Writes the loop to a small TOML sidecar, conventionally named \
+            with a .loop extension, so loop points can be kept and \
+            reviewed as text in version control.  It holds the loop start \
+            and end in samples and seconds, the loop's label, and a \
+            SHA-256 digest of the sample data",
+        arguments: &[],
+        shared: &[],
+        examples: &["export-loop-meta music.wav music.loop"],
+    },
+    CommandSpec {
+        kind: CommandKind::ImportLoopMeta,
+        name: "import-loop-meta",
+        usage: &[
+            "import-loop-meta -from=<SIDECAR> [-ignore-hash] [-repair] [--] \
+            <input> <output>",
+        ],
+        description: "Sets the loop and its label from a sidecar written by \
+            export-loop-meta.  Fails if the input's sample data doesn't \
+            match the digest in the sidecar, unless -ignore-hash is given, \
+            in which case it only warns",
+        arguments: &["from", "ignore-hash"],
+        shared: &[&WRITE_ARGUMENTS],
+        examples: &["import-loop-meta -from=music.loop in.wav out.wav"],
+    },
+    CommandSpec {
+        kind: CommandKind::Config,
+        name: "config",
+        usage: &["config [path|show|<sub-command>]"],
+        description:
+            "Prints the location of the config file, the defaults it holds, \
+            or given a sub-command, the defaults it takes from the file \
+            once those for every sub-command are merged with its table",
+        arguments: &[],
+        shared: &[],
+        examples: &["config show", "config blend"],
+    },
+    CommandSpec {
+        kind: CommandKind::Devices,
+        name: "devices",
+        usage: &["devices"],
+        description:
+            "Lists output devices by name, marking the default, with the \
+            common sample rates each plays",
+        arguments: &[],
+        shared: &[],
+        examples: &["devices"],
+    },
+    CommandSpec {
+        kind: CommandKind::Version,
+        name: "version",
+        usage: &["version [-format=text|json]"],
+        description:
+            "Prints the version of quadio, the git commit it was built \
+            from, its enabled cargo features, and the versions of key \
+            dependencies.  -V and --version do the same in place of a \
+            sub-command",
+        arguments: &["format"],
+        shared: &[],
+        examples: &["version -format=json"],
+    },
+];
+
+/// Print usage for every sub-command, then what they have in common
+pub fn usage() {
+    println!(
+        "Usage: quadio-cli <sub-command> [<arg>...] [--] <input> [<output>]\n"
+    );
+    println!("Sub-commands:");
+
+    for spec in &COMMANDS {
+        for form in spec.usage {
+            println!("{}", wrap_form("    ", form));
+        }
+
+        println!("{}\n", wrap(spec.description, 8, 8));
+    }
+
+    print!("{}", GENERAL_USAGE);
+    println!("Time:");
+    println!("{}\n", time_grammar(&TIME_ARGUMENTS));
+    println!("{}", PLAYBACK_USAGE);
+}
+
+/// Print the usage of one sub-command, the arguments it takes, and examples
+pub fn print_help(kind: CommandKind) {
+    let spec = kind.spec();
+
+    println!("Usage:");
+
+    for form in spec.usage {
+        println!("{}", wrap_form("    quadio-cli ", form));
+    }
+
+    println!("\n{}", wrap(spec.description, 4, 4));
+
+    let arguments = spec
+        .accepted()
+        .map(|arg| {
+            let value = spec.value_of(arg);
+
+            if FLAGS.contains(&arg) {
+                format!("-{}", arg)
+            } else if OPTIONAL_VALUES.contains(&arg) {
+                format!("-{}[={}]", arg, value)
+            } else {
+                format!("-{}={}", arg, value)
+            }
+        })
+        .collect::<Vec<_>>();
+
+    if !arguments.is_empty() {
+        println!("\nArguments:");
+        println!("{}", wrap(&arguments.join(" "), 4, 4));
+    }
+
+    let times = spec
+        .accepted()
+        .filter(|arg| TIME_ARGUMENTS.contains(arg))
+        .collect::<Vec<_>>();
+
+    if !times.is_empty() {
+        println!("\nTime:");
+        println!("{}", time_grammar(&times));
+    }
+
+    println!("\nExamples:");
+
+    for example in spec.examples {
+        println!("    quadio-cli {}", example);
+    }
+}
+
+/// Print the forms a sub-command is given in, after a mistake in giving it
+pub fn print_short_usage(kind: CommandKind) {
+    let spec = kind.spec();
+
+    for (i, form) in spec.usage.iter().enumerate() {
+        let prefix = if i == 0 { "Usage:" } else { "" };
+        let prefix = format!("{:6} quadio-cli ", prefix);
+        eprintln!("{}", wrap_form(&prefix, form));
+    }
+
+    eprintln!("Run 'quadio-cli help {}' for more", spec.name);
+}
+
+// Paragraph describing the time grammar for `arguments`
+fn time_grammar(arguments: &[&str]) -> String {
+    let text =
+        format!("Time arguments ({}) {}", arguments.join(", "), TIME_GRAMMAR);
+    wrap(&text, 4, 4)
+}
+
+// A form of a sub-command following `prefix`, wrapped with its continued
+// lines lined up after the sub-command's name
+fn wrap_form(prefix: &str, form: &str) -> String {
+    let name_len = form.find(' ').unwrap_or(form.len());
+    wrap(
+        &format!("{}{}", prefix, form),
+        0,
+        prefix.len() + name_len + 1,
+    )
+}
+
+// Text wrapped to the usage width, the first line indented `indent` spaces
+// and the rest `hanging`.  Line breaks in the text are kept.
+fn wrap(text: &str, indent: usize, hanging: usize) -> String {
+    let mut lines = vec![];
+    let mut line = " ".repeat(indent);
+
+    for paragraph in text.split('\n') {
+        let mut margin = line.len();
+        let mut words: Vec<String> = vec![];
+
+        // Words keep the spaces following them, so sentences stay two apart
+        for piece in paragraph.split_inclusive(' ') {
+            match words.last_mut() {
+                Some(word) if piece.trim().is_empty() => word.push_str(piece),
+                _ => words.push(String::from(piece)),
+            }
+        }
+
+        for word in words {
+            if line.len() > margin
+                && line.len() + word.trim_end().len() > USAGE_WIDTH
+            {
+                lines.push(String::from(line.trim_end()));
+                line = " ".repeat(hanging);
+                margin = hanging;
+            }
+
+            line.push_str(&word);
+        }
+
+        lines.push(String::from(line.trim_end()));
+        line = " ".repeat(hanging);
+    }
+
+    lines.join("\n")
+}
+
+// Usage common to every sub-command, up to the time grammar
+const GENERAL_USAGE: &str = r#"Config:
+    Defaults for any sub-command's arguments can be kept in a TOML file, by
+    default ~/.config/quadio/config.toml (%APPDATA%\quadio\config.toml on
+    Windows), or the file named by QUADIO_CONFIG.  Each sub-command takes its
+    defaults from the table of the same name, then from those before any
+    table, which go to every sub-command taking them.  Flags are set with
+    true, other arguments with a string or number.  Arguments given on the
+    command line take precedence over the file, and a file that can't be read
+    is warned of and ignored, e.g.
+
+        duration = "40ms"
+        volume = 0.8
+
+        [info]
+        format = "json"
+
+        [set-loop]
+        strict = true
+
+Warnings:
+    After writing, set-loop, blend, strip, and convert warn of what may sound
+    bad or confuse Quake: a loop under 20ms, a loop point on a loud sample, a
+    sample rate other than 11025, 22050, or 44100 Hz, or 16-bit samples,
+    which vanilla Quake doesn't play.  With -strict, any of these is an error
+    and nothing is written
+
+Loop cue:
+    Write commands give the loop's cue point ID 0 and no label.  Pass
+    -cue-id=<N> to use another ID, or -cue-label=<TEXT> to label the loop
+
+Batch:
+    Write commands given -out-dir=<DIRECTORY> take any number of inputs in
+    place of an input and output, e.g. 'strip -out-dir=build a.wav b.wav',
+    writing each to the directory under its own name.  Every input is tried
+    even when an earlier one fails; a summary of each file's result follows,
+    and the command fails if any file did
+
+In place:
+    Write commands given -in-place write back to the input, with no output
+    given.  Naming the input as the output does the same.  Either way the
+    file is only replaced once the new one is written in full, so a failed
+    write leaves it as it was
+
+Preview:
+    set-loop and blend given -preview play the result looped instead of
+    writing it, starting 2 seconds before the loop end so the seam is heard
+    straight away.  No output is given, the playback controls work as in
+    loop, and nothing is written on quitting
+
+Backup:
+    Write commands given -backup move an existing output file to <output>.bak
+    before writing.  With -backup=N, up to N earlier versions are kept as
+    <output>.bak1 (newest) through <output>.bakN
+
+Output:
+    Write commands that leave the samples alone, such as set-loop and strip,
+    copy the input's fmt and data chunks byte for byte; -reencode writes them
+    afresh instead.  Chunks other than the loop's, such as INFO lists and bext
+    chunks, are carried over unless -clean is given.  -bits=8 or -bits=16
+    sets the bit depth written, with dither when narrowing to 8 bits drawn from
+    -seed=<SEED>, so the same seed always gives the same bytes.  Chunks are
+    written in a fixed order: fmt, data, the loop's cue, LIST, and smpl
+    chunks, then the carried-over chunks in the order they were read.  Write
+    commands given -sync flush the output file to disk before exiting.  With
+    -hash, they print the output's digests as the hash sub-command does, in
+    the format chosen by -format
+
+Playback level:
+    Resampling for the output device can overshoot full scale on loud
+    transients.  Overshooting samples are clamped, or with -soft-clip, rounded
+    off gently.  -headroom=<DB> attenuates the file before resampling to leave
+    room, and -verbose prints how many resampled samples overshot
+
+Playback quality:
+    Files are resampled to the output device's rate by drawing lines between
+    samples, which starts at once but can alias.  -quality=medium or
+    -quality=high resamples through a sinc filter instead, slower to prepare
+    but faithful, for a final listen.  Loop points and seeks land on the same
+    samples at any quality
+
+Playback volume:
+    -volume=<LEVEL> scales playback by a ratio or a percentage from 0 to
+    200%, e.g. -volume=0.3 or -volume=50%.  The plus and minus keys change it
+    during playback
+
+Output device:
+    -device=<NAME> plays on the output device of that name, as listed by
+    devices, rather than the default one.  Resuming after a pause reopens the
+    same device
+
+Playback speed:
+    -speed=<RATIO> plays at that many times the original speed, from 0.125 to
+    8, shifting pitch with it; slowing down helps hear a click at a loop seam.
+    Positions printed stay in samples of the file
+
+Standard input and output:
+    Sub-commands taking one input read it from standard input when it is
+    given as '-', e.g. 'ffmpeg -i in.flac -f wav - | quadio-cli info -'.  The
+    stream is held in memory while it is read, up to 1024 MiB.
+    Write commands given '-' as the output write the WAV to standard output,
+    printing anything else they report to standard error; -backup and -hash
+    need an output file.  Playing from standard input turns off the playback
+    controls, since key presses can't be read from it; stop with Ctrl-C
+
+Pak archives:
+    info, play, loop, and write commands given -pak=<PAK> read the input from
+    the entry of that name in the pak archive, e.g. 'info -pak=id1/pak0.pak
+    sound/ambience/wind2.wav'.  Write commands then write the output back
+    into the archive as the entry it names, replacing one of the same name or
+    adding it, so -in-place rewrites the entry itself.  The archive is only
+    replaced once the new one is written in full.  Writing to standard output
+    extracts the entry instead; -backup, -hash, and -out-dir can't be given
+
+Repair:
+    Write commands refuse files whose header declares a different number of
+    samples than the file actually holds.  Pass -repair to use only the
+    samples present, clamping the loop to fit
+
+Arguments:
+    Arguments may come before or after the input and output.  Those taking a
+    value can be given it after '=' or as the next argument, e.g. '-start
+    1000'; those whose value is optional, written [=<VALUE>] above, only after
+    '='.  A sub-command given an argument it doesn't take fails rather than
+    ignoring it.  Any sub-command given -help prints its own usage, the
+    arguments it takes, and examples instead of running, as help
+    <sub-command> does.  Everything after '--' is an input or output, even
+    when it starts with '-'; a path beyond those the sub-command takes is an
+    error rather than replacing one
+
+"#;
+
+// Usage following the time grammar
+const PLAYBACK_USAGE: &str = r#"Playback controls:
+    space - Pause and resume playback.  Prints current sample on pause.
+            After the output device was lost, tries to reopen it
+    left  - Seek back 5 seconds
+    right - Seek forward 5 seconds, wrapping into the loop when looping
+    , .   - Seek back or forward 1000 samples, for a close look at a seam
+    l     - Seek to a second before the loop end, to hear the seam
+    0     - Seek to the beginning
+    1-9   - Playing several files, hear that one from the same moment
+    [ ]   - Slow down or speed up playback; two presses halve or double it
+    - +   - Turn the volume down or up by 10%
+    q     - Stop & quit
+
+    On a terminal, a status line shows the time, sample, loop wraps and
+    levels as it plays, and which file is heard when there are several."#;
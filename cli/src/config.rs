@@ -0,0 +1,216 @@
+use std::env;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+const CONFIG_ENV: &str = "QUADIO_CONFIG";
+
+/// Default arguments per sub-command, as read from the config file, and
+/// those given before any table for every sub-command taking them.  Flags set
+/// to `true` are kept with an empty value, matching a bare `-flag` on the
+/// command line; flags set to `false` are left out.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Config {
+    global: Vec<(String, String)>,
+    sections: Vec<(String, Vec<(String, String)>)>,
+}
+
+impl Config {
+    /// Defaults for every sub-command, in file order
+    pub fn global(&self) -> &[(String, String)] {
+        &self.global
+    }
+
+    /// Defaults for the sub-command, in file order
+    pub fn defaults(&self, subcommand: &str) -> Vec<(String, String)> {
+        self.sections
+            .iter()
+            .filter(|(name, _)| name == subcommand)
+            .flat_map(|(_, entries)| entries.iter().cloned())
+            .collect()
+    }
+
+    pub fn sections(&self) -> &[(String, Vec<(String, String)>)] {
+        &self.sections
+    }
+}
+
+/// Location of the config file: $QUADIO_CONFIG if set, otherwise
+/// quadio/config.toml under $XDG_CONFIG_HOME or ~/.config, or on Windows,
+/// under %APPDATA%
+pub fn path() -> Option<PathBuf> {
+    if let Some(path) = env::var_os(CONFIG_ENV) {
+        return Some(PathBuf::from(path));
+    }
+
+    let non_empty = |name| env::var_os(name).filter(|dir| !dir.is_empty());
+    let home_config =
+        || non_empty("HOME").map(|home| PathBuf::from(home).join(".config"));
+    let dir = if cfg!(windows) {
+        non_empty("APPDATA").map(PathBuf::from).or_else(home_config)
+    } else {
+        non_empty("XDG_CONFIG_HOME")
+            .map(PathBuf::from)
+            .or_else(home_config)
+    };
+
+    dir.map(|dir| dir.join("quadio").join("config.toml"))
+}
+
+/// Read the config file.  A missing file gives an empty config; an unreadable
+/// or malformed one is reported on stderr and also gives an empty config.
+pub fn load() -> Config {
+    let Some(path) = path() else {
+        return Config::default();
+    };
+
+    let text = match fs::read_to_string(&path) {
+        Ok(text) => text,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {
+            return Config::default();
+        }
+        Err(e) => {
+            eprintln!(
+                "Warning: {}: {}; ignoring config file",
+                path.display(),
+                e
+            );
+            return Config::default();
+        }
+    };
+
+    parse(&text).unwrap_or_else(|e| {
+        eprintln!("Warning: {}: {}; ignoring config file", path.display(), e);
+        Config::default()
+    })
+}
+
+/// Parse the subset of TOML used by the config file: keys set to strings,
+/// booleans, or numbers, for every sub-command, then `[sub-command]` tables
+/// holding more
+pub fn parse(text: &str) -> Result<Config, String> {
+    let mut config = Config::default();
+
+    for (line_idx, line) in text.lines().enumerate() {
+        let at_line = |msg: &str| format!("line {}: {}", line_idx + 1, msg);
+        let line = line.trim();
+
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        if let Some(header) = line.strip_prefix('[') {
+            let (name, rest) =
+                header.split_once(']').ok_or(at_line("Unclosed table"))?;
+
+            if !is_blank(rest) {
+                return Err(at_line("Unexpected text after table"));
+            }
+
+            let name = parse_key(name.trim()).ok_or(at_line("Bad table"))?;
+            config.sections.push((name, vec![]));
+            continue;
+        }
+
+        let (key, value) = line
+            .split_once('=')
+            .ok_or(at_line("Expected key = value"))?;
+        let key = parse_key(key.trim()).ok_or(at_line("Bad key"))?;
+        let value = parse_value(value.trim()).ok_or(at_line("Bad value"))?;
+
+        let entries = match config.sections.last_mut() {
+            Some((_, entries)) => entries,
+            None => &mut config.global,
+        };
+
+        if let Some(value) = value {
+            entries.push((key, value));
+        }
+    }
+
+    Ok(config)
+}
+
+fn is_blank(rest: &str) -> bool {
+    let rest = rest.trim();
+    rest.is_empty() || rest.starts_with('#')
+}
+
+fn parse_key(key: &str) -> Option<String> {
+    if let Some(quoted) = key.strip_prefix('"') {
+        parse_basic_string(quoted)
+            .filter(|(_, rest)| rest.is_empty())
+            .map(|(key, _)| key)
+    } else if !key.is_empty()
+        && key
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+    {
+        Some(String::from(key))
+    } else {
+        None
+    }
+}
+
+// Outer None for a malformed value, inner None for false
+fn parse_value(value: &str) -> Option<Option<String>> {
+    let (parsed, rest) = if let Some(quoted) = value.strip_prefix('"') {
+        let (s, rest) = parse_basic_string(quoted)?;
+        (Some(s), rest)
+    } else if let Some(quoted) = value.strip_prefix('\'') {
+        let (s, rest) = quoted.split_once('\'')?;
+        (Some(String::from(s)), rest)
+    } else {
+        let end = value.find(['#', ' ', '\t']).unwrap_or(value.len());
+        let (token, rest) = value.split_at(end);
+
+        let parsed = match token {
+            "true" => Some(String::new()),
+            "false" => None,
+            number
+                if !number.is_empty()
+                    && number.chars().all(|c| {
+                        c.is_ascii_alphanumeric() || "+-._".contains(c)
+                    })
+                    && number
+                        .trim_start_matches(['+', '-'])
+                        .starts_with(|c: char| c.is_ascii_digit()) =>
+            {
+                Some(number.replace('_', ""))
+            }
+            _ => return None,
+        };
+
+        (parsed, rest)
+    };
+
+    if is_blank(rest) {
+        Some(parsed)
+    } else {
+        None
+    }
+}
+
+// Parse a double-quoted string whose opening quote has been consumed,
+// returning it and whatever follows the closing quote
+fn parse_basic_string(s: &str) -> Option<(String, &str)> {
+    let mut parsed = String::new();
+    let mut chars = s.char_indices();
+
+    while let Some((idx, c)) = chars.next() {
+        match c {
+            '"' => return Some((parsed, &s[idx + 1..])),
+            '\\' => parsed.push(match chars.next()?.1 {
+                '"' => '"',
+                '\\' => '\\',
+                'n' => '\n',
+                't' => '\t',
+                'r' => '\r',
+                _ => return None,
+            }),
+            c => parsed.push(c),
+        }
+    }
+
+    None
+}
@@ -0,0 +1,315 @@
+use crate::output::{self, OutputFormat};
+use quadio_core as core;
+use std::fs::File;
+use std::io::BufReader;
+use std::ops::Range;
+use std::path::Path;
+
+/// Error diff fails with when the files could be compared and differ, as
+/// opposed to when they could not be compared
+pub const FILES_DIFFER: &str = "Files differ";
+
+type Reader = core::QWaveReader<BufReader<File>>;
+
+fn open(path: &Path) -> Result<Reader, String> {
+    Ok(core::QWaveReader::open(path)?)
+}
+
+fn bits(format: core::SampleFmt) -> u16 {
+    match format {
+        core::SampleFmt::Unsigned8 => 8,
+        core::SampleFmt::Signed16 => 16,
+    }
+}
+
+fn loop_text(sample_loop: &Option<Range<u32>>) -> String {
+    sample_loop
+        .as_ref()
+        .map(|sample_loop| {
+            format!("{}..{}", sample_loop.start, sample_loop.end)
+        })
+        .unwrap_or(String::from("none"))
+}
+
+// Loop end of a file, where it loops at all
+fn loop_end(metadata: &core::Metadata) -> Option<u32> {
+    metadata
+        .loop_start
+        .map(|_| metadata.end.unwrap_or(metadata.sample_count))
+}
+
+// Rows comparing the loop start, loop end, end marker, and other cue points
+// of each file side by side
+fn metadata_rows(a: &Reader, b: &Reader) -> Vec<Vec<String>> {
+    let optional = |value: Option<u32>| {
+        value.map_or(String::from("none"), |value| value.to_string())
+    };
+    let cue_points = |reader: &Reader| {
+        let positions = reader
+            .markers()
+            .iter()
+            .map(|marker| marker.position.to_string())
+            .collect::<Vec<_>>();
+
+        if positions.is_empty() {
+            String::from("none")
+        } else {
+            positions.join(" ")
+        }
+    };
+    let (meta_a, meta_b) = (a.metadata(), b.metadata());
+
+    [
+        (
+            "Loop start",
+            optional(meta_a.loop_start),
+            optional(meta_b.loop_start),
+        ),
+        (
+            "Loop end",
+            optional(loop_end(&meta_a)),
+            optional(loop_end(&meta_b)),
+        ),
+        (
+            "End marker",
+            optional(meta_a.play_end),
+            optional(meta_b.play_end),
+        ),
+        ("Cue points", cue_points(a), cue_points(b)),
+    ]
+    .into_iter()
+    .map(|(name, a, b)| {
+        let same = if a == b { "" } else { "differs" };
+        vec![String::from(name), a, b, String::from(same)]
+    })
+    .collect()
+}
+
+// Where the differences lie against the loop end of the first file
+struct BlendCoverage {
+    // Frames before the loop end a blend window must span to cover them all,
+    // or None if they reach past it
+    frames: Option<u32>,
+
+    // Whether the expected blend window, if given, covers them
+    within: Option<bool>,
+}
+
+// None when the first file has no loop or the files' samples don't differ
+fn blend_coverage(
+    report: &core::CompareReport,
+    metadata: &core::Metadata,
+    window: Option<u32>,
+) -> Option<BlendCoverage> {
+    let channels = metadata.channels.max(1) as usize;
+    let end = loop_end(metadata)? as usize;
+    let first = report.first_difference? / channels;
+    let last = report.last_difference? / channels;
+    let frames = (last < end).then(|| (end - first) as u32);
+
+    Some(BlendCoverage {
+        frames,
+        within: window
+            .map(|window| frames.is_some_and(|frames| frames <= window)),
+    })
+}
+
+fn print_text(
+    report: &core::CompareReport,
+    rows: &[Vec<String>],
+    coverage: Option<BlendCoverage>,
+) {
+    println!("Comparison");
+
+    if let Some((a, b)) = report.sample_rates {
+        println!("\tSample rates differ: {} vs {}", a, b);
+    }
+
+    if let Some((a, b)) = report.render_formats {
+        println!("\tBit depths differ: {} vs {}", bits(a), bits(b));
+    }
+
+    if let Some((a, b)) = report.channels {
+        println!("\tChannel counts differ: {} vs {}", a, b);
+    }
+
+    if let Some((a, b)) = report.sample_counts {
+        println!("\tSample counts differ: {} vs {}", a, b);
+    }
+
+    if let Some((a, b)) = &report.loops {
+        println!("\tLoops differ: {} vs {}", loop_text(a), loop_text(b));
+    }
+
+    match (report.first_difference, report.last_difference) {
+        (Some(first), Some(last)) => println!(
+            "\t{} samples differ, from sample {} to {}, by at most {}",
+            report.differing_samples, first, last, report.max_difference,
+        ),
+        _ => println!("\tShared samples are identical"),
+    }
+
+    match coverage {
+        Some(BlendCoverage {
+            frames: Some(frames),
+            within,
+        }) => println!(
+            "\tDifferences lie in the last {} samples of the loop{}",
+            frames,
+            match within {
+                Some(true) => ", within the blend window",
+                Some(false) => ", beyond the blend window",
+                None => "",
+            },
+        ),
+        Some(BlendCoverage { frames: None, .. }) => {
+            println!("\tDifferences reach past the loop end");
+        }
+        None => {}
+    }
+
+    println!();
+    println!("{}", output::text_table(&["Metadata", "A", "B", ""], rows));
+}
+
+fn print_json(report: &core::CompareReport, coverage: Option<BlendCoverage>) {
+    let pair = |pair: Option<(u32, u32)>| {
+        pair.map(|(a, b)| format!("[{},{}]", a, b))
+            .unwrap_or(String::from("null"))
+    };
+    let optional = |value: Option<usize>| {
+        value
+            .map(|value| value.to_string())
+            .unwrap_or(String::from("null"))
+    };
+    let loop_json = |sample_loop: &Option<Range<u32>>| {
+        sample_loop
+            .as_ref()
+            .map(|sample_loop| {
+                format!("[{},{}]", sample_loop.start, sample_loop.end)
+            })
+            .unwrap_or(String::from("null"))
+    };
+    let play_end_json = |play_end: Option<u32>| {
+        play_end
+            .map(|play_end| play_end.to_string())
+            .unwrap_or(String::from("null"))
+    };
+    let positions_json = |positions: &[u32]| {
+        let positions = positions
+            .iter()
+            .map(|position| position.to_string())
+            .collect::<Vec<_>>();
+        format!("[{}]", positions.join(","))
+    };
+
+    let fields = [
+        ("identical", report.is_identical().to_string()),
+        ("sample_rates", pair(report.sample_rates)),
+        (
+            "bits",
+            pair(
+                report
+                    .render_formats
+                    .map(|(a, b)| (bits(a).into(), bits(b).into())),
+            ),
+        ),
+        (
+            "channels",
+            pair(report.channels.map(|(a, b)| (a.into(), b.into()))),
+        ),
+        ("sample_counts", pair(report.sample_counts)),
+        (
+            "loops",
+            report
+                .loops
+                .as_ref()
+                .map(|(a, b)| format!("[{},{}]", loop_json(a), loop_json(b)))
+                .unwrap_or(String::from("null")),
+        ),
+        (
+            "play_ends",
+            report
+                .play_ends
+                .map(|(a, b)| {
+                    format!("[{},{}]", play_end_json(a), play_end_json(b))
+                })
+                .unwrap_or(String::from("null")),
+        ),
+        (
+            "markers",
+            report
+                .markers
+                .as_ref()
+                .map(|(a, b)| {
+                    format!("[{},{}]", positions_json(a), positions_json(b))
+                })
+                .unwrap_or(String::from("null")),
+        ),
+        ("first_difference", optional(report.first_difference)),
+        ("last_difference", optional(report.last_difference)),
+        ("differing_samples", report.differing_samples.to_string()),
+        ("max_difference", report.max_difference.to_string()),
+        (
+            "loop_end_span",
+            coverage
+                .as_ref()
+                .and_then(|coverage| coverage.frames)
+                .map_or(String::from("null"), |frames| frames.to_string()),
+        ),
+        (
+            "within_blend_window",
+            coverage
+                .and_then(|coverage| coverage.within)
+                .map_or(String::from("null"), |within| within.to_string()),
+        ),
+    ];
+
+    let members = fields
+        .iter()
+        .map(|(key, value)| format!("{}:{}", output::json_string(key), value))
+        .collect::<Vec<_>>();
+
+    println!("{{{}}}", members.join(","));
+}
+
+/// Compare two WAVs sample by sample, a chunk at a time, and print the
+/// differences, with their loop metadata side by side and where the
+/// differences lie against the loop end.  `blend_window` is the time of the
+/// blend window they are expected to lie in, if any.  Fails with
+/// `FILES_DIFFER` unless the files match, allowing samples to differ by up to
+/// `tolerance`.
+pub fn diff(
+    a_path: &Path,
+    b_path: &Path,
+    format: OutputFormat,
+    tolerance: u32,
+    blend_window: Option<&str>,
+) -> Result<(), String> {
+    let (mut a, mut b) = (open(a_path)?, open(b_path)?);
+    let meta_a = a.metadata();
+    let window = blend_window
+        .map(|time| {
+            core::TimeSpec::parse(time)?
+                .resolve(meta_a.sample_rate, meta_a.sample_count)
+        })
+        .transpose()?;
+    let rows = metadata_rows(&a, &b);
+    let report =
+        core::compare_readers(&mut a, &mut b, core::CompareOptions::default())?;
+    let coverage = blend_coverage(&report, &meta_a, window);
+
+    match format {
+        OutputFormat::Text => print_text(&report, &rows, coverage),
+        OutputFormat::Json => print_json(&report, coverage),
+        OutputFormat::Csv => {
+            return Err(String::from("diff does not support CSV output"));
+        }
+    }
+
+    if report.matches_within(tolerance) {
+        Ok(())
+    } else {
+        Err(String::from(FILES_DIFFER))
+    }
+}
@@ -1,14 +1,207 @@
-use io::{Read, Seek};
+mod backup;
+mod commands;
+mod config;
+mod diff;
+mod output;
+mod package;
+mod report;
+mod scan;
+mod verify;
+
+use commands::{CommandSpec, COMMANDS};
+use io::{IsTerminal, Read, Seek, Write};
+use output::OutputFormat;
 use quadio_core as core;
 use std::collections::HashMap;
 use std::path::Path;
+use std::process::ExitCode;
 use std::thread::sleep;
 use std::time::Duration;
 use std::{env, fs, io};
 
-const ARGUMENTS: [&str; 5] = ["in", "out", "start", "end", "duration"];
+const ARGUMENTS: [&str; 77] = [
+    "in",
+    "out",
+    "start",
+    "end",
+    "duration",
+    "repair",
+    "width",
+    "height",
+    "range",
+    "background",
+    "peak-color",
+    "rms-color",
+    "marker-color",
+    "strict",
+    "engine-accurate",
+    "clean",
+    "clamp",
+    "format",
+    "filter",
+    "sort",
+    "iterations",
+    "seed",
+    "cue-id",
+    "cue-label",
+    "entry",
+    "tolerance",
+    "backup",
+    "rate",
+    "at",
+    "end-marker",
+    "sync",
+    "hash",
+    "clips",
+    "headroom",
+    "soft-clip",
+    "verbose",
+    "max-impact",
+    "yes",
+    "reencode",
+    "template",
+    "out-dir",
+    "plan",
+    "report",
+    "max-seam",
+    "blend-preview",
+    "from",
+    "ignore-hash",
+    "target",
+    "curve",
+    "symmetric",
+    "peak",
+    "rms",
+    "bits",
+    "count",
+    "tail",
+    "snap",
+    "min-length",
+    "apply",
+    "append",
+    "loop-second",
+    "before",
+    "after",
+    "from-markers",
+    "speed",
+    "device",
+    "blend",
+    "in-place",
+    "volume",
+    "preview",
+    "seam",
+    "help",
+    "pak",
+    "reference",
+    "with-blend",
+    "quality",
+    "roundtrip",
+    "loop-chunks",
+];
+
+// Arguments that are switched on by name alone and take no value
+const FLAGS: [&str; 22] = [
+    "repair",
+    "strict",
+    "engine-accurate",
+    "clean",
+    "clamp",
+    "entry",
+    "sync",
+    "hash",
+    "soft-clip",
+    "verbose",
+    "yes",
+    "reencode",
+    "ignore-hash",
+    "symmetric",
+    "tail",
+    "apply",
+    "loop-second",
+    "in-place",
+    "preview",
+    "help",
+    "with-blend",
+    "roundtrip",
+];
+
+// Arguments whose value is optional, and so can only be given after '='
+const OPTIONAL_VALUES: [&str; 4] = ["backup", "clips", "blend", "seam"];
+
+// Arguments taken by every command that writes a file
+const WRITE_ARGUMENTS: [&str; 14] = [
+    "repair",
+    "clean",
+    "backup",
+    "hash",
+    "sync",
+    "reencode",
+    "bits",
+    "seed",
+    "cue-id",
+    "cue-label",
+    "format",
+    "in-place",
+    "out-dir",
+    "pak",
+];
+
+// Arguments taken by every command that plays a file
+const PLAYBACK_ARGUMENTS: [&str; 7] = [
+    "headroom",
+    "soft-clip",
+    "speed",
+    "device",
+    "volume",
+    "verbose",
+    "quality",
+];
 const INPUT_BUFFER_SZ: usize = 4096;
 
+// Most files play and loop switch between, one to each number key
+const MAX_PLAY_FILES: usize = 9;
+
+// Seconds moved by each arrow key press during playback
+const SEEK_SECONDS: usize = 5;
+
+// Samples moved by each comma or period press during playback
+const FINE_SEEK: usize = 1000;
+
+// Bars in the level meter drawn on the status line during playback
+const METER_WIDTH: usize = 20;
+
+// Seconds before the loop end that -seam starts playing from by default
+const SEAM_LEAD_SECONDS: u32 = 2;
+
+// Volume added or taken away by each plus or minus key press during playback
+const VOLUME_STEP: f32 = 0.1;
+
+// Factor each bracket key press slows or speeds playback by; two presses
+// halve or double it
+const SPEED_NUDGE: f64 = std::f64::consts::SQRT_2;
+
+// Joins the inputs of sub-commands taking many; can't appear in a path
+const INPUT_SEPARATOR: char = '\0';
+
+// Input path standing for standard input
+const STDIN_PATH: &str = "-";
+
+// Output path standing for standard output
+const STDOUT_PATH: &str = "-";
+
+// Rates outside this range are unusual enough to warn about when retagging
+const COMMON_RATES: std::ops::RangeInclusive<u32> = 8000..=48000;
+
+// Color marking clipped columns when -clips is given without one
+const CLIP_COLOR: core::Rgb = [0xff, 0x30, 0xff];
+
+// Colors of a previewed blend and the window behind it in waveform renders
+const PREVIEW_COLOR: core::Rgb = [0x40, 0xc0, 0xf0];
+const PREVIEW_HIGHLIGHT: core::Rgb = [0x30, 0x40, 0x58];
+
+// Percentage of the loop a blend may rewrite before asking for confirmation
+const MAX_BLEND_IMPACT: f64 = 25.0;
+
 type CommandArgs = HashMap<&'static str, String>;
 
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
@@ -18,290 +211,2872 @@ enum CommandKind {
     PlayLooped,
     Strip,
     SetLoop,
+    SetEnd,
     Blend,
+    Clean,
+    RetagRate,
+    Convert,
+    Trim,
+    Normalize,
+    MatchLoudness,
+    DcOffset,
+    Concat,
+    Pad,
+    Unroll,
+    Extract,
+    FindLoop,
+    Waveform,
+    Scan,
+    Soak,
+    Hash,
+    Markers,
+    Config,
+    Diff,
+    ApplyTemplate,
+    Verify,
+    CheckSeam,
+    ExportLoopMeta,
+    ImportLoopMeta,
+    Package,
+    Devices,
+    Version,
     Help,
 }
 
-impl TryFrom<&str> for CommandKind {
-    type Error = String;
+impl TryFrom<&str> for CommandKind {
+    type Error = String;
+
+    fn try_from(from: &str) -> Result<CommandKind, Self::Error> {
+        let name = match from {
+            "-V" | "--version" => "version",
+            name => name,
+        };
+
+        COMMANDS
+            .iter()
+            .find(|spec| spec.name == name)
+            .map(|spec| spec.kind)
+            .ok_or_else(|| format!("Unknown sub-command \"{}\"", from))
+    }
+}
+
+impl CommandKind {
+    // The sub-command's declaration, giving its name, the arguments it
+    // takes, and its help
+    fn spec(&self) -> &'static CommandSpec {
+        COMMANDS
+            .iter()
+            .find(|spec| spec.kind == *self)
+            .expect("Every sub-command is declared")
+    }
+
+    fn name(&self) -> &'static str {
+        self.spec().name
+    }
+
+    // Whether the sub-command writes one input to one output, and so can be
+    // run over many inputs given -out-dir
+    fn writes_output(&self) -> bool {
+        matches!(
+            self,
+            CommandKind::Strip
+                | CommandKind::SetLoop
+                | CommandKind::SetEnd
+                | CommandKind::Blend
+                | CommandKind::Clean
+                | CommandKind::RetagRate
+                | CommandKind::Convert
+                | CommandKind::Trim
+                | CommandKind::Normalize
+                | CommandKind::MatchLoudness
+                | CommandKind::DcOffset
+                | CommandKind::Pad
+                | CommandKind::Unroll
+                | CommandKind::Extract
+                | CommandKind::FindLoop
+                | CommandKind::ImportLoopMeta
+        )
+    }
+
+    // Whether every positional argument is an input, with no output
+    fn takes_many_inputs(&self) -> bool {
+        matches!(
+            self,
+            CommandKind::ApplyTemplate
+                | CommandKind::Verify
+                | CommandKind::Package
+                | CommandKind::Play
+                | CommandKind::PlayLooped
+        )
+    }
+
+    // Most positional arguments the sub-command takes, when it doesn't take
+    // many inputs
+    fn max_positionals(&self) -> usize {
+        match self {
+            CommandKind::Devices | CommandKind::Version => 0,
+            CommandKind::Concat => 3,
+            CommandKind::Diff
+            | CommandKind::Waveform
+            | CommandKind::ExportLoopMeta => 2,
+            _ if self.writes_output() => 2,
+            _ => 1,
+        }
+    }
+
+    // Whether the sub-command reads the named argument, as declared.  Inputs
+    // and outputs can be named by any of them, and any takes -help.
+    fn accepts(&self, arg: &str) -> bool {
+        let spec = self.spec();
+
+        // Concat writes a file, but only ever one
+        if *self == CommandKind::Concat && arg == "out-dir" {
+            return false;
+        }
+
+        matches!(arg, "in" | "out" | "append" | "help")
+            || spec.arguments.contains(&arg)
+            || spec.shared.iter().any(|arguments| arguments.contains(&arg))
+    }
+}
+
+type Command = (CommandKind, CommandArgs);
+
+// A file or buffered standard input
+trait Input: Read + Seek {}
+
+impl<T: Read + Seek> Input for T {}
+
+// Split "name=value" into a known argument name and the value, if any.
+// Unknown names are reported with the closest known one.
+fn parse_arg_param(
+    arg_param: &str,
+) -> Result<(&'static str, Option<&str>), String> {
+    let (arg_slice, param) = match arg_param.split_once('=') {
+        Some((arg_slice, param)) => (arg_slice, Some(param)),
+        None => (arg_param, None),
+    };
+
+    match ARGUMENTS.into_iter().find(|&s| s == arg_slice) {
+        Some(arg) => Ok((arg, param)),
+        None => match closest_argument(arg_slice) {
+            Some(close) => Err(format!(
+                "Unrecognized argument {}; did you mean -{}?",
+                arg_slice, close
+            )),
+            None => Err(format!("Unrecognized argument {}", arg_slice)),
+        },
+    }
+}
+
+// Known argument name within a few edits of a misspelt one, if any
+fn closest_argument(misspelt: &str) -> Option<&'static str> {
+    let max_edits = (misspelt.chars().count() / 3).clamp(1, 3);
+
+    ARGUMENTS
+        .into_iter()
+        .map(|arg| (edit_distance(misspelt, arg), arg))
+        .filter(|&(edits, _)| edits <= max_edits)
+        .min_by_key(|&(edits, _)| edits)
+        .map(|(_, arg)| arg)
+}
+
+// Levenshtein distance: the fewest insertions, deletions, and substitutions
+// of characters turning one string into the other
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b = b.chars().collect::<Vec<_>>();
+    let mut row = (0..=b.len()).collect::<Vec<_>>();
+
+    for (i, a_char) in a.chars().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+
+        for (j, &b_char) in b.iter().enumerate() {
+            let substitution = diagonal + usize::from(a_char != b_char);
+            diagonal = row[j + 1];
+            row[j + 1] = substitution.min(row[j] + 1).min(diagonal + 1);
+        }
+    }
+
+    row[b.len()]
+}
+
+fn parse_args<'a, T: Iterator<Item = &'a str>>(
+    mut args: T,
+) -> Result<Command, String> {
+    let cmd: CommandKind = args
+        .next()
+        .map(|cmd| cmd.try_into())
+        .ok_or(String::from("Missing sub-command"))
+        .and_then(|x| x)?;
+
+    let mut map = HashMap::new();
+    let mut positionals = vec![];
+    let mut reached_divider = false;
+
+    while let Some(arg) = args.next() {
+        // Past the divider, everything is positional, even another divider
+        // or a path starting with '-'.  Before it, options and positionals
+        // may come in any order.
+        if reached_divider || arg == STDIN_PATH || !arg.starts_with('-') {
+            positionals.push(arg);
+            continue;
+        }
+
+        if arg == "--" {
+            reached_divider = true;
+            continue;
+        }
+
+        let (argname, param) = parse_arg_param(arg.trim_start_matches('-'))?;
+
+        if !cmd.accepts(argname) {
+            return Err(format!("{} doesn't take -{}", cmd.name(), argname));
+        }
+
+        let param = if FLAGS.contains(&argname) {
+            match param {
+                Some(_) => {
+                    return Err(format!(
+                        "Argument -{} takes no value",
+                        argname
+                    ));
+                }
+                None => "",
+            }
+        } else if OPTIONAL_VALUES.contains(&argname) {
+            param.unwrap_or("")
+        } else {
+            // Required values may follow as the next argument instead, but
+            // the divider is never taken for one
+            match param.or_else(|| args.next()) {
+                Some(param) if !param.is_empty() && param != "--" => param,
+                _ => {
+                    return Err(format!(
+                        "Argument -{} requires a value",
+                        argname
+                    ));
+                }
+            }
+        };
+
+        map.insert(argname, String::from(param));
+    }
+
+    // Given an output directory, every positional is an input
+    let batch = cmd.writes_output() && map.contains_key("out-dir");
+    let max_positionals = if cmd.takes_many_inputs() || batch {
+        usize::MAX
+    } else {
+        cmd.max_positionals()
+    };
+
+    if let Some(extra) = positionals.get(max_positionals) {
+        return Err(unexpected_argument(extra));
+    }
+
+    for arg in positionals {
+        if !map.contains_key("in") {
+            map.insert("in", arg.into());
+        } else if cmd == CommandKind::Concat && !map.contains_key("append") {
+            map.insert("append", arg.into());
+        } else if cmd.takes_many_inputs() || batch {
+            let inputs: &mut String = map.get_mut("in").unwrap();
+            inputs.push(INPUT_SEPARATOR);
+            inputs.push_str(arg);
+        } else if !map.contains_key("out") {
+            map.insert("out", arg.into());
+        } else {
+            // A named output leaves no room for another positional
+            return Err(unexpected_argument(arg));
+        }
+    }
+
+    Ok((cmd, map))
+}
+
+// Positional argument beyond the inputs and output the sub-command takes
+fn unexpected_argument(arg: &str) -> String {
+    format!("Unexpected extra argument '{}'", arg)
+}
+
+// Fill in arguments not given on the command line from the config file: from
+// the sub-command's table, then from the defaults before any table that the
+// sub-command takes
+fn apply_config((cmd, mut args): Command, config: &config::Config) -> Command {
+    let table = config.defaults(cmd.name()).into_iter().map(|e| (e, true));
+    let global = config.global().iter().cloned().map(|e| (e, false));
+
+    for ((key, value), in_table) in table.chain(global) {
+        match ARGUMENTS.into_iter().find(|&arg| arg == key) {
+            None => {
+                eprintln!("Warning: unrecognized argument {} in config", key)
+            }
+            Some("in") | Some("out") | Some("append") => {
+                eprintln!("Warning: {} can't be set in config", key)
+            }
+            Some(arg) if !cmd.accepts(arg) => {
+                // Defaults for every sub-command only go to those taking them
+                if in_table {
+                    eprintln!(
+                        "Warning: {} doesn't take {} in config",
+                        cmd.name(),
+                        key
+                    )
+                }
+            }
+            Some(arg) => {
+                args.entry(arg).or_insert(value);
+            }
+        }
+    }
+
+    (cmd, args)
+}
+
+fn expect_arg<'a>(
+    args: &'a CommandArgs,
+    argname: &str,
+) -> Result<&'a String, String> {
+    args.get(argname).ok_or_else(|| {
+        if argname == "in" {
+            "No input file provided".into()
+        } else {
+            format!("Expected argument \"{}\"", argname)
+        }
+    })
+}
+
+fn run_command((cmd, args): Command) -> Result<(), String> {
+    if cmd == CommandKind::Help {
+        match args.get("in") {
+            Some(name) => commands::print_help(name[..].try_into()?),
+            None => {
+                println!("QUADIO - Quake Looped Audio Utilities\n");
+                commands::usage();
+            }
+        }
+    } else if cmd == CommandKind::Version {
+        print_version(output_format(&args)?)?;
+    } else if cmd == CommandKind::Devices {
+        print_devices()?;
+    } else if cmd == CommandKind::Scan {
+        let dir = Path::new(expect_arg(&args, "in")?);
+        let filter = args
+            .get("filter")
+            .map(|f| scan::ScanFilter::try_from(&f[..]))
+            .transpose()?;
+        let sort = args
+            .get("sort")
+            .map(|key| scan::SortKey::try_from(&key[..]))
+            .transpose()?
+            .unwrap_or(scan::SortKey::Name);
+
+        scan::scan(dir, filter, sort, output_format(&args)?)?;
+    } else if cmd == CommandKind::Diff {
+        let tolerance = args
+            .get("tolerance")
+            .map(|tolerance| tolerance.parse::<u32>())
+            .transpose()
+            .map_err(|e| format!("Bad tolerance: {}", e))?
+            .unwrap_or(0);
+
+        diff::diff(
+            Path::new(expect_arg(&args, "in")?),
+            Path::new(expect_arg(&args, "out")?),
+            output_format(&args)?,
+            tolerance,
+            args.get("duration").map(String::as_str),
+        )?;
+    } else if cmd == CommandKind::Config {
+        show_config(args.get("in").map(|action| &action[..]))?;
+    } else if cmd.writes_output() && args.contains_key("out-dir") {
+        run_batch((cmd, args))?;
+    } else if cmd == CommandKind::ApplyTemplate {
+        apply_template(&args)?;
+    } else if cmd == CommandKind::Verify {
+        let options = verify::VerifyOptions {
+            sample_rate: args
+                .get("rate")
+                .map(|rate| rate.parse::<u32>())
+                .transpose()
+                .map_err(|e| format!("Bad sample rate: {}", e))?,
+            max_seam_score: max_seam_score(&args)?,
+            require_loop: true,
+            round_trip: args.contains_key("roundtrip"),
+            target: args
+                .contains_key("target")
+                .then(|| target_profile(&args))
+                .transpose()?,
+        };
+
+        verify::verify(
+            &inputs(&args)?,
+            &options,
+            args.get("report").map(Path::new),
+        )?;
+    } else if cmd == CommandKind::Package {
+        package::package(
+            &inputs(&args)?,
+            target_profile(&args)?,
+            Path::new(expect_arg(&args, "out-dir")?),
+            max_seam_score(&args)?,
+            output_format(&args)?,
+        )?;
+    } else if matches!(cmd, CommandKind::Play | CommandKind::PlayLooped)
+        && inputs(&args)?.len() > 1
+    {
+        let looped = cmd == CommandKind::PlayLooped;
+        let bounds = if looped || args.contains_key("engine-accurate") {
+            core::PlaybackBounds::ToEndMarker
+        } else {
+            core::PlaybackBounds::Full
+        };
+
+        play_files(&inputs(&args)?, looped, bounds, &args)?;
+    } else {
+        let inpath = expect_arg(&args, "in")?.clone();
+        let inpath = Path::new(&inpath);
+
+        // Standard input can't seek or be read twice, and a pak entry has to
+        // be read out of its archive, so either is buffered once up front
+        let buffered = if let Some(pak) = args.get("pak") {
+            let entry = core::PakReader::open(pak)
+                .and_then(|mut reader| reader.read_entry(&args["in"]))
+                .map_err(|e| e.at_path(Path::new(pak)))?;
+            Some((entry, format!("{}:{}", pak, inpath.display())))
+        } else if inpath == Path::new(STDIN_PATH) {
+            let stdin = core::buffer_unseekable(io::stdin().lock())?;
+            Some((stdin.into_inner(), String::from("<stdin>")))
+        } else {
+            None
+        };
+
+        let open_file = || -> Result<Box<dyn Input + '_>, String> {
+            match &buffered {
+                Some((bytes, _)) => Ok(Box::new(io::Cursor::new(&bytes[..]))),
+                None => match fs::File::open(inpath) {
+                    Ok(file) => Ok(Box::new(io::BufReader::new(file))),
+                    Err(e) => Err(format!("{}: {}", inpath.display(), e)),
+                },
+            }
+        };
+
+        let open_wave = || -> Result<core::QWaveReader<_>, String> {
+            let name = match &buffered {
+                Some((_, name)) => Path::new(name),
+                None => inpath,
+            };
+
+            Ok(core::QWaveReader::new(open_file()?)
+                .map_err(|e| e.at_path(name))?)
+        };
+
+        match cmd {
+            CommandKind::Info => {
+                print_info(open_wave()?, output_format(&args)?)?;
+            }
+            CommandKind::Play => {
+                let bounds = if args.contains_key("engine-accurate") {
+                    core::PlaybackBounds::ToEndMarker
+                } else {
+                    core::PlaybackBounds::Full
+                };
+
+                play_wave(open_wave()?, false, bounds, &args)?;
+            }
+            CommandKind::PlayLooped => {
+                play_wave(
+                    open_wave()?,
+                    true,
+                    core::PlaybackBounds::ToEndMarker,
+                    &args,
+                )?;
+            }
+            CommandKind::CheckSeam => {
+                check_seam(open_wave()?, &args)?;
+            }
+            CommandKind::FindLoop if !args.contains_key("apply") => {
+                let proj = core::Project::from_reader(open_wave()?)?;
+                print_loop_candidates(&args, &proj)?;
+            }
+            CommandKind::Strip
+            | CommandKind::SetLoop
+            | CommandKind::SetEnd
+            | CommandKind::Blend
+            | CommandKind::Clean
+            | CommandKind::RetagRate
+            | CommandKind::Convert
+            | CommandKind::Trim
+            | CommandKind::Normalize
+            | CommandKind::MatchLoudness
+            | CommandKind::DcOffset
+            | CommandKind::Concat
+            | CommandKind::Pad
+            | CommandKind::Unroll
+            | CommandKind::Extract
+            | CommandKind::FindLoop
+            | CommandKind::ImportLoopMeta => {
+                let args = in_place(&args)?;
+                let q_wave_reader = open_wave()?;
+                let repair = args.contains_key("repair");
+
+                if let (Some((declared, actual)), false) =
+                    (q_wave_reader.metadata().declared_vs_actual, repair)
+                {
+                    return Err(format!(
+                        "Header declares {} samples, but data holds {}; use \
+                        -repair to clamp to the actual data",
+                        declared, actual,
+                    ));
+                }
+
+                let discarded = if cmd == CommandKind::Clean
+                    || args.contains_key("clean")
+                {
+                    Some((
+                        q_wave_reader.unmanaged_chunks(),
+                        q_wave_reader.trailing_bytes(),
+                    ))
+                } else {
+                    None
+                };
+
+                let mut project = core::Project::from_reader(q_wave_reader)?;
+
+                if repair {
+                    project.clamp_loop();
+                }
+
+                if discarded.is_some() {
+                    project.set_extra_chunks(vec![]);
+                }
+
+                run_write_command((cmd, args.clone()), project, &open_file)?;
+
+                if let Some((chunks, trailing_bytes)) = discarded {
+                    report_discarded(&args, &chunks, trailing_bytes);
+                }
+            }
+            CommandKind::Waveform => {
+                let wave_reader = open_wave()?;
+                write_waveform((cmd, args), wave_reader)?;
+            }
+            CommandKind::Soak => {
+                soak_wave(&args, open_wave()?)?;
+            }
+            CommandKind::Hash => {
+                let digest = core::hash::digest_wave(&mut open_file()?)?;
+                print_digest(inpath, &digest, output_format(&args)?)?;
+            }
+            CommandKind::Markers => {
+                print_markers(open_wave()?, output_format(&args)?);
+            }
+            CommandKind::ExportLoopMeta => {
+                export_loop_meta(&args, open_file()?)?;
+            }
+            CommandKind::Help
+            | CommandKind::Scan
+            | CommandKind::Config
+            | CommandKind::Diff
+            | CommandKind::ApplyTemplate
+            | CommandKind::Verify
+            | CommandKind::Package
+            | CommandKind::Devices
+            | CommandKind::Version => {
+                unreachable!();
+            }
+        }
+    }
+
+    Ok(())
+}
+
+// Describe the format, length, loop and end marker of a file
+fn print_info<R: Read + Seek>(
+    q_wave_reader: core::QWaveReader<R>,
+    format: OutputFormat,
+) -> Result<(), String> {
+    match format {
+        OutputFormat::Text => {}
+        OutputFormat::Json => return print_info_json(q_wave_reader),
+        OutputFormat::Csv => {
+            return Err(String::from("info does not support CSV output"));
+        }
+    }
+
+    let info = q_wave_reader.metadata();
+    let proj = core::Project::from_reader(q_wave_reader).ok();
+    println!("Information");
+    println!("\tSample rate = {}", info.sample_rate);
+    println!(
+        "\tChannels = {}{}",
+        info.channels,
+        if info.channels == 1 {
+            ""
+        } else {
+            " (only mono files can be played or drawn)"
+        },
+    );
+    println!(
+        "\tBits per sample = {}{}",
+        info.bits_per_sample,
+        if info.float_samples { " (float)" } else { "" },
+    );
+
+    let duration_s = f64::from(info.sample_count) / f64::from(info.sample_rate);
+
+    println!(
+        "\tDuration = {} samples ({:.3}s)",
+        info.sample_count, duration_s,
+    );
+
+    println!("\tData size = {} bytes", info.data_bytes);
+
+    if let Some((declared, actual)) = info.declared_vs_actual {
+        println!(
+            "\tWarning: header declares {} samples, but data \
+            holds {}",
+            declared, actual,
+        );
+    }
+
+    match info.loop_start {
+        Some(start) => {
+            let cue_time = f64::from(start) / f64::from(info.sample_rate);
+
+            println!("\tLoop starts at sample {} ({:.3}s)", start, cue_time,);
+
+            if let Some(id) = info.loop_cue_id {
+                println!("\tLoop read from cue point {}", id);
+            } else if info.loop_source == Some(core::LoopSource::Smpl) {
+                println!("\tLoop read from smpl chunk");
+            }
+
+            if let Some(declared) = info.loop_end_past_eof {
+                println!(
+                    "\tWarning: loop length runs to sample {}, \
+                    past the end of the file; clamped to {}",
+                    declared,
+                    info.end.unwrap_or(info.sample_count),
+                );
+            }
+
+            let loop_end = info.end.unwrap_or(info.sample_count);
+
+            let end_time = f64::from(loop_end) / f64::from(info.sample_rate);
+
+            println!("\tLoop ends at sample {} ({:.3}s)", loop_end, end_time);
+
+            if let Some(Ok(score)) =
+                proj.as_ref().map(|proj| proj.seam_artifact_score())
+            {
+                println!("\tSeam artifact score = {:.1} dB", score);
+            }
+        }
+        None => println!("No loop point found"),
+    }
+
+    if let Some(play_end) = info.play_end {
+        let end_time = f64::from(play_end) / f64::from(info.sample_rate);
+
+        println!("\tPlayback ends at sample {} ({:.3}s)", play_end, end_time);
+    }
+
+    if let Some(stats) = proj.map(|proj| proj.loudness()) {
+        println!("Loudness");
+        println!("\tPeak = {:.2} dBFS", stats.peak_dbfs);
+        println!("\tRMS = {:.2} dBFS", stats.rms_dbfs);
+        println!(
+            "\tLoudest 400ms RMS = {:.2} dBFS",
+            stats.max_window_rms_dbfs
+        );
+
+        if let Some(loop_rms) = stats.loop_rms_dbfs {
+            println!("\tLoop RMS = {:.2} dBFS", loop_rms);
+        }
+    }
+
+    Ok(())
+}
+
+// Print how sharply the loop wraps, failing if its seam artifact score is
+// above -max-seam
+fn check_seam<R: Read + Seek>(
+    q_wave_reader: core::QWaveReader<R>,
+    args: &CommandArgs,
+) -> Result<(), String> {
+    let max_score = max_seam_score(args)?;
+    let report = core::Project::from_reader(q_wave_reader)?.seam_analysis()?;
+
+    match output_format(args)? {
+        OutputFormat::Text => println!("Seam\n{}", seam_report(&report)),
+        OutputFormat::Json => println!(
+            "{{\"amplitude_step\":{},\"amplitude_step_dbfs\":{},\
+            \"slope_step\":{},\"slope_step_dbfs\":{},\
+            \"spectral_excess\":{},\"spectral_excess_db\":{}}}",
+            report.amplitude_step,
+            json_level(report.amplitude_step_dbfs),
+            report.slope_step,
+            json_level(report.slope_step_dbfs),
+            report.spectral_excess,
+            json_level(report.spectral_excess_db),
+        ),
+        OutputFormat::Csv => {
+            return Err(String::from("check-seam does not support CSV output"));
+        }
+    }
+
+    if report.spectral_excess_db > max_score {
+        return Err(format!(
+            "Seam artifact score {:.1} dB is above {:.1} dB",
+            report.spectral_excess_db, max_score,
+        ));
+    }
+
+    Ok(())
+}
+
+// Describe the steps and spectral excess across a loop seam, a line each
+fn seam_report(report: &core::SeamReport) -> String {
+    format!(
+        "\tAmplitude step = {} ({:.1} dBFS)\n\
+        \tSlope step = {} ({:.1} dBFS)\n\
+        \tSpectral excess = {:.1} dB",
+        report.amplitude_step,
+        report.amplitude_step_dbfs,
+        report.slope_step,
+        report.slope_step_dbfs,
+        report.spectral_excess_db,
+    )
+}
+
+// Level in dBFS for JSON, with two decimals, or null for silence
+fn json_level(dbfs: f64) -> String {
+    if dbfs.is_finite() {
+        format!("{:.2}", dbfs)
+    } else {
+        String::from("null")
+    }
+}
+
+// Describe a file as print_info does, as one JSON object
+fn print_info_json<R: Read + Seek>(
+    q_wave_reader: core::QWaveReader<R>,
+) -> Result<(), String> {
+    let info = q_wave_reader.metadata();
+    let optional = |value: Option<u32>| {
+        value.map_or(String::from("null"), |value| value.to_string())
+    };
+    let level =
+        |dbfs: Option<f64>| dbfs.map_or(String::from("null"), json_level);
+    let markers = q_wave_reader
+        .markers()
+        .iter()
+        .map(|marker| {
+            format!(
+                "{{\"id\":{},\"position\":{},\"label\":{}}}",
+                marker.id,
+                marker.position,
+                output::json_string(&marker.label),
+            )
+        })
+        .collect::<Vec<_>>();
+    let loop_end = info
+        .loop_start
+        .map(|_| info.end.unwrap_or(info.sample_count));
+    let stats = core::Project::from_reader(q_wave_reader)
+        .ok()
+        .map(|proj| proj.loudness());
+
+    println!(
+        "{{\"sample_rate\":{},\"sample_count\":{},\"duration_seconds\":{},\
+        \"bits_per_sample\":{},\"float_samples\":{},\"channels\":{},\
+        \"data_bytes\":{},\"loop_start\":{},\"loop_end\":{},\
+        \"play_end\":{},\"peak_dbfs\":{},\"rms_dbfs\":{},\
+        \"loop_rms_dbfs\":{},\"markers\":[{}]}}",
+        info.sample_rate,
+        info.sample_count,
+        f64::from(info.sample_count) / f64::from(info.sample_rate.max(1)),
+        info.bits_per_sample,
+        info.float_samples,
+        info.channels,
+        info.data_bytes,
+        optional(info.loop_start),
+        optional(loop_end),
+        optional(info.play_end),
+        level(stats.map(|stats| stats.peak_dbfs)),
+        level(stats.map(|stats| stats.rms_dbfs)),
+        level(stats.and_then(|stats| stats.loop_rms_dbfs)),
+        markers.join(","),
+    );
+
+    Ok(())
+}
+
+// List every marker, in the order of its cue point, with its time and any
+// region length
+fn print_markers<R: Read + Seek>(
+    q_wave_reader: core::QWaveReader<R>,
+    format: OutputFormat,
+) {
+    const HEADER: [&str; 5] = ["ID", "Sample", "Seconds", "Length", "Label"];
+
+    let rate = f64::from(q_wave_reader.metadata().sample_rate).max(1.0);
+    let markers = q_wave_reader.markers();
+    let seconds = |marker: &core::Marker| f64::from(marker.position) / rate;
+
+    match format {
+        OutputFormat::Text if markers.is_empty() => println!("No markers"),
+        OutputFormat::Text | OutputFormat::Csv => {
+            let rows = markers
+                .iter()
+                .map(|marker| {
+                    vec![
+                        marker.id.to_string(),
+                        marker.position.to_string(),
+                        format!("{:.3}", seconds(marker)),
+                        marker
+                            .length
+                            .map_or(String::from("-"), |len| len.to_string()),
+                        marker.label.clone(),
+                    ]
+                })
+                .collect::<Vec<_>>();
+
+            if format == OutputFormat::Text {
+                println!("{}", output::text_table(&HEADER, &rows));
+                return;
+            }
+
+            println!("{}", HEADER.join(","));
+
+            for row in &rows {
+                let cells = row
+                    .iter()
+                    .map(|cell| output::csv_field(cell))
+                    .collect::<Vec<_>>();
+                println!("{}", cells.join(","));
+            }
+        }
+        OutputFormat::Json => {
+            let objects = markers
+                .iter()
+                .map(|marker| {
+                    format!(
+                        "{{\"id\":{},\"position\":{},\"seconds\":{:.6},\
+                        \"length\":{},\"label\":{}}}",
+                        marker.id,
+                        marker.position,
+                        seconds(marker),
+                        marker.length.map_or(String::from("null"), |len| len
+                            .to_string()),
+                        output::json_string(&marker.label),
+                    )
+                })
+                .collect::<Vec<_>>();
+            println!("[{}]", objects.join(","));
+        }
+    }
+}
+
+// Arguments with the input as the output too, given -in-place
+fn in_place(args: &CommandArgs) -> Result<CommandArgs, String> {
+    let mut args = args.clone();
+
+    if !args.contains_key("in-place") {
+        return Ok(args);
+    }
+
+    let inpath = expect_arg(&args, "in")?.clone();
+
+    if args.contains_key("out") {
+        return Err(String::from("-in-place can't be given with an output"));
+    }
+
+    if inpath == STDIN_PATH {
+        return Err(String::from("Standard input can't be edited in place"));
+    }
+
+    args.insert("out", inpath);
+    Ok(args)
+}
+
+// Look up the port named by -target
+fn target_profile(
+    args: &CommandArgs,
+) -> Result<&'static core::TargetProfile, String> {
+    let target = expect_arg(args, "target")?;
+
+    core::TargetProfile::find(target).ok_or_else(|| {
+        let names = core::TARGET_PROFILES
+            .iter()
+            .map(|profile| profile.name)
+            .collect::<Vec<_>>();
+        format!(
+            "Unknown target \"{}\"; expected one of {}",
+            target,
+            names.join(", "),
+        )
+    })
+}
+
+// Write the input's loop to a sidecar, with a digest of its sample data
+fn export_loop_meta<R: Read + Seek>(
+    args: &CommandArgs,
+    mut reader: R,
+) -> Result<(), String> {
+    let outpath = Path::new(expect_arg(args, "out")?);
+    let digest = core::hash::digest_wave(&mut reader)?;
+    reader.rewind().map_err(|e| e.to_string())?;
+
+    let q_wave_reader = core::QWaveReader::new(reader)?;
+    let label = q_wave_reader.loop_label().map(String::from);
+    let mut proj = core::Project::from_reader(q_wave_reader)?;
+    proj.set_loop_label(label);
+
+    let sidecar = core::LoopSidecar::from_project(&proj, digest.data)?;
+
+    if outpath.extension().and_then(|ext| ext.to_str())
+        != Some(core::LOOP_SIDECAR_EXTENSION)
+    {
+        eprintln!(
+            "Warning: loop sidecars are usually named with a .{} extension",
+            core::LOOP_SIDECAR_EXTENSION,
+        );
+    }
+
+    fs::write(outpath, sidecar.to_toml())
+        .map_err(|e| format!("{}: {}", outpath.display(), e))
+}
+
+// Inputs of a sub-command taking many
+fn inputs(args: &CommandArgs) -> Result<Vec<&Path>, String> {
+    Ok(expect_arg(args, "in")?
+        .split(INPUT_SEPARATOR)
+        .map(Path::new)
+        .collect())
+}
+
+// Run a write command over each input in turn, writing each to the output
+// directory under its own name, and going on past those that fail
+fn run_batch((cmd, args): Command) -> Result<(), String> {
+    let inputs = inputs(&args)?;
+    let out_dir = Path::new(expect_arg(&args, "out-dir")?);
+
+    if !out_dir.is_dir() {
+        return Err(format!("{} is not a directory", out_dir.display()));
+    }
+
+    if inputs.contains(&Path::new(STDIN_PATH)) {
+        return Err(String::from("Standard input can't be one of many inputs"));
+    }
+
+    if args.contains_key("pak") {
+        return Err(String::from("-pak can't be given with -out-dir"));
+    }
+
+    let run_one = |inpath: &Path| -> Result<(), String> {
+        let name = inpath.file_name().ok_or("Not a file")?;
+        let outpath = out_dir.join(name);
+        let mut args = args.clone();
+        args.remove("out-dir");
+        args.insert("in", inpath.to_string_lossy().into_owned());
+        args.insert("out", outpath.to_string_lossy().into_owned());
+
+        run_command((cmd, args))
+    };
+
+    let results = inputs
+        .iter()
+        .map(|&inpath| {
+            println!("{}:", inpath.display());
+            (inpath, run_one(inpath))
+        })
+        .collect::<Vec<_>>();
+
+    let name_width = inputs
+        .iter()
+        .map(|inpath| inpath.display().to_string().len())
+        .max()
+        .unwrap_or(0)
+        .max("File".len());
+
+    println!("\n{:<name_width$}  Result", "File");
+
+    for (inpath, result) in &results {
+        match result {
+            Ok(()) => println!("{:<name_width$}  ok", inpath.display()),
+            Err(e) => println!("{:<name_width$}  {}", inpath.display(), e),
+        }
+    }
+
+    let failed = results.iter().filter(|(_, result)| result.is_err()).count();
+
+    if failed > 0 {
+        Err(format!("{} of {} files failed", failed, results.len()))
+    } else {
+        Ok(())
+    }
+}
+
+// Place and blend the same loop in many files, writing each to the output
+// directory under its own name
+fn apply_template(args: &CommandArgs) -> Result<(), String> {
+    let inputs = inputs(args)?;
+    let out_dir = Path::new(expect_arg(args, "out-dir")?);
+    let spec = expect_arg(args, "template")?;
+
+    let mut template = if spec.to_ascii_lowercase().ends_with(".wav") {
+        let metadata = core::QWaveReader::open(spec)?.metadata();
+        core::LoopTemplate::from_reference(&metadata)?
+    } else {
+        core::LoopTemplate::parse(spec)?
+    };
+
+    if let Some(duration) = args.get("duration") {
+        template.blend = Some(core::TimeSpec::parse(duration)?);
+    }
+
+    if !out_dir.is_dir() {
+        return Err(format!("{} is not a directory", out_dir.display()));
+    }
+
+    let options = core::WriteOptions {
+        sync: args.contains_key("sync"),
+        reencode: args.contains_key("reencode"),
+    };
+
+    let apply = |inpath: &Path| -> Result<core::ResolvedTemplate, String> {
+        let name = inpath.file_name().ok_or("Not a file")?;
+        let mut project = core::Project::open(inpath)?;
+
+        let resolved = template.apply(&mut project)?;
+        project.write_to_with(&out_dir.join(name), options)?;
+        Ok(resolved)
+    };
+
+    let results = inputs
+        .iter()
+        .map(|&inpath| (inpath, apply(inpath)))
+        .collect::<Vec<_>>();
+
+    let name_width = inputs
+        .iter()
+        .map(|inpath| inpath.display().to_string().len())
+        .max()
+        .unwrap_or(0)
+        .max("File".len());
+
+    println!(
+        "{:<name_width$}  {:>10}  {:>10}  {:>8}  Result",
+        "File", "Start", "End", "Blend",
+    );
+
+    for (inpath, result) in &results {
+        match result {
+            Ok(resolved) => println!(
+                "{:<name_width$}  {:>10}  {:>10}  {:>8}  ok",
+                inpath.display(),
+                resolved.sample_loop.start,
+                resolved.sample_loop.end,
+                resolved
+                    .blend
+                    .map(|window_sz| window_sz.to_string())
+                    .unwrap_or(String::from("-")),
+            ),
+            Err(e) => println!(
+                "{:<name_width$}  {:>10}  {:>10}  {:>8}  {}",
+                inpath.display(),
+                "-",
+                "-",
+                "-",
+                e,
+            ),
+        }
+    }
+
+    let failed = results.iter().filter(|(_, result)| result.is_err()).count();
+
+    if failed > 0 {
+        Err(format!("{} of {} files failed", failed, results.len()))
+    } else {
+        Ok(())
+    }
+}
+
+// Output devices, marking the default, with the sample rates each supports
+fn print_devices() -> Result<(), String> {
+    let devices = core::Player::devices()?;
+
+    if devices.is_empty() {
+        println!("No output devices found");
+    }
+
+    for device in devices {
+        let rates = device
+            .sample_rates
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>();
+
+        println!(
+            "{}{}\t{}",
+            device.name,
+            if device.is_default { " (default)" } else { "" },
+            if rates.is_empty() {
+                String::from("no supported rates")
+            } else {
+                format!("{} Hz", rates.join(", "))
+            },
+        );
+    }
+
+    Ok(())
+}
+
+// Version of this build, with the commit, cargo features, and dependency
+// versions embedded by the build script
+fn print_version(format: OutputFormat) -> Result<(), String> {
+    let version = env!("CARGO_PKG_VERSION");
+    let commit = env!("QUADIO_GIT_COMMIT");
+    let split = |list: &'static str| list.split(',').filter(|s| !s.is_empty());
+    let features = split(env!("QUADIO_FEATURES")).collect::<Vec<_>>();
+    let dependencies = split(env!("QUADIO_DEPENDENCIES"))
+        .filter_map(|dependency| dependency.split_once(' '))
+        .collect::<Vec<_>>();
+
+    match format {
+        OutputFormat::Text => {
+            println!("quadio-cli {}", version);
+            println!("\tCommit = {}", commit);
+
+            if features.is_empty() {
+                println!("\tFeatures = none");
+            } else {
+                println!("\tFeatures = {}", features.join(", "));
+            }
+
+            for (name, version) in dependencies {
+                println!("\t{} = {}", name, version);
+            }
+        }
+        OutputFormat::Json => {
+            let features = features
+                .iter()
+                .map(|feature| output::json_string(feature))
+                .collect::<Vec<_>>();
+            let dependencies = dependencies
+                .iter()
+                .map(|(name, version)| {
+                    format!(
+                        "{}:{}",
+                        output::json_string(name),
+                        output::json_string(version),
+                    )
+                })
+                .collect::<Vec<_>>();
+
+            println!(
+                "{{\"version\":{},\"commit\":{},\"features\":[{}],\
+                \"dependencies\":{{{}}}}}",
+                output::json_string(version),
+                output::json_string(commit),
+                features.join(","),
+                dependencies.join(","),
+            );
+        }
+        OutputFormat::Csv => {
+            return Err(String::from("version does not support CSV output"));
+        }
+    }
+
+    Ok(())
+}
+
+fn show_config(action: Option<&str>) -> Result<(), String> {
+    let path = config::path()
+        .ok_or("No config location; set QUADIO_CONFIG or HOME")?;
+
+    let print_entry = |key: &str, value: &str| {
+        if value.is_empty() {
+            println!("{} = true", key);
+        } else {
+            println!("{} = {}", key, output::json_string(value));
+        }
+    };
+    let print_path = || {
+        if !path.exists() {
+            println!("# {} (not found)", path.display());
+        } else {
+            println!("# {}", path.display());
+        }
+    };
+
+    match action.map(|action| (action, CommandKind::try_from(action))) {
+        Some(("path", _)) => println!("{}", path.display()),
+        Some(("show", _)) | None => {
+            let config = config::load();
+            print_path();
+
+            if !config.global().is_empty() {
+                println!();
+            }
+
+            for (key, value) in config.global() {
+                print_entry(key, value);
+            }
+
+            for (name, entries) in config.sections() {
+                println!("\n[{}]", name);
+
+                for (key, value) in entries {
+                    print_entry(key, value);
+                }
+            }
+        }
+        Some((_, Ok(cmd))) => {
+            let (_, args) =
+                apply_config((cmd, HashMap::new()), &config::load());
+            let mut args = args.into_iter().collect::<Vec<_>>();
+            args.sort();
+            print_path();
+            println!("\n[{}]", cmd.name());
+
+            for (key, value) in args {
+                print_entry(key, &value);
+            }
+        }
+        Some((other, Err(_))) => {
+            return Err(format!("Unknown config action \"{}\"", other));
+        }
+    }
+
+    Ok(())
+}
+
+// Seam artifact score, in dB, above which a loop fails checks
+fn max_seam_score(args: &CommandArgs) -> Result<f64, String> {
+    args.get("max-seam")
+        .map(|db| db.trim_end_matches("dB").parse::<f64>())
+        .transpose()
+        .map_err(|e| format!("Bad maximum seam score: {}", e))
+        .map(|db| db.unwrap_or(verify::MAX_SEAM_SCORE))
+}
+
+fn output_format(args: &CommandArgs) -> Result<OutputFormat, String> {
+    args.get("format")
+        .map(|format| OutputFormat::try_from(&format[..]))
+        .transpose()
+        .map(|format| format.unwrap_or(OutputFormat::Text))
+}
+
+fn loop_chunk_format(name: &str) -> Result<core::LoopChunkFormat, String> {
+    match name {
+        "cue" => Ok(core::LoopChunkFormat::Cue),
+        "smpl" => Ok(core::LoopChunkFormat::Smpl),
+        "both" => Ok(core::LoopChunkFormat::Both),
+        other => Err(format!(
+            "Unknown loop chunk format \"{}\"; expected cue, smpl, or both",
+            other
+        )),
+    }
+}
+
+fn blend_shape(name: &str) -> Result<core::dsp::BlendShape, String> {
+    match name {
+        "smooth" => Ok(core::dsp::BlendShape::Smooth),
+        "linear" => Ok(core::dsp::BlendShape::Linear),
+        "equal-power" => Ok(core::dsp::BlendShape::EqualPower),
+        other => Err(format!(
+            "Unknown blend curve \"{}\"; expected smooth, linear, or \
+            equal-power",
+            other
+        )),
+    }
+}
+
+fn resample_quality(name: &str) -> Result<core::dsp::ResampleQuality, String> {
+    match name {
+        "fast" => Ok(core::dsp::ResampleQuality::Fast),
+        "medium" => Ok(core::dsp::ResampleQuality::Medium),
+        "high" => Ok(core::dsp::ResampleQuality::High),
+        other => Err(format!(
+            "Unknown resample quality \"{}\"; expected fast, medium, or high",
+            other
+        )),
+    }
+}
+
+// Print the loops suggested for the project, returning the best
+fn print_loop_candidates(
+    args: &CommandArgs,
+    proj: &core::Project,
+) -> Result<Option<core::LoopCandidate>, String> {
+    let min_length = args
+        .get("min-length")
+        .map(|time| parse_time(time, proj))
+        .transpose()?
+        .unwrap_or(proj.sample_rate());
+    let range = args
+        .get("range")
+        .map(|range| parse_range(range, proj))
+        .transpose()?;
+
+    let candidates = proj.find_loop(min_length, range)?;
+    let seconds = |frame: u32| f64::from(frame) / f64::from(proj.sample_rate());
+
+    if candidates.is_empty() {
+        note(args, "No loop candidates found");
+    }
+
+    for (rank, candidate) in candidates.iter().enumerate() {
+        note(
+            args,
+            format!(
+                "{}. {}..{} ({:.3}s..{:.3}s), score {:.3}",
+                rank + 1,
+                candidate.start,
+                candidate.end,
+                seconds(candidate.start),
+                seconds(candidate.end),
+                candidate.score,
+            ),
+        );
+    }
+
+    Ok(candidates.first().copied())
+}
+
+fn run_write_command<'a>(
+    (cmd, args): Command,
+    mut proj: core::Project,
+    open_file: &dyn Fn() -> Result<Box<dyn Input + 'a>, String>,
+) -> Result<(), String> {
+    if args.contains_key("preview") {
+        if args.contains_key("out") || args.contains_key("out-dir") {
+            return Err(String::from("-preview can't be given with an output"));
+        }
+
+        edit_project((cmd, &args), &mut proj, open_file)?;
+        return preview_project(&args, proj);
+    }
+
+    let outpath = Path::new(expect_arg(&args, "out")?);
+    let to_stdout = outpath == Path::new(STDOUT_PATH);
+
+    // Given a pak, the output names an entry in it
+    let pak = args.get("pak").filter(|_| !to_stdout).map(Path::new);
+
+    for argname in ["backup", "hash"] {
+        if (to_stdout || pak.is_some()) && args.contains_key(argname) {
+            return Err(format!("-{} needs an output file", argname));
+        }
+    }
+
+    edit_project((cmd, &args), &mut proj, open_file)?;
+
+    let warnings = if matches!(
+        cmd,
+        CommandKind::SetLoop
+            | CommandKind::Blend
+            | CommandKind::Strip
+            | CommandKind::Convert
+    ) {
+        proj.lint()
+    } else {
+        vec![]
+    };
+
+    if args.contains_key("strict") && !warnings.is_empty() {
+        let warnings =
+            warnings.iter().map(ToString::to_string).collect::<Vec<_>>();
+        return Err(warnings.join("; "));
+    }
+
+    if let Some(keep) = args.get("backup") {
+        let keep = if keep.is_empty() {
+            1
+        } else {
+            keep.parse::<usize>()
+                .map_err(|e| format!("Bad backup count: {}", e))?
+        };
+
+        proj.validate()?;
+
+        if let Some(backup) = backup::backup(outpath, keep)? {
+            println!("Backed up {} to {}", outpath.display(), backup.display());
+        }
+    }
+
+    let options = core::WriteOptions {
+        sync: args.contains_key("sync"),
+        reencode: args.contains_key("reencode"),
+    };
+
+    if to_stdout {
+        // Appending the loop's chunks needs to seek, which pipes can't
+        let mut buffer = io::Cursor::new(vec![]);
+        proj.write_with(&mut buffer, options)?;
+
+        let mut stdout = io::stdout().lock();
+        io::Write::write_all(&mut stdout, buffer.get_ref())
+            .and_then(|_| io::Write::flush(&mut stdout))
+            .map_err(|e| e.to_string())?;
+    } else if let Some(pak) = pak {
+        let mut buffer = io::Cursor::new(vec![]);
+        proj.write_with(&mut buffer, options)?;
+
+        let mut archive = core::PakReader::open(pak)
+            .and_then(|mut reader| core::PakWriter::from_reader(&mut reader))
+            .map_err(|e| e.at_path(pak))?;
+        archive.replace(&args["out"], buffer.into_inner())?;
+        archive.write_to(&pak, options.sync)?;
+    } else {
+        proj.write_to_with(&outpath, options)?;
+    }
+
+    match (proj.sample_loop(), proj.loop_duration_seconds()) {
+        (Some(sample_loop), Some(seconds)) => note(
+            &args,
+            format!(
+                "Loop is {}..{} ({:.3}s)",
+                sample_loop.start, sample_loop.end, seconds,
+            ),
+        ),
+        _ => note(&args, "No loop"),
+    }
+
+    for warning in warnings {
+        eprintln!("Warning: {}", warning);
+    }
+
+    if args.contains_key("hash") {
+        let mut outfile = io::BufReader::new(
+            fs::File::open(outpath).map_err(|e| e.to_string())?,
+        );
+        let digest = core::hash::digest_wave(&mut outfile)?;
+        print_digest(outpath, &digest, output_format(&args)?)?;
+    }
+
+    Ok(())
+}
+
+// Make the edit a write command stands for, as it is written or previewed
+fn edit_project<'a>(
+    (cmd, args): (CommandKind, &CommandArgs),
+    proj: &mut core::Project,
+    open_file: &dyn Fn() -> Result<Box<dyn Input + 'a>, String>,
+) -> Result<(), String> {
+    match cmd {
+        CommandKind::Strip => {
+            proj.set_loop(None);
+            proj.set_play_end(None);
+        }
+        CommandKind::Clean => {}
+        CommandKind::RetagRate => {
+            let rate = expect_arg(args, "rate")?
+                .parse::<u32>()
+                .map_err(|e| format!("Bad sample rate: {}", e))?;
+
+            let old_rate = proj.sample_rate();
+            let frames = f64::from(proj.sample_count());
+            proj.set_sample_rate_raw(rate)?;
+
+            if !COMMON_RATES.contains(&rate) {
+                eprintln!(
+                    "Warning: {} Hz is outside the usual {}-{} Hz",
+                    rate,
+                    COMMON_RATES.start(),
+                    COMMON_RATES.end(),
+                );
+            }
+
+            note(
+                args,
+                format!(
+                    "Sample rate {} Hz -> {} Hz; duration {:.3}s -> {:.3}s",
+                    old_rate,
+                    rate,
+                    frames / f64::from(old_rate),
+                    frames / f64::from(rate),
+                ),
+            );
+        }
+        CommandKind::Convert => {
+            let rate = args
+                .get("rate")
+                .map(|rate| rate.parse::<u32>())
+                .transpose()
+                .map_err(|e| format!("Bad sample rate: {}", e))?;
+            let format =
+                args.get("bits").map(|bits| sample_fmt(bits)).transpose()?;
+
+            if (rate, format) == (None, None) {
+                return Err(String::from("Expected -rate or -bits"));
+            }
+
+            let old_rate = proj.sample_rate();
+            let rate = rate.unwrap_or(old_rate);
+
+            if rate > old_rate
+                && format.unwrap_or(proj.render_format())
+                    == core::SampleFmt::Unsigned8
+                && !args.contains_key("yes")
+            {
+                return Err(format!(
+                    "Upsampling {} Hz to {} Hz gains nothing written as 8 \
+                    bits but a larger file; use -yes to convert anyway",
+                    old_rate, rate,
+                ));
+            }
+
+            let (old_count, old_loop) =
+                (proj.sample_count(), proj.sample_loop());
+            proj.resample(rate, core::dsp::ResampleQuality::High)?;
+
+            note(
+                args,
+                format!(
+                    "Sample rate {} Hz -> {} Hz; {} -> {} samples",
+                    old_rate,
+                    rate,
+                    old_count,
+                    proj.sample_count(),
+                ),
+            );
+
+            if let (Some(old_loop), Some(sample_loop)) =
+                (old_loop, proj.sample_loop())
+            {
+                note(
+                    args,
+                    format!(
+                        "Loop {}..{} -> {}..{}",
+                        old_loop.start,
+                        old_loop.end,
+                        sample_loop.start,
+                        sample_loop.end,
+                    ),
+                );
+            }
+
+            if let Some(duration) = args.get("blend") {
+                let click_free = proj.click_free_blend_window();
+                let window_sz = if duration.is_empty() {
+                    proj.default_blend_window()
+                } else {
+                    parse_time(duration, proj)?
+                };
+
+                confirm_blend(args, proj.blend_impact(window_sz)?)?;
+                proj.blend(window_sz)?;
+                note_blend_window(
+                    args,
+                    window_sz,
+                    duration.is_empty().then_some(click_free),
+                );
+            }
+        }
+        CommandKind::SetLoop => {
+            let (start, end) = if let Some(labels) = args.get("from-markers") {
+                if args.contains_key("start") || args.contains_key("end") {
+                    return Err(String::from(
+                        "-from-markers can't be given with -start or -end",
+                    ));
+                }
+
+                let (start, end) = labels
+                    .split_once(',')
+                    .ok_or("Markers must be given as <LABEL>,<LABEL>")?;
+                let position = |label: &str| {
+                    proj.markers()
+                        .iter()
+                        .find(|marker| marker.label == label)
+                        .map(|marker| marker.position)
+                        .ok_or_else(|| {
+                            format!("No marker labeled \"{}\"", label)
+                        })
+                };
+
+                (position(start)?, position(end)?)
+            } else {
+                let start = parse_time(expect_arg(args, "start")?, proj)?;
+                let end = args
+                    .get("end")
+                    .map(|e| parse_time(e, proj))
+                    .transpose()?
+                    .unwrap_or(proj.sample_count());
+
+                (start, end)
+            };
+
+            proj.set_loop(Some(start..end));
+
+            if let Some(max_shift) = args.get("snap") {
+                let max_shift = parse_time(max_shift, proj)?;
+                let snapped = proj.snap_loop_to_zero_crossings(max_shift)?;
+
+                for (name, point) in [("start", snapped.0), ("end", snapped.1)]
+                {
+                    if point.is_none() {
+                        eprintln!(
+                            "Warning: no rising zero crossing within {} \
+                            samples of the loop {}; left as given",
+                            max_shift, name,
+                        );
+                    }
+                }
+            }
+
+            if let Some(format) = args.get("loop-chunks") {
+                proj.set_loop_chunk_format(loop_chunk_format(format)?);
+            }
+
+            if let Some(play_end) = args.get("end-marker") {
+                proj.set_play_end(Some(parse_time(play_end, proj)?));
+            }
+        }
+        CommandKind::SetEnd => {
+            let play_end = parse_time(expect_arg(args, "at")?, proj)?;
+            proj.set_play_end(Some(play_end));
+        }
+        CommandKind::Trim => {
+            let start = args
+                .get("start")
+                .map(|start| parse_time(start, proj))
+                .transpose()?;
+            let end = args
+                .get("end")
+                .map(|end| parse_time(end, proj))
+                .transpose()?;
+
+            if (start, end, proj.sample_loop()) == (None, None, None) {
+                return Err(String::from(
+                    "No loop to trim after; give -start or -end",
+                ));
+            }
+
+            let end = end
+                .or(proj.sample_loop().map(|sample_loop| sample_loop.end))
+                .unwrap_or(proj.sample_count());
+            let frames = proj.sample_count();
+            proj.trim(start.unwrap_or(0)..end)?;
+            note(
+                args,
+                format!(
+                    "Trimmed {} to {} samples",
+                    frames,
+                    proj.sample_count()
+                ),
+            );
+        }
+        CommandKind::FindLoop => {
+            let best =
+                print_loop_candidates(args, proj)?.ok_or("No loop to apply")?;
+            proj.set_loop(Some(best.start..best.end));
+        }
+        CommandKind::Unroll => {
+            let count = expect_arg(args, "count")?
+                .parse::<u32>()
+                .map_err(|e| format!("Bad loop count: {}", e))?;
+            let frames = proj.sample_count();
+            proj.unroll(count, args.contains_key("tail"))?;
+            note(
+                args,
+                format!(
+                    "Unrolled {} to {} samples",
+                    frames,
+                    proj.sample_count()
+                ),
+            );
+        }
+        CommandKind::Normalize => {
+            let target = |argname| {
+                args.get(argname)
+                    .map(|db| db.trim_end_matches("dB").parse::<f64>())
+                    .transpose()
+                    .map_err(|e| format!("Bad {} target: {}", argname, e))
+            };
+
+            let normalization = match (target("peak")?, target("rms")?) {
+                (Some(peak), None) => proj.normalize_peak(peak)?,
+                (None, Some(rms)) => proj.normalize_rms(rms)?,
+                _ => {
+                    return Err(String::from("Expected one of -peak or -rms"));
+                }
+            };
+
+            note(
+                args,
+                format!("Applied {:+.2} dB of gain", normalization.gain_db),
+            );
+
+            if normalization.clipped > 0 {
+                note(
+                    args,
+                    format!("Clipped {} samples", normalization.clipped),
+                );
+            }
+        }
+        CommandKind::Extract => {
+            if proj.sample_loop().is_none() {
+                return Err(String::from(
+                    "No loop to extract; set one with set-loop first",
+                ));
+            }
+
+            if args.contains_key("with-blend") {
+                let window = proj.blend_default_window()?;
+                note_blend_window(args, window.window_sz, window.clamped_from);
+            }
+
+            let frames = proj.sample_count();
+            *proj = proj.extract_loop()?;
+            note(
+                args,
+                format!(
+                    "Extracted the loop's {} of {} samples",
+                    proj.sample_count(),
+                    frames,
+                ),
+            );
+        }
+        CommandKind::MatchLoudness => {
+            let reference = expect_arg(args, "reference")?;
+            let stats = core::Project::open(reference)?.loudness();
+            let target = stats.loop_rms_dbfs.unwrap_or(stats.rms_dbfs);
+
+            if !target.is_finite() {
+                return Err(format!("Reference {} is silent", reference));
+            }
+
+            let normalization = proj.normalize_loop_rms(target)?;
+
+            note(
+                args,
+                format!(
+                    "Matched {} RMS of {:.2} dBFS",
+                    if stats.loop_rms_dbfs.is_some() {
+                        "loop"
+                    } else {
+                        "file"
+                    },
+                    target,
+                ),
+            );
+            note(
+                args,
+                format!("Applied {:+.2} dB of gain", normalization.gain_db),
+            );
+
+            if normalization.clipped > 0 {
+                note(
+                    args,
+                    format!("Clipped {} samples", normalization.clipped),
+                );
+            }
+        }
+        CommandKind::Pad => {
+            let frames = |argname| {
+                args.get(argname)
+                    .map(|time| parse_time(time, proj))
+                    .transpose()
+            };
+            let (before, after) = match (frames("before")?, frames("after")?) {
+                (None, None) => {
+                    return Err(String::from("Expected -before or -after"));
+                }
+                (before, after) => (before.unwrap_or(0), after.unwrap_or(0)),
+            };
+
+            let old_count = proj.sample_count();
+            proj.pad(before, after)?;
+            note(
+                args,
+                format!(
+                    "Padded {} to {} samples",
+                    old_count,
+                    proj.sample_count(),
+                ),
+            );
+        }
+        CommandKind::Concat => {
+            let other = core::Project::open(expect_arg(args, "append")?)?;
+            let frames = proj.sample_count();
+
+            if args.contains_key("loop-second") {
+                proj.append_as_loop(&other)?;
+            } else {
+                proj.append(&other)?;
+            }
+
+            note(
+                args,
+                format!(
+                    "Appended {} samples to {}",
+                    other.sample_count(),
+                    frames,
+                ),
+            );
+        }
+        CommandKind::DcOffset => {
+            let offsets = proj.remove_dc_offset();
+
+            if offsets.iter().all(|&offset| offset == 0) {
+                note(args, "No DC offset to remove");
+            } else {
+                let offsets =
+                    offsets.iter().map(ToString::to_string).collect::<Vec<_>>();
+                note(
+                    args,
+                    format!("Removed a DC offset of {}", offsets.join(", ")),
+                );
+            }
+        }
+        CommandKind::ImportLoopMeta => {
+            let from = expect_arg(args, "from")?;
+            let sidecar = core::LoopSidecar::parse(
+                &fs::read_to_string(from)
+                    .map_err(|e| format!("{}: {}", from, e))?,
+            )
+            .map_err(|e| format!("{}: {}", from, e))?;
+
+            let digest = core::hash::digest_wave(&mut open_file()?)?;
+
+            if let Err(e) = sidecar.verify_source(&digest.data) {
+                if args.contains_key("ignore-hash") {
+                    eprintln!("Warning: {}", e);
+                } else {
+                    return Err(format!(
+                        "{}; use -ignore-hash to apply it anyway",
+                        e
+                    ));
+                }
+            }
+
+            sidecar.apply(proj)?;
+        }
+        CommandKind::Blend => {
+            let blend_duration = args
+                .get("duration")
+                .map(|e| parse_time(e, proj))
+                .transpose()?;
+
+            let clamp = args.contains_key("clamp");
+            let click_free = proj.click_free_blend_window();
+            let window_sz = match (blend_duration, clamp) {
+                (_, true) => proj.clamp_blend_window(
+                    blend_duration.unwrap_or(proj.default_blend_window()),
+                )?,
+                (Some(window_sz), false) => window_sz,
+                (None, false) => proj.default_blend_window(),
+            };
+
+            let shape = args
+                .get("curve")
+                .map(|name| blend_shape(name))
+                .transpose()?
+                .unwrap_or_default();
+
+            let symmetric = args.contains_key("symmetric");
+            let mut impact = proj.blend_impact(window_sz)?;
+
+            // Symmetric blends rewrite a window either side of the wrap
+            if symmetric {
+                impact.window_sz = impact.window_sz.saturating_mul(2);
+            }
+
+            confirm_blend(args, impact)?;
+            let before = proj.seam_analysis().ok();
+
+            if args.contains_key("entry") {
+                proj.blend_entry(window_sz, shape)?;
+            }
+
+            if symmetric {
+                proj.blend_symmetric(window_sz, shape)?;
+            } else {
+                proj.blend_with_shape(window_sz, shape)?;
+            }
+
+            if clamp || blend_duration.is_none() {
+                note_blend_window(
+                    args,
+                    window_sz,
+                    blend_duration.is_none().then_some(click_free),
+                );
+            }
+
+            // Loops too short to score are blended all the same
+            if let (Some(before), Ok(after)) = (before, proj.seam_analysis()) {
+                note(args, format!("Seam before\n{}", seam_report(&before)));
+                note(args, format!("Seam after\n{}", seam_report(&after)));
+            }
+        }
+        _ => {
+            unreachable!();
+        }
+    };
+
+    if let Some(bits) = args.get("bits") {
+        let seed = args
+            .get("seed")
+            .map(|seed| seed.parse::<u64>())
+            .transpose()
+            .map_err(|e| format!("Bad seed: {}", e))?
+            .unwrap_or(package::DITHER_SEED);
+        proj.set_render_format(sample_fmt(bits)?, seed);
+    }
+
+    if let Some(id) = args.get("cue-id") {
+        let id = id
+            .parse::<u32>()
+            .map_err(|e| format!("Bad cue ID: {}", e))?;
+        proj.set_loop_cue_id(id);
+    }
+
+    if let Some(label) = args.get("cue-label") {
+        proj.set_loop_label(Some(label.clone()));
+    }
+
+    Ok(())
+}
+
+// Play an edited project looped from shortly before the loop end, as -seam
+// does, without writing anything
+fn preview_project(
+    args: &CommandArgs,
+    proj: core::Project,
+) -> Result<(), String> {
+    if proj.sample_loop().is_none() {
+        return Err(String::from("No loop to preview"));
+    }
+
+    // -start is the loop start to set-loop, so the preview drops it
+    let mut args = args.clone();
+    args.remove("start");
+    args.insert("seam", String::new());
+
+    let metadata = proj.metadata()?;
+    play_project(
+        proj,
+        metadata,
+        true,
+        core::PlaybackBounds::ToEndMarker,
+        &args,
+    )
+}
+
+fn print_digest(
+    path: &Path,
+    digest: &core::hash::WaveDigest,
+    format: OutputFormat,
+) -> Result<(), String> {
+    let data = core::hash::to_hex(&digest.data);
+    let file = core::hash::to_hex(&digest.file);
+
+    match format {
+        OutputFormat::Text => {
+            println!("SHA-256 of {}", path.display());
+            println!("\tData = {}", data);
+            println!("\tFile = {}", file);
+        }
+        OutputFormat::Json => println!(
+            "{{\"path\":{},\"data\":\"{}\",\"file\":\"{}\"}}",
+            output::json_string(&path.to_string_lossy()),
+            data,
+            file,
+        ),
+        OutputFormat::Csv => {
+            return Err(String::from("hash does not support CSV output"));
+        }
+    }
+
+    Ok(())
+}
+
+// Format written for -bits
+fn sample_fmt(bits: &str) -> Result<core::SampleFmt, String> {
+    match bits {
+        "8" => Ok(core::SampleFmt::Unsigned8),
+        "16" => Ok(core::SampleFmt::Signed16),
+        other => Err(format!(
+            "Unsupported bit depth \"{}\"; expected 8 or 16",
+            other
+        )),
+    }
+}
+
+// Playback volume given as a ratio or a percentage, e.g. 0.3 or 30%
+fn parse_volume(volume: &str) -> Result<f32, String> {
+    let parsed = match volume.strip_suffix('%') {
+        Some(percent) => percent.parse::<f32>().map(|percent| percent / 100.0),
+        None => volume.parse::<f32>(),
+    }
+    .map_err(|e| format!("Bad volume: {}", e))?;
+
+    if (0.0..=core::MAX_VOLUME).contains(&parsed) {
+        Ok(parsed)
+    } else {
+        Err(format!(
+            "Volume must be from 0 to {}%",
+            core::MAX_VOLUME * 100.0
+        ))
+    }
+}
+
+// Ask before a blend rewrites more of the loop than -max-impact allows
+fn confirm_blend(
+    args: &CommandArgs,
+    impact: core::BlendImpact,
+) -> Result<(), String> {
+    let max_impact = args
+        .get("max-impact")
+        .map(|percent| percent.trim_end_matches('%').parse::<f64>())
+        .transpose()
+        .map_err(|e| format!("Bad maximum impact: {}", e))?
+        .unwrap_or(MAX_BLEND_IMPACT);
+
+    let percent = impact.fraction() * 100.0;
+
+    if percent <= max_impact {
+        return Ok(());
+    }
+
+    note(
+        args,
+        format!(
+            "Blend window of {} samples covers {:.1}% of the {}-sample loop, \
+            more than {}%",
+            impact.window_sz, percent, impact.loop_length, max_impact,
+        ),
+    );
+
+    if args.contains_key("yes") {
+        return Ok(());
+    }
+
+    // The prompt goes where notes do, clear of a WAV written to stdout
+    if args.get("out").map(String::as_str) == Some(STDOUT_PATH) {
+        eprint!("Continue? [y/N] ");
+        io::Write::flush(&mut io::stderr()).map_err(|e| e.to_string())?;
+    } else {
+        print!("Continue? [y/N] ");
+        io::Write::flush(&mut io::stdout()).map_err(|e| e.to_string())?;
+    }
+
+    let mut answer = String::new();
+    io::stdin()
+        .read_line(&mut answer)
+        .map_err(|e| e.to_string())?;
+
+    if matches!(answer.trim(), "y" | "Y" | "yes") {
+        Ok(())
+    } else {
+        Err(String::from("Blend cancelled"))
+    }
+}
+
+fn report_discarded(
+    args: &CommandArgs,
+    chunks: &[core::ChunkEntry],
+    trailing_bytes: u64,
+) {
+    for chunk in chunks {
+        note(
+            args,
+            format!(
+                "Discarded chunk \"{}\" ({} bytes)",
+                String::from_utf8_lossy(&chunk.tag),
+                chunk.size,
+            ),
+        );
+    }
+
+    if trailing_bytes > 0 {
+        note(args, format!("Discarded {} trailing bytes", trailing_bytes));
+    }
+
+    if chunks.is_empty() && trailing_bytes == 0 {
+        note(args, "Nothing to discard");
+    }
+}
+
+// Print what a write command did, to standard error when the WAV itself is
+// going to standard output
+fn note(args: &CommandArgs, message: impl std::fmt::Display) {
+    if args.get("out").map(String::as_str) == Some(STDOUT_PATH) {
+        eprintln!("{}", message);
+    } else {
+        println!("{}", message);
+    }
+}
+
+// Note the window a blend was made over, warning if it falls short of the
+// click-free window it was meant to be
+fn note_blend_window(
+    args: &CommandArgs,
+    window_sz: u32,
+    click_free: Option<u32>,
+) {
+    note(args, format!("Blended over {} samples", window_sz));
+
+    if let Some(click_free) = click_free.filter(|&sz| sz > window_sz) {
+        eprintln!(
+            "Warning: blend window clamped to {} samples, short of the {} \
+            expected to blend without a click; pad the start or lengthen the \
+            loop",
+            window_sz, click_free,
+        );
+    }
+}
+
+// Parse arguments, fill in config defaults, and run the sub-command
+fn run<'a>(args: impl Iterator<Item = &'a str>) -> Result<(), String> {
+    let (cmd, args) = parse_args(args)?;
+
+    if args.contains_key("help") {
+        commands::print_help(cmd);
+        return Ok(());
+    }
+
+    let command = if matches!(
+        cmd,
+        CommandKind::Help
+            | CommandKind::Config
+            | CommandKind::Devices
+            | CommandKind::Version
+    ) {
+        (cmd, args)
+    } else {
+        apply_config((cmd, args), &config::load())
+    };
+
+    run_command(command)
+}
+
+fn main() -> ExitCode {
+    let args_owned: Vec<String> = env::args().skip(1).collect();
+
+    if let Err(e) = run(args_owned.iter().map(|arg| &arg[..])) {
+        eprintln!("{}", e);
+
+        if e.contains("sub-command") {
+            commands::usage();
+        } else if let Some(cmd) = args_owned
+            .first()
+            .and_then(|name| CommandKind::try_from(&name[..]).ok())
+            .filter(|_| is_usage_error(&e))
+        {
+            commands::print_short_usage(cmd);
+        }
+
+        // As with cmp, diff exits with 1 only when the files differ
+        let diff_failed = args_owned.first().map(String::as_str)
+            == Some(CommandKind::Diff.name())
+            && e != diff::FILES_DIFFER;
+        return ExitCode::from(if diff_failed { 2 } else { 1 });
+    }
+
+    ExitCode::SUCCESS
+}
+
+// Whether an error is in how a sub-command was given, rather than in what it
+// was given
+fn is_usage_error(message: &str) -> bool {
+    [
+        "Expected ",
+        "No input file",
+        "Argument -",
+        "Unrecognized argument",
+        "Unexpected extra argument",
+    ]
+    .iter()
+    .any(|start| message.starts_with(start))
+        || message.contains(" doesn't take -")
+}
+
+fn parse_time(
+    time_str: impl AsRef<str>,
+    proj: &core::Project,
+) -> Result<u32, String> {
+    core::TimeSpec::parse(time_str.as_ref())?
+        .resolve(proj.sample_rate(), proj.sample_count())
+}
+
+fn parse_range(
+    range: &str,
+    proj: &core::Project,
+) -> Result<std::ops::Range<u32>, String> {
+    let (start, end) = range
+        .split_once("..")
+        .ok_or("Range must be given as <TIME>..<TIME>")?;
+    Ok(parse_time(start, proj)?..parse_time(end, proj)?)
+}
+
+fn write_waveform<R: Read + Seek>(
+    (_, args): Command,
+    q_wave_reader: core::QWaveReader<R>,
+) -> Result<(), String> {
+    let outpath = Path::new(expect_arg(&args, "out")?);
+    let metadata = q_wave_reader.metadata();
+    let proj = core::Project::from_reader(q_wave_reader)?;
+
+    if proj.channels() != 1 {
+        return Err(String::from("Only mono files can be drawn"));
+    }
+
+    if proj.sample_count() == 0 {
+        return Err(String::from("No audio samples"));
+    }
+
+    let parse_px = |argname| -> Result<u32, String> {
+        expect_arg(&args, argname)?
+            .parse::<u32>()
+            .ok()
+            .filter(|&px| px > 0)
+            .ok_or(format!("Expected positive pixel count for \"{}\"", argname))
+    };
+
+    let width = parse_px("width")?;
+    let height = parse_px("height")?;
+
+    let range = args
+        .get("range")
+        .map(|range| parse_range(range, &proj))
+        .transpose()?
+        .unwrap_or(0..proj.sample_count());
+
+    if range.start >= range.end || range.end > proj.sample_count() {
+        return Err(String::from("Range must be non-empty and within file"));
+    }
+
+    let defaults = core::Theme::default();
+    let color = |argname, default| {
+        args.get(argname)
+            .map(|hex| parse_color(hex))
+            .transpose()
+            .map(|color| color.unwrap_or(default))
+    };
+
+    let theme = core::Theme {
+        background: color("background", defaults.background)?,
+        peak: color("peak-color", defaults.peak)?,
+        rms: color("rms-color", defaults.rms)?,
+        marker: color("marker-color", defaults.marker)?,
+        clip: args
+            .get("clips")
+            .map(|hex| {
+                if hex.is_empty() {
+                    Ok(CLIP_COLOR)
+                } else {
+                    parse_color(hex)
+                }
+            })
+            .transpose()?,
+    };
+
+    let markers = metadata
+        .loop_start
+        .map(|start| vec![start, metadata.end.unwrap_or(proj.sample_count())])
+        .unwrap_or_default()
+        .into_iter()
+        .map(|marker| marker as usize)
+        .collect::<Vec<_>>();
+
+    let mut rgb = core::render_rgb(
+        proj.samples(),
+        range.start as usize..range.end as usize,
+        width as usize,
+        height as usize,
+        &markers,
+        &theme,
+    );
 
-    fn try_from(from: &str) -> Result<CommandKind, Self::Error> {
-        match from {
-            "info" => Ok(CommandKind::Info),
-            "play" => Ok(CommandKind::Play),
-            "loop" => Ok(CommandKind::PlayLooped),
-            "set-loop" => Ok(CommandKind::SetLoop),
-            "strip" => Ok(CommandKind::Strip),
-            "blend" => Ok(CommandKind::Blend),
-            "help" => Ok(CommandKind::Help),
-            other => Err(format!("Unknown sub-command \"{}\"", other)),
-        }
+    if let Some(duration) = args.get("blend-preview") {
+        let preview = proj.blend_preview(parse_time(duration, &proj)?)?;
+
+        core::render_overlay(
+            &mut rgb,
+            proj.samples(),
+            range.start as usize..range.end as usize,
+            width as usize,
+            height as usize,
+            &core::Overlay {
+                start: preview.range.start as usize,
+                samples: &preview.samples,
+                color: PREVIEW_COLOR,
+                highlight: Some(PREVIEW_HIGHLIGHT),
+            },
+            &theme,
+        );
     }
-}
 
-type Command = (CommandKind, CommandArgs);
+    let outfile = fs::File::create(outpath).map_err(|e| e.to_string())?;
+    let mut encoder =
+        png::Encoder::new(io::BufWriter::new(outfile), width, height);
+    encoder.set_color(png::ColorType::Rgb);
+    encoder.set_depth(png::BitDepth::Eight);
 
-fn parse_arg_param(arg_param: &str) -> Result<(&'static str, String), String> {
-    let mut arg_param_iter = arg_param.splitn(2, '=');
-    let arg_slice = arg_param_iter.next().unwrap();
-    let param = arg_param_iter.next().map(String::from).unwrap_or("".into());
+    encoder
+        .write_header()
+        .and_then(|mut writer| writer.write_image_data(&rgb))
+        .map_err(|e| e.to_string())?;
 
-    let arg = ARGUMENTS.into_iter().find(|&s| s == arg_slice);
+    if theme.clip.is_some() {
+        let clipped = proj.samples()[range.start as usize..range.end as usize]
+            .iter()
+            .filter(|&&s| core::is_clipped(s))
+            .count();
 
-    match arg {
-        None => Err(format!("Unrecognized argument {}", arg_slice)),
-        Some(a) => Ok((a, param)),
+        println!("{} clipped samples in range", clipped);
     }
-}
 
-fn parse_args<'a, T: Iterator<Item = &'a str>>(
-    mut args: T,
-) -> Result<Command, String> {
-    let cmd = args
-        .next()
-        .map(|cmd| cmd.try_into())
-        .ok_or(String::from("Missing sub-command"))
-        .and_then(|x| x)?;
+    Ok(())
+}
 
-    let mut map = HashMap::new();
-    let mut reached_end = false;
-    let mut reached_divider = false;
+fn parse_color(hex: &str) -> Result<core::Rgb, String> {
+    let hex = hex.strip_prefix('#').unwrap_or(hex);
+    let error = || format!("Failed to parse color \"{}\"", hex);
 
-    while !reached_end {
-        if let Some(arg) = args.next() {
-            if arg.starts_with('-') && !reached_divider {
-                if arg == "--" {
-                    reached_divider = true;
-                } else {
-                    let arg = arg.trim_start_matches('-');
-                    let (argname, param) = parse_arg_param(arg)?;
-                    map.insert(argname, param);
-                }
-            } else if !map.contains_key("in") {
-                map.insert("in", arg.into());
-            } else {
-                map.insert("out", arg.into());
-                reached_end = true;
-            }
-        } else {
-            reached_end = true;
-        }
+    if hex.len() != 6 {
+        return Err(error());
     }
 
-    if let (Some(last), true) = (args.next(), reached_end) {
-        return Err(format!("Unrecognized argument \"{}\"", last));
+    let mut color = [0u8; 3];
+
+    for (i, channel) in color.iter_mut().enumerate() {
+        *channel = hex
+            .get(i * 2..i * 2 + 2)
+            .and_then(|byte| u8::from_str_radix(byte, 16).ok())
+            .ok_or_else(error)?;
     }
 
-    Ok((cmd, map))
+    Ok(color)
 }
 
-fn expect_arg<'a>(
-    args: &'a CommandArgs,
-    argname: &str,
-) -> Result<&'a String, String> {
-    args.get(argname).ok_or_else(|| {
-        if argname == "in" {
-            "No input file provided".into()
-        } else {
-            format!("Expected argument \"{}\"", argname)
-        }
-    })
-}
+fn soak_wave<R: Read + Seek>(
+    args: &CommandArgs,
+    mut wave_reader: core::QWaveReader<R>,
+) -> Result<(), String> {
+    let iterations = expect_arg(args, "iterations")?
+        .parse::<usize>()
+        .map_err(|e| format!("Bad iteration count: {}", e))?;
+
+    let seed = match args.get("seed") {
+        Some(seed) => seed
+            .parse::<u64>()
+            .map_err(|e| format!("Bad seed: {}", e))?,
+        None => std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|since| since.as_nanos() as u64)
+            .unwrap_or(0),
+    };
 
-fn run_command((cmd, args): Command) -> Result<(), String> {
-    if cmd == CommandKind::Help {
-        println!("QUADIO - Quake Looped Audio Utilities\n");
-        usage();
+    let metadata = wave_reader.metadata();
+    let samples = wave_reader
+        .collect_samples()?
+        .iter()
+        .map(|&s| f32::from(s) / 32768.0)
+        .collect::<Vec<_>>();
+
+    let loop_start = metadata.loop_start.unwrap_or(0).try_into().unwrap_or(0);
+    let end = metadata
+        .end
+        .and_then(|end| end.try_into().ok())
+        .unwrap_or(samples.len());
+
+    let report = core::soak_loop(&samples, loop_start, end, iterations, seed)?;
+
+    if let Some(failure) = &report.failure {
+        let sizes = report
+            .buffer_sizes
+            .iter()
+            .map(|size| size.to_string())
+            .collect::<Vec<_>>();
+
+        println!(
+            "FAIL after {} callbacks and {} wraps (seed {}): {}",
+            report.callbacks, report.wraps, report.seed, failure,
+        );
+        println!("Buffer sizes: {}", sizes.join(","));
+
+        Err(String::from("Soak test failed"))
     } else {
-        let inpath = Path::new(expect_arg(&args, "in")?);
-        let file = fs::File::open(inpath).map_err(|e| e.to_string())?;
-        let reader = io::BufReader::new(file);
+        println!(
+            "PASS: {} wraps over {} callbacks and {} restarts (seed {})",
+            report.wraps, report.callbacks, report.restarts, report.seed,
+        );
 
-        match cmd {
-            CommandKind::Info => {
-                let info = core::QWaveReader::new(reader)?.metadata();
-                println!("Information");
-                println!("\tSample rate = {}", info.sample_rate);
+        Ok(())
+    }
+}
 
-                let duration_s =
-                    f64::from(info.sample_count) / f64::from(info.sample_rate);
+fn play_wave<R: Read + Seek>(
+    wave_reader: core::QWaveReader<R>,
+    looped: bool,
+    bounds: core::PlaybackBounds,
+    args: &CommandArgs,
+) -> Result<(), String> {
+    let metadata = wave_reader.metadata();
+    let proj = core::Project::from_reader(wave_reader)?;
 
-                println!(
-                    "\tDuration = {} samples ({:.3}s)",
-                    info.sample_count, duration_s,
-                );
+    play_project(proj, metadata, looped, bounds, args)
+}
 
-                match info.loop_start {
-                    Some(start) => {
-                        let cue_time =
-                            f64::from(start) / f64::from(info.sample_rate);
+// Play several files at once with all but one muted, to switch between them
+// with the number keys
+fn play_files(
+    paths: &[&Path],
+    looped: bool,
+    bounds: core::PlaybackBounds,
+    args: &CommandArgs,
+) -> Result<(), String> {
+    if paths.len() > MAX_PLAY_FILES {
+        return Err(format!(
+            "Expected at most {} files to switch between",
+            MAX_PLAY_FILES,
+        ));
+    }
 
-                        println!(
-                            "\tLoop starts at sample {} ({:.3}s)",
-                            start, cue_time,
-                        );
+    if args.contains_key("pak") {
+        return Err(String::from("-pak can't be given with several inputs"));
+    }
 
-                        let loop_end = info.end.unwrap_or(info.sample_count);
+    if paths.contains(&Path::new(STDIN_PATH)) {
+        return Err(String::from(
+            "Standard input can't be played with other files",
+        ));
+    }
 
-                        let end_time =
-                            f64::from(loop_end) / f64::from(info.sample_rate);
+    let files = paths
+        .iter()
+        .map(|path| {
+            let file = fs::File::open(path)
+                .map_err(|e| format!("{}: {}", path.display(), e))?;
+            let reader = core::QWaveReader::new(io::BufReader::new(file))
+                .map_err(|e| e.at_path(path))?;
+            let metadata = reader.metadata();
+
+            Ok(PlayFile {
+                name: path.display().to_string(),
+                proj: core::Project::from_reader(reader)?,
+                metadata,
+            })
+        })
+        .collect::<Result<Vec<_>, String>>()?;
+
+    play_projects(files, looped, bounds, args)
+}
 
-                        println!(
-                            "\tLoop ends at sample {} ({:.3}s)",
-                            loop_end, end_time
-                        );
-                    }
-                    None => println!("No loop point found"),
-                }
-            }
-            CommandKind::Play => {
-                play_wave(reader, false)?;
-            }
-            CommandKind::PlayLooped => {
-                play_wave(reader, true)?;
-            }
-            CommandKind::Strip | CommandKind::SetLoop | CommandKind::Blend => {
-                let q_wave_reader = core::QWaveReader::new(reader)?;
-                let project = core::Project::from_reader(q_wave_reader)?;
-                run_write_command((cmd, args), project)?;
-            }
-            CommandKind::Help => {
-                unreachable!();
-            }
-        }
-    }
+// Play a project described by `metadata`, taking keys until it stops
+fn play_project(
+    proj: core::Project,
+    metadata: core::Metadata,
+    looped: bool,
+    bounds: core::PlaybackBounds,
+    args: &CommandArgs,
+) -> Result<(), String> {
+    let file = PlayFile {
+        name: args.get("in").cloned().unwrap_or_default(),
+        proj,
+        metadata,
+    };
 
-    Ok(())
+    play_projects(vec![file], looped, bounds, args)
 }
 
-fn run_write_command(
-    (cmd, args): Command,
-    mut proj: core::Project,
+// A file played by play_projects
+struct PlayFile {
+    name: String,
+    proj: core::Project,
+    metadata: core::Metadata,
+}
+
+// Play each of `files` on a player of its own, only the first heard at
+// first, taking keys until the one heard stops
+fn play_projects(
+    files: Vec<PlayFile>,
+    looped: bool,
+    bounds: core::PlaybackBounds,
+    args: &CommandArgs,
 ) -> Result<(), String> {
-    let outpath = Path::new(expect_arg(&args, "out")?);
+    let headroom_db = args
+        .get("headroom")
+        .map(|db| db.trim_end_matches("dB").parse::<f32>())
+        .transpose()
+        .map_err(|e| format!("Bad headroom: {}", e))?
+        .unwrap_or(0.0);
+
+    let level = core::OutputLevel {
+        headroom_db,
+        clip: if args.contains_key("soft-clip") {
+            core::ClipMode::Soft
+        } else {
+            core::ClipMode::Hard
+        },
+    };
 
-    match cmd {
-        CommandKind::Strip => {
-            proj.set_loop(None);
-        }
-        CommandKind::SetLoop => {
-            let start = parse_time(expect_arg(&args, "start")?, &proj)?;
+    // Interactive playback favors starting at once over fidelity
+    let quality = args
+        .get("quality")
+        .map(|name| resample_quality(name))
+        .transpose()?
+        .unwrap_or(core::dsp::ResampleQuality::Fast);
 
-            let end = args
-                .get("end")
-                .map(|e| parse_time(e, &proj))
-                .transpose()?
-                .unwrap_or(proj.sample_count());
+    // Standard input holds the audio rather than key presses
+    let key_reader = if args.get("in").map(String::as_str) == Some(STDIN_PATH) {
+        None
+    } else {
+        Some(KeyReader::new().ok_or("Error creating key reader")?)
+    };
+    let mut quit = false;
+    let mut done = false;
 
-            proj.set_loop(Some(start..end));
+    // Files are only named where there are several to tell apart
+    let several = files.len() > 1;
+    let prefix = |file: &PlayFile| {
+        if several {
+            format!("{}: ", file.name)
+        } else {
+            String::new()
         }
-        CommandKind::Blend => {
-            let blend_duration = args
-                .get("duration")
-                .map(|e| parse_time(e, &proj))
-                .transpose()?;
+    };
 
-            if let Some(window_sz) = blend_duration {
-                proj.blend(window_sz)?;
-            } else {
-                proj.blend_default_window()?;
+    let speed = args
+        .get("speed")
+        .map(|speed| speed.trim_end_matches('x').parse::<f64>())
+        .transpose()
+        .map_err(|e| format!("Bad speed: {}", e))?;
+    let mut volume = args
+        .get("volume")
+        .map(|volume| parse_volume(volume))
+        .transpose()?;
+
+    let mut players = files
+        .iter()
+        .map(|file| {
+            let mut player = core::Player::new(&core::PlayerConfig {
+                level,
+                device: args.get("device").cloned(),
+                quality,
+                ..core::PlayerConfig::from_project(&file.proj)
+            })?;
+            player.set_bounds(bounds);
+
+            if let Some(speed) = speed {
+                player.set_speed(speed)?;
             }
-        }
-        _ => {
-            unreachable!();
-        }
-    };
 
-    proj.write_to(&outpath)?;
+            if let Some(volume) = volume {
+                player.set_volume(volume)?;
+            }
 
-    Ok(())
-}
+            Ok(player)
+        })
+        .collect::<Result<Vec<_>, String>>()?;
+    let mut active = 0;
+    let volume = volume.get_or_insert(players[active].volume());
 
-fn main() {
-    let args_owned: Vec<String> = env::args().skip(1).collect();
-    let args = args_owned.iter().map(|arg| &arg[..]);
+    for player in &mut players[1..] {
+        player.set_volume(0.0)?;
+    }
+
+    if args.contains_key("verbose") {
+        for (file, player) in files.iter().zip(&players) {
+            player.wait_for_resampling();
+            println!(
+                "{}{} resampled samples exceeded full scale",
+                prefix(file),
+                player.overshoot_count()
+            );
+        }
+    }
 
-    let result = parse_args(args);
+    for file in &files {
+        let metadata = &file.metadata;
 
-    if let Err(e) = result.and_then(run_command) {
-        eprintln!("{}", e);
+        if let (Some(end), false, core::PlaybackBounds::Full) =
+            (metadata.end, looped, bounds)
+        {
+            let play_end = metadata.play_end.unwrap_or(metadata.sample_count);
 
-        if e.contains("sub-command") {
-            usage();
+            if end < play_end {
+                let stop = if metadata.play_end.is_some() {
+                    format!("end marker at sample {}", play_end)
+                } else {
+                    String::from("end of file")
+                };
+
+                println!(
+                    "{}Playing past loop end at sample {} to {}; use \
+                    -engine-accurate to stop at the loop end",
+                    prefix(file),
+                    end,
+                    stop
+                );
+            }
         }
     }
-}
 
-fn parse_time(
-    time_str: impl AsRef<str>,
-    proj: &core::Project,
-) -> Result<u32, String> {
-    let time_str = time_str.as_ref();
-
-    Ok(if time_str == "LAST" {
-        proj.sample_count()
-    } else if let Some(stripped) = time_str.strip_suffix("ms") {
-        let millis = stripped
-            .parse::<f64>()
-            .or(Err("Failed to parse time in milliseconds"))?;
-        (millis / 1000.0 * f64::from(proj.sample_rate())).round() as u32
-    } else if let Some(stripped) = time_str.strip_suffix("s") {
-        let seconds = stripped
-            .parse::<f64>()
-            .or(Err("Failed to parse time in seconds"))?;
-        (seconds * f64::from(proj.sample_rate())).round() as u32
-    } else {
-        time_str.parse::<u32>().or(Err("Failed to parse time"))?
-    })
-}
+    let finished = players
+        .iter()
+        .map(core::Player::finished_receiver)
+        .collect::<Vec<_>>();
 
-fn play_wave<R: Read + Seek>(reader: R, looped: bool) -> Result<(), String> {
-    let key_reader = KeyReader::new().ok_or("Error creating key reader")?;
-    let mut wave_reader = core::QWaveReader::new(reader)?;
-    let mut quit = false;
-    let mut done = false;
-    let metadata = wave_reader.metadata();
-    let samples = wave_reader.collect_samples()?;
+    let loop_count = args
+        .get("count")
+        .filter(|_| looped)
+        .map(|count| count.parse::<u32>())
+        .transpose()
+        .map_err(|e| format!("Bad loop count: {}", e))?;
+
+    let seam = args.get("seam").filter(|_| looped);
+
+    for (file, player) in files.iter().zip(&mut players) {
+        start_playback(player, file, looped, loop_count, args)?;
+    }
+
+    println!(
+        "Playing from sample {} ({:.3}s)...",
+        players[active].playhead(),
+        players[active].playhead_seconds(),
+    );
+
+    if several {
+        println!(
+            "Press 1 to {} to switch files; hearing 1: {}",
+            files.len(),
+            files[active].name,
+        );
+    }
 
-    let mut player = core::setup_player(&metadata, &samples)?;
-    player.play(0, looped)?;
-    println!("Playing...");
+    if key_reader.is_none() {
+        println!("Playing from standard input; playback keys are off");
+    }
+
+    // Only a terminal can redraw the status line in place
+    let meter = io::stdout().is_terminal();
+    let counts_wraps = looped || loop_count.is_some();
+    let mut status_len = 0;
+    let mut wraps = 0;
+    let mut last_playhead = players[active].playhead();
 
     while !done {
         sleep(Duration::from_millis(30));
 
-        if let Some(key) = key_reader.read() {
-            let state_tag = player.state();
+        if let Some(key) = key_reader.as_ref().and_then(KeyReader::read) {
+            let state_tag = players[active].state();
+            let playing = state_tag == core::PlayerStateTag::Playing
+                || state_tag == core::PlayerStateTag::PlayingLooped;
+
+            if meter {
+                clear_meter(status_len);
+            }
 
-            if key == b' ' {
-                if state_tag == core::PlayerStateTag::Playing
-                    || state_tag == core::PlayerStateTag::PlayingLooped
+            if key == Key::Char(b' ')
+                && players.iter().any(core::Player::is_disconnected)
+            {
+                for player in players.iter_mut().filter(|p| p.is_disconnected())
                 {
-                    player.pause();
-                    let playhead_pos = player.playhead();
-                    let playhead_time =
-                        playhead_pos as f64 / f64::from(metadata.sample_rate);
+                    match player.reopen() {
+                        Ok(()) => println!("Reopened output device"),
+                        Err(e) => println!(
+                            "Couldn't reopen output device: {}; press space \
+                            to retry",
+                            e
+                        ),
+                    }
+                }
+            } else if key == Key::Char(b' ') {
+                if playing {
+                    for player in &mut players {
+                        player.pause();
+                    }
+
+                    // Each paused at its own moment, so they are lined up
+                    // again before resuming
+                    if let Err(e) = sync_players(&mut players, active) {
+                        println!("Couldn't line up files: {}", e);
+                    }
+
                     println!(
                         "Paused at sample {} ({:.3}s)",
-                        playhead_pos, playhead_time
+                        players[active].playhead(),
+                        players[active].playhead_seconds()
                     );
                 } else {
-                    player.resume().unwrap();
+                    for player in &mut players {
+                        player.resume().unwrap();
+                    }
+
                     println!("Resumed");
                 }
             }
 
-            if key == b'q' {
+            let player = &mut players[active];
+            let rate = player.input_rate() as usize;
+            let playhead = player.playhead();
+
+            let seek_to = match key {
+                Key::Left => Some(playhead.saturating_sub(SEEK_SECONDS * rate)),
+                Key::Right => {
+                    Some(playhead.saturating_add(SEEK_SECONDS * rate))
+                }
+                Key::Char(b',') => Some(playhead.saturating_sub(FINE_SEEK)),
+                Key::Char(b'.') => Some(playhead.saturating_add(FINE_SEEK)),
+                Key::Char(b'0') => Some(0),
+                _ => None,
+            };
+            let seeked = match (key, seek_to) {
+                (Key::Char(b'l'), _) => Some(player.skip_to_pre_seam(rate)),
+                (_, Some(position)) => Some(player.seek(position)),
+                _ => None,
+            };
+
+            if let Some(seeked) = seeked {
+                match seeked.and_then(|()| sync_players(&mut players, active)) {
+                    Ok(()) => println!(
+                        "Seeked to sample {} ({:.3}s)",
+                        players[active].playhead(),
+                        players[active].playhead_seconds(),
+                    ),
+                    Err(e) => println!("Couldn't seek: {}", e),
+                }
+            }
+
+            if key == Key::Char(b'[') || key == Key::Char(b']') {
+                let speed = if key == Key::Char(b'[') {
+                    players[active].speed() / SPEED_NUDGE
+                } else {
+                    players[active].speed() * SPEED_NUDGE
+                };
+
+                for player in &mut players {
+                    player.set_speed(
+                        speed.clamp(core::MIN_SPEED, core::MAX_SPEED),
+                    )?;
+                }
+
+                println!("Speed {:.3}x", players[active].speed());
+            }
+
+            if matches!(key, Key::Char(b'+' | b'=' | b'-')) {
+                let step = if key == Key::Char(b'-') {
+                    -VOLUME_STEP
+                } else {
+                    VOLUME_STEP
+                };
+
+                // Steps land on whole percentages despite rounding error
+                *volume = ((*volume + step).clamp(0.0, core::MAX_VOLUME)
+                    * 100.0)
+                    .round()
+                    / 100.0;
+                players[active].set_volume(*volume)?;
+                println!("Volume {:.0}%", *volume * 100.0);
+            }
+
+            if let Key::Char(digit @ b'1'..=b'9') = key {
+                let chosen = usize::from(digit - b'1');
+
+                if chosen >= players.len() {
+                    println!(
+                        "No file {} to switch to; there are {}",
+                        chosen + 1,
+                        players.len(),
+                    );
+                } else if chosen != active {
+                    match switch_player(&mut players, active, chosen, *volume) {
+                        Ok(()) => {
+                            active = chosen;
+                            println!(
+                                "Hearing {}: {}",
+                                active + 1,
+                                files[active].name
+                            );
+                        }
+                        Err(e) => println!("Couldn't switch files: {}", e),
+                    }
+                }
+            }
+
+            if key == Key::Char(b'q') {
                 quit = true;
                 done = true;
             }
+
+            // A seek, resume or switch moves the playhead without wrapping
+            last_playhead = players[active].playhead();
+        }
+
+        for (file, player) in files.iter().zip(&mut players) {
+            let Some(e) = player.take_error() else {
+                continue;
+            };
+
+            if meter {
+                clear_meter(status_len);
+            }
+
+            if !player.is_disconnected() {
+                return Err(format!("{}Playback failed: {}", prefix(file), e));
+            }
+
+            // One try straight away, in case another device took over
+            match player.reopen() {
+                Ok(()) => println!("Output device lost ({}); reopened", e),
+                Err(reopen_error) => println!(
+                    "Output device lost ({}), and couldn't reopen it: {}; \
+                    press space to retry",
+                    e, reopen_error
+                ),
+            }
+        }
+
+        // Only the file heard ending stops playback; others that end first
+        // are restarted when switched to
+        for (index, finished) in finished.iter().enumerate() {
+            if finished.try_recv().is_ok() && index == active {
+                done = true;
+            }
+        }
+
+        // Otherwise the playhead only goes back when playback wraps
+        let playhead = players[active].playhead();
+
+        if playhead < last_playhead {
+            wraps += 1;
+
+            if seam.is_some() {
+                if meter {
+                    clear_meter(status_len);
+                }
+
+                println!("--- loop wrapped (pass {}) ---", wraps + 1);
+            }
         }
 
-        if player.samples_remaining() == 0 && !looped {
-            done = true;
+        last_playhead = playhead;
+
+        if meter && !done {
+            let label = several
+                .then(|| format!("{}: {}", active + 1, files[active].name));
+
+            status_len = draw_meter(
+                &players[active],
+                counts_wraps.then_some(wraps),
+                label.as_deref(),
+                status_len,
+            );
         }
     }
 
+    if meter {
+        clear_meter(status_len);
+    }
+
     if !quit {
         println!("Stopped.");
     }
@@ -309,50 +3084,225 @@ fn play_wave<R: Read + Seek>(reader: R, looped: bool) -> Result<(), String> {
     Ok(())
 }
 
-fn usage() {
-    println!(
-        r#"Usage: quadio-cli <sub-command> [<arg>...] [--] <input> [<output>]
-
-Sub-commands:
-    help
-        Print usage
-
-    info <input>
-        Print information about WAV file
-
-    play <input>
-        Play file from start to end, ignoring loops
-
-    loop <input>
-        Play file with loops.  If file contains no loops, loop from file start
-        to end
-
-    set-loop -start=<TIME> [-end=<TIME>] [--] <input> <output>
-        Set loop point, ranging from start to end.  If end is not provided,
-        the last sample in the file is chosen.  Points in time are 0-based (0
-        refers to the first sample)
-
-    strip <input> <output>
-        Strips loop (CUE and length markers) from file
-
-    blend [-duration=<TIME>] [--] <input> <output>
-        Blends samples from a *duration* window before the loop starts with
-        samples a *duration* window before the loop ends.  Loop must start after
-        *duration* and be at least as long as *duration*.  If the duration is
-        not provided, the smallest value is chosen which should eliminate
-        clicks and pops in playback
-
-Time:
-    Time arguments (start, end, duration) are given in non-zero integer numbers
-    of samples.  A suffix can be provided to use rational-valued times in the
-    desired unit, seconds or milliseconds, e.g. '0.5s' for seconds or '111.1ms'
-    for milliseconds.
-
-Playback controls:
-    space - Pause and resume playback.  Prints current sample on pause
-    q     - Stop & quit
-"#
+// Start a file's player as -start, -seam, -plan and -count ask
+fn start_playback(
+    player: &mut core::Player,
+    file: &PlayFile,
+    looped: bool,
+    loop_count: Option<u32>,
+    args: &CommandArgs,
+) -> Result<(), String> {
+    let proj = &file.proj;
+    let seam = args.get("seam").filter(|_| looped);
+    let start = args
+        .get("start")
+        .map(|start| parse_time(start, proj))
+        .transpose()?;
+
+    let start = match (seam, proj.sample_loop()) {
+        (Some(_), _) if start.is_some() => {
+            return Err(String::from("Expected -seam or -start, not both"));
+        }
+        (Some(lead), Some(sample_loop)) => {
+            let lead = if lead.is_empty() {
+                SEAM_LEAD_SECONDS * proj.sample_rate()
+            } else {
+                parse_time(lead, proj)?
+            };
+
+            Some(sample_loop.end.saturating_sub(lead))
+        }
+        (Some(_), None) => {
+            println!("No loop, so no seam; playing from the start");
+            None
+        }
+        (None, _) => start,
+    };
+
+    if let Some(start) = start.filter(|&start| start >= proj.sample_count()) {
+        return Err(format!(
+            "Start at sample {} is past the end of the {}-sample file",
+            start,
+            proj.sample_count(),
+        ));
+    }
+
+    let start = start.unwrap_or(0) as usize;
+
+    match (args.get("plan"), loop_count) {
+        (Some(_), Some(_)) => {
+            return Err(String::from("Expected -plan or -count, not both"));
+        }
+        (Some(_), None) if args.contains_key("start") => {
+            return Err(String::from("-start can't be given with -plan"));
+        }
+        (Some(_), None) if seam.is_some() => {
+            return Err(String::from("-seam can't be given with -plan"));
+        }
+        (Some(plan), None) => {
+            player.play_plan(&parse_plan(plan, &file.metadata)?)?
+        }
+        (None, Some(count)) => player.play_n_loops(start, count)?,
+        (None, None) => player.play(start, looped)?,
+    }
+
+    Ok(())
+}
+
+// Move every player but `active` to the moment it has reached, in seconds,
+// so switching carries on from the same point
+fn sync_players(
+    players: &mut [core::Player],
+    active: usize,
+) -> Result<(), core::Error> {
+    let playhead = players[active].playhead() as u64;
+    let rate = u64::from(players[active].input_rate());
+
+    for (index, player) in players.iter_mut().enumerate() {
+        if index != active {
+            let position = playhead * u64::from(player.input_rate()) / rate;
+            player.seek(usize::try_from(position).unwrap_or(usize::MAX))?;
+        }
+    }
+
+    Ok(())
+}
+
+// Make `chosen` the player heard, at the moment `active` has reached, by
+// ramping one up as the other goes down
+fn switch_player(
+    players: &mut [core::Player],
+    active: usize,
+    chosen: usize,
+    volume: f32,
+) -> Result<(), core::Error> {
+    sync_players(players, active)?;
+
+    let playing = matches!(
+        players[active].state(),
+        core::PlayerStateTag::Playing | core::PlayerStateTag::PlayingLooped
+    );
+
+    // A file that ended before the one heard is left paused by the seek
+    if playing && players[chosen].state() == core::PlayerStateTag::Paused {
+        players[chosen].resume()?;
+    }
+
+    players[chosen].set_volume(volume)?;
+    players[active].set_volume(0.0)
+}
+
+// Draw the status line over the current one: playhead time, total time,
+// playhead sample, the number of loop wraps if counted, whether playback is
+// paused, a level meter of '#' up to the RMS level of the latest buffer and
+// '-' on up to its peak, and the file heard if labeled. Returns the length
+// drawn, so the next line can blank any of it left over.
+fn draw_meter(
+    player: &core::Player,
+    wraps: Option<usize>,
+    label: Option<&str>,
+    last_len: usize,
+) -> usize {
+    let bars = |level: f32| {
+        ((level.clamp(0.0, 1.0) * METER_WIDTH as f32).round() as usize)
+            .min(METER_WIDTH)
+    };
+    let rms = bars(player.current_rms());
+    let peak = bars(player.current_peak()).max(rms);
+    let state = match player.state() {
+        core::PlayerStateTag::Paused => "paused",
+        _ => "playing",
+    };
+    let wraps =
+        wraps.map_or(String::new(), |wraps| format!("  wrap {}", wraps));
+    let label = label.map_or(String::new(), |label| format!("  {}", label));
+    let line = format!(
+        "{:>10.3}s / {:.3}s  sample {}{}  {:<7} [{}{}{}]{}",
+        player.playhead_seconds(),
+        player.duration_seconds(),
+        player.playhead(),
+        wraps,
+        state,
+        "#".repeat(rms),
+        "-".repeat(peak - rms),
+        " ".repeat(METER_WIDTH - peak),
+        label,
     );
+    let len = line.chars().count();
+
+    print!("\r{}{}", line, " ".repeat(last_len.saturating_sub(len)));
+    let _ = io::stdout().flush();
+    len
+}
+
+fn clear_meter(len: usize) {
+    print!("\r{}\r", " ".repeat(len));
+    let _ = io::stdout().flush();
+}
+
+// Parse a playback plan of ';'-separated segments, each a range of
+// <TIME>..<TIME>, "intro" (up to the loop), or "loop", optionally followed by
+// "*N" or "*inf" to repeat it
+fn parse_plan(
+    spec: &str,
+    metadata: &core::Metadata,
+) -> Result<Vec<core::PlaySegment>, String> {
+    let loop_start = metadata.loop_start.ok_or("File has no loop");
+    let loop_end = metadata.end.unwrap_or(metadata.sample_count);
+    let to_index = |frame: u32| -> Result<usize, String> {
+        frame
+            .try_into()
+            .map_err(|_| String::from("Time out of range"))
+    };
+
+    spec.split(';')
+        .map(|segment| {
+            let segment = segment.trim();
+            let (range, repeats) = match segment.rsplit_once('*') {
+                Some((range, "inf")) => (range, 0),
+                Some((range, count)) => (
+                    range,
+                    count
+                        .parse::<u32>()
+                        .ok()
+                        .filter(|&count| count > 0)
+                        .ok_or(format!("Bad repeat count \"{}\"", count))?,
+                ),
+                None => (segment, 1),
+            };
+
+            let range = match range.trim() {
+                "intro" => 0..loop_start?,
+                "loop" => loop_start?..loop_end,
+                range => {
+                    let (start, end) = range.split_once("..").ok_or(
+                        format!("Expected <TIME>..<TIME>, got \"{}\"", range),
+                    )?;
+                    let resolve = |time: &str| {
+                        core::TimeSpec::parse(time.trim())?.resolve(
+                            metadata.sample_rate,
+                            metadata.sample_count,
+                        )
+                    };
+
+                    resolve(start)?..resolve(end)?
+                }
+            };
+
+            Ok(core::PlaySegment {
+                range: to_index(range.start)?..to_index(range.end)?,
+                repeats,
+            })
+        })
+        .collect()
+}
+
+// Key pressed during playback
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Key {
+    Char(u8),
+    Left,
+    Right,
 }
 
 #[cfg(not(target_os = "windows"))]
@@ -387,7 +3337,7 @@ impl KeyReader {
         Some(KeyReader { old_attr })
     }
 
-    pub fn read(&self) -> Option<u8> {
+    pub fn read(&self) -> Option<Key> {
         let mut buffer = vec![0u8; INPUT_BUFFER_SZ];
 
         let ret = unsafe {
@@ -398,10 +3348,16 @@ impl KeyReader {
             )
         };
 
-        if ret > 0 {
-            Some(buffer[ret as usize - 1])
-        } else {
-            None
+        if ret <= 0 {
+            return None;
+        }
+
+        // Arrow keys arrive as escape sequences
+        match &buffer[..ret as usize] {
+            [.., 0x1b, b'[', b'D'] => Some(Key::Left),
+            [.., 0x1b, b'[', b'C'] => Some(Key::Right),
+            [.., last] => Some(Key::Char(*last)),
+            [] => None,
         }
     }
 }
@@ -420,6 +3376,12 @@ use winapi::um::{
     consoleapi as conapi, processenv, winbase as base, wincon as con,
 };
 
+// Virtual key codes of the arrow keys
+#[cfg(target_os = "windows")]
+const VK_LEFT: u16 = 0x25;
+#[cfg(target_os = "windows")]
+const VK_RIGHT: u16 = 0x27;
+
 #[cfg(target_os = "windows")]
 struct KeyReader {
     old_mode: u32,
@@ -451,7 +3413,7 @@ impl KeyReader {
         Some(Self { old_mode })
     }
 
-    pub fn read(&self) -> Option<u8> {
+    pub fn read(&self) -> Option<Key> {
         let mut peek_buffer: [con::INPUT_RECORD; 1] =
             unsafe { std::mem::zeroed() };
         let mut peeked_records = 0u32;
@@ -499,7 +3461,11 @@ impl KeyReader {
                 let evt = unsafe { read_buffer[i].Event.KeyEvent() };
 
                 if evt.bKeyDown != 0 {
-                    return Some(*unsafe { evt.uChar.AsciiChar() } as u8);
+                    return Some(match evt.wVirtualKeyCode {
+                        VK_LEFT => Key::Left,
+                        VK_RIGHT => Key::Right,
+                        _ => Key::Char(*unsafe { evt.uChar.AsciiChar() } as u8),
+                    });
                 }
             }
         }
@@ -0,0 +1,188 @@
+use crate::output::{self, OutputFormat};
+use crate::verify::{self, VerifyOptions};
+use quadio_core as core;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+// Seed of the dither added when narrowing to 8 bits, fixed so that packaging
+// or writing the same files twice gives the same bytes
+pub const DITHER_SEED: u64 = 0x5155_4144_494f;
+
+const HEADER: [&str; 4] = ["input", "output", "changes", "result"];
+
+/// What packaging did to one input
+#[derive(Debug, Clone, PartialEq)]
+pub struct ManifestEntry {
+    pub input: PathBuf,
+
+    /// Path written, relative to the output directory; none on failure
+    pub output: Option<PathBuf>,
+
+    pub transforms: Vec<core::Transform>,
+    pub error: Option<String>,
+}
+
+impl ManifestEntry {
+    fn cells(&self) -> Vec<String> {
+        let changes = self
+            .transforms
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>();
+
+        vec![
+            self.input.display().to_string(),
+            self.output
+                .as_ref()
+                .map(|output| output.display().to_string())
+                .unwrap_or_default(),
+            if changes.is_empty() {
+                String::from("none")
+            } else {
+                changes.join("; ")
+            },
+            self.error.clone().unwrap_or(String::from("ok")),
+        ]
+    }
+
+    fn to_json(&self) -> String {
+        let path = |path: &Path| output::json_string(&path.to_string_lossy());
+        let changes = self
+            .transforms
+            .iter()
+            .map(|transform| output::json_string(&transform.to_string()))
+            .collect::<Vec<_>>();
+
+        format!(
+            "{{\"input\":{},\"output\":{},\"changes\":[{}],\"error\":{}}}",
+            path(&self.input),
+            self.output
+                .as_deref()
+                .map(path)
+                .unwrap_or(String::from("null")),
+            changes.join(","),
+            self.error
+                .as_deref()
+                .map(output::json_string)
+                .unwrap_or(String::from("null")),
+        )
+    }
+}
+
+/// Fit every input to the target and write it under the output directory,
+/// then print a manifest.  Inputs failing the same checks as verify, other
+/// than lacking a loop, are not written.  Fails if any input does.
+pub fn package(
+    inputs: &[&Path],
+    profile: &core::TargetProfile,
+    out_dir: &Path,
+    max_seam_score: f64,
+    format: OutputFormat,
+) -> Result<(), String> {
+    if !out_dir.is_dir() {
+        return Err(format!("{} is not a directory", out_dir.display()));
+    }
+
+    let options = VerifyOptions {
+        sample_rate: None,
+        max_seam_score,
+        require_loop: false,
+        round_trip: false,
+        target: None,
+    };
+
+    let mut entries = Vec::<ManifestEntry>::with_capacity(inputs.len());
+
+    for &input in inputs {
+        let mut entry = ManifestEntry {
+            input: input.to_path_buf(),
+            output: None,
+            transforms: vec![],
+            error: None,
+        };
+
+        let result = profile.package_path(input).and_then(|output| {
+            let taken = entries
+                .iter()
+                .find(|other| other.output.as_ref() == Some(&output));
+
+            if let Some(other) = taken {
+                return Err(format!(
+                    "Packages to {} as {} does",
+                    output.display(),
+                    other.input.display(),
+                ));
+            }
+
+            package_file(
+                input, &output, profile, out_dir, &options, &mut entry,
+            )?;
+            Ok(output)
+        });
+
+        match result {
+            Ok(output) => entry.output = Some(output),
+            Err(e) => entry.error = Some(e),
+        }
+
+        entries.push(entry);
+    }
+
+    match format {
+        OutputFormat::Text => {
+            let rows =
+                entries.iter().map(ManifestEntry::cells).collect::<Vec<_>>();
+            println!("{}", output::text_table(&HEADER, &rows));
+        }
+        OutputFormat::Json => {
+            let files = entries
+                .iter()
+                .map(ManifestEntry::to_json)
+                .collect::<Vec<_>>();
+            println!(
+                "{{\"target\":{},\"files\":[{}]}}",
+                output::json_string(profile.name),
+                files.join(","),
+            );
+        }
+        OutputFormat::Csv => {
+            return Err(String::from("package does not support CSV output"));
+        }
+    }
+
+    let failed = entries.iter().filter(|entry| entry.error.is_some()).count();
+
+    if failed > 0 {
+        Err(format!("{} of {} files failed", failed, entries.len()))
+    } else {
+        Ok(())
+    }
+}
+
+fn package_file(
+    input: &Path,
+    output: &Path,
+    profile: &core::TargetProfile,
+    out_dir: &Path,
+    options: &VerifyOptions,
+    entry: &mut ManifestEntry,
+) -> Result<(), String> {
+    let case = verify::verify_file(input, options);
+
+    if !case.passed() {
+        return Err(case.failures.join("; "));
+    }
+
+    let mut proj = core::Project::open(input)?;
+
+    entry.transforms = profile.apply(&mut proj, DITHER_SEED)?;
+
+    let outpath = out_dir.join(output);
+
+    if let Some(parent) = outpath.parent() {
+        fs::create_dir_all(parent)
+            .map_err(|e| format!("{}: {}", parent.display(), e))?;
+    }
+
+    Ok(proj.write_to(&outpath)?)
+}
@@ -0,0 +1,91 @@
+/// Outcome of one test case in a report
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct TestCase {
+    pub name: String,
+
+    /// Checks that failed, each a message; the case passes if there are none
+    pub failures: Vec<String>,
+
+    /// Warnings that don't fail the case, written as its output
+    pub output: Vec<String>,
+}
+
+impl TestCase {
+    pub fn passed(&self) -> bool {
+        self.failures.is_empty()
+    }
+}
+
+/// Serialize cases as a JUnit XML test suite, as understood by most CI report
+/// tooling.  The first failure gives the failure message; all of them are
+/// listed in its body.
+pub fn junit(suite: &str, cases: &[TestCase]) -> String {
+    let failed = cases.iter().filter(|case| !case.passed()).count();
+    let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+
+    xml.push_str(&format!(
+        "<testsuite name=\"{}\" tests=\"{}\" failures=\"{}\">\n",
+        xml_escape(suite),
+        cases.len(),
+        failed,
+    ));
+
+    for case in cases {
+        xml.push_str(&format!(
+            "  <testcase classname=\"{}\" name=\"{}\"",
+            xml_escape(suite),
+            xml_escape(&case.name),
+        ));
+
+        if case.passed() && case.output.is_empty() {
+            xml.push_str("/>\n");
+            continue;
+        }
+
+        xml.push_str(">\n");
+
+        if let Some(first) = case.failures.first() {
+            xml.push_str(&format!(
+                "    <failure message=\"{}\">{}</failure>\n",
+                xml_escape(first),
+                xml_escape(&case.failures.join("\n")),
+            ));
+        }
+
+        if !case.output.is_empty() {
+            xml.push_str(&format!(
+                "    <system-out>{}</system-out>\n",
+                xml_escape(&case.output.join("\n")),
+            ));
+        }
+
+        xml.push_str("  </testcase>\n");
+    }
+
+    xml.push_str("</testsuite>\n");
+    xml
+}
+
+/// Escape text for an XML attribute or element body
+pub fn xml_escape(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+
+    for c in s.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            '\n' | '\r' | '\t' => escaped.push(c),
+
+            // Not allowed in XML 1.0, even as character references
+            c if u32::from(c) < 0x20 => {
+                escaped.push(char::REPLACEMENT_CHARACTER)
+            }
+            c => escaped.push(c),
+        }
+    }
+
+    escaped
+}
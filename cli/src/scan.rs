@@ -0,0 +1,219 @@
+use crate::output::{self, OutputFormat};
+use quadio_core as core;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const HEADER: [&str; 6] =
+    ["name", "rate", "duration", "loop", "bits", "warnings"];
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScanRow {
+    pub name: String,
+    pub sample_rate: u32,
+    pub sample_count: u32,
+    pub looped: bool,
+    pub bits_per_sample: u16,
+    pub warnings: usize,
+}
+
+impl ScanRow {
+    fn from_path(root: &Path, path: &Path) -> Result<Self, String> {
+        let reader = core::QWaveReader::open(path)?;
+        let metadata = reader.metadata();
+
+        let loop_past_end = metadata.loop_end_past_eof.is_some();
+
+        let warnings = [
+            metadata.declared_vs_actual.is_some(),
+            loop_past_end,
+            reader.trailing_bytes() > 0,
+        ]
+        .into_iter()
+        .filter(|&warning| warning)
+        .count();
+
+        let name = path.strip_prefix(root).unwrap_or(path);
+
+        Ok(ScanRow {
+            name: name.to_string_lossy().into_owned(),
+            sample_rate: metadata.sample_rate,
+            sample_count: metadata.sample_count,
+            looped: metadata.loop_start.is_some(),
+            bits_per_sample: metadata.bits_per_sample,
+            warnings,
+        })
+    }
+
+    fn duration_s(&self) -> f64 {
+        f64::from(self.sample_count) / f64::from(self.sample_rate)
+    }
+
+    fn cells(&self) -> Vec<String> {
+        vec![
+            self.name.clone(),
+            self.sample_rate.to_string(),
+            format!("{:.3}", self.duration_s()),
+            String::from(if self.looped { "yes" } else { "no" }),
+            self.bits_per_sample.to_string(),
+            self.warnings.to_string(),
+        ]
+    }
+
+    fn to_json(&self) -> String {
+        format!(
+            "{{\"name\":{},\"rate\":{},\"duration\":{:.3},\"samples\":{},\
+            \"loop\":{},\"bits\":{},\"warnings\":{}}}",
+            output::json_string(&self.name),
+            self.sample_rate,
+            self.duration_s(),
+            self.sample_count,
+            self.looped,
+            self.bits_per_sample,
+            self.warnings,
+        )
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum ScanFilter {
+    Looped,
+    Unlooped,
+    Warnings,
+}
+
+impl TryFrom<&str> for ScanFilter {
+    type Error = String;
+
+    fn try_from(from: &str) -> Result<ScanFilter, Self::Error> {
+        match from {
+            "looped" => Ok(ScanFilter::Looped),
+            "unlooped" => Ok(ScanFilter::Unlooped),
+            "warnings" => Ok(ScanFilter::Warnings),
+            other => Err(format!("Unknown filter \"{}\"", other)),
+        }
+    }
+}
+
+impl ScanFilter {
+    fn accepts(&self, row: &ScanRow) -> bool {
+        match self {
+            ScanFilter::Looped => row.looped,
+            ScanFilter::Unlooped => !row.looped,
+            ScanFilter::Warnings => row.warnings > 0,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum SortKey {
+    Name,
+    Rate,
+    Duration,
+    Loop,
+    Bits,
+    Warnings,
+}
+
+impl TryFrom<&str> for SortKey {
+    type Error = String;
+
+    fn try_from(from: &str) -> Result<SortKey, Self::Error> {
+        match from {
+            "name" => Ok(SortKey::Name),
+            "rate" => Ok(SortKey::Rate),
+            "duration" => Ok(SortKey::Duration),
+            "loop" => Ok(SortKey::Loop),
+            "bits" => Ok(SortKey::Bits),
+            "warnings" => Ok(SortKey::Warnings),
+            other => Err(format!("Unknown sort column \"{}\"", other)),
+        }
+    }
+}
+
+fn sort_rows(rows: &mut [ScanRow], key: SortKey) {
+    rows.sort_by(|a, b| {
+        let ordering = match key {
+            SortKey::Name => std::cmp::Ordering::Equal,
+            SortKey::Rate => a.sample_rate.cmp(&b.sample_rate),
+            SortKey::Duration => a.duration_s().total_cmp(&b.duration_s()),
+            SortKey::Loop => a.looped.cmp(&b.looped),
+            SortKey::Bits => a.bits_per_sample.cmp(&b.bits_per_sample),
+            SortKey::Warnings => a.warnings.cmp(&b.warnings),
+        };
+
+        ordering.then_with(|| a.name.cmp(&b.name))
+    });
+}
+
+/// Recursively collect paths of files with a .wav extension (any case)
+pub fn find_waves(dir: &Path) -> Result<Vec<PathBuf>, String> {
+    let mut waves = vec![];
+    let mut pending = vec![dir.to_path_buf()];
+
+    while let Some(dir) = pending.pop() {
+        let entries = fs::read_dir(&dir)
+            .map_err(|e| format!("{}: {}", dir.display(), e))?;
+
+        for entry in entries {
+            let path = entry.map_err(|e| e.to_string())?.path();
+
+            if path.is_dir() {
+                pending.push(path);
+            } else if path
+                .extension()
+                .map(|ext| ext.eq_ignore_ascii_case("wav"))
+                .unwrap_or(false)
+            {
+                waves.push(path);
+            }
+        }
+    }
+
+    Ok(waves)
+}
+
+/// Read metadata of every WAV under `dir` and print one row per file.  Files
+/// that can't be read are reported on stderr and left out of the listing.
+pub fn scan(
+    dir: &Path,
+    filter: Option<ScanFilter>,
+    sort: SortKey,
+    format: OutputFormat,
+) -> Result<(), String> {
+    let mut rows = vec![];
+
+    for path in find_waves(dir)? {
+        match ScanRow::from_path(dir, &path) {
+            Ok(row) => rows.push(row),
+            Err(e) => eprintln!("{}: {}", path.display(), e),
+        }
+    }
+
+    rows.retain(|row| filter.map(|f| f.accepts(row)).unwrap_or(true));
+    sort_rows(&mut rows, sort);
+
+    match format {
+        OutputFormat::Text => {
+            let cells = rows.iter().map(ScanRow::cells).collect::<Vec<_>>();
+            println!("{}", output::text_table(&HEADER, &cells));
+        }
+        OutputFormat::Json => {
+            let objects = rows.iter().map(ScanRow::to_json).collect::<Vec<_>>();
+            println!("[{}]", objects.join(","));
+        }
+        OutputFormat::Csv => {
+            println!("{}", HEADER.join(","));
+
+            for row in &rows {
+                let cells = row
+                    .cells()
+                    .iter()
+                    .map(|cell| output::csv_field(cell))
+                    .collect::<Vec<_>>();
+                println!("{}", cells.join(","));
+            }
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,200 @@
+use crate::report::{self, TestCase};
+use quadio_core as core;
+use std::fs;
+use std::io::Cursor;
+use std::path::Path;
+
+// Seam artifact score, in dB, above which a loop fails verification
+pub const MAX_SEAM_SCORE: f64 = -30.0;
+
+const SUITE: &str = "quadio.verify";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct VerifyOptions {
+    /// Sample rate every file must have, if any
+    pub sample_rate: Option<u32>,
+    pub max_seam_score: f64,
+
+    /// Whether a file without a loop fails, rather than skipping the loop
+    /// checks
+    pub require_loop: bool,
+
+    /// Whether to write each file twice and read it back, failing unless the
+    /// writes match byte for byte and the file read back matches the input
+    pub round_trip: bool,
+
+    /// Port whose compatibility checks are run and reported one by one, if
+    /// any.  These include the loop check, which then always applies.
+    pub target: Option<&'static core::TargetProfile>,
+}
+
+/// Check that a file loops cleanly: it has a loop lying within the file, a
+/// seam scoring no worse than the maximum, and the expected sample rate, that
+/// the target port can play it, and that it survives a round trip.  Problems
+/// that don't stop the file from playing are reported as output.
+pub fn verify_file(path: &Path, options: &VerifyOptions) -> TestCase {
+    let mut case = TestCase {
+        name: path.display().to_string(),
+        ..Default::default()
+    };
+
+    if let Err(e) = check(path, options, &mut case) {
+        case.failures.push(e);
+    }
+
+    case
+}
+
+fn check(
+    path: &Path,
+    options: &VerifyOptions,
+    case: &mut TestCase,
+) -> Result<(), String> {
+    let reader = core::QWaveReader::open(path)?;
+    let metadata = reader.metadata();
+
+    if let Some((declared, actual)) = metadata.declared_vs_actual {
+        case.output.push(format!(
+            "Header declares {} samples, but data holds {}",
+            declared, actual,
+        ));
+    }
+
+    if reader.trailing_bytes() > 0 {
+        case.output.push(format!(
+            "{} trailing bytes after the RIFF chunk",
+            reader.trailing_bytes(),
+        ));
+    }
+
+    if let Some(rate) = options.sample_rate {
+        if metadata.sample_rate != rate {
+            case.failures.push(format!(
+                "Sample rate is {} Hz, expected {} Hz",
+                metadata.sample_rate, rate,
+            ));
+        }
+    }
+
+    let has_loop = metadata.loop_start.is_some();
+
+    match options.target {
+        Some(target) => {
+            for (name, result) in target.check_compatibility(&metadata) {
+                match result {
+                    Ok(()) => case.output.push(format!("PASS {}", name)),
+                    Err(e) => {
+                        case.output.push(format!("FAIL {}", name));
+                        case.failures.push(e);
+                    }
+                }
+            }
+        }
+        None if has_loop || options.require_loop => {
+            if let Err(e) = core::check_loop(&metadata) {
+                case.failures.push(e);
+            }
+        }
+        None => {}
+    }
+
+    if options.round_trip {
+        check_round_trip(path, case)?;
+    }
+
+    if !has_loop {
+        return Ok(());
+    }
+
+    match core::Project::from_reader(reader)?.seam_artifact_score() {
+        Ok(score) if score > options.max_seam_score => {
+            case.failures.push(format!(
+                "Seam artifact score {:.1} dB is above {:.1} dB",
+                score, options.max_seam_score,
+            ));
+        }
+        Ok(_) => {}
+        Err(e) => case.output.push(format!("Seam not scored: {}", e)),
+    }
+
+    Ok(())
+}
+
+// Write the file twice as the write commands would and read the output back:
+// the writes must match byte for byte, and what is read back must match the
+// file in samples and metadata, and write the same bytes in turn
+fn check_round_trip(path: &Path, case: &mut TestCase) -> Result<(), String> {
+    let write = |proj: &core::Project| -> Result<Vec<u8>, String> {
+        let mut wave = Cursor::new(vec![]);
+        proj.write(&mut wave)?;
+        Ok(wave.into_inner())
+    };
+
+    let proj = core::Project::open(path)?;
+    let written = write(&proj)?;
+
+    if write(&proj)? != written {
+        case.failures
+            .push(String::from("Writing twice gave different bytes"));
+        return Ok(());
+    }
+
+    let reader = core::QWaveReader::new(Cursor::new(&written[..]))?;
+    let reread = core::Project::from_reader(reader)?;
+
+    if !proj.diff(&reread).is_identical() {
+        case.failures.push(String::from(
+            "Reading the output back gave different samples or metadata",
+        ));
+    } else if write(&reread)? != written {
+        case.failures.push(String::from(
+            "Writing the output again gave different bytes",
+        ));
+    } else {
+        case.output.push(String::from("PASS round trip"));
+    }
+
+    Ok(())
+}
+
+/// Verify every input, printing a line per file, and write a JUnit report to
+/// `report_path` if given.  Fails if any file does.
+pub fn verify(
+    inputs: &[&Path],
+    options: &VerifyOptions,
+    report_path: Option<&Path>,
+) -> Result<(), String> {
+    let cases = inputs
+        .iter()
+        .map(|path| verify_file(path, options))
+        .collect::<Vec<_>>();
+
+    for case in &cases {
+        if case.passed() {
+            println!("PASS {}", case.name);
+        } else {
+            println!("FAIL {}: {}", case.name, case.failures.join("; "));
+        }
+
+        for line in &case.output {
+            println!("\t{}", line);
+        }
+    }
+
+    if let Some(report_path) = report_path {
+        fs::write(report_path, report::junit(SUITE, &cases))
+            .map_err(|e| format!("{}: {}", report_path.display(), e))?;
+    }
+
+    let failed = cases.iter().filter(|case| !case.passed()).count();
+
+    if failed > 0 {
+        Err(format!(
+            "{} of {} files failed verification",
+            failed,
+            cases.len()
+        ))
+    } else {
+        Ok(())
+    }
+}
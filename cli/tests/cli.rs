@@ -0,0 +1,2554 @@
+use assert_cmd::Command;
+use quadio_core as core;
+use std::fs;
+use std::io::BufReader;
+use std::path::PathBuf;
+use std::process::Output;
+use tempfile::TempDir;
+
+const RATE: u32 = 22050;
+
+struct Fixture {
+    dir: TempDir,
+}
+
+impl Fixture {
+    // A second of 16-bit, 441 Hz sine as plain.wav, and the same with a loop
+    // from 1000 to 6025 as looped.wav; the loop end is half a cycle out of
+    // phase with the start, so blending has something to smooth
+    fn new() -> Self {
+        let fixture = Fixture {
+            dir: tempfile::tempdir().unwrap(),
+        };
+
+        fixture.write_sine("plain.wav", 441.0);
+
+        let output = fixture.run(&[
+            "set-loop",
+            "-start=1000",
+            "-end=6025",
+            "plain.wav",
+            "looped.wav",
+        ]);
+        assert!(output.status.success());
+
+        fixture
+    }
+
+    // A second of 16-bit sine at `freq` Hz
+    fn write_sine(&self, name: &str, freq: f64) {
+        let spec = hound::WavSpec {
+            channels: 1,
+            sample_rate: RATE,
+            bits_per_sample: 16,
+            sample_format: hound::SampleFormat::Int,
+        };
+
+        let mut writer =
+            hound::WavWriter::create(self.path(name), spec).unwrap();
+
+        for i in 0..RATE {
+            let phase = f64::from(i) * freq / f64::from(RATE);
+            let sample = (phase * std::f64::consts::TAU).sin() * 16000.0;
+            writer.write_sample(sample as i16).unwrap();
+        }
+
+        writer.finalize().unwrap();
+    }
+
+    fn path(&self, name: &str) -> PathBuf {
+        self.dir.path().join(name)
+    }
+
+    fn command(&self) -> Command {
+        let mut command = Command::cargo_bin("quadio-cli").unwrap();
+        command
+            .current_dir(self.dir.path())
+            .env("QUADIO_CONFIG", self.path("no-config.toml"));
+        command
+    }
+
+    fn run(&self, args: &[&str]) -> Output {
+        self.command().args(args).output().unwrap()
+    }
+
+    fn metadata(&self, name: &str) -> core::Metadata {
+        let file = fs::File::open(self.path(name)).unwrap();
+        core::QWaveReader::new(BufReader::new(file))
+            .unwrap()
+            .metadata()
+    }
+
+    fn project(&self, name: &str) -> core::Project {
+        let file = fs::File::open(self.path(name)).unwrap();
+        let reader = core::QWaveReader::new(BufReader::new(file)).unwrap();
+        core::Project::from_reader(reader).unwrap()
+    }
+}
+
+fn stdout(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stdout).into_owned()
+}
+
+fn stderr(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stderr).into_owned()
+}
+
+fn assert_fails(output: &Output, message: &str) {
+    assert_eq!(output.status.code(), Some(1));
+    assert!(
+        stderr(output).contains(message),
+        "stderr {:?} lacks {:?}",
+        stderr(output),
+        message,
+    );
+}
+
+#[test]
+fn info_reports_rate_duration_and_loop() {
+    let fixture = Fixture::new();
+    let output = fixture.run(&["info", "looped.wav"]);
+    let text = stdout(&output);
+
+    assert!(output.status.success());
+    assert!(text.contains("Sample rate = 22050"));
+    assert!(text.contains("Duration = 22050 samples (1.000s)"));
+    assert!(text.contains("Loop starts at sample 1000"));
+    assert!(text.contains("Loop ends at sample 6025"));
+}
+
+#[test]
+fn info_prints_json_alone() {
+    let fixture = Fixture::new();
+
+    let output = fixture.run(&["info", "-format=json", "looped.wav"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(
+        stdout(&output).trim(),
+        "{\"sample_rate\":22050,\"sample_count\":22050,\
+        \"duration_seconds\":1,\"bits_per_sample\":16,\
+        \"float_samples\":false,\"channels\":1,\"data_bytes\":44100,\
+        \"loop_start\":1000,\"loop_end\":6025,\"play_end\":null,\
+        \"peak_dbfs\":-6.24,\"rms_dbfs\":-9.24,\"loop_rms_dbfs\":-9.24,\
+        \"markers\":[]}",
+    );
+
+    let output = fixture.run(&["info", "-format=json", "missing.wav"]);
+    assert!(!output.status.success());
+    assert!(stdout(&output).is_empty());
+}
+
+#[test]
+fn info_without_loop() {
+    let fixture = Fixture::new();
+    let output = fixture.run(&["info", "plain.wav"]);
+
+    assert!(output.status.success());
+    assert!(stdout(&output).contains("No loop point found"));
+}
+
+#[test]
+fn set_loop_accepts_each_time_form() {
+    let fixture = Fixture::new();
+    let cases = [
+        ("-start=100", "-end=200", 100, 200),
+        ("-start=0.5s", "-end=1s", RATE / 2, RATE),
+        ("-start=100ms", "-end=LAST", 2205, RATE),
+    ];
+
+    for (start, end, expected_start, expected_end) in cases {
+        let output =
+            fixture.run(&["set-loop", start, end, "plain.wav", "out.wav"]);
+        assert!(output.status.success(), "{}", stderr(&output));
+
+        let metadata = fixture.metadata("out.wav");
+        assert_eq!(metadata.loop_start, Some(expected_start));
+        assert_eq!(metadata.end.unwrap_or(metadata.sample_count), expected_end);
+    }
+}
+
+#[test]
+fn set_loop_without_end_runs_to_file_end() {
+    let fixture = Fixture::new();
+    let output =
+        fixture.run(&["set-loop", "-start=300", "plain.wav", "out.wav"]);
+    assert!(output.status.success());
+
+    let metadata = fixture.metadata("out.wav");
+    assert_eq!(metadata.loop_start, Some(300));
+    assert_eq!(metadata.end.unwrap_or(metadata.sample_count), RATE);
+}
+
+#[test]
+fn set_end_round_trips_apart_from_loop() {
+    let fixture = Fixture::new();
+    let output = fixture.run(&["set-end", "-at=0.5s", "looped.wav", "out.wav"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+
+    let metadata = fixture.metadata("out.wav");
+    assert_eq!(metadata.loop_start, Some(1000));
+    assert_eq!(metadata.end, Some(6025));
+    assert_eq!(metadata.play_end, Some(RATE / 2));
+
+    let output = fixture.run(&["info", "out.wav"]);
+    assert!(stdout(&output).contains("Playback ends at sample 11025"));
+
+    let output = fixture.run(&["strip", "out.wav", "stripped.wav"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(fixture.metadata("stripped.wav").play_end, None);
+
+    assert_fails(
+        &fixture.run(&["set-end", "-at=100", "plain.wav", "bad.wav"]),
+        "End marker requires a loop",
+    );
+}
+
+#[test]
+fn strip_removes_loop_and_keeps_samples() {
+    let fixture = Fixture::new();
+    let output = fixture.run(&["strip", "looped.wav", "out.wav"]);
+    assert!(output.status.success());
+
+    assert_eq!(fixture.metadata("out.wav").loop_start, None);
+    assert_eq!(
+        fixture.project("out.wav").samples(),
+        fixture.project("plain.wav").samples(),
+    );
+}
+
+#[test]
+fn blend_changes_only_the_end_of_the_loop() {
+    let fixture = Fixture::new();
+    let output =
+        fixture.run(&["blend", "-duration=500", "looped.wav", "out.wav"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+
+    let report = core::compare(
+        &fixture.project("looped.wav"),
+        &fixture.project("out.wav"),
+    );
+
+    assert!(report.matches_within(u32::from(u16::MAX)));
+    assert!(report.first_difference.unwrap() >= 5525);
+    assert!(report.differing_samples <= 500);
+}
+
+#[test]
+fn trim_defaults_to_the_loop_end() {
+    let fixture = Fixture::new();
+    let output = fixture.run(&["trim", "looped.wav", "trimmed.wav"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stdout(&output).contains("Trimmed 22050 to 6025 samples"));
+
+    let metadata = fixture.metadata("trimmed.wav");
+    assert_eq!(metadata.sample_count, 6025);
+    assert_eq!(metadata.loop_start, Some(1000));
+
+    let output = fixture.run(&[
+        "trim",
+        "-start=500",
+        "-end=3000",
+        "looped.wav",
+        "cropped.wav",
+    ]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    let proj = fixture.project("cropped.wav");
+    assert_eq!(proj.sample_count(), 2500);
+    assert_eq!(proj.sample_loop(), Some(500..2500));
+
+    assert_fails(
+        &fixture.run(&["trim", "plain.wav", "out.wav"]),
+        "No loop to trim after",
+    );
+    assert_fails(
+        &fixture.run(&[
+            "trim",
+            "-start=3000",
+            "-end=3000",
+            "looped.wav",
+            "x.wav",
+        ]),
+        "Trim would leave no samples",
+    );
+    assert!(!fixture.path("x.wav").exists());
+}
+
+#[test]
+fn set_loop_warns_and_strict_refuses() {
+    let fixture = Fixture::new();
+    let args = ["-start=1012", "-end=6025", "plain.wav", "out.wav"];
+    let output = fixture.run(&[&["set-loop"][..], &args].concat());
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stderr(&output).contains("Warning: loop start lands at amplitude"));
+    assert!(stderr(&output).contains("Warning: vanilla Quake plays only 8-bit"));
+
+    fs::remove_file(fixture.path("out.wav")).unwrap();
+    let output = fixture.run(&[&["set-loop", "-strict"][..], &args].concat());
+    assert_fails(&output, "loop start lands at amplitude");
+    assert!(!fixture.path("out.wav").exists());
+
+    let output = fixture.run(&["strip", "-bits=8", "looped.wav", "out.wav"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(!stderr(&output).contains("Warning"));
+}
+
+#[test]
+fn markers_are_listed_and_bound_loops() {
+    let fixture = Fixture::new();
+    let mut proj = fixture.project("looped.wav");
+    proj.add_marker(2000, "verse").unwrap();
+    proj.add_marker(7000, "drone starts").unwrap();
+    proj.write_to(&fixture.path("marked.wav")).unwrap();
+
+    let output = fixture.run(&["markers", "marked.wav"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(
+        stdout(&output),
+        "ID  Sample  Seconds  Length  Label\n\
+         2   2000    0.091    -       verse\n\
+         3   7000    0.317    -       drone starts\n"
+    );
+
+    let output = fixture.run(&["markers", "-format=json", "marked.wav"]);
+    assert_eq!(
+        stdout(&output),
+        "[{\"id\":2,\"position\":2000,\"seconds\":0.090703,\"length\":null,\
+         \"label\":\"verse\"},{\"id\":3,\"position\":7000,\
+         \"seconds\":0.317460,\"length\":null,\"label\":\"drone starts\"}]\n"
+    );
+
+    let output = fixture.run(&[
+        "set-loop",
+        "-from-markers=verse,drone starts",
+        "marked.wav",
+        "out.wav",
+    ]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(fixture.project("out.wav").sample_loop(), Some(2000..7000));
+    assert_eq!(fixture.project("out.wav").markers().len(), 2);
+
+    assert_fails(
+        &fixture.run(&[
+            "set-loop",
+            "-from-markers=verse,chorus",
+            "marked.wav",
+            "out.wav",
+        ]),
+        "No marker labeled \"chorus\"",
+    );
+}
+
+#[test]
+fn set_loop_snaps_to_zero_crossings() {
+    let fixture = Fixture::new();
+    let output = fixture.run(&[
+        "set-loop",
+        "-start=1010",
+        "-end=6025",
+        "-snap=30",
+        "plain.wav",
+        "snapped.wav",
+    ]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(
+        fixture.project("snapped.wav").sample_loop(),
+        Some(1000..6000)
+    );
+
+    let output = fixture.run(&[
+        "set-loop",
+        "-start=1020",
+        "-end=6000",
+        "-snap=2",
+        "plain.wav",
+        "unsnapped.wav",
+    ]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stderr(&output).contains("of the loop start; left as given"));
+    assert!(!stderr(&output).contains("of the loop end"));
+    assert_eq!(
+        fixture.project("unsnapped.wav").sample_loop(),
+        Some(1020..6000)
+    );
+}
+
+#[test]
+fn unroll_repeats_the_loop() {
+    let fixture = Fixture::new();
+    let output =
+        fixture.run(&["unroll", "-count=2", "looped.wav", "unrolled.wav"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stdout(&output).contains("Unrolled 22050 to 11050 samples"));
+
+    let unrolled = fixture.project("unrolled.wav");
+    let looped = fixture.project("looped.wav");
+    assert_eq!(unrolled.sample_loop(), None);
+    assert_eq!(unrolled.samples()[..6025], looped.samples()[..6025]);
+    assert_eq!(unrolled.samples()[6025..], looped.samples()[1000..6025]);
+
+    let output =
+        fixture.run(&["unroll", "-count=2", "-tail", "looped.wav", "tail.wav"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(fixture.metadata("tail.wav").sample_count, 11050 + 16025);
+
+    assert_fails(
+        &fixture.run(&["unroll", "-count=2", "plain.wav", "out.wav"]),
+        "No loop to unroll",
+    );
+}
+
+#[test]
+fn find_loop_suggests_and_applies_loops() {
+    let fixture = Fixture::new();
+    let output = fixture.run(&["find-loop", "-min-length=5000", "plain.wav"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stdout(&output).starts_with("1. "));
+    assert!(stdout(&output).contains("s), score "));
+
+    let output = fixture.run(&[
+        "find-loop",
+        "-apply",
+        "-min-length=5000",
+        "plain.wav",
+        "found.wav",
+    ]);
+    assert!(output.status.success(), "{}", stderr(&output));
+
+    // The fixture repeats every 50 samples
+    let sample_loop = fixture.project("found.wav").sample_loop().unwrap();
+    assert!(sample_loop.len() >= 5000);
+    assert_eq!(sample_loop.len() % 50, 0);
+}
+
+#[test]
+fn concat_joins_files_and_loops_the_second() {
+    let fixture = Fixture::new();
+    let output = fixture.run(&[
+        "concat",
+        "-loop-second",
+        "plain.wav",
+        "looped.wav",
+        "joined.wav",
+    ]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stdout(&output).contains("Appended 22050 samples to 22050"));
+
+    let joined = fixture.project("joined.wav");
+    assert_eq!(joined.sample_count(), 44100);
+    assert_eq!(joined.sample_loop(), Some(22050..44100));
+
+    let output =
+        fixture.run(&["concat", "plain.wav", "looped.wav", "joined.wav"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(
+        fixture.project("joined.wav").sample_loop(),
+        Some(23050..28075)
+    );
+}
+
+#[test]
+fn dc_offset_reports_the_offset_removed() {
+    let fixture = Fixture::new();
+    let output = fixture.run(&["dc-offset", "looped.wav", "centered.wav"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stdout(&output).contains("DC offset"));
+
+    let centered = fixture.project("centered.wav");
+    let output = fixture.run(&["dc-offset", "centered.wav", "again.wav"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stdout(&output).contains("No DC offset to remove"));
+    assert_eq!(fixture.project("again.wav").samples(), centered.samples());
+}
+
+#[test]
+fn normalize_reports_gain_and_clipping() {
+    let fixture = Fixture::new();
+    let output =
+        fixture.run(&["normalize", "-peak=-0.5dB", "looped.wav", "peak.wav"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stdout(&output).contains("dB of gain"));
+    assert!(!stdout(&output).contains("Clipped"));
+
+    let proj = fixture.project("peak.wav");
+    let peak = proj.samples().iter().map(|&s| s.unsigned_abs()).max();
+    assert_eq!(peak, Some(30935));
+    assert_eq!(proj.sample_loop(), Some(1000..6025));
+
+    let output =
+        fixture.run(&["normalize", "-rms=0dB", "looped.wav", "rms.wav"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stdout(&output).contains("Clipped"));
+
+    assert_fails(
+        &fixture.run(&["normalize", "looped.wav", "out.wav"]),
+        "Expected one of -peak or -rms",
+    );
+}
+
+#[test]
+fn extract_writes_the_loop_alone() {
+    let fixture = Fixture::new();
+    let output = fixture.run(&["extract", "looped.wav", "loop.wav"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+
+    let metadata = fixture.metadata("loop.wav");
+    assert_eq!(metadata.sample_count, 5025);
+    assert_eq!(metadata.loop_start, Some(0));
+    assert_eq!(metadata.end, Some(5025));
+    assert_eq!(
+        fixture.project("loop.wav").samples(),
+        &fixture.project("looped.wav").samples()[1000..6025],
+    );
+
+    let output =
+        fixture.run(&["extract", "-with-blend", "looped.wav", "blended.wav"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stdout(&output).contains("Blended over"));
+    let blended = fixture.project("blended.wav");
+    assert_eq!(blended.sample_loop(), Some(0..5025));
+    assert!(
+        blended.seam_artifact_score().unwrap()
+            < fixture.project("loop.wav").seam_artifact_score().unwrap()
+    );
+
+    assert_fails(
+        &fixture.run(&["extract", "plain.wav", "out.wav"]),
+        "No loop to extract; set one with set-loop first",
+    );
+}
+
+#[test]
+fn diff_locates_changes_and_exits_by_outcome() {
+    let fixture = Fixture::new();
+    let output = fixture.run(&["diff", "looped.wav", "looped.wav"]);
+    assert_eq!(output.status.code(), Some(0), "{}", stderr(&output));
+    assert!(stdout(&output).contains("Shared samples are identical"));
+
+    let output = fixture.run(&[
+        "blend",
+        "-duration=500",
+        "-yes",
+        "looped.wav",
+        "blended.wav",
+    ]);
+    assert!(output.status.success(), "{}", stderr(&output));
+
+    let output =
+        fixture.run(&["diff", "-duration=500", "looped.wav", "blended.wav"]);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(stderr(&output).contains("Files differ"));
+    let text = stdout(&output);
+    assert!(
+        text.contains("samples of the loop, within the blend window"),
+        "{}",
+        text,
+    );
+    assert!(text.contains("Loop end    6025  6025"), "{}", text);
+
+    let output =
+        fixture.run(&["diff", "-duration=100", "looped.wav", "blended.wav"]);
+    assert!(stdout(&output).contains("beyond the blend window"));
+
+    let output = fixture.run(&["diff", "looped.wav", "plain.wav"]);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(stdout(&output).contains("Loop start  1000  none  differs"));
+
+    let output = fixture.run(&["diff", "looped.wav", "missing.wav"]);
+    assert_eq!(output.status.code(), Some(2));
+}
+
+#[test]
+fn check_seam_reports_and_blend_improves_the_seam() {
+    let fixture = Fixture::new();
+    let output = fixture.run(&["check-seam", "-max-seam=0dB", "looped.wav"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stdout(&output).contains("Slope step = 3979 (-18.3 dBFS)"));
+
+    assert_fails(
+        &fixture.run(&["check-seam", "looped.wav"]),
+        "is above -30.0 dB",
+    );
+
+    let output = fixture.run(&[
+        "blend",
+        "-duration=500",
+        "-yes",
+        "looped.wav",
+        "blended.wav",
+    ]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    let text = stdout(&output);
+    let (before, after) = text.split_once("Seam after").unwrap();
+    assert!(before.contains("Slope step = 3979"), "{}", text);
+    assert!(after.contains("Slope step = 31 "), "{}", text);
+
+    let output = fixture.run(&[
+        "check-seam",
+        "-format=json",
+        "-max-seam=0",
+        "blended.wav",
+    ]);
+    assert!(stdout(&output).contains("\"slope_step\":31,"));
+}
+
+#[test]
+fn match_loudness_levels_the_loop_with_the_reference() {
+    let fixture = Fixture::new();
+    let output =
+        fixture.run(&["normalize", "-rms=-20dB", "looped.wav", "quiet.wav"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+
+    let output = fixture.run(&[
+        "match-loudness",
+        "-reference=looped.wav",
+        "quiet.wav",
+        "matched.wav",
+    ]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stdout(&output).contains("Matched loop RMS of -9.24 dBFS"));
+    assert!(stdout(&output).contains("Applied +10."));
+
+    let level = |name: &str| fixture.project(name).loudness().loop_rms_dbfs;
+    let (matched, reference) = (level("matched.wav"), level("looped.wav"));
+    assert!((matched.unwrap() - reference.unwrap()).abs() < 0.01);
+
+    let output = fixture.run(&["info", "matched.wav"]);
+    assert!(stdout(&output).contains("Loop RMS = -9.24 dBFS"));
+
+    assert_fails(
+        &fixture.run(&["match-loudness", "looped.wav", "out.wav"]),
+        "reference",
+    );
+}
+
+#[test]
+fn bits_sets_the_depth_written() {
+    let fixture = Fixture::new();
+    let data = |name: &str| {
+        let file = fs::File::open(fixture.path(name)).unwrap();
+        core::QWaveReader::new(BufReader::new(file))
+            .unwrap()
+            .into_source_chunks()
+            .unwrap()
+            .data
+    };
+
+    let output = fixture.run(&[
+        "set-loop",
+        "-bits=8",
+        "-start=10",
+        "plain.wav",
+        "8.wav",
+    ]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(fixture.metadata("8.wav").bits_per_sample, 8);
+
+    // Stored unsigned and dithered, so within a step or so of the source
+    let source = fixture.project("plain.wav");
+    let narrowed = fixture.project("8.wav");
+    assert!(source
+        .samples()
+        .iter()
+        .zip(narrowed.samples())
+        .all(|(&a, &b)| (i32::from(a) - i32::from(b)).abs() <= 512));
+
+    let output = fixture.run(&[
+        "set-loop",
+        "-bits=8",
+        "-start=20",
+        "8.wav",
+        "again.wav",
+    ]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(data("again.wav"), data("8.wav"));
+
+    let output = fixture.run(&["strip", "-bits=16", "8.wav", "16.wav"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(fixture.metadata("16.wav").bits_per_sample, 16);
+
+    assert_fails(
+        &fixture.run(&["strip", "-bits=24", "plain.wav", "out.wav"]),
+        "Unsupported bit depth \"24\"",
+    );
+}
+
+#[test]
+fn bits_narrows_wider_input_to_16() {
+    let fixture = Fixture::new();
+
+    for (name, bits, sample_format) in [
+        ("24.wav", 24, hound::SampleFormat::Int),
+        ("float.wav", 32, hound::SampleFormat::Float),
+    ] {
+        let spec = hound::WavSpec {
+            channels: 1,
+            sample_rate: RATE,
+            bits_per_sample: bits,
+            sample_format,
+        };
+        let mut writer =
+            hound::WavWriter::create(fixture.path(name), spec).unwrap();
+
+        for i in 0..RATE {
+            let sample = (f64::from(i) / 20.0).sin() * 0.5;
+
+            match sample_format {
+                hound::SampleFormat::Int => {
+                    writer.write_sample((sample * 8388607.0) as i32).unwrap()
+                }
+                hound::SampleFormat::Float => {
+                    writer.write_sample(sample as f32).unwrap()
+                }
+            }
+        }
+
+        writer.finalize().unwrap();
+
+        let output = fixture.run(&[
+            "set-loop",
+            "-bits=16",
+            "-start=100",
+            name,
+            "out.wav",
+        ]);
+        assert!(output.status.success(), "{}", stderr(&output));
+
+        let metadata = fixture.metadata("out.wav");
+        assert_eq!(metadata.bits_per_sample, 16, "{name}");
+        assert!(!metadata.float_samples, "{name}");
+        assert_eq!(
+            fixture.project("out.wav").samples(),
+            fixture.project(name).samples(),
+            "{name}"
+        );
+    }
+}
+
+#[test]
+fn convert_rescales_the_loop() {
+    let fixture = Fixture::new();
+
+    let output = fixture.run(&[
+        "convert",
+        "-rate=11025",
+        "-bits=8",
+        "-blend=100",
+        "looped.wav",
+        "converted.wav",
+    ]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stdout(&output).contains("22050 Hz -> 11025 Hz"));
+    assert!(stdout(&output).contains("Loop 1000..6025 -> 500..3013"));
+
+    let metadata = fixture.metadata("converted.wav");
+    assert_eq!(metadata.sample_rate, 11025);
+    assert_eq!(metadata.sample_count, 11025);
+    assert_eq!(metadata.bits_per_sample, 8);
+    assert_eq!((metadata.loop_start, metadata.end), (Some(500), Some(3013)));
+
+    assert_fails(
+        &fixture.run(&["convert", "-rate=22050", "converted.wav", "up.wav"]),
+        "use -yes to convert anyway",
+    );
+    assert_fails(
+        &fixture.run(&["convert", "looped.wav", "same.wav"]),
+        "Expected -rate or -bits",
+    );
+}
+
+#[test]
+fn symmetric_blend_changes_both_seams() {
+    let fixture = Fixture::new();
+    let output = fixture.run(&[
+        "blend",
+        "-duration=500",
+        "-symmetric",
+        "looped.wav",
+        "out.wav",
+    ]);
+    assert!(output.status.success(), "{}", stderr(&output));
+
+    let report = core::compare(
+        &fixture.project("looped.wav"),
+        &fixture.project("out.wav"),
+    );
+
+    let first_difference = report.first_difference.unwrap();
+    assert!((1000..1500).contains(&first_difference));
+    assert!(report.differing_samples <= 1000);
+}
+
+#[test]
+fn blend_curve_is_selectable() {
+    let fixture = Fixture::new();
+
+    for curve in ["smooth", "equal-power"] {
+        let output = fixture.run(&[
+            "blend",
+            "-duration=500",
+            &format!("-curve={}", curve),
+            "looped.wav",
+            &format!("{}.wav", curve),
+        ]);
+        assert!(output.status.success(), "{}", stderr(&output));
+    }
+
+    let output =
+        fixture.run(&["blend", "-duration=500", "looped.wav", "default.wav"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(
+        fs::read(fixture.path("default.wav")).unwrap(),
+        fs::read(fixture.path("smooth.wav")).unwrap(),
+    );
+
+    let smooth = fixture.project("smooth.wav");
+    let equal_power = fixture.project("equal-power.wav");
+    assert_ne!(smooth.samples(), equal_power.samples());
+    assert_eq!(smooth.samples()[..5525], equal_power.samples()[..5525]);
+
+    assert_fails(
+        &fixture.run(&["blend", "-curve=cubic", "looped.wav", "out.wav"]),
+        "Unknown blend curve \"cubic\"",
+    );
+}
+
+#[test]
+fn default_blend_window_follows_lowest_frequency() {
+    let fixture = Fixture::new();
+    let blended_over = |freq: f64| {
+        fixture.write_sine("tone.wav", freq);
+        let output =
+            fixture.run(&["set-loop", "-start=10000", "tone.wav", "tone.wav"]);
+        assert!(output.status.success(), "{}", stderr(&output));
+
+        let output = fixture.run(&["blend", "tone.wav", "out.wav"]);
+        assert!(output.status.success(), "{}", stderr(&output));
+
+        stdout(&output)
+            .lines()
+            .next()
+            .and_then(|line| line.strip_prefix("Blended over "))
+            .and_then(|rest| rest.strip_suffix(" samples"))
+            .map(|window_sz| window_sz.parse::<u32>().unwrap())
+            .unwrap()
+    };
+
+    let low = blended_over(30.0);
+    let high = blended_over(500.0);
+
+    assert!(low > high, "{} <= {}", low, high);
+    assert!(low >= 2 * RATE / 35, "{}", low);
+    assert_eq!(high, RATE / 1000 * 5);
+}
+
+#[test]
+fn default_blend_window_shrinks_to_short_loops() {
+    let fixture = Fixture::new();
+    let output = fixture.run(&[
+        "set-loop",
+        "-start=1000",
+        "-end=1040",
+        "looped.wav",
+        "short.wav",
+    ]);
+    assert!(output.status.success(), "{}", stderr(&output));
+
+    let output = fixture.run(&["blend", "-yes", "short.wav", "out.wav"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stdout(&output).contains("Blended over 40 samples\n"));
+    assert!(
+        stderr(&output).contains("Warning: blend window clamped to 40 samples"),
+        "{}",
+        stderr(&output)
+    );
+
+    let output =
+        fixture.run(&["blend", "-yes", "-duration=41", "short.wav", "out.wav"]);
+    assert_fails(&output, "longer than the 40-sample loop");
+    assert_fails(&output, "blend over at most 40 samples");
+}
+
+#[test]
+fn blend_over_much_of_the_loop_asks_first() {
+    let fixture = Fixture::new();
+    let blend = |extra: &[&str], answer: &str| {
+        let mut args = vec!["blend", "-duration=800", "-max-impact=10"];
+        args.extend_from_slice(extra);
+        args.extend(["looped.wav", "out.wav"]);
+
+        fixture
+            .command()
+            .args(&args)
+            .write_stdin(answer)
+            .output()
+            .unwrap()
+    };
+
+    let output = blend(&[], "\n");
+    assert_fails(&output, "Blend cancelled");
+    assert!(stdout(&output).contains("covers 15.9% of the 5025-sample loop"));
+    assert!(!fixture.path("out.wav").exists());
+
+    assert!(blend(&[], "y\n").status.success());
+    assert!(blend(&["-yes"], "").status.success());
+    assert!(blend(&["-max-impact=20"], "").status.success());
+}
+
+#[test]
+fn blend_to_standard_output_asks_on_stderr() {
+    let fixture = Fixture::new();
+    let output = fixture.run(&[
+        "set-loop",
+        "-start=2000",
+        "-end=4000",
+        "plain.wav",
+        "short.wav",
+    ]);
+    assert!(output.status.success(), "{}", stderr(&output));
+
+    // Half the loop, well over the 25% allowed without asking
+    for (extra, answer) in [(&["-yes"][..], ""), (&[], "y\n")] {
+        let mut args = vec!["blend", "-duration=1000"];
+        args.extend_from_slice(extra);
+        args.extend(["short.wav", "-"]);
+
+        let output = fixture
+            .command()
+            .args(&args)
+            .write_stdin(answer)
+            .output()
+            .unwrap();
+        assert!(output.status.success(), "{}", stderr(&output));
+        assert!(stderr(&output).contains("covers 50.0% of the 2000-sample"));
+
+        let metadata =
+            core::QWaveReader::new(std::io::Cursor::new(output.stdout))
+                .unwrap()
+                .metadata();
+        assert_eq!(metadata.loop_start, Some(2000));
+    }
+}
+
+#[test]
+fn blend_without_lead_fails() {
+    let fixture = Fixture::new();
+    let output =
+        fixture.run(&["blend", "-duration=2000", "looped.wav", "out.wav"]);
+
+    assert_fails(&output, "pad the start by at least 1000 samples");
+    assert!(!fixture.path("out.wav").exists());
+
+    let output =
+        fixture.run(&["pad", "-before=1000", "looped.wav", "padded.wav"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stdout(&output).contains("Padded 22050 to 23050 samples"));
+    assert!(stdout(&output).contains("Loop is 2000..7025 (0.228s)"));
+    assert_eq!(
+        fixture.project("padded.wav").sample_loop(),
+        Some(2000..7025)
+    );
+
+    let output = fixture.run(&[
+        "blend",
+        "-yes",
+        "-duration=2000",
+        "padded.wav",
+        "out.wav",
+    ]);
+    assert!(output.status.success(), "{}", stderr(&output));
+}
+
+#[test]
+fn data_hash_ignores_loop_edits_but_not_blend() {
+    let fixture = Fixture::new();
+    let data_hash = |name: &str| {
+        let output = fixture.run(&["hash", name]);
+        assert!(output.status.success(), "{}", stderr(&output));
+
+        stdout(&output)
+            .lines()
+            .find_map(|line| line.trim().strip_prefix("Data = "))
+            .map(String::from)
+            .unwrap()
+    };
+
+    let output = fixture.run(&[
+        "set-loop",
+        "-start=2000",
+        "-hash",
+        "looped.wav",
+        "moved.wav",
+    ]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stdout(&output).contains(&data_hash("moved.wav")));
+    assert_eq!(data_hash("moved.wav"), data_hash("plain.wav"));
+    assert_eq!(data_hash("looped.wav"), data_hash("plain.wav"));
+
+    let output =
+        fixture.run(&["blend", "-duration=500", "looped.wav", "blended.wav"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_ne!(data_hash("blended.wav"), data_hash("looped.wav"));
+}
+
+#[test]
+fn huge_sizes_fail_cleanly() {
+    let fixture = Fixture::new();
+
+    // 8-bit mono whose data chunk declares the most samples its size field
+    // can, with only 100 present
+    let mut wave = b"RIFF\0\0\0\0WAVEfmt ".to_vec();
+    wave.extend(16u32.to_le_bytes());
+    wave.extend(1u16.to_le_bytes());
+    wave.extend(1u16.to_le_bytes());
+    wave.extend(RATE.to_le_bytes());
+    wave.extend(RATE.to_le_bytes());
+    wave.extend(1u16.to_le_bytes());
+    wave.extend(8u16.to_le_bytes());
+    wave.extend(b"data");
+    wave.extend(u32::MAX.to_le_bytes());
+    wave.extend([0x80u8; 100]);
+    let riff_size = u32::try_from(wave.len() - 8).unwrap();
+    wave[4..8].copy_from_slice(&riff_size.to_le_bytes());
+    fs::write(fixture.path("huge.wav"), wave).unwrap();
+
+    let output = fixture.run(&["info", "huge.wav"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stdout(&output).contains("declares 4294967295 samples"));
+
+    assert_fails(
+        &fixture.run(&["set-loop", "-start=10", "huge.wav", "out.wav"]),
+        "use -repair",
+    );
+
+    let output = fixture.run(&[
+        "set-loop",
+        "-start=10",
+        "-end=LAST",
+        "-repair",
+        "huge.wav",
+        "out.wav",
+    ]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(fixture.metadata("out.wav").sample_count, 100);
+
+    for start in ["-start=1000000s", "-start=4294967296"] {
+        assert_fails(
+            &fixture.run(&["set-loop", start, "plain.wav", "out.wav"]),
+            "beyond the maximum of 4294967295 samples",
+        );
+    }
+
+    assert_fails(
+        &fixture.run(&["set-loop", "-start=-1s", "plain.wav", "out.wav"]),
+        "is negative",
+    );
+}
+
+#[test]
+fn loop_past_eof_is_clamped() {
+    let fixture = Fixture::new();
+
+    // Stretch the labeled text's sample length far past the last sample
+    let mut wave = fs::read(fixture.path("looped.wav")).unwrap();
+    let ltxt = wave.windows(4).position(|w| w == b"ltxt").unwrap();
+    wave[ltxt + 12..ltxt + 16].copy_from_slice(&u32::MAX.to_le_bytes());
+    fs::write(fixture.path("long.wav"), wave).unwrap();
+
+    let metadata = fixture.metadata("long.wav");
+    assert_eq!(metadata.end, Some(RATE));
+    assert_eq!(metadata.loop_end_past_eof, Some(u32::MAX));
+    assert_eq!(fixture.project("long.wav").sample_loop(), Some(1000..RATE));
+
+    let output = fixture.run(&["info", "long.wav"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stdout(&output).contains(&format!(
+        "runs to sample {}, past the end of the file; clamped to {}",
+        u32::MAX,
+        RATE,
+    )));
+
+    // No audio device in CI, but the loop must not be what's rejected
+    let project = fixture.project("long.wav");
+    if let Err(e) = core::setup_player(
+        &metadata,
+        project.samples(),
+        core::OutputLevel::default(),
+        None,
+    ) {
+        assert!(matches!(e, core::Error::Audio(_)), "{}", e);
+    }
+}
+
+#[test]
+fn rf64_is_rejected_with_advice() {
+    let fixture = Fixture::new();
+
+    // Sizes of -1 defer to the ds64 chunk, as in RF64 files too large for
+    // 32-bit sizes
+    let mut wave = b"RF64\xff\xff\xff\xffWAVEds64".to_vec();
+    wave.extend(28u32.to_le_bytes());
+    wave.extend(136u64.to_le_bytes());
+    wave.extend(100u64.to_le_bytes());
+    wave.extend(100u64.to_le_bytes());
+    wave.extend(0u32.to_le_bytes());
+    wave.extend(b"fmt ");
+    wave.extend(16u32.to_le_bytes());
+    wave.extend(1u16.to_le_bytes());
+    wave.extend(1u16.to_le_bytes());
+    wave.extend(RATE.to_le_bytes());
+    wave.extend(RATE.to_le_bytes());
+    wave.extend(1u16.to_le_bytes());
+    wave.extend(8u16.to_le_bytes());
+    wave.extend(b"data");
+    wave.extend(u32::MAX.to_le_bytes());
+    wave.extend([0x80u8; 100]);
+    fs::write(fixture.path("long.wav"), wave).unwrap();
+
+    for args in [&["info", "long.wav"][..], &["strip", "long.wav", "out.wav"]] {
+        assert_fails(&fixture.run(args), "RF64 (64-bit RIFF) files are");
+    }
+}
+
+#[test]
+fn metadata_edits_copy_audio_chunks_verbatim() {
+    let fixture = Fixture::new();
+
+    // 8-bit mono with an 18-byte fmt chunk and an odd number of samples, both
+    // of which re-encoding would change
+    let mut wave = b"RIFF\0\0\0\0WAVEfmt ".to_vec();
+    wave.extend(18u32.to_le_bytes());
+    wave.extend(1u16.to_le_bytes());
+    wave.extend(1u16.to_le_bytes());
+    wave.extend(RATE.to_le_bytes());
+    wave.extend(RATE.to_le_bytes());
+    wave.extend(1u16.to_le_bytes());
+    wave.extend(8u16.to_le_bytes());
+    wave.extend(0u16.to_le_bytes());
+    wave.extend(b"data");
+    wave.extend(1001u32.to_le_bytes());
+    wave.extend((0..1001).map(|i| (i % 256) as u8));
+    wave.push(0);
+    let riff_size = u32::try_from(wave.len() - 8).unwrap();
+    wave[4..8].copy_from_slice(&riff_size.to_le_bytes());
+    fs::write(fixture.path("odd.wav"), wave).unwrap();
+
+    let data_hash = |name: &str| {
+        let output = fixture.run(&["hash", "-format=json", name]);
+        assert!(output.status.success(), "{}", stderr(&output));
+        stdout(&output).split("\"data\":").nth(1).unwrap()[..66].to_owned()
+    };
+    let fmt_size = |name: &str| {
+        let file = fs::File::open(fixture.path(name)).unwrap();
+        core::QWaveReader::new(BufReader::new(file))
+            .unwrap()
+            .chunks()
+            .iter()
+            .find(|chunk| chunk.tag == *b"fmt ")
+            .unwrap()
+            .size
+    };
+
+    for args in [&["set-loop", "-start=10"][..], &["strip"], &["clean"]] {
+        let mut args = args.to_vec();
+        args.extend(["odd.wav", "out.wav"]);
+        let output = fixture.run(&args);
+        assert!(output.status.success(), "{}", stderr(&output));
+
+        assert_eq!(fmt_size("out.wav"), 18);
+        assert_eq!(data_hash("out.wav"), data_hash("odd.wav"));
+    }
+
+    let output = fixture.run(&[
+        "set-loop",
+        "-start=10",
+        "-reencode",
+        "odd.wav",
+        "out.wav",
+    ]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(fmt_size("out.wav"), 16);
+    assert_eq!(fixture.metadata("out.wav").loop_start, Some(10));
+}
+
+#[test]
+fn missing_input_fails() {
+    let fixture = Fixture::new();
+    assert_fails(
+        &fixture.run(&["info", "absent.wav"]),
+        "absent.wav: No such file",
+    );
+    assert_fails(
+        &fixture.run(&["blend", "absent.wav", "out.wav"]),
+        "absent.wav: No such file",
+    );
+    assert_fails(&fixture.run(&["info"]), "No input file provided");
+}
+
+#[test]
+fn bad_arguments_fail() {
+    let fixture = Fixture::new();
+
+    assert_fails(
+        &fixture.run(&["info", "-bogus", "plain.wav"]),
+        "Unrecognized argument bogus",
+    );
+    assert_fails(
+        &fixture.run(&["set-loop", "plain.wav", "out.wav"]),
+        "Expected argument \"start\"",
+    );
+    assert_fails(
+        &fixture.run(&["info", "plain.wav", "a.wav", "b.wav"]),
+        "Unexpected extra argument 'a.wav'",
+    );
+}
+
+#[test]
+fn arguments_are_checked_as_parsed() {
+    let fixture = Fixture::new();
+
+    assert_fails(
+        &fixture.run(&["blend", "-duraton=10ms", "looped.wav", "out.wav"]),
+        "Unrecognized argument duraton; did you mean -duration?",
+    );
+    assert_fails(
+        &fixture.run(&["set-loop", "-start=", "plain.wav", "out.wav"]),
+        "Argument -start requires a value",
+    );
+    assert_fails(
+        &fixture.run(&["set-loop", "plain.wav", "out.wav", "-start"]),
+        "Argument -start requires a value",
+    );
+    assert_fails(
+        &fixture.run(&["strip", "-repair=yes", "plain.wav", "out.wav"]),
+        "Argument -repair takes no value",
+    );
+    assert_fails(
+        &fixture.run(&["strip", "-start=5", "looped.wav", "out.wav"]),
+        "strip doesn't take -start",
+    );
+    assert!(!fixture.path("out.wav").exists());
+
+    // Values may follow as the next argument, and options may follow inputs
+    let output =
+        fixture.run(&["set-loop", "-start", "1000", "plain.wav", "out.wav"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(fixture.metadata("out.wav").loop_start, Some(1000));
+
+    let output = fixture.run(&["info", "out.wav", "-format", "json"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stdout(&output).starts_with('{'));
+
+    // Negative values need no '='
+    let output =
+        fixture.run(&["normalize", "-peak", "-6dB", "plain.wav", "loud.wav"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+}
+
+#[test]
+fn write_commands_run_over_many_inputs() {
+    let fixture = Fixture::new();
+    fs::create_dir(fixture.path("build")).unwrap();
+    fs::write(fixture.path("broken.wav"), b"not a wave").unwrap();
+
+    let output = fixture.run(&[
+        "strip",
+        "-out-dir=build",
+        "looped.wav",
+        "broken.wav",
+        "plain.wav",
+    ]);
+    assert_fails(&output, "1 of 3 files failed");
+    assert!(stdout(&output).contains("looped.wav  ok"));
+    assert!(stdout(&output).contains("plain.wav   ok"));
+
+    assert_eq!(fixture.metadata("build/looped.wav").loop_start, None);
+    assert!(fixture.path("build/plain.wav").exists());
+    assert!(!fixture.path("build/broken.wav").exists());
+
+    // Without -out-dir, a third path is still an error
+    assert_fails(
+        &fixture.run(&["strip", "looped.wav", "a.wav", "b.wav"]),
+        "Unexpected extra argument 'b.wav'",
+    );
+}
+
+#[test]
+fn in_place_edits_replace_the_input_whole() {
+    let fixture = Fixture::new();
+
+    let output =
+        fixture.run(&["set-loop", "-in-place", "-start=2000", "looped.wav"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(fixture.metadata("looped.wav").loop_start, Some(2000));
+
+    let output = fixture.run(&["strip", "looped.wav", "looped.wav"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(fixture.metadata("looped.wav").loop_start, None);
+    assert_eq!(fixture.project("looped.wav").samples().len(), RATE as usize);
+
+    // Nowhere to write the new file first, so the original must survive
+    let original = fs::read(fixture.path("plain.wav")).unwrap();
+    fs::create_dir(fixture.path(".plain.wav.tmp")).unwrap();
+    assert!(!fixture
+        .run(&["set-loop", "-in-place", "-start=10", "plain.wav"])
+        .status
+        .success());
+    assert_eq!(fs::read(fixture.path("plain.wav")).unwrap(), original);
+
+    assert_fails(
+        &fixture.run(&["strip", "-in-place", "plain.wav", "out.wav"]),
+        "-in-place can't be given with an output",
+    );
+}
+
+#[test]
+fn unknown_sub_command_prints_usage() {
+    let fixture = Fixture::new();
+    let output = fixture.run(&["frobnicate"]);
+
+    assert_fails(&output, "Unknown sub-command \"frobnicate\"");
+    assert!(stdout(&output).contains("Usage: quadio-cli"));
+}
+
+#[test]
+fn pak_entries_are_read_and_written_in_place() {
+    let fixture = Fixture::new();
+    let mut writer = core::PakWriter::new();
+    let looped = fs::read(fixture.path("looped.wav")).unwrap();
+    writer.replace("sound/looped.wav", looped).unwrap();
+    writer.replace("maps/e1m1.bsp", b"map".to_vec()).unwrap();
+    writer.write_to(&fixture.path("pak0.pak"), false).unwrap();
+
+    let output = fixture.run(&[
+        "info",
+        "-pak=pak0.pak",
+        "-format=json",
+        "sound/looped.wav",
+    ]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stdout(&output).contains("\"loop_start\":1000"));
+
+    let output = fixture.run(&[
+        "set-loop",
+        "-pak=pak0.pak",
+        "-start=2000",
+        "-in-place",
+        "sound/looped.wav",
+    ]);
+    assert!(output.status.success(), "{}", stderr(&output));
+
+    let mut reader = core::PakReader::open(fixture.path("pak0.pak")).unwrap();
+    let entry = reader.read_entry("sound/looped.wav").unwrap();
+    let metadata = core::QWaveReader::new(std::io::Cursor::new(entry))
+        .unwrap()
+        .metadata();
+    assert_eq!(metadata.loop_start, Some(2000));
+    assert_eq!(reader.read_entry("maps/e1m1.bsp").unwrap(), b"map");
+
+    assert_fails(
+        &fixture.run(&["info", "-pak=pak0.pak", "sound/missing.wav"]),
+        "No entry \"sound/missing.wav\" in pak",
+    );
+    assert_fails(
+        &fixture.run(&["info", "-pak=looped.wav", "sound/looped.wav"]),
+        "looped.wav: Not a pak archive",
+    );
+    assert_fails(
+        &fixture.run(&[
+            "strip",
+            "-pak=pak0.pak",
+            "-hash",
+            "sound/looped.wav",
+            "sound/out.wav",
+        ]),
+        "-hash needs an output file",
+    );
+}
+
+#[test]
+fn help_describes_one_sub_command() {
+    let fixture = Fixture::new();
+    let output = fixture.run(&["help", "set-loop"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+
+    let help = stdout(&output);
+    assert!(help.contains("quadio-cli set-loop -start=<TIME>"));
+    assert!(help.contains("-from-markers=<LABEL>,<LABEL>"));
+    assert!(help.contains("Time arguments (start, end, end-marker, snap)"));
+    assert!(help.contains("Examples:"));
+    assert!(!help.contains("-duration"));
+    assert!(!help.contains("Sub-commands:"));
+
+    let output = fixture.run(&["set-loop", "-help"]);
+    assert_eq!(stdout(&output), help);
+
+    // Concat writes only one file, so it lists no -out-dir
+    let output = fixture.run(&["concat", "-help"]);
+    assert!(!stdout(&output).contains("-out-dir"));
+    assert!(!stdout(&output).contains("Time:"));
+
+    assert_fails(
+        &fixture.run(&["help", "frobnicate"]),
+        "Unknown sub-command \"frobnicate\"",
+    );
+}
+
+#[test]
+fn mistakes_print_the_sub_commands_usage() {
+    let fixture = Fixture::new();
+    let output = fixture.run(&["set-loop", "plain.wav", "out.wav"]);
+
+    assert_fails(&output, "Expected argument \"start\"");
+    assert!(stderr(&output).contains("Usage: quadio-cli set-loop -start="));
+    assert!(stderr(&output).contains("quadio-cli help set-loop"));
+    assert!(!stderr(&output).contains("blend"));
+    assert!(stdout(&output).is_empty());
+
+    let output = fixture.run(&["strip", "-start=0", "plain.wav", "out.wav"]);
+    assert_fails(&output, "strip doesn't take -start");
+    assert!(stderr(&output).contains("Usage: quadio-cli strip"));
+}
+
+#[test]
+fn playing_several_files_checks_them_first() {
+    let fixture = Fixture::new();
+    let output =
+        fixture.run(&["play", "-pak=music.pak", "plain.wav", "looped.wav"]);
+    assert_fails(&output, "-pak can't be given with several inputs");
+
+    let output = fixture.run(&["loop", "looped.wav", "missing.wav"]);
+    assert_fails(&output, "missing.wav");
+
+    let output = fixture.run(&["loop", "looped.wav", "-", "plain.wav"]);
+    assert_fails(&output, "Standard input can't be played with other files");
+
+    let mut args = vec!["loop"];
+    args.extend(["looped.wav"; 10]);
+    let output = fixture.run(&args);
+    assert_fails(&output, "Expected at most 9 files to switch between");
+}
+
+#[test]
+fn playback_quality_is_checked() {
+    let fixture = Fixture::new();
+    let output = fixture.run(&["loop", "-quality=best", "looped.wav"]);
+    assert_fails(&output, "Unknown resample quality \"best\"");
+}
+
+#[test]
+fn arguments_parse_in_any_order_before_the_divider() {
+    let fixture = Fixture::new();
+    fs::copy(fixture.path("looped.wav"), fixture.path("-dash.wav")).unwrap();
+
+    // Each ordering either writes the named output or fails as given
+    let cases: [(&[&str], Result<&str, &str>); 14] = [
+        (&["looped.wav", "out.wav"], Ok("out.wav")),
+        (&["-repair", "looped.wav", "out.wav"], Ok("out.wav")),
+        (&["looped.wav", "-repair", "out.wav"], Ok("out.wav")),
+        (&["looped.wav", "out.wav", "-repair"], Ok("out.wav")),
+        (&["--", "looped.wav", "out.wav"], Ok("out.wav")),
+        (&["looped.wav", "--", "-out.wav"], Ok("-out.wav")),
+        (&["-repair", "--", "-dash.wav", "-out.wav"], Ok("-out.wav")),
+        (&["--", "looped.wav", "-repair"], Ok("-repair")),
+        (&["-out", "out.wav", "looped.wav"], Ok("out.wav")),
+        (
+            &["looped.wav", "out.wav", "extra.wav"],
+            Err("Unexpected extra argument 'extra.wav'"),
+        ),
+        (
+            &["--", "looped.wav", "out.wav", "-extra.wav"],
+            Err("Unexpected extra argument '-extra.wav'"),
+        ),
+        (
+            &["looped.wav", "out.wav", "--", "--"],
+            Err("Unexpected extra argument '--'"),
+        ),
+        (
+            &["-out=out.wav", "looped.wav", "other.wav"],
+            Err("Unexpected extra argument 'other.wav'"),
+        ),
+        (
+            &["-dash.wav", "out.wav"],
+            Err("Unrecognized argument dash.wav"),
+        ),
+    ];
+
+    for (args, expected) in cases {
+        let output = fixture.run(&[&["strip"], args].concat());
+
+        match expected {
+            Ok(written) => {
+                assert!(
+                    output.status.success(),
+                    "{:?}: {}",
+                    args,
+                    stderr(&output)
+                );
+                assert_eq!(fixture.metadata(written).loop_start, None);
+                fs::remove_file(fixture.path(written)).unwrap();
+            }
+            Err(message) => {
+                assert_fails(&output, message);
+                assert!(!fixture.path("out.wav").exists(), "{:?}", args);
+            }
+        }
+    }
+
+    // The divider is never taken for a value
+    assert_fails(
+        &fixture.run(&["set-loop", "-start", "--", "looped.wav", "out.wav"]),
+        "Argument -start requires a value",
+    );
+}
+
+#[test]
+fn divider_allows_paths_starting_with_dash() {
+    let fixture = Fixture::new();
+    fs::copy(fixture.path("looped.wav"), fixture.path("-dash.wav")).unwrap();
+
+    assert_fails(
+        &fixture.run(&["info", "-dash.wav"]),
+        "Unrecognized argument dash.wav",
+    );
+
+    let output = fixture.run(&["strip", "--", "-dash.wav", "-out.wav"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(fixture.metadata("-out.wav").loop_start, None);
+}
+
+#[test]
+fn waveform_renders_png_of_requested_size() {
+    let fixture = Fixture::new();
+    let output = fixture.run(&[
+        "waveform",
+        "-width=200",
+        "-height=50",
+        "looped.wav",
+        "looped.png",
+    ]);
+    assert!(output.status.success(), "{}", stderr(&output));
+
+    let file = fs::File::open(fixture.path("looped.png")).unwrap();
+    let mut reader = png::Decoder::new(file).read_info().unwrap();
+    let mut rgb = vec![0; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut rgb).unwrap();
+    let rgb = &rgb[..info.buffer_size()];
+
+    assert_eq!((info.width, info.height), (200, 50));
+    assert_eq!(info.color_type, png::ColorType::Rgb);
+
+    // Every pixel in the background color would mean nothing was drawn
+    let background = &rgb[..3];
+    let drawn = rgb
+        .chunks_exact(3)
+        .filter(|pixel| *pixel != background)
+        .count();
+    assert!(drawn > 200, "only {drawn} pixels drawn");
+}
+
+#[test]
+fn strict_set_loop_fails_on_loud_endpoint() {
+    let fixture = Fixture::new();
+
+    // A quarter cycle in, the sine is at its peak
+    let output =
+        fixture.run(&["set-loop", "-start=12", "plain.wav", "warned.wav"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stderr(&output).contains("Warning: loop start lands"));
+
+    let output = fixture.run(&[
+        "set-loop",
+        "-start=12",
+        "-strict",
+        "plain.wav",
+        "strict.wav",
+    ]);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(stderr(&output).contains("loop start lands"));
+    assert!(!fixture.path("strict.wav").exists());
+}
+
+#[test]
+fn clean_removes_trailing_junk() {
+    let fixture = Fixture::new();
+    let junk = b"\n-- appended by another tool --\n";
+    let mut bytes = fs::read(fixture.path("plain.wav")).unwrap();
+    bytes.extend(junk);
+    fs::write(fixture.path("junk.wav"), &bytes).unwrap();
+
+    let output = fixture.run(&["clean", "junk.wav", "clean.wav"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stdout(&output)
+        .contains(&format!("Discarded {} trailing bytes", junk.len())));
+
+    let cleaned = fs::read(fixture.path("clean.wav")).unwrap();
+    assert_eq!(cleaned, fs::read(fixture.path("plain.wav")).unwrap());
+
+    let output = fixture.run(&["clean", "clean.wav", "clean-again.wav"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stdout(&output).contains("Nothing to discard"));
+}
+
+#[test]
+fn scan_lists_waves_and_reports_bad_ones() {
+    let fixture = Fixture::new();
+    fs::create_dir(fixture.path("sub")).unwrap();
+    fs::copy(fixture.path("looped.wav"), fixture.path("sub/nested.WAV"))
+        .unwrap();
+    fs::write(fixture.path("bad.wav"), "not a wave").unwrap();
+    fs::write(fixture.path("notes.txt"), "not a wave either").unwrap();
+
+    let output = fixture.run(&["scan", "-format=csv", "."]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(
+        stdout(&output),
+        "name,rate,duration,loop,bits,warnings\n\
+         looped.wav,22050,1.000,yes,16,0\n\
+         plain.wav,22050,1.000,no,16,0\n\
+         sub/nested.WAV,22050,1.000,yes,16,0\n",
+    );
+    assert!(stderr(&output).contains("bad.wav"));
+    assert!(!stderr(&output).contains("notes.txt"));
+
+    let output = fixture.run(&["scan", "-format=csv", "-filter=unlooped", "."]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(
+        stdout(&output),
+        "name,rate,duration,loop,bits,warnings\nplain.wav,22050,1.000,no,16,0\n",
+    );
+}
+
+#[test]
+fn seeded_soak_passes_and_repeats() {
+    let fixture = Fixture::new();
+    let soak = || {
+        let output =
+            fixture.run(&["soak", "-iterations=20", "-seed=7", "looped.wav"]);
+        assert!(output.status.success(), "{}", stderr(&output));
+        stdout(&output)
+    };
+
+    let first = soak();
+    assert!(first.starts_with("PASS: 20 wraps over "), "{first}");
+    assert!(first.contains("(seed 7)"));
+    assert_eq!(soak(), first);
+}
+
+#[test]
+fn soak_validates_arguments() {
+    let fixture = Fixture::new();
+    let cases: [(&[&str], &str); 3] = [
+        (&["soak", "looped.wav"], "Expected argument \"iterations\""),
+        (
+            &["soak", "-iterations=many", "looped.wav"],
+            "Bad iteration count",
+        ),
+        (
+            &["soak", "-iterations=1", "-seed=-1", "looped.wav"],
+            "Bad seed",
+        ),
+    ];
+
+    for (args, message) in cases {
+        let output = fixture.run(args);
+        assert_eq!(output.status.code(), Some(1), "{args:?}");
+        assert!(stderr(&output).contains(message), "{}", stderr(&output));
+    }
+}
+
+#[test]
+fn tiny_files_fail_cleanly_in_every_sub_command() {
+    let fixture = Fixture::new();
+
+    // play and loop are left out, as they need an output device
+    let commands: [&[&str]; 8] = [
+        &["info", "in.wav"],
+        &["set-loop", "-start=0", "in.wav", "out.wav"],
+        &["strip", "in.wav", "out.wav"],
+        &["clean", "in.wav", "out.wav"],
+        &["blend", "in.wav", "out.wav"],
+        &["waveform", "-width=40", "-height=20", "in.wav", "out.png"],
+        &["scan", "."],
+        &["soak", "-iterations=3", "in.wav"],
+    ];
+
+    for len in [0, 1, 100] {
+        let spec = hound::WavSpec {
+            channels: 1,
+            sample_rate: RATE,
+            bits_per_sample: 16,
+            sample_format: hound::SampleFormat::Int,
+        };
+        let mut writer =
+            hound::WavWriter::create(fixture.path("in.wav"), spec).unwrap();
+
+        for i in 0..len {
+            writer.write_sample((i * 300) as i16).unwrap();
+        }
+
+        writer.finalize().unwrap();
+
+        for args in commands {
+            let output = fixture.run(args);
+            let stderr = stderr(&output);
+
+            assert!(!stderr.contains("panicked"), "{len}: {args:?}: {stderr}");
+
+            match output.status.code() {
+                Some(0) => {}
+                Some(1) => assert!(!stderr.is_empty(), "{len}: {args:?}"),
+                code => panic!("{len}: {args:?} exited with {code:?}"),
+            }
+        }
+    }
+}
+
+#[test]
+fn backups_rotate_dropping_the_oldest() {
+    const KEEP: usize = 3;
+    let fixture = Fixture::new();
+    let mut versions = Vec::new();
+
+    // One write with nothing to back up, then one more than can be kept
+    for version in 0..KEEP + 2 {
+        let start = format!("-start={}", 1000 + version * 50);
+        let output = fixture.run(&[
+            "set-loop",
+            &start,
+            &format!("-backup={KEEP}"),
+            "plain.wav",
+            "out.wav",
+        ]);
+        assert!(output.status.success(), "{}", stderr(&output));
+        versions.push(fs::read(fixture.path("out.wav")).unwrap());
+    }
+
+    let backups: Vec<_> = (1..=KEEP)
+        .map(|index| fs::read(fixture.path(&format!("out.wav.bak{index}"))))
+        .collect::<Result<_, _>>()
+        .unwrap();
+
+    // Newest backup first, the very first version dropped
+    let expected: Vec<_> = versions[1..=KEEP].iter().rev().cloned().collect();
+    assert_eq!(backups, expected);
+    assert!(!fixture.path(&format!("out.wav.bak{}", KEEP + 1)).exists());
+    assert!(!fixture.path("out.wav.bak").exists());
+    assert!(!backups.contains(&versions[0]));
+}
+
+#[test]
+fn retag_rate_changes_only_the_rate() {
+    let fixture = Fixture::new();
+    let output =
+        fixture.run(&["retag-rate", "-rate=44100", "looped.wav", "fast.wav"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stdout(&output).contains("22050 Hz -> 44100 Hz"));
+    assert!(stdout(&output).contains("1.000s -> 0.500s"));
+
+    // Both the rate and the byte rate follow in the fmt chunk
+    let bytes = fs::read(fixture.path("fast.wav")).unwrap();
+    let fmt = bytes.windows(4).position(|tag| tag == b"fmt ").unwrap() + 8;
+    let field = |offset: usize| {
+        u32::from_le_bytes(
+            bytes[fmt + offset..fmt + offset + 4].try_into().unwrap(),
+        )
+    };
+    assert_eq!(field(4), 44100);
+    assert_eq!(field(8), 44100 * 2);
+
+    let samples = |name: &str| {
+        hound::WavReader::open(fixture.path(name))
+            .unwrap()
+            .into_samples::<i16>()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap()
+    };
+    assert_eq!(samples("fast.wav"), samples("looped.wav"));
+
+    let output =
+        fixture.run(&["retag-rate", "-rate=1000", "looped.wav", "slow.wav"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stderr(&output).contains("outside the usual"));
+}
+
+#[test]
+fn apply_template_sets_loops_per_file() {
+    let fixture = Fixture::new();
+    fixture.write_sine("short.wav", 441.0);
+    fs::write(fixture.path("broken.wav"), b"not a wave").unwrap();
+    fs::create_dir(fixture.path("out")).unwrap();
+
+    let output = fixture.run(&[
+        "apply-template",
+        "-template=start=25%,end=LAST",
+        "-out-dir=out",
+        "plain.wav",
+        "short.wav",
+    ]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stdout(&output).contains("short.wav"));
+
+    let metadata = fixture.metadata("out/plain.wav");
+    assert_eq!(metadata.loop_start, Some(RATE / 4 + 1));
+    assert_eq!(metadata.end.unwrap_or(metadata.sample_count), RATE);
+
+    // looped.wav loops from 1000 to 6025
+    let output = fixture.run(&[
+        "apply-template",
+        "-template=looped.wav",
+        "-out-dir=out",
+        "plain.wav",
+        "broken.wav",
+    ]);
+    assert_fails(&output, "1 of 2 files failed");
+    assert!(stdout(&output).contains("broken.wav"));
+
+    let metadata = fixture.metadata("out/plain.wav");
+    assert_eq!(metadata.loop_start, Some(1000));
+    assert_eq!(metadata.end, Some(6025));
+}
+
+#[test]
+fn devices_lists_outputs_or_says_there_are_none() {
+    let fixture = Fixture::new();
+
+    // No audio device in CI, so only the shape of the listing is checked
+    let output = fixture.run(&["devices"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+
+    let text = stdout(&output);
+    assert!(
+        text == "No output devices found\n"
+            || text.lines().all(|line| line.ends_with(" Hz")
+                || line.ends_with("no supported rates")),
+        "{}",
+        text
+    );
+}
+
+#[test]
+fn version_prints_build_metadata() {
+    let fixture = Fixture::new();
+
+    for flag in ["--version", "-V", "version"] {
+        let output = fixture.run(&[flag]);
+        assert!(output.status.success(), "{}", stderr(&output));
+
+        let text = stdout(&output);
+        assert!(text.starts_with(&format!(
+            "quadio-cli {}\n",
+            env!("CARGO_PKG_VERSION")
+        )));
+        assert!(text.contains("\tCommit = "));
+        assert!(text.contains("\tFeatures = "));
+    }
+
+    let output = fixture.run(&["--version", "-format=json"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+
+    let json = stdout(&output);
+    for field in [
+        "\"version\":",
+        "\"commit\":",
+        "\"features\":[",
+        "\"hound\":",
+    ] {
+        assert!(json.contains(field), "{} lacks {}", json, field);
+    }
+}
+
+#[test]
+fn verify_writes_junit_report() {
+    let fixture = Fixture::new();
+    let output = fixture.run(&[
+        "set-loop",
+        "-start=1000",
+        "-end=6000",
+        "plain.wav",
+        "seamless.wav",
+    ]);
+    assert!(output.status.success(), "{}", stderr(&output));
+
+    let mut wave = fs::read(fixture.path("seamless.wav")).unwrap();
+    wave.extend(b"tail");
+    fs::write(fixture.path("tail.wav"), wave).unwrap();
+
+    let output = fixture.run(&["verify", "seamless.wav", "tail.wav"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stdout(&output).contains("PASS seamless.wav\n"));
+
+    let output = fixture.run(&[
+        "verify",
+        "-rate=44100",
+        "-report=report.xml",
+        "tail.wav",
+        "looped.wav",
+        "plain.wav",
+    ]);
+    assert_fails(&output, "3 of 3 files failed verification");
+    assert_eq!(
+        fs::read_to_string(fixture.path("report.xml")).unwrap(),
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<testsuite name="quadio.verify" tests="3" failures="3">
+  <testcase classname="quadio.verify" name="tail.wav">
+    <failure message="Sample rate is 22050 Hz, expected 44100 Hz">Sample rate is 22050 Hz, expected 44100 Hz</failure>
+    <system-out>4 trailing bytes after the RIFF chunk</system-out>
+  </testcase>
+  <testcase classname="quadio.verify" name="looped.wav">
+    <failure message="Sample rate is 22050 Hz, expected 44100 Hz">Sample rate is 22050 Hz, expected 44100 Hz
+Seam artifact score -1.4 dB is above -30.0 dB</failure>
+  </testcase>
+  <testcase classname="quadio.verify" name="plain.wav">
+    <failure message="Sample rate is 22050 Hz, expected 44100 Hz">Sample rate is 22050 Hz, expected 44100 Hz
+No loop</failure>
+  </testcase>
+</testsuite>
+"#
+    );
+}
+
+#[test]
+fn verify_checks_the_target_port() {
+    let fixture = Fixture::new();
+
+    let output = fixture.run(&["verify", "-target=quakespasm", "looped.wav"]);
+    let text = stdout(&output);
+    assert!(text.contains("\tPASS channels\n"), "{}", text);
+    assert!(text.contains("\tPASS sample format\n"), "{}", text);
+    assert!(text.contains("\tPASS loop\n"), "{}", text);
+
+    let output = fixture.run(&["verify", "-target=quake1", "plain.wav"]);
+    assert_fails(&output, "1 of 1 files failed verification");
+    let text = stdout(&output);
+    assert!(text.contains("\tFAIL sample format\n"), "{}", text);
+    assert!(text.contains("\tFAIL loop\n"), "{}", text);
+    assert!(text.contains("\tPASS sample rate\n"), "{}", text);
+
+    let output = fixture.run(&["verify", "-target=doom", "plain.wav"]);
+    assert_fails(&output, "Unknown target \"doom\"");
+}
+
+#[test]
+fn verify_round_trips_files() {
+    let fixture = Fixture::new();
+
+    let output = fixture.run(&[
+        "set-loop",
+        "-bits=8",
+        "-start=1000",
+        "looped.wav",
+        "8.wav",
+    ]);
+    assert!(output.status.success(), "{}", stderr(&output));
+
+    for name in ["looped.wav", "8.wav"] {
+        let output =
+            fixture.run(&["verify", "-roundtrip", "-max-seam=0dB", name]);
+        assert!(output.status.success(), "{}", stderr(&output));
+        let text = stdout(&output);
+        assert!(text.contains("\tPASS round trip\n"), "{}", text);
+    }
+
+    let output = fixture.run(&["verify", "-max-seam=0dB", "looped.wav"]);
+    assert!(!stdout(&output).contains("round trip"));
+}
+
+#[test]
+fn preview_takes_no_output() {
+    let fixture = Fixture::new();
+
+    let output =
+        fixture.run(&["blend", "-preview", "looped.wav", "blended.wav"]);
+    assert_fails(&output, "-preview can't be given with an output");
+    assert!(!fixture.path("blended.wav").exists());
+
+    let output = fixture.run(&["strip", "-preview", "looped.wav"]);
+    assert_fails(&output, "strip doesn't take -preview");
+}
+
+#[test]
+fn waveform_previews_blend() {
+    let fixture = Fixture::new();
+    let render = |name: &str, extra: &[&str]| {
+        let mut args = vec!["waveform", "-width=200", "-height=50"];
+        args.extend(extra);
+        args.extend(["looped.wav", name]);
+        let output = fixture.run(&args);
+        assert!(output.status.success(), "{}", stderr(&output));
+        fs::read(fixture.path(name)).unwrap()
+    };
+
+    let plain = render("plain.png", &[]);
+    let preview = render("preview.png", &["-blend-preview=500"]);
+    assert_ne!(plain, preview);
+
+    assert_fails(
+        &fixture.run(&[
+            "waveform",
+            "-width=200",
+            "-height=50",
+            "-blend-preview=2000",
+            "looped.wav",
+            "bad.png",
+        ]),
+        "lead",
+    );
+}
+
+#[test]
+fn loop_meta_round_trips_through_sidecar() {
+    let fixture = Fixture::new();
+    let output = fixture.run(&[
+        "set-loop",
+        "-start=2000",
+        "-end=8000",
+        "-cue-label=chorus",
+        "plain.wav",
+        "labeled.wav",
+    ]);
+    assert!(output.status.success());
+
+    let output = fixture.run(&["export-loop-meta", "labeled.wav", "x.loop"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+
+    let sidecar = fs::read_to_string(fixture.path("x.loop")).unwrap();
+    assert!(sidecar.contains("start = 2000"));
+    assert!(sidecar.contains("end_seconds = 0.362812"));
+    assert!(sidecar.contains("label = \"chorus\""));
+
+    let output = fixture.run(&[
+        "import-loop-meta",
+        "-from=x.loop",
+        "looped.wav",
+        "imported.wav",
+    ]);
+    assert!(output.status.success(), "{}", stderr(&output));
+
+    let imported = fixture.project("imported.wav");
+    assert_eq!(imported.sample_loop(), Some(2000..8000));
+    assert_eq!(imported.samples(), fixture.project("plain.wav").samples());
+
+    // Sample data differing from the export's fails unless told otherwise
+    fixture.write_sine("other.wav", 440.0);
+    let output = fixture.run(&[
+        "import-loop-meta",
+        "-from=x.loop",
+        "other.wav",
+        "forced.wav",
+    ]);
+    assert_fails(&output, "use -ignore-hash");
+    assert!(!fixture.path("forced.wav").exists());
+
+    let output = fixture.run(&[
+        "import-loop-meta",
+        "-from=x.loop",
+        "-ignore-hash",
+        "other.wav",
+        "forced.wav",
+    ]);
+    assert!(output.status.success());
+    assert!(stderr(&output).contains("doesn't match"));
+    assert_eq!(fixture.metadata("forced.wav").loop_start, Some(2000));
+}
+
+#[test]
+fn writes_are_reproducible() {
+    let fixture = Fixture::new();
+    let tags = |name: &str| {
+        let file = fs::File::open(fixture.path(name)).unwrap();
+        core::QWaveReader::new(BufReader::new(file))
+            .unwrap()
+            .chunks()
+            .iter()
+            .map(|chunk| String::from_utf8_lossy(&chunk.tag).into_owned())
+            .collect::<Vec<_>>()
+    };
+
+    // A leftover, longer file at the output path mustn't leak into the result
+    fs::write(fixture.path("run1-3.wav"), vec![0xaa; 100_000]).unwrap();
+
+    for extra in [&[][..], &["-reencode"]] {
+        for run in ["run1", "run2"] {
+            let pipeline: [&[&str]; 3] = [
+                &["set-loop", "-start=1000", "-end=6025", "-cue-label=odd"],
+                &["blend", "-duration=20ms", "-entry"],
+                &["set-end", "-at=9001"],
+            ];
+            let mut input = String::from("plain.wav");
+
+            for (step, args) in pipeline.iter().enumerate() {
+                let output_name = format!("{}-{}.wav", run, step + 1);
+                let mut args = args.to_vec();
+                args.extend(extra);
+                args.extend([&input[..], &output_name]);
+
+                let output = fixture.run(&args);
+                assert!(output.status.success(), "{}", stderr(&output));
+                input = output_name;
+            }
+        }
+
+        let run1 = fs::read(fixture.path("run1-3.wav")).unwrap();
+        assert_eq!(run1, fs::read(fixture.path("run2-3.wav")).unwrap());
+        assert_eq!(tags("run1-3.wav"), ["fmt ", "data", "cue ", "LIST"]);
+    }
+}
+
+#[test]
+fn dither_follows_the_seed() {
+    let fixture = Fixture::new();
+    let narrow = |name: &str, seed: Option<&str>| {
+        let mut args = vec!["set-loop", "-bits=8", "-start=1000", "-end=6025"];
+        args.extend(seed);
+        args.extend(["plain.wav", name]);
+        let output = fixture.run(&args);
+        assert!(output.status.success(), "{}", stderr(&output));
+        fs::read(fixture.path(name)).unwrap()
+    };
+
+    assert_eq!(narrow("a.wav", None), narrow("b.wav", None));
+    assert_eq!(
+        narrow("c.wav", Some("-seed=7")),
+        narrow("d.wav", Some("-seed=7"))
+    );
+    assert_ne!(narrow("e.wav", None), narrow("f.wav", Some("-seed=7")));
+
+    let output = fixture.run(&[
+        "set-loop",
+        "-bits=8",
+        "-seed=many",
+        "-start=1000",
+        "plain.wav",
+        "g.wav",
+    ]);
+    assert_fails(&output, "Bad seed");
+}
+
+#[test]
+fn other_chunks_are_carried_over() {
+    let fixture = Fixture::new();
+    let tags = |name: &str| {
+        let file = fs::File::open(fixture.path(name)).unwrap();
+        core::QWaveReader::new(BufReader::new(file))
+            .unwrap()
+            .chunks()
+            .iter()
+            .map(|chunk| String::from_utf8_lossy(&chunk.tag).into_owned())
+            .collect::<Vec<_>>()
+    };
+
+    // An odd-sized INFO list, padded, and a bext chunk, written after the
+    // loop's
+    let info = b"INFOINAM\x05\0\0\0Song\0".to_vec();
+    let mut wave = fs::read(fixture.path("looped.wav")).unwrap();
+    wave.extend(b"LIST");
+    wave.extend(u32::try_from(info.len()).unwrap().to_le_bytes());
+    wave.extend(&info);
+    wave.push(0);
+    wave.extend(b"bext\x04\0\0\0abcd");
+    let riff_size = u32::try_from(wave.len() - 8).unwrap();
+    wave[4..8].copy_from_slice(&riff_size.to_le_bytes());
+    fs::write(fixture.path("tagged.wav"), &wave).unwrap();
+
+    let output = fixture.run(&[
+        "set-loop",
+        "-start=2000",
+        "-end=6025",
+        "tagged.wav",
+        "a.wav",
+    ]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(
+        tags("a.wav"),
+        ["fmt ", "data", "cue ", "LIST", "LIST", "bext"]
+    );
+    assert_eq!(fixture.metadata("a.wav").loop_start, Some(2000));
+
+    let written = fs::read(fixture.path("a.wav")).unwrap();
+    assert!(written
+        .windows(info.len() + 1)
+        .any(|w| w[..info.len()] == info));
+    assert_eq!(
+        u32::from_le_bytes(written[4..8].try_into().unwrap()),
+        u32::try_from(written.len() - 8).unwrap(),
+    );
+
+    // Carried over once, not once per write
+    let output = fixture.run(&[
+        "set-loop",
+        "-start=3000",
+        "-end=6025",
+        "a.wav",
+        "b.wav",
+    ]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(tags("b.wav"), tags("a.wav"));
+
+    let output = fixture.run(&["strip", "tagged.wav", "stripped.wav"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(tags("stripped.wav"), ["fmt ", "data", "LIST", "bext"]);
+    assert_eq!(fixture.metadata("stripped.wav").loop_start, None);
+
+    let output = fixture.run(&["clean", "tagged.wav", "clean.wav"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stdout(&output).contains("Discarded chunk \"bext\""));
+    assert_eq!(tags("clean.wav"), ["fmt ", "data", "cue ", "LIST"]);
+}
+
+#[test]
+fn float_input_is_read_and_reencoded_as_16_bits() {
+    let fixture = Fixture::new();
+    let spec = hound::WavSpec {
+        channels: 1,
+        sample_rate: RATE,
+        bits_per_sample: 32,
+        sample_format: hound::SampleFormat::Float,
+    };
+    let mut writer =
+        hound::WavWriter::create(fixture.path("float.wav"), spec).unwrap();
+
+    for i in 0..RATE {
+        writer
+            .write_sample((f64::from(i) / 20.0).sin() as f32 * 1.5)
+            .unwrap();
+    }
+
+    writer.finalize().unwrap();
+
+    let output = fixture.run(&["info", "float.wav"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stdout(&output).contains("Bits per sample = 32 (float)"));
+
+    let output =
+        fixture.run(&["set-loop", "-start=100", "float.wav", "out.wav"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+
+    let metadata = fixture.metadata("out.wav");
+    assert_eq!(metadata.bits_per_sample, 16);
+    assert!(!metadata.float_samples);
+    assert_eq!(metadata.loop_start, Some(100));
+    assert_eq!(
+        fixture.project("out.wav").samples().iter().max(),
+        Some(&32767)
+    );
+}
+
+#[test]
+fn package_fits_mixed_rates_to_the_target() {
+    let fixture = Fixture::new();
+    let write = |name: &str, rate: u32, freq: f64| {
+        let path = fixture.path(name);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        let spec = hound::WavSpec {
+            channels: 1,
+            sample_rate: rate,
+            bits_per_sample: 16,
+            sample_format: hound::SampleFormat::Int,
+        };
+        let mut writer = hound::WavWriter::create(path, spec).unwrap();
+
+        for i in 0..rate {
+            let phase = f64::from(i) * freq / f64::from(rate);
+            let sample = (phase * std::f64::consts::TAU).sin() * 8000.0;
+            writer.write_sample(sample as i16).unwrap();
+        }
+
+        writer.finalize().unwrap();
+    };
+
+    // Loops below span whole cycles, so they're seamless
+    write("src/sound/amb/wind.wav", 44100, 441.0);
+    write("src/music/track02.wav", 16000, 400.0);
+    write("loose.wav", 11025, 441.0);
+
+    for (input, start, end) in [
+        ("src/sound/amb/wind.wav", "-start=4400", "-end=39600"),
+        ("src/music/track02.wav", "-start=0", "-end=16000"),
+    ] {
+        let output = fixture.run(&["set-loop", start, end, input, input]);
+        assert!(output.status.success(), "{}", stderr(&output));
+    }
+
+    fs::create_dir(fixture.path("pak")).unwrap();
+    let inputs = [
+        "src/sound/amb/wind.wav",
+        "src/music/track02.wav",
+        "loose.wav",
+    ];
+    let mut args =
+        vec!["package", "-target=quake1", "-out-dir=pak", "-format=json"];
+    args.extend(inputs);
+
+    let output = fixture.run(&args);
+    assert!(output.status.success(), "{}", stderr(&output));
+    let manifest = stdout(&output);
+    assert!(manifest.starts_with("{\"target\":\"quake1\""));
+    assert!(manifest.contains("\"resampled 44100 Hz -> 22050 Hz\""));
+    assert!(manifest.contains("\"16-bit -> 8-bit with dither\""));
+    assert!(
+        manifest.contains("\"truncated 22050 -> 19800 samples at loop end\"")
+    );
+    assert!(manifest.contains("\"output\":\"music/track02.wav\""));
+
+    for (output, rate, end) in [
+        ("pak/sound/amb/wind.wav", 22050, Some(19800)),
+        ("pak/music/track02.wav", 22050, Some(22050)),
+        ("pak/sound/loose.wav", 11025, None),
+    ] {
+        let metadata = fixture.metadata(output);
+        assert_eq!(metadata.sample_rate, rate, "{}", output);
+        assert_eq!(metadata.bits_per_sample, 8, "{}", output);
+        assert_eq!(
+            metadata.loop_start.map(|_| metadata.sample_count),
+            end,
+            "{}",
+            output
+        );
+    }
+
+    let output = fixture.run(&[
+        "package",
+        "-target=quakespasm",
+        "-out-dir=pak",
+        "src/sound/amb/wind.wav",
+        "looped.wav",
+    ]);
+    assert_fails(&output, "1 of 2 files failed");
+    assert!(stdout(&output).contains("Seam artifact score"));
+    assert_eq!(
+        fixture.metadata("pak/sound/amb/wind.wav").bits_per_sample,
+        16
+    );
+    assert_eq!(
+        fixture.metadata("pak/sound/amb/wind.wav").sample_rate,
+        44100
+    );
+
+    assert_fails(
+        &fixture.run(&["package", "-target=doom", "-out-dir=pak", "loose.wav"]),
+        "expected one of quake1, quakespasm",
+    );
+}
+
+#[test]
+fn loops_are_written_to_the_chosen_chunks() {
+    let fixture = Fixture::new();
+    let set_loop = |format: &str, input: &str, output: &str| {
+        let format = format!("-loop-chunks={}", format);
+        fixture.run(&[
+            "set-loop",
+            "-start=1000",
+            "-end=6025",
+            &format,
+            input,
+            output,
+        ])
+    };
+
+    assert!(set_loop("both", "plain.wav", "both.wav").status.success());
+    assert!(set_loop("smpl", "plain.wav", "smpl.wav").status.success());
+
+    let both = fixture.metadata("both.wav");
+    assert_eq!(both.loop_source, Some(core::LoopSource::Cue));
+    assert!(both.smpl_loop);
+    assert_eq!(both.end, Some(6025));
+
+    let smpl = fixture.metadata("smpl.wav");
+    assert_eq!(smpl.loop_source, Some(core::LoopSource::Smpl));
+    assert_eq!((smpl.loop_start, smpl.end), (Some(1000), Some(6025)));
+    assert!(stdout(&fixture.run(&["info", "smpl.wav"]))
+        .contains("Loop read from smpl chunk"));
+
+    // Both chunks survive a rewrite that doesn't choose the format
+    let output = fixture.run(&["set-end", "-at=8000", "both.wav", "again.wav"]);
+    assert!(output.status.success());
+    assert_eq!(
+        fixture.metadata("again.wav"),
+        core::Metadata {
+            play_end: Some(8000),
+            ..both
+        }
+    );
+
+    let output = fixture.run(&["set-end", "-at=8000", "smpl.wav", "bad.wav"]);
+    assert_fails(&output, "End marker requires the loop in a cue chunk");
+    assert_fails(&set_loop("sampler", "plain.wav", "bad.wav"), "\"sampler\"");
+
+    // -format is left to choose how -hash prints
+    let output = fixture.run(&[
+        "set-loop",
+        "-start=1000",
+        "-loop-chunks=smpl",
+        "-hash",
+        "-format=json",
+        "plain.wav",
+        "hashed.wav",
+    ]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(
+        stdout(&output).contains("\"data\":\""),
+        "{}",
+        stdout(&output)
+    );
+    assert!(fixture.metadata("hashed.wav").smpl_loop);
+}
+
+#[test]
+fn info_reports_channels_and_data_size() {
+    let fixture = Fixture::new();
+    let text = stdout(&fixture.run(&["info", "plain.wav"]));
+    assert!(text.contains("Channels = 1\n"), "{}", text);
+    assert!(text.contains(&format!("Data size = {} bytes", RATE * 2)));
+
+    let spec = hound::WavSpec {
+        channels: 2,
+        sample_rate: RATE,
+        bits_per_sample: 16,
+        sample_format: hound::SampleFormat::Int,
+    };
+    let mut writer =
+        hound::WavWriter::create(fixture.path("stereo.wav"), spec).unwrap();
+
+    for _ in 0..1000 * 2 {
+        writer.write_sample(0i16).unwrap();
+    }
+
+    writer.finalize().unwrap();
+
+    let metadata = fixture.metadata("stereo.wav");
+    assert_eq!(metadata.channels, 2);
+    assert_eq!(metadata.sample_count, 1000);
+    assert_eq!(metadata.data_bytes, 4000);
+
+    let output = fixture.run(&["info", "stereo.wav"]);
+    assert!(output.status.success());
+    assert!(
+        stdout(&output).contains("Channels = 2 (only mono files can be played")
+    );
+}
+
+#[test]
+fn dash_writes_standard_output() {
+    let fixture = Fixture::new();
+    let looped = fs::read(fixture.path("looped.wav")).unwrap();
+
+    let output = fixture
+        .command()
+        .args(["trim", "-", "-"])
+        .write_stdin(looped)
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stderr(&output).contains("Trimmed 22050 to 6025 samples"));
+
+    let metadata = core::QWaveReader::new(std::io::Cursor::new(output.stdout))
+        .unwrap()
+        .metadata();
+    assert_eq!(metadata.sample_count, 6025);
+    assert_eq!(metadata.loop_start, Some(1000));
+
+    assert_fails(
+        &fixture.run(&["strip", "-hash", "looped.wav", "-"]),
+        "-hash needs an output file",
+    );
+}
+
+#[test]
+fn dash_reads_standard_input() {
+    let fixture = Fixture::new();
+    let looped = fs::read(fixture.path("looped.wav")).unwrap();
+
+    let output = fixture
+        .command()
+        .args(["info", "-"])
+        .write_stdin(looped.clone())
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stdout(&output).contains("Loop starts at sample 1000"));
+
+    let output = fixture
+        .command()
+        .args(["set-end", "-at=8000", "-", "out.wav"])
+        .write_stdin(looped.clone())
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(fixture.metadata("out.wav").play_end, Some(8000));
+
+    // Streamed WAVs can't go back to fill in their sizes
+    let mut streamed = fs::read(fixture.path("plain.wav")).unwrap();
+    let data = streamed.windows(4).position(|w| w == b"data").unwrap();
+    streamed[4..8].copy_from_slice(&u32::MAX.to_le_bytes());
+    streamed[data + 4..data + 8].copy_from_slice(&u32::MAX.to_le_bytes());
+
+    let output = fixture
+        .command()
+        .args(["info", "-"])
+        .write_stdin(streamed)
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stdout(&output).contains(&format!("Duration = {} samples", RATE)));
+    assert!(!stdout(&output).contains("Warning"));
+
+    let output = fixture
+        .command()
+        .args(["info", "-"])
+        .write_stdin(b"not a wave".to_vec())
+        .output()
+        .unwrap();
+    assert_fails(&output, "<stdin>: ");
+}
+
+#[test]
+fn config_defaults_merge_under_the_command_line() {
+    let fixture = Fixture::new();
+    fs::write(
+        fixture.path("no-config.toml"),
+        "duration = \"100\"\nvolume = 0.5\nbits = 8\n\n[blend]\ncurve = \
+        \"equal-power\"\nbits = 16\n",
+    )
+    .unwrap();
+
+    let output = fixture.run(&["config", "blend"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    let text = stdout(&output);
+    assert!(
+        text.ends_with(
+            "[blend]\nbits = \"16\"\ncurve = \"equal-power\"\n\
+            duration = \"100\"\nvolume = \"0.5\"\n"
+        ),
+        "{}",
+        text,
+    );
+
+    // Defaults for every sub-command skip those not taking them, quietly
+    let output = fixture.run(&["config", "info"]);
+    assert!(stdout(&output).ends_with("[info]\n"), "{}", stdout(&output));
+    assert_eq!(stderr(&output), "");
+
+    let output = fixture.run(&["config", "show"]);
+    assert!(stdout(&output).contains("\nduration = \"100\"\nvolume = \"0.5\""));
+
+    let output = fixture.run(&[
+        "blend",
+        "-duration=200",
+        "-yes",
+        "looped.wav",
+        "blended.wav",
+    ]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    let report = core::compare(
+        &fixture.project("looped.wav"),
+        &fixture.project("blended.wav"),
+    );
+    assert!(report.first_difference.unwrap() >= 6025 - 200);
+    assert_eq!(fixture.metadata("blended.wav").bits_per_sample, 16);
+
+    // A file that can't be parsed is warned of and left out
+    fs::write(fixture.path("no-config.toml"), "duration = [\n").unwrap();
+    let output = fixture.run(&["info", "looped.wav"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stderr(&output).contains("ignoring config file"));
+}
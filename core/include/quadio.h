@@ -0,0 +1,102 @@
+/*
+ * C ABI of quadio-core, built with the "ffi" feature.
+ *
+ * Every function but quadio_project_free and quadio_last_error_message
+ * returns QUADIO_OK or one of the error codes below, and on error leaves a
+ * message for quadio_last_error_message on the calling thread.  Paths are
+ * NUL-terminated UTF-8.  Sample positions are frames, i.e. samples per
+ * channel.
+ */
+
+#ifndef QUADIO_H
+#define QUADIO_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+#define QUADIO_OK 0
+
+/* A pointer argument was null, or a path wasn't UTF-8 */
+#define QUADIO_ERR_ARGUMENT 1
+#define QUADIO_ERR_IO 2
+
+/* The bytes aren't a well-formed WAV, or not one that is supported */
+#define QUADIO_ERR_DECODE 3
+
+/* The loop is missing, or doesn't fit the audio */
+#define QUADIO_ERR_LOOP 4
+
+/* A value is out of range, such as a blend window longer than the loop */
+#define QUADIO_ERR_INVALID 5
+
+/* The library panicked; the project may be left part-way through the edit */
+#define QUADIO_ERR_PANIC 6
+
+typedef struct QuadioProject QuadioProject;
+
+typedef struct QuadioMetadata {
+    uint32_t sample_rate;
+    uint16_t channels;
+    uint32_t sample_count;
+
+    /* Non-zero if the project loops, from loop_start up to loop_end */
+    int has_loop;
+    uint32_t loop_start;
+    uint32_t loop_end;
+} QuadioMetadata;
+
+/*
+ * Message of the last failure on this thread, or NULL if there has been
+ * none.  It stays valid until the next failure on the thread.
+ */
+const char *quadio_last_error_message(void);
+
+/* Read the WAV at path into a new project, stored in *out */
+int quadio_project_open(const char *path, QuadioProject **out);
+
+/*
+ * Read a WAV held in memory into a new project, stored in *out.  The bytes
+ * are copied, and can be freed once this returns.
+ */
+int quadio_project_from_memory(
+    const uint8_t *bytes,
+    size_t len,
+    QuadioProject **out
+);
+
+/* Fill *out with the project's format and loop */
+int quadio_project_metadata(
+    const QuadioProject *project,
+    QuadioMetadata *out
+);
+
+/*
+ * Loop the project from start up to end.  A loop that doesn't fit the audio
+ * is refused, leaving the loop as it was.
+ */
+int quadio_project_set_loop(QuadioProject *project, uint32_t start, uint32_t end);
+
+/* Remove the project's loop, if any */
+int quadio_project_clear_loop(QuadioProject *project);
+
+/*
+ * Crossfade the end of the loop into the samples before its start, over
+ * window_sz frames
+ */
+int quadio_project_blend(QuadioProject *project, uint32_t window_sz);
+
+/* Write the project as a WAV to path, replacing any file there */
+int quadio_project_write(const QuadioProject *project, const char *path);
+
+/* Free a project.  NULL is ignored. */
+void quadio_project_free(QuadioProject *project);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
@@ -0,0 +1,205 @@
+use std::ops::Range;
+
+// Frames are grouped into at most this many bins for the coarse search, and
+// bins hold at least `MIN_BIN` frames
+const MAX_BINS: usize = 4096;
+const MIN_BIN: usize = 64;
+
+// Bins before each loop point whose features are compared
+const CONTEXT_BINS: usize = 8;
+
+// Longest run of samples before each loop point correlated when refining
+const MAX_REFINE_CONTEXT: usize = 2048;
+
+// Coarse candidates refined sample by sample, and candidates returned
+const REFINED: usize = 8;
+const RETURNED: usize = 5;
+
+/// A loop suggested by `Project::find_loop`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LoopCandidate {
+    pub start: u32,
+    pub end: u32,
+
+    /// Normalized cross-correlation, from -1 to 1, of the samples leading up
+    /// to the loop start with those leading up to the loop end.  Near 1, the
+    /// wrap continues the signal as the loop start does.
+    pub score: f64,
+}
+
+// Loudness and brightness of a bin of frames
+#[derive(Clone, Copy)]
+struct BinFeatures {
+    rms: f64,
+    zero_crossing_rate: f64,
+}
+
+// Search `signal`, a single channel, for loops of at least `min_length`
+// frames lying within `range`.  Bins of the signal are first compared by
+// loudness and zero crossing rate to find start and end bins whose context
+// matches, and the best pairs are then refined by correlating the samples
+// themselves, the end moving by up to a bin.  Best candidates come first.
+pub(crate) fn loop_candidates(
+    signal: &[i16],
+    min_length: u32,
+    range: Range<u32>,
+) -> Vec<LoopCandidate> {
+    let range = range.start as usize..(range.end as usize).min(signal.len());
+    let min_length = (min_length as usize).max(1);
+    let bin = range.len().div_ceil(MAX_BINS).max(MIN_BIN);
+    let bin_count = range.len() / bin;
+    let min_bins = min_length.div_ceil(bin);
+
+    if bin_count < CONTEXT_BINS + min_bins || range.len() < min_length {
+        return vec![];
+    }
+
+    let features = (0..bin_count)
+        .map(|idx| {
+            let start = range.start + idx * bin;
+            bin_features(&signal[start..start + bin])
+        })
+        .collect::<Vec<_>>();
+
+    let mean_rms =
+        features.iter().map(|bin| bin.rms).sum::<f64>() / bin_count as f64;
+
+    if mean_rms == 0.0 {
+        return vec![];
+    }
+
+    // Best end bin for every start bin, as (similarity, start, end)
+    let mut coarse = vec![];
+
+    for start in CONTEXT_BINS..=bin_count - min_bins {
+        let best = (start + min_bins..=bin_count)
+            .map(|end| {
+                let similarity =
+                    context_similarity(&features, start, end, mean_rms);
+                (similarity, start, end)
+            })
+            .max_by(|a, b| a.0.total_cmp(&b.0));
+
+        coarse.extend(best);
+    }
+
+    coarse.sort_by(|a, b| b.0.total_cmp(&a.0));
+
+    // Skip pairs whose points both lie within the context of a better one
+    let mut kept: Vec<(f64, usize, usize)> = vec![];
+
+    for candidate in coarse {
+        let near = |other: &(f64, usize, usize)| {
+            candidate.1.abs_diff(other.1) < CONTEXT_BINS
+                && candidate.2.abs_diff(other.2) < CONTEXT_BINS
+        };
+
+        if !kept.iter().any(near) {
+            kept.push(candidate);
+        }
+
+        if kept.len() == REFINED {
+            break;
+        }
+    }
+
+    let context = (CONTEXT_BINS * bin).min(MAX_REFINE_CONTEXT);
+    let mut candidates = kept
+        .into_iter()
+        .filter_map(|(_, start_bin, end_bin)| {
+            let start = range.start + start_bin * bin;
+            let coarse_end = range.start + end_bin * bin;
+            let ends = (coarse_end - bin).max(start + min_length)
+                ..=(coarse_end + bin).min(range.end);
+            let before_start = &signal[start - context..start];
+
+            ends.map(|end| {
+                let score =
+                    correlation(before_start, &signal[end - context..end]);
+                (score, end)
+            })
+            .max_by(|a, b| a.0.total_cmp(&b.0))
+            .map(|(score, end)| LoopCandidate {
+                start: start as u32,
+                end: end as u32,
+                score,
+            })
+        })
+        .collect::<Vec<_>>();
+
+    candidates.sort_by(|a, b| b.score.total_cmp(&a.score));
+    candidates.truncate(RETURNED);
+    candidates
+}
+
+fn bin_features(frames: &[i16]) -> BinFeatures {
+    let power = frames.iter().map(|&s| f64::from(s).powi(2)).sum::<f64>()
+        / frames.len() as f64;
+    let crossings = frames
+        .windows(2)
+        .filter(|pair| (pair[0] < 0) != (pair[1] < 0))
+        .count();
+
+    BinFeatures {
+        rms: power.sqrt(),
+        zero_crossing_rate: crossings as f64 / frames.len() as f64,
+    }
+}
+
+// How alike, from 0 to 1, the bins before `start` are to those before `end`
+fn context_similarity(
+    features: &[BinFeatures],
+    start: usize,
+    end: usize,
+    mean_rms: f64,
+) -> f64 {
+    let vector =
+        |bin: &BinFeatures| [bin.rms / mean_rms, bin.zero_crossing_rate];
+    let mut distance = 0.0;
+    let mut norm_a = 0.0;
+    let mut norm_b = 0.0;
+
+    for (a, b) in features[start - CONTEXT_BINS..start]
+        .iter()
+        .zip(&features[end - CONTEXT_BINS..end])
+    {
+        for (a, b) in vector(a).into_iter().zip(vector(b)) {
+            distance += (a - b).powi(2);
+            norm_a += a * a;
+            norm_b += b * b;
+        }
+    }
+
+    let scale = norm_a.sqrt() + norm_b.sqrt();
+
+    if scale == 0.0 {
+        1.0
+    } else {
+        1.0 - distance.sqrt() / scale
+    }
+}
+
+// Normalized cross-correlation of two runs of samples of the same length;
+// 0 if either is constant
+fn correlation(a: &[i16], b: &[i16]) -> f64 {
+    let mean = |run: &[i16]| {
+        run.iter().map(|&s| f64::from(s)).sum::<f64>() / run.len() as f64
+    };
+    let (mean_a, mean_b) = (mean(a), mean(b));
+    let mut product = 0.0;
+    let mut power_a = 0.0;
+    let mut power_b = 0.0;
+
+    for (&a, &b) in a.iter().zip(b) {
+        let (a, b) = (f64::from(a) - mean_a, f64::from(b) - mean_b);
+        product += a * b;
+        power_a += a * a;
+        power_b += b * b;
+    }
+
+    if power_a == 0.0 || power_b == 0.0 {
+        0.0
+    } else {
+        product / (power_a * power_b).sqrt()
+    }
+}
@@ -0,0 +1,190 @@
+use crate::{Error, Marker, Project, QWaveReader, SampleFmt};
+use std::io::{Read, Seek};
+use std::ops::Range;
+
+// Samples read from each file at once when comparing readers
+const COMPARE_CHUNK_LEN: usize = 1 << 16;
+
+// Loop as held by a project
+type ProjectLoop = Option<Range<u32>>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct CompareOptions {
+    /// Stop scanning samples at the first difference, leaving the count and
+    /// maximum covering only that sample
+    pub stop_at_first: bool,
+}
+
+/// Differences between two projects.  Pairs are given as (a, b) and are only
+/// present when the two disagree.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct CompareReport {
+    pub sample_rates: Option<(u32, u32)>,
+    pub render_formats: Option<(SampleFmt, SampleFmt)>,
+    pub channels: Option<(u16, u16)>,
+    pub sample_counts: Option<(u32, u32)>,
+    pub loops: Option<(ProjectLoop, ProjectLoop)>,
+    pub play_ends: Option<(Option<u32>, Option<u32>)>,
+
+    /// Positions of the cue points kept besides the loop's and the end
+    /// marker's, in order
+    pub markers: Option<(Vec<u32>, Vec<u32>)>,
+
+    /// Index of the first interleaved sample that differs, among those both
+    /// projects hold
+    pub first_difference: Option<usize>,
+    pub last_difference: Option<usize>,
+
+    /// Number of differing samples among those both projects hold
+    pub differing_samples: usize,
+
+    /// Largest absolute difference between samples both projects hold
+    pub max_difference: u32,
+}
+
+impl CompareReport {
+    /// True when the projects match in format and loop, and hold the same
+    /// number of samples, none differing by more than `tolerance`
+    pub fn matches_within(&self, tolerance: u32) -> bool {
+        self.sample_rates.is_none()
+            && self.render_formats.is_none()
+            && self.channels.is_none()
+            && self.sample_counts.is_none()
+            && self.loops.is_none()
+            && self.play_ends.is_none()
+            && self.markers.is_none()
+            && self.max_difference <= tolerance
+    }
+
+    /// True when every differing sample lies within `range` of interleaved
+    /// samples, as when only a blend window was rewritten
+    pub fn differences_within(&self, range: Range<usize>) -> bool {
+        match (self.first_difference, self.last_difference) {
+            (Some(first), Some(last)) => {
+                range.contains(&first) && range.contains(&last)
+            }
+            _ => true,
+        }
+    }
+
+    pub fn is_identical(&self) -> bool {
+        self.matches_within(0)
+    }
+}
+
+/// Compare two projects sample by sample
+pub fn compare(a: &Project, b: &Project) -> CompareReport {
+    compare_with(a, b, CompareOptions::default())
+}
+
+pub fn compare_with(
+    a: &Project,
+    b: &Project,
+    options: CompareOptions,
+) -> CompareReport {
+    let positions = |project: &Project| {
+        project
+            .markers()
+            .iter()
+            .map(|marker| marker.position)
+            .collect()
+    };
+    let mut report = CompareReport {
+        sample_rates: differ(a.sample_rate(), b.sample_rate()),
+        render_formats: differ(a.render_format(), b.render_format()),
+        channels: differ(a.channels(), b.channels()),
+        sample_counts: differ(a.sample_count(), b.sample_count()),
+        loops: differ(a.sample_loop(), b.sample_loop()),
+        play_ends: differ(a.play_end(), b.play_end()),
+        markers: differ(positions(a), positions(b)),
+        ..CompareReport::default()
+    };
+
+    compare_samples(&mut report, 0, a.samples(), b.samples(), options);
+    report
+}
+
+/// Compare two WAVs as `compare_with` compares the projects read from them,
+/// reading the samples of each a chunk at a time rather than all at once
+pub fn compare_readers<A: Read + Seek, B: Read + Seek>(
+    a: &mut QWaveReader<A>,
+    b: &mut QWaveReader<B>,
+    options: CompareOptions,
+) -> Result<CompareReport, Error> {
+    let (meta_a, meta_b) = (a.metadata(), b.metadata());
+    let sample_loop = |metadata: crate::Metadata| {
+        metadata
+            .loop_start
+            .map(|start| start..metadata.end.unwrap_or(metadata.sample_count))
+    };
+    let positions = |markers: &[Marker]| {
+        markers.iter().map(|marker| marker.position).collect()
+    };
+
+    let mut report = CompareReport {
+        sample_rates: differ(meta_a.sample_rate, meta_b.sample_rate),
+        render_formats: differ(
+            crate::project::render_format_of(&meta_a)?,
+            crate::project::render_format_of(&meta_b)?,
+        ),
+        channels: differ(meta_a.channels, meta_b.channels),
+        sample_counts: differ(meta_a.sample_count, meta_b.sample_count),
+        loops: differ(sample_loop(meta_a), sample_loop(meta_b)),
+        play_ends: differ(meta_a.play_end, meta_b.play_end),
+        markers: differ(positions(a.markers()), positions(b.markers())),
+        ..CompareReport::default()
+    };
+
+    let mut chunks_b = b.sample_chunks(COMPARE_CHUNK_LEN)?;
+    let mut offset = 0;
+
+    for chunk_a in a.sample_chunks(COMPARE_CHUNK_LEN)? {
+        let (chunk_a, chunk_b) = match chunks_b.next() {
+            Some(chunk_b) => (chunk_a?, chunk_b?),
+            None => break,
+        };
+
+        compare_samples(&mut report, offset, &chunk_a, &chunk_b, options);
+        offset += chunk_a.len();
+
+        if options.stop_at_first && report.first_difference.is_some() {
+            break;
+        }
+    }
+
+    Ok(report)
+}
+
+// Add the differences between samples both hold to the report, numbering
+// them from `offset`
+fn compare_samples(
+    report: &mut CompareReport,
+    offset: usize,
+    a: &[i16],
+    b: &[i16],
+    options: CompareOptions,
+) {
+    for (idx, (&sample_a, &sample_b)) in a.iter().zip(b).enumerate() {
+        let difference =
+            (i32::from(sample_a) - i32::from(sample_b)).unsigned_abs();
+
+        if difference > 0 {
+            report.first_difference.get_or_insert(offset + idx);
+            report.last_difference = Some(offset + idx);
+            report.differing_samples += 1;
+            report.max_difference = report.max_difference.max(difference);
+
+            if options.stop_at_first {
+                break;
+            }
+        }
+    }
+}
+
+fn differ<T: PartialEq>(a: T, b: T) -> Option<(T, T)> {
+    if a != b {
+        Some((a, b))
+    } else {
+        None
+    }
+}
@@ -0,0 +1,442 @@
+use crate::Error;
+use rubato::{
+    Resampler, SincFixedIn, SincInterpolationParameters, SincInterpolationType,
+    WindowFunction,
+};
+
+/// Whether the signal crosses zero arriving at `index`, i.e. the sample is
+/// silent or has the opposite sign of the one before it
+pub fn is_zero_crossing(samples: &[i16], index: usize) -> bool {
+    match (index.checked_sub(1), samples.get(index)) {
+        (_, Some(0)) => true,
+        (Some(prev), Some(&s)) => (samples[prev] < 0) != (s < 0),
+        _ => false,
+    }
+}
+
+/// Find the zero crossing nearest to `position`, searching no further than
+/// `max_distance` samples in either direction.  Ties favor the earlier index.
+pub fn nearest_zero_crossing(
+    samples: &[i16],
+    position: usize,
+    max_distance: usize,
+) -> Option<usize> {
+    (0..=max_distance).find_map(|distance| {
+        let before = position.checked_sub(distance);
+        let after = position.checked_add(distance);
+
+        before
+            .filter(|&idx| is_zero_crossing(samples, idx))
+            .or(after.filter(|&idx| is_zero_crossing(samples, idx)))
+    })
+}
+
+/// Whether the signal rises through zero arriving at `index`, i.e. the sample
+/// is non-negative and the one before it negative
+pub fn is_rising_zero_crossing(samples: &[i16], index: usize) -> bool {
+    match (index.checked_sub(1), samples.get(index)) {
+        (Some(prev), Some(&s)) => samples[prev] < 0 && s >= 0,
+        _ => false,
+    }
+}
+
+/// Find the rising zero crossing nearest to `position`, as
+/// `nearest_zero_crossing` finds any crossing
+pub fn nearest_rising_zero_crossing(
+    samples: &[i16],
+    position: usize,
+    max_distance: usize,
+) -> Option<usize> {
+    (0..=max_distance).find_map(|distance| {
+        let before = position.checked_sub(distance);
+        let after = position.checked_add(distance);
+
+        before
+            .filter(|&idx| is_rising_zero_crossing(samples, idx))
+            .or(after.filter(|&idx| is_rising_zero_crossing(samples, idx)))
+    })
+}
+
+/// Weighting curve for `crossfade`, rising from 0 to 1 across the window
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BlendShape {
+    /// Cubic smoothstep, flat at both ends
+    #[default]
+    Smooth,
+
+    Linear,
+
+    /// Sine/cosine weights which keep the power of uncorrelated signals
+    /// constant.  Weights sum to more than 1 mid-window, so this may clip.
+    EqualPower,
+}
+
+impl BlendShape {
+    // Weights given to the signal faded in and the one faded out at `t`
+    fn weights(&self, t: f64) -> (f64, f64) {
+        match self {
+            BlendShape::Smooth => {
+                let w = t * t * (3.0 - 2.0 * t);
+                (w, 1.0 - w)
+            }
+            BlendShape::Linear => (t, 1.0 - t),
+            BlendShape::EqualPower => {
+                let angle = t * std::f64::consts::FRAC_PI_2;
+                (angle.sin(), angle.cos())
+            }
+        }
+    }
+}
+
+/// Fade from `b` into `a`, writing the mix to `dst`.  The weight given to `a`
+/// starts at 0 on the first sample and approaches 1 toward the last.  All three
+/// slices must be the same length.  Returns the number of samples which fell
+/// outside the range of an i16 and were clipped.
+pub fn crossfade(
+    dst: &mut [i16],
+    a: &[i16],
+    b: &[i16],
+    shape: BlendShape,
+) -> Result<usize, Error> {
+    if a.len() != dst.len() || b.len() != dst.len() {
+        return Err(Error::InvalidArgument(String::from(
+            "Crossfade slices differ in length",
+        )));
+    }
+
+    let len = dst.len() as f64;
+    let mut clipped = 0usize;
+
+    for (i, (out, (&sample_a, &sample_b))) in
+        dst.iter_mut().zip(a.iter().zip(b)).enumerate()
+    {
+        let (weight_a, weight_b) = shape.weights(i as f64 / len);
+        let mixed = (weight_a * f64::from(sample_a)
+            + weight_b * f64::from(sample_b))
+        .round();
+
+        if mixed < f64::from(i16::MIN) || mixed > f64::from(i16::MAX) {
+            clipped += 1;
+        }
+
+        *out = mixed as i16;
+    }
+
+    Ok(clipped)
+}
+
+// Range and spacing of frequencies probed by `lowest_frequency`
+const PROBE_MIN_HZ: f64 = 20.0;
+const PROBE_MAX_HZ: f64 = 2000.0;
+const PROBES_PER_OCTAVE: f64 = 12.0;
+
+// Fraction of the strongest probe's magnitude a component needs to count
+const SIGNIFICANT: f64 = 0.3;
+
+/// Estimate the lowest frequency, in Hz, carrying a significant share of the
+/// signal's energy.  Probes frequencies from 20 Hz to 2 kHz a semitone apart,
+/// counting any within 30% of the strongest magnitude.  Gives none for
+/// silence or when no probe fits below the Nyquist frequency.
+pub fn lowest_frequency(samples: &[i16], sample_rate: u32) -> Option<f64> {
+    let rate = f64::from(sample_rate);
+    let len = samples.len() as f64;
+    let mean = samples.iter().map(|&s| f64::from(s)).sum::<f64>() / len;
+
+    // Hann window, so that leakage from strong components doesn't mask weak
+    // ones or read as lower components
+    let windowed = samples
+        .iter()
+        .enumerate()
+        .map(|(i, &s)| {
+            let hann =
+                0.5 - 0.5 * (std::f64::consts::TAU * i as f64 / len).cos();
+            (f64::from(s) - mean) * hann
+        })
+        .collect::<Vec<_>>();
+
+    let probes = (0..)
+        .map(|step| PROBE_MIN_HZ * 2f64.powf(step as f64 / PROBES_PER_OCTAVE))
+        .take_while(|&freq| freq <= PROBE_MAX_HZ && freq < rate / 2.0)
+        .map(|freq| (freq, goertzel(&windowed, freq / rate)))
+        .collect::<Vec<_>>();
+
+    let strongest = probes
+        .iter()
+        .map(|&(_, magnitude)| magnitude)
+        .fold(0.0, f64::max);
+
+    if strongest < 1.0 {
+        return None;
+    }
+
+    probes
+        .into_iter()
+        .find(|&(_, magnitude)| magnitude >= strongest * SIGNIFICANT)
+        .map(|(freq, _)| freq)
+}
+
+// Magnitude of a single frequency, given in cycles per sample
+fn goertzel(samples: &[f64], freq: f64) -> f64 {
+    let coeff = 2.0 * (std::f64::consts::TAU * freq).cos();
+    let (mut prev, mut prev2) = (0f64, 0f64);
+
+    for &s in samples {
+        let next = s + coeff * prev - prev2;
+        prev2 = prev;
+        prev = next;
+    }
+
+    (prev * prev + prev2 * prev2 - coeff * prev * prev2)
+        .max(0.0)
+        .sqrt()
+}
+
+/// Trade-off between speed and fidelity for `resample`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ResampleQuality {
+    /// Long sinc filter, for final checks and writing files
+    #[default]
+    High,
+
+    /// Short sinc filter with linear interpolation between its taps
+    Medium,
+
+    /// Straight lines between neighboring samples, with no filter at all;
+    /// aliases, but is quick enough to audition with at once
+    Fast,
+}
+
+impl ResampleQuality {
+    // Sinc filter resampling at this quality, if it takes one
+    fn parameters(&self) -> Option<SincInterpolationParameters> {
+        match self {
+            ResampleQuality::High => {
+                let sinc_len = 256usize;
+
+                Some(SincInterpolationParameters {
+                    sinc_len,
+                    f_cutoff: 1f32 + 1f32 / sinc_len as f32,
+                    oversampling_factor: 128,
+                    interpolation: SincInterpolationType::Cubic,
+                    window: WindowFunction::Blackman,
+                })
+            }
+            ResampleQuality::Medium => Some(SincInterpolationParameters {
+                sinc_len: 64,
+                f_cutoff: 0.95,
+                oversampling_factor: 32,
+                interpolation: SincInterpolationType::Linear,
+                window: WindowFunction::Blackman,
+            }),
+            ResampleQuality::Fast => None,
+        }
+    }
+}
+
+/// Resample mono samples from `inrate` to `outrate`.  The output holds
+/// exactly `round(input.len() * outrate / inrate)` samples, aligned with the
+/// input so that sample `i` of the input lands at `i * outrate / inrate`,
+/// give or take one input sample or two output samples, whichever is more.
+pub fn resample(
+    input: &[f32],
+    inrate: u32,
+    outrate: u32,
+    quality: ResampleQuality,
+) -> Result<Vec<f32>, Error> {
+    let mut output =
+        Vec::with_capacity(resampled_len(input.len(), inrate, outrate));
+
+    resample_blocks(input, inrate, outrate, quality, input.len(), |block| {
+        output.extend_from_slice(block);
+        true
+    })?;
+
+    Ok(output)
+}
+
+// Resample by drawing straight lines between input samples, handing the
+// output to `emit` about a `block_len` of input at a time.  Output sample `j`
+// lies at input position `j / ratio`, which keeps it aligned as the sinc
+// filters are.
+fn interpolate_blocks(
+    input: &[f32],
+    ratio: f64,
+    out_len: usize,
+    block_len: usize,
+    mut emit: impl FnMut(&[f32]) -> bool,
+) {
+    let out_block_len = ((block_len as f64 * ratio).ceil() as usize).max(1);
+    let last = input.len() - 1;
+    let mut block = Vec::with_capacity(out_block_len);
+
+    for start in (0..out_len).step_by(out_block_len) {
+        block.clear();
+        block.extend((start..out_len.min(start + out_block_len)).map(|j| {
+            let position = j as f64 / ratio;
+            let index = (position as usize).min(last);
+            let frac = (position - index as f64).clamp(0.0, 1.0) as f32;
+            let next = input[(index + 1).min(last)];
+
+            input[index] + (next - input[index]) * frac
+        }));
+
+        if !emit(&block) {
+            return;
+        }
+    }
+}
+
+/// Length of `len` samples at `inrate` resampled to `outrate`, as `resample`
+/// gives
+pub fn resampled_len(len: usize, inrate: u32, outrate: u32) -> usize {
+    if inrate == outrate || inrate == 0 {
+        len
+    } else {
+        (len as f64 * f64::from(outrate) / f64::from(inrate)).round() as usize
+    }
+}
+
+/// As `resample`, handing the output to `emit` in order as it is made, a
+/// block for every `block_len` samples of input, so that the first of it can
+/// be used before the rest is ready.  Stops early once `emit` returns false.
+pub fn resample_blocks(
+    input: &[f32],
+    inrate: u32,
+    outrate: u32,
+    quality: ResampleQuality,
+    block_len: usize,
+    mut emit: impl FnMut(&[f32]) -> bool,
+) -> Result<(), Error> {
+    if inrate == 0 || outrate == 0 {
+        return Err(Error::InvalidArgument(String::from(
+            "Sample rate must be non-zero",
+        )));
+    }
+
+    if inrate == outrate || input.is_empty() {
+        emit(input);
+        return Ok(());
+    }
+
+    let ratio = f64::from(outrate) / f64::from(inrate);
+    let out_len = resampled_len(input.len(), inrate, outrate);
+    let block_len = block_len.clamp(1, input.len());
+
+    let Some(parameters) = quality.parameters() else {
+        interpolate_blocks(input, ratio, out_len, block_len, emit);
+        return Ok(());
+    };
+
+    let mut resampler = SincFixedIn::new(ratio, 1.0, parameters, block_len, 1)
+        .map_err(|e| Error::Resample(e.to_string()))?;
+
+    let mut emitted = 0;
+    let mut send = |block: Vec<f32>, emitted: &mut usize| {
+        let take = block.len().min(out_len - *emitted);
+        *emitted += take;
+        emit(&block[..take])
+    };
+
+    for chunk in input.chunks(block_len) {
+        let output = if chunk.len() == block_len {
+            resampler.process(&[chunk], None)
+        } else {
+            resampler.process_partial(Some(&[chunk]), None)
+        }
+        .map_err(|e| Error::Resample(e.to_string()))?
+        .remove(0);
+
+        if !send(output, &mut emitted) {
+            return Ok(());
+        }
+    }
+
+    // The filter holds back the last of the input until it sees what follows;
+    // flush it out with silence
+    while emitted < out_len {
+        let flushed = resampler
+            .process_partial::<&[f32]>(None, None)
+            .map_err(|e| Error::Resample(e.to_string()))?
+            .remove(0);
+
+        if flushed.is_empty() {
+            break;
+        }
+
+        if !send(flushed, &mut emitted) {
+            return Ok(());
+        }
+    }
+
+    if emitted < out_len {
+        send(vec![0.0; out_len - emitted], &mut emitted);
+    }
+
+    Ok(())
+}
+
+/// Power of each frequency bin, from DC to Nyquist, of a Hann-windowed frame
+/// whose length is a power of two
+pub fn power_spectrum(frame: &[f64]) -> Result<Vec<f64>, Error> {
+    let len = frame.len();
+
+    if !len.is_power_of_two() || len < 2 {
+        return Err(Error::InvalidArgument(String::from(
+            "Frame length must be a power of two",
+        )));
+    }
+
+    let mut re = frame
+        .iter()
+        .enumerate()
+        .map(|(i, &s)| {
+            let hann = 0.5
+                - 0.5 * (std::f64::consts::TAU * i as f64 / len as f64).cos();
+            s * hann
+        })
+        .collect::<Vec<_>>();
+    let mut im = vec![0f64; len];
+
+    fft(&mut re, &mut im);
+
+    Ok((0..=len / 2)
+        .map(|bin| re[bin] * re[bin] + im[bin] * im[bin])
+        .collect())
+}
+
+// In-place iterative radix-2 FFT; the length must be a power of two
+fn fft(re: &mut [f64], im: &mut [f64]) {
+    let len = re.len();
+    let bits = len.trailing_zeros();
+
+    for i in 0..len {
+        let j = i.reverse_bits() >> (usize::BITS - bits);
+
+        if i < j {
+            re.swap(i, j);
+            im.swap(i, j);
+        }
+    }
+
+    let mut span = 2;
+
+    while span <= len {
+        let angle = -std::f64::consts::TAU / span as f64;
+
+        for start in (0..len).step_by(span) {
+            for k in 0..span / 2 {
+                let (sin, cos) = (angle * k as f64).sin_cos();
+                let (a, b) = (start + k, start + k + span / 2);
+                let t_re = re[b] * cos - im[b] * sin;
+                let t_im = re[b] * sin + im[b] * cos;
+
+                re[b] = re[a] - t_re;
+                im[b] = im[a] - t_im;
+                re[a] += t_re;
+                im[a] += t_im;
+            }
+        }
+
+        span *= 2;
+    }
+}
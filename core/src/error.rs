@@ -0,0 +1,119 @@
+use std::path::Path;
+use std::{fmt, io};
+
+/// Why reading, editing, or playing audio failed.  Displayed, each gives the
+/// same message the library has always given.
+#[derive(Debug)]
+pub enum Error {
+    /// Reading or writing a file failed
+    Io(io::Error),
+
+    /// The file isn't a well-formed WAV
+    WavDecode(String),
+
+    /// The file isn't a well-formed pak archive
+    PakDecode(String),
+
+    /// The file is a WAV, but of a kind that isn't supported, such as 64-bit
+    /// RIFF, an unusual sample width, or too many samples
+    Unsupported(String),
+
+    /// An operation needing a loop was asked of audio without one; holds what
+    /// the loop was needed for, e.g. "blend"
+    NoLoop(&'static str),
+
+    /// Loop points or the end marker don't fit the audio or each other
+    InvalidLoop(String),
+
+    /// No output device, or it couldn't be opened or played
+    Audio(String),
+
+    /// Converting between sample rates failed
+    Resample(String),
+
+    /// An argument is out of range, such as a zero sample rate or a blend
+    /// window longer than the loop
+    InvalidArgument(String),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Io(e) => e.fmt(f),
+            Error::NoLoop(purpose) => write!(f, "No loop to {}", purpose),
+            Error::WavDecode(message)
+            | Error::PakDecode(message)
+            | Error::Unsupported(message)
+            | Error::InvalidLoop(message)
+            | Error::Audio(message)
+            | Error::Resample(message)
+            | Error::InvalidArgument(message) => f.write_str(message),
+        }
+    }
+}
+
+impl Error {
+    /// The same error, with its message prefixed by the path of the file it
+    /// concerns
+    pub fn at_path(self, path: &Path) -> Self {
+        let prefix =
+            |message: String| format!("{}: {}", path.display(), message);
+
+        match self {
+            Error::Io(e) => {
+                Error::Io(io::Error::new(e.kind(), prefix(e.to_string())))
+            }
+            Error::WavDecode(message) => Error::WavDecode(prefix(message)),
+            Error::PakDecode(message) => Error::PakDecode(prefix(message)),
+            Error::Unsupported(message) => Error::Unsupported(prefix(message)),
+            Error::InvalidLoop(message) => Error::InvalidLoop(prefix(message)),
+            Error::Audio(message) => Error::Audio(prefix(message)),
+            Error::Resample(message) => Error::Resample(prefix(message)),
+            Error::InvalidArgument(message) => {
+                Error::InvalidArgument(prefix(message))
+            }
+            e @ Error::NoLoop(_) => e,
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Self {
+        Error::Io(e)
+    }
+}
+
+impl From<hound::Error> for Error {
+    fn from(e: hound::Error) -> Self {
+        match e {
+            hound::Error::IoError(e) => Error::Io(e),
+            hound::Error::Unsupported => Error::Unsupported(e.to_string()),
+            e => Error::WavDecode(e.to_string()),
+        }
+    }
+}
+
+impl From<cuet::Error> for Error {
+    fn from(e: cuet::Error) -> Self {
+        match e {
+            cuet::Error::Io(e) => Error::Io(e),
+            cuet::Error::Wave(message) => Error::WavDecode(message),
+        }
+    }
+}
+
+// Modules still reporting errors as text take these with `?`
+impl From<Error> for String {
+    fn from(e: Error) -> Self {
+        e.to_string()
+    }
+}
@@ -0,0 +1,303 @@
+// C ABI over projects, for tools that can't shell out to the CLI for every
+// file; the declarations are in include/quadio.h.  Every function returns
+// QUADIO_OK or an error code, with the message available from
+// quadio_last_error_message on the same thread, and none lets a panic cross
+// into the caller.
+
+use crate::{Error, Project, QWaveReader};
+use std::cell::RefCell;
+use std::ffi::{c_char, c_int, CStr, CString};
+use std::io::Cursor;
+use std::panic::{self, AssertUnwindSafe};
+use std::ptr;
+
+pub const QUADIO_OK: c_int = 0;
+
+/// A pointer argument was null, or a path wasn't UTF-8
+pub const QUADIO_ERR_ARGUMENT: c_int = 1;
+pub const QUADIO_ERR_IO: c_int = 2;
+
+/// The bytes aren't a well-formed WAV, or not one that is supported
+pub const QUADIO_ERR_DECODE: c_int = 3;
+
+/// The loop is missing, or doesn't fit the audio
+pub const QUADIO_ERR_LOOP: c_int = 4;
+
+/// A value is out of range, such as a blend window longer than the loop
+pub const QUADIO_ERR_INVALID: c_int = 5;
+
+/// The library panicked; the project may be left part-way through the edit
+pub const QUADIO_ERR_PANIC: c_int = 6;
+
+/// Format and loop of a project, as `quadio_project_metadata` fills it
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct QuadioMetadata {
+    pub sample_rate: u32,
+    pub channels: u16,
+
+    /// Frames, i.e. samples per channel
+    pub sample_count: u32,
+
+    /// Non-zero if the project loops, in which case the loop runs from
+    /// `loop_start` up to `loop_end`
+    pub has_loop: c_int,
+    pub loop_start: u32,
+    pub loop_end: u32,
+}
+
+/// Opaque handle to a project, freed with `quadio_project_free`
+pub struct QuadioProject(Project);
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+// Failure of an entry point: a code, and the message kept for the caller
+struct Failure(c_int, String);
+
+impl From<Error> for Failure {
+    fn from(error: Error) -> Self {
+        let code = match error {
+            Error::Io(_) => QUADIO_ERR_IO,
+            Error::WavDecode(_)
+            | Error::PakDecode(_)
+            | Error::Unsupported(_) => QUADIO_ERR_DECODE,
+            Error::NoLoop(_) | Error::InvalidLoop(_) => QUADIO_ERR_LOOP,
+            Error::Audio(_)
+            | Error::Resample(_)
+            | Error::InvalidArgument(_) => QUADIO_ERR_INVALID,
+        };
+
+        Failure(code, error.to_string())
+    }
+}
+
+fn argument(message: &str) -> Failure {
+    Failure(QUADIO_ERR_ARGUMENT, String::from(message))
+}
+
+// Run an entry point, keeping the message of any failure or panic for
+// quadio_last_error_message, and returning its code
+fn guard(body: impl FnOnce() -> Result<(), Failure>) -> c_int {
+    let Failure(code, message) =
+        match panic::catch_unwind(AssertUnwindSafe(body)) {
+            Ok(Ok(())) => return QUADIO_OK,
+            Ok(Err(failure)) => failure,
+            Err(payload) => {
+                let message = payload
+                    .downcast_ref::<&str>()
+                    .map(|message| String::from(*message))
+                    .or_else(|| payload.downcast_ref::<String>().cloned())
+                    .unwrap_or_else(|| String::from("Unknown panic"));
+                Failure(QUADIO_ERR_PANIC, format!("Panicked: {}", message))
+            }
+        };
+
+    // Interior NULs would cut the message short anyway
+    let message = CString::new(message.replace('\0', " ")).unwrap_or_default();
+    LAST_ERROR.with(|last| *last.borrow_mut() = Some(message));
+    code
+}
+
+// Path given as a NUL-terminated UTF-8 string
+unsafe fn path_arg<'a>(path: *const c_char) -> Result<&'a str, Failure> {
+    if path.is_null() {
+        return Err(argument("Path is null"));
+    }
+
+    CStr::from_ptr(path)
+        .to_str()
+        .map_err(|_| argument("Path is not UTF-8"))
+}
+
+unsafe fn project_arg<'a>(
+    project: *const QuadioProject,
+) -> Result<&'a Project, Failure> {
+    project
+        .as_ref()
+        .map(|project| &project.0)
+        .ok_or_else(|| argument("Project is null"))
+}
+
+unsafe fn project_arg_mut<'a>(
+    project: *mut QuadioProject,
+) -> Result<&'a mut Project, Failure> {
+    project
+        .as_mut()
+        .map(|project| &mut project.0)
+        .ok_or_else(|| argument("Project is null"))
+}
+
+// Hand a project to the caller through `out`
+unsafe fn give(
+    out: *mut *mut QuadioProject,
+    project: Project,
+) -> Result<(), Failure> {
+    if out.is_null() {
+        return Err(argument("Output pointer is null"));
+    }
+
+    *out = Box::into_raw(Box::new(QuadioProject(project)));
+    Ok(())
+}
+
+/// Message of the last failure on this thread, or null if there has been
+/// none.  It stays valid until the next failure on the thread.
+#[no_mangle]
+pub extern "C" fn quadio_last_error_message() -> *const c_char {
+    LAST_ERROR.with(|last| {
+        last.borrow()
+            .as_ref()
+            .map_or(ptr::null(), |message| message.as_ptr())
+    })
+}
+
+/// Read the WAV at `path` into a new project, stored in `*out`
+///
+/// # Safety
+///
+/// `path` must be null or a NUL-terminated string, and `out` null or valid
+/// to write a pointer to.
+#[no_mangle]
+pub unsafe extern "C" fn quadio_project_open(
+    path: *const c_char,
+    out: *mut *mut QuadioProject,
+) -> c_int {
+    guard(|| give(out, Project::open(path_arg(path)?)?))
+}
+
+/// Read a WAV held in memory into a new project, stored in `*out`.  The bytes
+/// are copied, and can be freed once this returns.
+///
+/// # Safety
+///
+/// `bytes` must be null or valid to read `len` bytes from, and `out` null or
+/// valid to write a pointer to.
+#[no_mangle]
+pub unsafe extern "C" fn quadio_project_from_memory(
+    bytes: *const u8,
+    len: usize,
+    out: *mut *mut QuadioProject,
+) -> c_int {
+    guard(|| {
+        if bytes.is_null() {
+            return Err(argument("Bytes are null"));
+        }
+
+        let bytes = std::slice::from_raw_parts(bytes, len);
+        let reader = QWaveReader::new(Cursor::new(bytes))?;
+        give(out, Project::from_reader(reader)?)
+    })
+}
+
+/// Fill `*out` with the project's format and loop
+///
+/// # Safety
+///
+/// `project` must be null or a handle not yet freed, and `out` null or valid
+/// to write a `QuadioMetadata` to.
+#[no_mangle]
+pub unsafe extern "C" fn quadio_project_metadata(
+    project: *const QuadioProject,
+    out: *mut QuadioMetadata,
+) -> c_int {
+    guard(|| {
+        let project = project_arg(project)?;
+        let out = out.as_mut().ok_or_else(|| argument("Output is null"))?;
+        let sample_loop = project.sample_loop();
+
+        *out = QuadioMetadata {
+            sample_rate: project.sample_rate(),
+            channels: project.channels(),
+            sample_count: project.sample_count(),
+            has_loop: c_int::from(sample_loop.is_some()),
+            loop_start: sample_loop.as_ref().map_or(0, |l| l.start),
+            loop_end: sample_loop.map_or(0, |l| l.end),
+        };
+
+        Ok(())
+    })
+}
+
+/// Loop the project from `start` up to `end`.  A loop that doesn't fit the
+/// audio is refused, leaving the loop as it was.
+///
+/// # Safety
+///
+/// `project` must be null or a handle not yet freed.
+#[no_mangle]
+pub unsafe extern "C" fn quadio_project_set_loop(
+    project: *mut QuadioProject,
+    start: u32,
+    end: u32,
+) -> c_int {
+    guard(|| {
+        let project = project_arg_mut(project)?;
+        let old_loop = project.sample_loop();
+        project.set_loop(Some(start..end));
+
+        if let Err(e) = project.validate() {
+            project.set_loop(old_loop);
+            return Err(e.into());
+        }
+
+        Ok(())
+    })
+}
+
+/// Remove the project's loop, if any
+///
+/// # Safety
+///
+/// `project` must be null or a handle not yet freed.
+#[no_mangle]
+pub unsafe extern "C" fn quadio_project_clear_loop(
+    project: *mut QuadioProject,
+) -> c_int {
+    guard(|| {
+        project_arg_mut(project)?.set_loop(None);
+        Ok(())
+    })
+}
+
+/// Crossfade the end of the loop into the samples before its start, over
+/// `window_sz` frames
+///
+/// # Safety
+///
+/// `project` must be null or a handle not yet freed.
+#[no_mangle]
+pub unsafe extern "C" fn quadio_project_blend(
+    project: *mut QuadioProject,
+    window_sz: u32,
+) -> c_int {
+    guard(|| Ok(project_arg_mut(project)?.blend(window_sz)?))
+}
+
+/// Write the project as a WAV to `path`, replacing any file there
+///
+/// # Safety
+///
+/// `project` must be null or a handle not yet freed, and `path` null or a
+/// NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn quadio_project_write(
+    project: *const QuadioProject,
+    path: *const c_char,
+) -> c_int {
+    guard(|| Ok(project_arg(project)?.write_to(&path_arg(path)?)?))
+}
+
+/// Free a project.  Null is ignored.
+///
+/// # Safety
+///
+/// `project` must be null or a handle not yet freed, and is invalid after.
+#[no_mangle]
+pub unsafe extern "C" fn quadio_project_free(project: *mut QuadioProject) {
+    if !project.is_null() {
+        let project = AssertUnwindSafe(project);
+        let _ = panic::catch_unwind(|| drop(Box::from_raw(*project)));
+    }
+}
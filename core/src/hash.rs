@@ -0,0 +1,214 @@
+use crate::QWaveReader;
+use std::io::{Read, Seek, SeekFrom};
+
+const BLOCK_SZ: usize = 64;
+
+const ROUND_CONSTANTS: [u32; 64] = [
+    0x428a2f98, 0x71374491, 0xb5c0fbcf, 0xe9b5dba5, 0x3956c25b, 0x59f111f1,
+    0x923f82a4, 0xab1c5ed5, 0xd807aa98, 0x12835b01, 0x243185be, 0x550c7dc3,
+    0x72be5d74, 0x80deb1fe, 0x9bdc06a7, 0xc19bf174, 0xe49b69c1, 0xefbe4786,
+    0x0fc19dc6, 0x240ca1cc, 0x2de92c6f, 0x4a7484aa, 0x5cb0a9dc, 0x76f988da,
+    0x983e5152, 0xa831c66d, 0xb00327c8, 0xbf597fc7, 0xc6e00bf3, 0xd5a79147,
+    0x06ca6351, 0x14292967, 0x27b70a85, 0x2e1b2138, 0x4d2c6dfc, 0x53380d13,
+    0x650a7354, 0x766a0abb, 0x81c2c92e, 0x92722c85, 0xa2bfe8a1, 0xa81a664b,
+    0xc24b8b70, 0xc76c51a3, 0xd192e819, 0xd6990624, 0xf40e3585, 0x106aa070,
+    0x19a4c116, 0x1e376c08, 0x2748774c, 0x34b0bcb5, 0x391c0cb3, 0x4ed8aa4a,
+    0x5b9cca4f, 0x682e6ff3, 0x748f82ee, 0x78a5636f, 0x84c87814, 0x8cc70208,
+    0x90befffa, 0xa4506ceb, 0xbef9a3f7, 0xc67178f2,
+];
+
+const INITIAL_STATE: [u32; 8] = [
+    0x6a09e667, 0xbb67ae85, 0x3c6ef372, 0xa54ff53a, 0x510e527f, 0x9b05688c,
+    0x1f83d9ab, 0x5be0cd19,
+];
+
+pub type Digest = [u8; 32];
+
+/// Incremental SHA-256
+#[derive(Debug, Clone)]
+pub struct Sha256 {
+    state: [u32; 8],
+    block: [u8; BLOCK_SZ],
+    block_len: usize,
+    total_len: u64,
+}
+
+impl Default for Sha256 {
+    fn default() -> Self {
+        Sha256 {
+            state: INITIAL_STATE,
+            block: [0; BLOCK_SZ],
+            block_len: 0,
+            total_len: 0,
+        }
+    }
+}
+
+impl Sha256 {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn update(&mut self, mut bytes: &[u8]) {
+        self.total_len += bytes.len() as u64;
+
+        while !bytes.is_empty() {
+            let take = (BLOCK_SZ - self.block_len).min(bytes.len());
+            self.block[self.block_len..self.block_len + take]
+                .copy_from_slice(&bytes[..take]);
+            self.block_len += take;
+            bytes = &bytes[take..];
+
+            if self.block_len == BLOCK_SZ {
+                compress(&mut self.state, &self.block);
+                self.block_len = 0;
+            }
+        }
+    }
+
+    pub fn finish(mut self) -> Digest {
+        let bit_len = self.total_len.wrapping_mul(8);
+        self.update(&[0x80]);
+
+        while self.block_len != BLOCK_SZ - 8 {
+            self.update(&[0]);
+        }
+
+        self.update(&bit_len.to_be_bytes());
+
+        let mut digest = [0u8; 32];
+
+        for (bytes, word) in digest.chunks_exact_mut(4).zip(self.state) {
+            bytes.copy_from_slice(&word.to_be_bytes());
+        }
+
+        digest
+    }
+}
+
+fn compress(state: &mut [u32; 8], block: &[u8; BLOCK_SZ]) {
+    let mut schedule = [0u32; 64];
+
+    for (word, bytes) in schedule.iter_mut().zip(block.chunks_exact(4)) {
+        *word = u32::from_be_bytes(bytes.try_into().unwrap());
+    }
+
+    for i in 16..64 {
+        let w15 = schedule[i - 15];
+        let w2 = schedule[i - 2];
+        let s0 = w15.rotate_right(7) ^ w15.rotate_right(18) ^ (w15 >> 3);
+        let s1 = w2.rotate_right(17) ^ w2.rotate_right(19) ^ (w2 >> 10);
+        schedule[i] = schedule[i - 16]
+            .wrapping_add(s0)
+            .wrapping_add(schedule[i - 7])
+            .wrapping_add(s1);
+    }
+
+    let [mut a, mut b, mut c, mut d, mut e, mut f, mut g, mut h] = *state;
+
+    for (&k, &w) in ROUND_CONSTANTS.iter().zip(&schedule) {
+        let s1 = e.rotate_right(6) ^ e.rotate_right(11) ^ e.rotate_right(25);
+        let choice = (e & f) ^ (!e & g);
+        let t1 = h
+            .wrapping_add(s1)
+            .wrapping_add(choice)
+            .wrapping_add(k)
+            .wrapping_add(w);
+        let s0 = a.rotate_right(2) ^ a.rotate_right(13) ^ a.rotate_right(22);
+        let majority = (a & b) ^ (a & c) ^ (b & c);
+        let t2 = s0.wrapping_add(majority);
+
+        h = g;
+        g = f;
+        f = e;
+        e = d.wrapping_add(t1);
+        d = c;
+        c = b;
+        b = a;
+        a = t1.wrapping_add(t2);
+    }
+
+    for (word, add) in state.iter_mut().zip([a, b, c, d, e, f, g, h]) {
+        *word = word.wrapping_add(add);
+    }
+}
+
+/// Lowercase hexadecimal, as printed by sha256sum
+pub fn to_hex(digest: &Digest) -> String {
+    digest.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+/// Parse a digest written as by `to_hex`, in either case
+pub fn from_hex(hex: &str) -> Option<Digest> {
+    let mut digest = [0u8; 32];
+
+    if hex.len() != digest.len() * 2
+        || !hex.bytes().all(|b| b.is_ascii_hexdigit())
+    {
+        return None;
+    }
+
+    for (byte, pair) in digest.iter_mut().zip(hex.as_bytes().chunks(2)) {
+        let pair = std::str::from_utf8(pair).ok()?;
+        *byte = u8::from_str_radix(pair, 16).ok()?;
+    }
+
+    Some(digest)
+}
+
+/// Digests of a WAV as a whole and of its sample data alone
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WaveDigest {
+    /// Covers only the bytes of sample data present in the data chunk, so it
+    /// is unchanged by edits to the loop or other metadata
+    pub data: Digest,
+
+    /// Covers every byte from the start of the WAV to the end of the stream
+    pub file: Digest,
+}
+
+/// Hash the WAV starting at the stream's current position.  Cursor is left at
+/// the end of the stream.
+pub fn digest_wave<R: Read + Seek>(
+    reader: &mut R,
+) -> Result<WaveDigest, String> {
+    let wave_start = reader.stream_position().map_err(|e| e.to_string())?;
+    let data_range = QWaveReader::new(&mut *reader)?
+        .data_range()
+        .ok_or("No data chunk")?;
+
+    let mut digest_range = |start: u64, len: Option<u64>| {
+        reader
+            .seek(SeekFrom::Start(wave_start + start))
+            .map_err(|e| e.to_string())?;
+
+        let mut hasher = Sha256::new();
+        let mut buffer = vec![0u8; 1 << 16];
+        let mut remaining = len.unwrap_or(u64::MAX);
+
+        while remaining > 0 {
+            let want =
+                buffer.len().min(remaining.try_into().unwrap_or(usize::MAX));
+            let read = reader
+                .read(&mut buffer[..want])
+                .map_err(|e| e.to_string())?;
+
+            if read == 0 {
+                break;
+            }
+
+            hasher.update(&buffer[..read]);
+            remaining -= read as u64;
+        }
+
+        Ok::<_, String>(hasher.finish())
+    };
+
+    let data = digest_range(
+        data_range.start,
+        Some(data_range.end - data_range.start),
+    )?;
+    let file = digest_range(0, None)?;
+
+    Ok(WaveDigest { data, file })
+}
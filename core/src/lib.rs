@@ -1,3 +1,6 @@
+mod error;
+pub use error::*;
+
 mod player;
 pub use player::*;
 
@@ -7,13 +10,44 @@ pub use reader::*;
 mod project;
 pub use project::*;
 
+pub mod dsp;
+
+pub mod hash;
+
+mod compare;
+pub use compare::*;
+
+mod autoloop;
+pub use autoloop::*;
+
+mod waveform;
+pub use waveform::*;
+
+mod template;
+pub use template::*;
+
+mod sidecar;
+pub use sidecar::*;
+
+mod package;
+pub use package::*;
+
+mod pak;
+pub use pak::*;
+
+/// C ABI for reading, looping, blending and writing projects
+#[cfg(feature = "ffi")]
+pub mod ffi;
+
 pub fn setup_player(
     wave_metadata: &Metadata,
     samples: &[i16],
-) -> Result<Player, String> {
+    level: OutputLevel,
+    device: Option<String>,
+) -> Result<Player, Error> {
     let float_samples = samples
         .iter()
-        .map(|&s| s as f32 / i16::MAX as f32)
+        .map(|&s| f32::from(s) / 32768.0)
         .collect::<Vec<_>>();
 
     let loop_start = wave_metadata
@@ -22,11 +56,20 @@ pub fn setup_player(
 
     let end = wave_metadata.end.and_then(|end| end.try_into().ok());
 
+    let play_end = wave_metadata
+        .play_end
+        .and_then(|play_end| play_end.try_into().ok());
+
     let player_config = PlayerConfig {
         samples: float_samples,
         sample_rate: wave_metadata.sample_rate,
         loop_start,
         end,
+        play_end,
+        bounds: PlaybackBounds::Full,
+        level,
+        device,
+        quality: dsp::ResampleQuality::default(),
     };
 
     Player::new(&player_config)
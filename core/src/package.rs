@@ -0,0 +1,334 @@
+use crate::dsp::ResampleQuality;
+use crate::{Metadata, Project, SampleFmt};
+use std::fmt;
+use std::path::{Component, Path, PathBuf};
+
+/// What a Quake port can play, as applied to files packaged for it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TargetProfile {
+    pub name: &'static str,
+
+    /// Rates the port plays as they are, in ascending order
+    pub sample_rates: &'static [u32],
+
+    /// Sample formats the port reads, in order of preference
+    pub formats: &'static [SampleFmt],
+
+    /// Most channels the port plays
+    pub max_channels: u16,
+
+    /// Most bytes of sample data the port comfortably holds in its sound
+    /// cache for one file
+    pub max_data_bytes: u32,
+
+    /// Whether the port loops from the cue point to the end of the file
+    /// whatever the loop length says, in which case files are cut at the
+    /// loop end
+    pub ignores_loop_length: bool,
+
+    /// Directories under the game directory that sounds are loaded from, the
+    /// first being where files found under none of them go
+    pub directories: &'static [&'static str],
+}
+
+/// Ports that files can be packaged for.  Profiles are conservative: they
+/// hold what the port's sound code handles as shipped, not what it might
+/// tolerate.
+pub const TARGET_PROFILES: [TargetProfile; 3] = [
+    TargetProfile {
+        name: "quake1",
+        sample_rates: &[11025, 22050],
+        formats: &[SampleFmt::Unsigned8],
+        max_channels: 1,
+        max_data_bytes: 4 << 20,
+        ignores_loop_length: true,
+        directories: &["sound", "music"],
+    },
+    TargetProfile {
+        name: "quakespasm",
+        sample_rates: &[11025, 22050, 44100, 48000],
+        formats: &[SampleFmt::Signed16, SampleFmt::Unsigned8],
+        max_channels: 1,
+        max_data_bytes: 32 << 20,
+        ignores_loop_length: false,
+        directories: &["sound", "music"],
+    },
+    TargetProfile {
+        name: "fte",
+        sample_rates: &[8000, 11025, 16000, 22050, 32000, 44100, 48000],
+        formats: &[SampleFmt::Signed16, SampleFmt::Unsigned8],
+        max_channels: 2,
+        max_data_bytes: 64 << 20,
+        ignores_loop_length: false,
+        directories: &["sound", "music"],
+    },
+];
+
+/// Check of a file's metadata against a port, failing with what is wrong
+pub type CompatibilityCheck =
+    fn(&TargetProfile, &Metadata) -> Result<(), String>;
+
+/// Every check `TargetProfile::check_compatibility` makes, by name
+pub const COMPATIBILITY_CHECKS: [(&str, CompatibilityCheck); 5] = [
+    ("channels", TargetProfile::check_channels),
+    ("sample format", TargetProfile::check_format),
+    ("sample rate", TargetProfile::check_sample_rate),
+    ("loop", |_, metadata| check_loop(metadata)),
+    ("size", TargetProfile::check_size),
+];
+
+/// Check there is a loop cue and the loop lies within the file, as every port
+/// needs for a file to loop
+pub fn check_loop(metadata: &Metadata) -> Result<(), String> {
+    let Some(loop_start) = metadata.loop_start else {
+        return Err(String::from("No loop"));
+    };
+
+    if let Some(declared) = metadata.loop_end_past_eof {
+        return Err(format!(
+            "Loop runs to sample {}, past the end of the file at {}",
+            declared, metadata.sample_count,
+        ));
+    }
+
+    if loop_start >= metadata.sample_count {
+        return Err(format!(
+            "Loop starts at sample {}, past the end of the file at {}",
+            loop_start, metadata.sample_count,
+        ));
+    }
+
+    Ok(())
+}
+
+/// A change made to a file to fit a target
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Transform {
+    Resampled {
+        from: u32,
+        to: u32,
+    },
+    Reformatted {
+        from: SampleFmt,
+        to: SampleFmt,
+    },
+
+    /// Cut from `from` frames to `to` at the loop end
+    Truncated {
+        from: u32,
+        to: u32,
+    },
+}
+
+impl fmt::Display for Transform {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let bits = |format: &SampleFmt| match format {
+            SampleFmt::Unsigned8 => "8-bit",
+            SampleFmt::Signed16 => "16-bit",
+        };
+
+        match self {
+            Transform::Resampled { from, to } => {
+                write!(f, "resampled {} Hz -> {} Hz", from, to)
+            }
+            Transform::Reformatted { from, to } => {
+                write!(f, "{} -> {}", bits(from), bits(to))?;
+
+                if *to == SampleFmt::Unsigned8 {
+                    write!(f, " with dither")?;
+                }
+
+                Ok(())
+            }
+            Transform::Truncated { from, to } => {
+                write!(f, "truncated {} -> {} samples at loop end", from, to)
+            }
+        }
+    }
+}
+
+impl TargetProfile {
+    pub fn find(name: &str) -> Option<&'static TargetProfile> {
+        TARGET_PROFILES.iter().find(|profile| profile.name == name)
+    }
+
+    /// Run every compatibility check on a file's metadata, returning each
+    /// check's name and result in the order of `COMPATIBILITY_CHECKS`
+    pub fn check_compatibility(
+        &self,
+        metadata: &Metadata,
+    ) -> Vec<(&'static str, Result<(), String>)> {
+        COMPATIBILITY_CHECKS
+            .iter()
+            .map(|(name, check)| (*name, check(self, metadata)))
+            .collect()
+    }
+
+    pub fn check_channels(&self, metadata: &Metadata) -> Result<(), String> {
+        if metadata.channels > self.max_channels {
+            Err(format!(
+                "{} channels; {} plays at most {}",
+                metadata.channels, self.name, self.max_channels,
+            ))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Check the samples are stored as integers the port reads.  Floats are
+    /// refused even though they are converted on read here.
+    pub fn check_format(&self, metadata: &Metadata) -> Result<(), String> {
+        let format = match metadata.bits_per_sample {
+            _ if metadata.float_samples => None,
+            8 => Some(SampleFmt::Unsigned8),
+            16 => Some(SampleFmt::Signed16),
+            _ => None,
+        };
+
+        match format {
+            Some(format) if self.formats.contains(&format) => Ok(()),
+            _ => Err(format!(
+                "{}-bit {} samples; {} reads {}",
+                metadata.bits_per_sample,
+                if metadata.float_samples {
+                    "float"
+                } else {
+                    "integer"
+                },
+                self.name,
+                self.formats
+                    .iter()
+                    .map(|format| match format {
+                        SampleFmt::Unsigned8 => "8-bit",
+                        SampleFmt::Signed16 => "16-bit",
+                    })
+                    .collect::<Vec<_>>()
+                    .join(" or "),
+            )),
+        }
+    }
+
+    pub fn check_sample_rate(&self, metadata: &Metadata) -> Result<(), String> {
+        if self.sample_rates.contains(&metadata.sample_rate) {
+            Ok(())
+        } else {
+            Err(format!(
+                "Sample rate is {} Hz; {} plays {} Hz",
+                metadata.sample_rate,
+                self.name,
+                self.sample_rates
+                    .iter()
+                    .map(u32::to_string)
+                    .collect::<Vec<_>>()
+                    .join(", "),
+            ))
+        }
+    }
+
+    pub fn check_size(&self, metadata: &Metadata) -> Result<(), String> {
+        if metadata.data_bytes > self.max_data_bytes {
+            Err(format!(
+                "{} bytes of sample data; {} holds at most {}",
+                metadata.data_bytes, self.name, self.max_data_bytes,
+            ))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Rate a file at `rate` is packaged at: its own if the port plays it,
+    /// otherwise the lowest above it so nothing audible is lost, or failing
+    /// that the highest
+    pub fn package_rate(&self, rate: u32) -> u32 {
+        if self.sample_rates.contains(&rate) {
+            rate
+        } else {
+            self.sample_rates
+                .iter()
+                .copied()
+                .find(|&allowed| allowed > rate)
+                .or(self.sample_rates.last().copied())
+                .unwrap_or(rate)
+        }
+    }
+
+    /// Format a file in `format` is packaged in: its own if the port reads
+    /// it, otherwise the port's preferred one
+    pub fn package_format(&self, format: SampleFmt) -> SampleFmt {
+        if self.formats.contains(&format) {
+            format
+        } else {
+            self.formats.first().copied().unwrap_or(format)
+        }
+    }
+
+    /// Fit the project to the port, returning what was changed.  Dither
+    /// for narrowing to 8 bits is drawn from `dither_seed`.
+    pub fn apply(
+        &self,
+        proj: &mut Project,
+        dither_seed: u64,
+    ) -> Result<Vec<Transform>, String> {
+        let mut transforms = vec![];
+
+        let rate = self.package_rate(proj.sample_rate());
+        if rate != proj.sample_rate() {
+            transforms.push(Transform::Resampled {
+                from: proj.sample_rate(),
+                to: rate,
+            });
+            proj.resample(rate, ResampleQuality::High)?;
+        }
+
+        let format = self.package_format(proj.render_format());
+        if format != proj.render_format() {
+            transforms.push(Transform::Reformatted {
+                from: proj.render_format(),
+                to: format,
+            });
+            proj.set_render_format(format, dither_seed);
+        }
+
+        if let (true, Some(sample_loop)) =
+            (self.ignores_loop_length, proj.sample_loop())
+        {
+            if sample_loop.end < proj.sample_count() {
+                transforms.push(Transform::Truncated {
+                    from: proj.sample_count(),
+                    to: sample_loop.end,
+                });
+                proj.truncate(sample_loop.end);
+
+                // An end marker left at the end of the file marks nothing
+                if proj.play_end() >= Some(sample_loop.end) {
+                    proj.set_play_end(None);
+                }
+            }
+        }
+
+        proj.validate()?;
+        Ok(transforms)
+    }
+
+    /// Where a file is written under the output directory.  The path from
+    /// the last of the port's directories it lies under is kept, and files
+    /// under none go to the first by name.
+    pub fn package_path(&self, input: &Path) -> Result<PathBuf, String> {
+        let components = input.components().collect::<Vec<_>>();
+        let dir_index = components.iter().rposition(|component| {
+            matches!(component, Component::Normal(name)
+                if self.directories.iter().any(|&dir| name == &dir))
+        });
+
+        match dir_index {
+            Some(index) if index + 1 < components.len() => {
+                Ok(components[index..].iter().collect())
+            }
+            _ => {
+                let name = input.file_name().ok_or("Input is not a file")?;
+                let dir = self.directories.first().copied().unwrap_or("");
+                Ok(Path::new(dir).join(name))
+            }
+        }
+    }
+}
@@ -0,0 +1,300 @@
+use crate::Error;
+use std::ffi::OsString;
+use std::fs::{self, File};
+use std::io::{BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+
+const PAK_MAGIC: &[u8; 4] = b"PACK";
+
+// Bytes of the header: magic, then the directory's offset and length
+const HEADER_LEN: u32 = 12;
+
+// Bytes of each directory entry: a NUL-terminated name, offset, and length
+const ENTRY_LEN: u32 = 64;
+
+/// Bytes an entry name may hold, leaving room for its terminating NUL
+pub const MAX_PAK_NAME_LEN: usize = 55;
+
+/// A file held in a pak archive, as its directory lists it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PakEntry {
+    /// Path within the archive, e.g. "sound/ambience/wind2.wav"
+    pub name: String,
+
+    /// Where the file's bytes start in the archive
+    pub offset: u32,
+    pub length: u32,
+}
+
+/// Reads the files out of a Quake pak archive.  The directory is read and
+/// checked against the archive's length when opened, so entries can be read
+/// without further checks.
+pub struct PakReader<R: Read + Seek> {
+    reader: R,
+    entries: Vec<PakEntry>,
+}
+
+impl PakReader<BufReader<File>> {
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, Error> {
+        let path = path.as_ref();
+
+        File::open(path)
+            .map_err(Error::from)
+            .and_then(|file| PakReader::new(BufReader::new(file)))
+            .map_err(|e| e.at_path(path))
+    }
+}
+
+impl<R: Read + Seek> PakReader<R> {
+    pub fn new(mut reader: R) -> Result<Self, Error> {
+        let archive_len = reader.seek(SeekFrom::End(0))?;
+        reader.seek(SeekFrom::Start(0))?;
+
+        let mut header = [0u8; HEADER_LEN as usize];
+        reader.read_exact(&mut header).map_err(|_| {
+            Error::PakDecode(String::from("Too short for a pak header"))
+        })?;
+
+        if &header[..4] != PAK_MAGIC {
+            return Err(Error::PakDecode(String::from("Not a pak archive")));
+        }
+
+        let dir_offset = read_u32(&header[4..8], "Directory offset")?;
+        let dir_len = read_u32(&header[8..12], "Directory length")?;
+
+        if dir_len % ENTRY_LEN != 0 {
+            return Err(Error::PakDecode(format!(
+                "Directory length {} is not a whole number of entries",
+                dir_len,
+            )));
+        }
+
+        check_extent(dir_offset, dir_len, archive_len, "Directory")?;
+
+        let mut directory = vec![0u8; dir_len as usize];
+        reader.seek(SeekFrom::Start(u64::from(dir_offset)))?;
+        reader.read_exact(&mut directory)?;
+
+        let entries = directory
+            .chunks_exact(ENTRY_LEN as usize)
+            .map(|entry| {
+                let name = read_name(&entry[..56])?;
+                let offset = read_u32(&entry[56..60], "Entry offset")?;
+                let length = read_u32(&entry[60..64], "Entry length")?;
+                check_extent(offset, length, archive_len, &name)?;
+
+                Ok(PakEntry {
+                    name,
+                    offset,
+                    length,
+                })
+            })
+            .collect::<Result<Vec<_>, Error>>()?;
+
+        Ok(PakReader { reader, entries })
+    }
+
+    /// Entries in directory order
+    pub fn entries(&self) -> &[PakEntry] {
+        &self.entries
+    }
+
+    /// Read the bytes of the entry of the given name.  Names are matched
+    /// exactly, and the first entry of a name wins, as Quake finds files.
+    pub fn read_entry(&mut self, name: &str) -> Result<Vec<u8>, Error> {
+        let entry = self
+            .entries
+            .iter()
+            .find(|entry| entry.name == name)
+            .cloned()
+            .ok_or_else(|| {
+                Error::InvalidArgument(format!("No entry \"{}\" in pak", name))
+            })?;
+
+        self.read_at(&entry)
+    }
+
+    // Bytes of an entry, found by its offset and length rather than its name
+    fn read_at(&mut self, entry: &PakEntry) -> Result<Vec<u8>, Error> {
+        let mut bytes = vec![0u8; entry.length as usize];
+        self.reader.seek(SeekFrom::Start(u64::from(entry.offset)))?;
+        self.reader.read_exact(&mut bytes)?;
+        Ok(bytes)
+    }
+}
+
+/// Writes a pak archive: every file's bytes in order after the header, then
+/// the directory
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PakWriter {
+    files: Vec<(String, Vec<u8>)>,
+}
+
+impl PakWriter {
+    pub fn new() -> Self {
+        PakWriter::default()
+    }
+
+    /// Hold every entry of an archive, in order, to write it again with
+    /// some replaced
+    pub fn from_reader<R: Read + Seek>(
+        reader: &mut PakReader<R>,
+    ) -> Result<Self, Error> {
+        let entries = reader.entries().to_vec();
+        let mut writer = PakWriter::new();
+
+        for entry in entries {
+            // Later entries of a name are unreachable, but kept as they were
+            let bytes = reader.read_at(&entry)?;
+            writer.files.push((entry.name, bytes));
+        }
+
+        Ok(writer)
+    }
+
+    /// Replace the bytes of the entry of the given name, or add it at the end
+    /// if there is none
+    pub fn replace(&mut self, name: &str, bytes: Vec<u8>) -> Result<(), Error> {
+        if name.is_empty() || name.len() > MAX_PAK_NAME_LEN {
+            return Err(Error::InvalidArgument(format!(
+                "Pak entry names must be 1 to {} bytes long",
+                MAX_PAK_NAME_LEN,
+            )));
+        }
+
+        if name.contains('\0') {
+            return Err(Error::InvalidArgument(String::from(
+                "Pak entry names can't hold NUL",
+            )));
+        }
+
+        match self.files.iter_mut().find(|(file, _)| file == name) {
+            Some((_, old)) => *old = bytes,
+            None => self.files.push((String::from(name), bytes)),
+        }
+
+        Ok(())
+    }
+
+    pub fn write<W: Write>(&self, mut writer: W) -> Result<(), Error> {
+        let too_large =
+            || Error::Unsupported(String::from("Pak archive too large"));
+        let dir_len = u32::try_from(self.files.len())
+            .ok()
+            .and_then(|count| count.checked_mul(ENTRY_LEN))
+            .ok_or_else(too_large)?;
+
+        let mut directory = Vec::with_capacity(dir_len as usize);
+        let mut offset = HEADER_LEN;
+
+        for (name, bytes) in &self.files {
+            let length = u32::try_from(bytes.len()).map_err(|_| too_large())?;
+            let mut name_field = [0u8; 56];
+            name_field[..name.len()].copy_from_slice(name.as_bytes());
+
+            directory.extend_from_slice(&name_field);
+            directory.extend_from_slice(&offset.to_le_bytes());
+            directory.extend_from_slice(&length.to_le_bytes());
+            offset = offset
+                .checked_add(length)
+                .filter(|&end| i32::try_from(end).is_ok())
+                .ok_or_else(too_large)?;
+        }
+
+        offset
+            .checked_add(dir_len)
+            .filter(|&end| i32::try_from(end).is_ok())
+            .ok_or_else(too_large)?;
+
+        writer.write_all(PAK_MAGIC)?;
+        writer.write_all(&offset.to_le_bytes())?;
+        writer.write_all(&dir_len.to_le_bytes())?;
+
+        for (_, bytes) in &self.files {
+            writer.write_all(bytes)?;
+        }
+
+        writer.write_all(&directory)?;
+        writer.flush()?;
+        Ok(())
+    }
+
+    /// Write the archive to a file.  As with `Project::write_to_with`, an
+    /// existing file, which may be the one the entries were read from, is
+    /// only replaced once the new one is complete.  With `sync`, the file is
+    /// flushed to disk first.
+    pub fn write_to(
+        &self,
+        outpath: &impl AsRef<Path>,
+        sync: bool,
+    ) -> Result<(), Error> {
+        let outpath = outpath.as_ref();
+        let mut name = OsString::from(".");
+        name.push(outpath.file_name().unwrap_or_default());
+        name.push(".tmp");
+        let temp = outpath.with_file_name(name);
+
+        let written =
+            File::create(&temp).map_err(Error::from).and_then(|file| {
+                let mut writer = BufWriter::new(file);
+                self.write(&mut writer)?;
+                let file = writer.into_inner().map_err(|e| e.into_error())?;
+
+                if sync {
+                    file.sync_all()?;
+                }
+
+                Ok(fs::rename(&temp, outpath)?)
+            });
+
+        if written.is_err() {
+            let _ = fs::remove_file(&temp);
+        }
+
+        written.map_err(|e| e.at_path(outpath))
+    }
+}
+
+// Little-endian field, which pak archives store signed but never negative
+fn read_u32(bytes: &[u8], what: &str) -> Result<u32, Error> {
+    let value = i32::from_le_bytes(bytes.try_into().unwrap());
+
+    u32::try_from(value).map_err(|_| {
+        Error::PakDecode(format!("{} {} is negative", what, value))
+    })
+}
+
+fn read_name(field: &[u8]) -> Result<String, Error> {
+    let len = field.iter().position(|&byte| byte == 0).ok_or_else(|| {
+        Error::PakDecode(format!(
+            "Entry name \"{}\" is not terminated",
+            String::from_utf8_lossy(field),
+        ))
+    })?;
+
+    String::from_utf8(field[..len].to_vec()).map_err(|_| {
+        Error::PakDecode(format!(
+            "Entry name \"{}\" is not UTF-8",
+            String::from_utf8_lossy(&field[..len]),
+        ))
+    })
+}
+
+// Check `length` bytes from `offset` lie within the archive
+fn check_extent(
+    offset: u32,
+    length: u32,
+    archive_len: u64,
+    what: &str,
+) -> Result<(), Error> {
+    if u64::from(offset) + u64::from(length) > archive_len {
+        Err(Error::PakDecode(format!(
+            "{} at {}..{} lies past the end of the archive",
+            what,
+            offset,
+            u64::from(offset) + u64::from(length),
+        )))
+    } else {
+        Ok(())
+    }
+}
@@ -1,19 +1,171 @@
 use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
-use cpal::{Sample, SampleFormat, SampleRate, SupportedStreamConfig};
-use rubato::{
-    Resampler, SincFixedIn, SincInterpolationParameters, SincInterpolationType,
-    WindowFunction,
+use cpal::{
+    FromSample, Sample, SampleFormat, SampleRate, SizedSample,
+    SupportedStreamConfig,
 };
 
+use crate::dsp::{resample_blocks, resampled_len, ResampleQuality};
+use crate::{Error, Project, QWaveReader};
+use std::fmt;
+use std::io::{Read, Seek};
+use std::ops::Range;
 use std::sync::{
-    atomic::{AtomicUsize, Ordering},
-    Arc,
+    atomic::{fence, AtomicBool, AtomicU32, AtomicU64, AtomicUsize, Ordering},
+    mpsc::{self, Receiver, Sender},
+    Arc, Mutex,
 };
+use std::thread;
+use std::time::{Duration, Instant};
 
 const CD_SAMPLE_RATE: u32 = 44100;
 const DVD_SAMPLE_RATE: u32 = 48000;
 const DVD_DIVISOR: u32 = 8000;
 const NO_OUTPUT: &str = "No output device found";
+const HISTORY_LEN: usize = 64;
+
+// Input samples resampled at a time by the background resampler
+const RESAMPLE_BLOCK: usize = 16384;
+
+// How often waiting for the background resampler checks on it
+const RESAMPLE_POLL: Duration = Duration::from_millis(5);
+
+// Output sample formats played, most preferred first; the callback works in
+// f32 and converts for the others
+const OUTPUT_FORMATS: [SampleFormat; 3] =
+    [SampleFormat::F32, SampleFormat::I16, SampleFormat::U16];
+
+// Rates listed for output devices when they support them
+const LISTED_RATES: [u32; 9] =
+    [8000, 11025, 16000, 22050, 32000, 44100, 48000, 88200, 96000];
+
+// One in this many soak callbacks restarts the stream
+const SOAK_RESTART_ODDS: usize = 8;
+
+// Level above which soft clipping starts bending samples toward full scale
+const SOFT_CLIP_KNEE: f32 = 0.9;
+
+/// Slowest and fastest playback speeds, as ratios to the original
+pub const MIN_SPEED: f64 = 0.125;
+pub const MAX_SPEED: f64 = 8.0;
+
+/// Greatest gain `Player::set_volume` takes
+pub const MAX_VOLUME: f32 = 2.0;
+
+/// Where non-looped playback stops
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PlaybackBounds {
+    /// Play through to the end of the samples, or to the configured
+    /// `play_end`, past any loop's end
+    #[default]
+    Full,
+
+    /// Stop at the end marker, as the engine does
+    ToEndMarker,
+}
+
+/// How resampled samples beyond full scale are brought back within it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ClipMode {
+    /// Clamp to full scale
+    #[default]
+    Hard,
+
+    /// Leave samples below 90% of full scale alone and round off those above
+    /// on a tanh curve, which crackles less than clamping
+    Soft,
+}
+
+/// Level adjustments applied around resampling, which can overshoot full
+/// scale near loud transients
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct OutputLevel {
+    /// Attenuation in dB applied before resampling
+    pub headroom_db: f32,
+    pub clip: ClipMode,
+}
+
+/// A range of samples played some number of times, as one step of a
+/// playback plan
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PlaySegment {
+    pub range: Range<usize>,
+
+    /// Times to play the range before moving on; 0 repeats it forever
+    pub repeats: u32,
+}
+
+impl PlaySegment {
+    pub fn is_infinite(&self) -> bool {
+        self.repeats == 0
+    }
+}
+
+/// Transport of a player at a moment, as given by `Player::snapshot`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct TransportState {
+    /// Playhead in input-rate samples
+    pub position: usize,
+    pub looped: bool,
+    pub playing: bool,
+}
+
+impl TransportState {
+    /// Parse the comma-separated settings written by `Display`, e.g.
+    /// "position=4410,looped=true,playing=false", to carry a snapshot from one
+    /// session to the next.  Settings left out keep their defaults.
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut transport = TransportState::default();
+
+        for setting in text.split(',').map(str::trim) {
+            let (key, value) = setting.split_once('=').ok_or_else(|| {
+                format!("Expected key=value in transport, got \"{}\"", setting)
+            })?;
+            let value = value.trim();
+            let flag = || {
+                value.parse::<bool>().map_err(|_| {
+                    format!("Expected true or false for \"{}\"", key)
+                })
+            };
+
+            match key.trim() {
+                "position" => {
+                    transport.position = value
+                        .parse::<usize>()
+                        .map_err(|e| format!("Bad position: {}", e))?;
+                }
+                "looped" => transport.looped = flag()?,
+                "playing" => transport.playing = flag()?,
+                other => {
+                    return Err(format!("Unknown transport key \"{}\"", other))
+                }
+            }
+        }
+
+        Ok(transport)
+    }
+}
+
+impl fmt::Display for TransportState {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "position={},looped={},playing={}",
+            self.position, self.looped, self.playing,
+        )
+    }
+}
+
+/// An output device, as listed by `Player::devices`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeviceInfo {
+    pub name: String,
+
+    /// Common rates the device plays samples at in a format the player
+    /// supports, ascending
+    pub sample_rates: Vec<u32>,
+
+    pub is_default: bool,
+}
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct PlayerConfig {
@@ -21,154 +173,751 @@ pub struct PlayerConfig {
     pub sample_rate: u32,
     pub loop_start: Option<usize>,
     pub end: Option<usize>,
+
+    /// Where full, non-looped playback stops; the end of the samples if unset
+    pub play_end: Option<usize>,
+    pub bounds: PlaybackBounds,
+    pub level: OutputLevel,
+
+    /// Name of the output device to play on; the default device if unset
+    pub device: Option<String>,
+
+    /// Resampling to the device's rate; loop points and the playhead land on
+    /// the same samples at any quality
+    pub quality: ResampleQuality,
 }
 
 #[derive(Debug)]
 pub struct Player {
-    samples: Arc<Vec<f32>>,
+    // Resampled for the device in the background, ahead of the playhead
+    samples: Arc<SampleBuffer>,
     playback_rate: u32,
-    loop_start: usize,
-    end: usize,
+
+    // Device named in the config, reopened whenever a stream is started
+    device: Option<String>,
+
+    // Loop start and end in playback-rate samples
+    playback_loop_start: usize,
+    playback_end: usize,
+    play_end: usize,
+    bounds: PlaybackBounds,
     state: PlayerState,
     playhead: Arc<AtomicUsize>,
+    history: Arc<PlayheadHistory>,
+
+    // Bumped whenever a stream is retired; callbacks built for an earlier
+    // generation leave the playhead and history alone
+    generation: Arc<AtomicUsize>,
+
+    // Plan being played in playback-rate samples, and how far through it the
+    // callback has got; custom when set by play_plan rather than play
+    plan: Arc<Vec<PlaySegment>>,
+    cursor: Arc<PlanCursor>,
+    custom_plan: bool,
     input_rate: u32,
+    input_loop_start: Option<usize>,
+    input_end: usize,
+    input_duration: usize,
+
+    // First error reported by the current stream, until taken, and whether
+    // an error taken was the device going away
+    stream_error: Arc<Mutex<Option<StreamFault>>>,
+    disconnected: bool,
+
+    // Samples as configured, with headroom applied, for resampling to the
+    // rate of a device reopened; and the clipping applied after
+    input_samples: Arc<Vec<f32>>,
+    input_play_end: usize,
+    clip: ClipMode,
+    quality: ResampleQuality,
+}
+
+impl PlayerConfig {
+    /// Config playing a project's samples as they stand, with the loop and
+    /// end marker it has now, whether or not they have been written.  Other
+    /// channels than the first are mixed into it.  Bounds, level, device and
+    /// quality are left at their defaults.
+    pub fn from_project(project: &Project) -> Self {
+        let channels = usize::from(project.channels()).max(1);
+        let samples = project
+            .samples()
+            .chunks_exact(channels)
+            .map(|frame| {
+                frame.iter().map(|&s| f32::from(s) / 32768.0).sum::<f32>()
+                    / channels as f32
+            })
+            .collect();
+        let sample_loop = project.sample_loop();
+
+        PlayerConfig {
+            samples,
+            sample_rate: project.sample_rate(),
+            loop_start: sample_loop.as_ref().map(|l| l.start as usize),
+            end: sample_loop.map(|l| l.end as usize),
+            play_end: project.play_end().map(|play_end| play_end as usize),
+            bounds: PlaybackBounds::Full,
+            level: OutputLevel::default(),
+            device: None,
+            quality: ResampleQuality::default(),
+        }
+    }
 }
 
 impl Player {
-    pub fn new(config: &PlayerConfig) -> Result<Self, String> {
+    /// Player for a project's samples as they stand; see
+    /// `PlayerConfig::from_project`
+    pub fn from_project(project: &Project) -> Result<Self, Error> {
+        Player::new(&PlayerConfig::from_project(project))
+    }
+
+    /// Player for the samples, loop and end marker read by `reader`
+    pub fn from_reader<R: Read + Seek>(
+        reader: QWaveReader<R>,
+    ) -> Result<Self, Error> {
+        Player::from_project(&Project::from_reader(reader)?)
+    }
+
+    pub fn new(config: &PlayerConfig) -> Result<Self, Error> {
+        let device = output_device(config.device.as_deref())?;
+        let stream_config = stream_config(&device, config.sample_rate)?;
+        Player::with_playback_rate(config, stream_config.sample_rate().0)
+    }
+
+    /// Player resampling to `playback_rate` rather than to a rate the chosen
+    /// output device supports.  No device is needed until playback starts,
+    /// which fails if the device can't run at `playback_rate`.
+    pub fn with_playback_rate(
+        config: &PlayerConfig,
+        playback_rate: u32,
+    ) -> Result<Self, Error> {
         let loop_start = config.loop_start.unwrap_or(0);
         let end = config.end.unwrap_or(config.samples.len());
 
-        if config.sample_rate == 0 {
-            return Err(String::from("Sample rate must be non-zero"));
+        if config.sample_rate == 0 || playback_rate == 0 {
+            return Err(Error::InvalidArgument(String::from(
+                "Sample rate must be non-zero",
+            )));
+        }
+
+        if config.samples.is_empty() {
+            return Err(Error::InvalidArgument(String::from(
+                "No audio samples",
+            )));
         }
 
         if loop_start >= config.samples.len() {
-            return Err(String::from("Loop start beyond input buffer"));
+            return Err(Error::InvalidLoop(String::from(
+                "Loop start beyond input buffer",
+            )));
         }
 
         if end > config.samples.len() {
-            return Err(String::from("End beyond input buffer"));
+            return Err(Error::InvalidLoop(String::from(
+                "End beyond input buffer",
+            )));
         }
 
-        let device = cpal::default_host()
-            .default_output_device()
-            .ok_or(NO_OUTPUT)?;
+        let play_end = config.play_end.unwrap_or(config.samples.len());
 
-        let stream_config = stream_config(&device, config.sample_rate)?;
-        let playback_rate = stream_config.sample_rate().0;
+        if play_end > config.samples.len() {
+            return Err(Error::InvalidLoop(String::from(
+                "End marker beyond input buffer",
+            )));
+        }
+
+        let headroom_db = config.level.headroom_db;
 
-        let mut playback_samples =
-            resample(config.sample_rate, playback_rate, &config.samples);
+        if !headroom_db.is_finite() || headroom_db < 0.0 {
+            return Err(Error::InvalidArgument(String::from(
+                "Headroom must be a non-negative dB value",
+            )));
+        }
+
+        let gain = 10f32.powf(-headroom_db / 20.0);
+        let attenuated =
+            config.samples.iter().map(|&s| s * gain).collect::<Vec<_>>();
+        let playback_len = resampled_len(
+            config.samples.len(),
+            config.sample_rate,
+            playback_rate,
+        );
 
         let end = scale_index(config.sample_rate, playback_rate, end)
-            .ok_or("Scaled end too large")?
-            .min(playback_samples.len());
+            .ok_or_else(|| too_large("end"))?
+            .min(playback_len);
 
-        playback_samples.truncate(end);
+        let play_end = scale_index(config.sample_rate, playback_rate, play_end)
+            .ok_or_else(|| too_large("end marker"))?
+            .min(playback_len);
 
         let loop_start =
             scale_index(config.sample_rate, playback_rate, loop_start)
-                .ok_or("Scaled loop start too large")
+                .ok_or_else(|| too_large("loop start"))
                 .and_then(|start| {
                     if start < end {
                         Ok(start)
                     } else {
-                        Err("Loop start is AT or AFTER end")
+                        Err(Error::InvalidLoop(String::from(
+                            "Loop start is AT or AFTER end",
+                        )))
                     }
                 })?;
 
+        let input_samples = Arc::new(attenuated);
+        let stream_error = Arc::new(Mutex::new(None));
+        let samples = start_resampling(
+            &input_samples,
+            config.sample_rate,
+            playback_rate,
+            config.level.clip,
+            config.quality,
+            &stream_error,
+        )?;
+
         Ok(Player {
-            samples: Arc::new(playback_samples),
+            samples,
             playback_rate,
-            loop_start,
-            end,
+            device: config.device.clone(),
+            playback_loop_start: loop_start,
+            playback_end: end,
+            play_end,
+            bounds: config.bounds,
             state: PlayerState::Stopped,
             playhead: Arc::new(AtomicUsize::new(0)),
+            history: Arc::new(PlayheadHistory::new()),
+            generation: Arc::new(AtomicUsize::new(0)),
+            plan: Arc::new(vec![]),
+            cursor: Arc::new(PlanCursor::default()),
+            custom_plan: false,
             input_rate: config.sample_rate,
+            input_loop_start: config.loop_start,
+            input_end: config.end.unwrap_or(config.samples.len()),
+            input_duration: config.samples.len(),
+            stream_error,
+            disconnected: false,
+            input_samples,
+            input_play_end: config.play_end.unwrap_or(config.samples.len()),
+            clip: config.level.clip,
+            quality: config.quality,
         })
     }
 
+    /// Start playback at input-rate sample `play_from`.  Looped playback from
+    /// at or past the end begins at the same point in the loop it would have
+    /// reached by wrapping; non-looped playback from at or past where it would
+    /// stop is an error.
     pub fn play(
         &mut self,
         play_from: usize,
         looped: bool,
-    ) -> Result<(), String> {
+    ) -> Result<(), Error> {
+        let play_from = self.playback_position(play_from, looped)?;
+        self.play_from_playback_position(play_from, looped)
+    }
+
+    /// As `play`, starting `seconds` into the input, rounded to the nearest
+    /// sample
+    pub fn play_from_seconds(
+        &mut self,
+        seconds: f64,
+        looped: bool,
+    ) -> Result<(), Error> {
+        let play_from = seconds_to_samples(seconds, self.input_rate)
+            .ok_or_else(|| {
+                Error::InvalidArgument(String::from("Bad play time"))
+            })?;
+        self.play(play_from, looped)
+    }
+
+    // Playback-rate position to start from for input-rate `play_from`, as
+    // described for `play`
+    fn playback_position(
+        &self,
+        play_from: usize,
+        looped: bool,
+    ) -> Result<usize, Error> {
         let play_from =
             scale_index(self.input_rate, self.playback_rate, play_from)
-                .ok_or("Bad playhead position")?;
+                .ok_or_else(|| {
+                    Error::InvalidArgument(String::from(
+                        "Bad playhead position",
+                    ))
+                })?;
 
-        self.play_from_playback_position(play_from, looped)
+        if !looped && play_from >= self.stop_position(false) {
+            Err(Error::InvalidArgument(String::from(
+                "Play position is past the end",
+            )))
+        } else {
+            Ok(self.wrap_position(play_from, looped))
+        }
     }
 
-    fn play_from_playback_position(
+    // Playback-rate `play_from` brought into where playback can start: looped,
+    // a position at or past the end is wrapped into the loop, and otherwise
+    // one past where playback stops is held there
+    fn wrap_position(&self, play_from: usize, looped: bool) -> usize {
+        if looped && play_from >= self.playback_end {
+            let loop_len = self.playback_end - self.playback_loop_start;
+            (play_from - self.playback_loop_start) % loop_len
+                + self.playback_loop_start
+        } else {
+            play_from.min(self.stop_position(looped))
+        }
+    }
+
+    /// Where playback is and whether it is running, to hand to `restore` on
+    /// this or another player of the same samples.  The position is in
+    /// input-rate samples, so it carries over when the playback rate differs.
+    pub fn snapshot(&self) -> TransportState {
+        TransportState {
+            position: self.playhead(),
+            looped: self.is_looping(),
+            playing: matches!(
+                self.state(),
+                PlayerStateTag::Playing | PlayerStateTag::PlayingLooped
+            ),
+        }
+    }
+
+    /// Return to the position of a snapshot, playing if it was playing and
+    /// paused there otherwise.  Playback plans aren't restored; looped
+    /// snapshots resume plain looped playback.
+    pub fn restore(&mut self, transport: &TransportState) -> Result<(), Error> {
+        if transport.playing {
+            return self.play(transport.position, transport.looped);
+        }
+
+        let playhead =
+            self.playback_position(transport.position, transport.looped)?;
+
+        self.retire_stream();
+        self.playhead.store(playhead, Ordering::Relaxed);
+        self.custom_plan = false;
+        self.state = PlayerState::Paused(PlaybackState {
+            looped: transport.looped,
+            playhead,
+        });
+
+        Ok(())
+    }
+
+    /// Play each segment of `plan` in turn, given in input-rate samples,
+    /// repeating each as many times as it asks.  Playback stops after the last
+    /// segment unless one of them repeats forever, in which case the segments
+    /// after it are never reached.
+    pub fn play_plan(&mut self, plan: &[PlaySegment]) -> Result<(), Error> {
+        check_plan(plan, self.input_duration)?;
+
+        let plan = plan
+            .iter()
+            .map(|segment| {
+                let scale = |index| {
+                    scale_index(self.input_rate, self.playback_rate, index)
+                        .map(|index| index.min(self.samples.len()))
+                        .ok_or_else(|| too_large("segment"))
+                };
+
+                Ok(PlaySegment {
+                    range: scale(segment.range.start)?
+                        ..scale(segment.range.end)?,
+                    repeats: segment.repeats,
+                })
+            })
+            .collect::<Result<Vec<_>, Error>>()?;
+
+        check_plan(&plan, self.samples.len())?;
+        let looped = plan.iter().any(PlaySegment::is_infinite);
+        let play_from = plan[0].range.start;
+
+        self.start_plan(plan, PlanPosition::default(), play_from, looped)?;
+        self.custom_plan = true;
+        Ok(())
+    }
+
+    /// Play from input-rate sample `play_from` through the loop `loop_count`
+    /// times in all, as `unroll` bakes it, and then on past the loop end to
+    /// the end marker or the end of the file.  Starting inside the loop, the
+    /// rest of that pass counts as the first; starting at or past the loop
+    /// end begins at the same point in the loop that wrapping would reach.
+    /// Played as a plan, so `set_loop` leaves it alone.
+    pub fn play_n_loops(
         &mut self,
         play_from: usize,
+        loop_count: u32,
+    ) -> Result<(), Error> {
+        if loop_count == 0 {
+            return Err(Error::InvalidArgument(String::from(
+                "Loop count must be non-zero",
+            )));
+        }
+
+        let play_from = self.playback_position(play_from, true)?;
+        let mut plan = vec![PlaySegment {
+            range: play_from..self.playback_end,
+            repeats: 1,
+        }];
+
+        if loop_count > 1 {
+            plan.push(PlaySegment {
+                range: self.playback_loop_start..self.playback_end,
+                repeats: loop_count - 1,
+            });
+        }
+
+        if self.play_end > self.playback_end {
+            plan.push(PlaySegment {
+                range: self.playback_end..self.play_end,
+                repeats: 1,
+            });
+        }
+
+        self.start_plan(plan, PlanPosition::default(), play_from, false)?;
+        self.custom_plan = true;
+        Ok(())
+    }
+
+    /// Render what playing from the start would send to a mono output
+    /// device, at the playback rate and speed, without one.  Playback plans
+    /// aren't rendered; looped, the loop repeats until `frames` are filled.
+    pub fn render_offline(
+        &self,
+        frames: usize,
         looped: bool,
-    ) -> Result<(), String> {
-        match self.state {
-            PlayerState::PlayingLooped(_) | PlayerState::Playing(_) => {
-                self.stop();
-            }
-            _ => {}
+    ) -> Result<Vec<f32>, Error> {
+        let options = RenderOptions {
+            speed: self.speed(),
+            volume: self.volume(),
+            ..RenderOptions::default()
         };
 
+        self.wait_for_resampling();
+
+        render_plan_with(
+            &self.samples.to_vec(),
+            &self.playback_plan(0, looped),
+            frames,
+            &options,
+        )
+    }
+
+    fn play_from_playback_position(
+        &mut self,
+        play_from: usize,
+        looped: bool,
+    ) -> Result<(), Error> {
+        let play_from = self.wrap_position(play_from, looped);
+        let plan = self.playback_plan(play_from, looped);
+        self.start_plan(plan, PlanPosition::default(), play_from, looped)?;
+        self.custom_plan = false;
+        Ok(())
+    }
+
+    // Plan played by play from playback-rate `play_from`: up to where
+    // playback stops, then with looping the loop forever
+    fn playback_plan(
+        &self,
+        play_from: usize,
+        looped: bool,
+    ) -> Vec<PlaySegment> {
+        let end = self.stop_position(looped);
+        let mut plan = vec![];
+
+        if play_from < end {
+            plan.push(PlaySegment {
+                range: play_from..end,
+                repeats: 1,
+            });
+        }
+
+        if looped {
+            plan.push(PlaySegment {
+                range: self.playback_loop_start..end,
+                repeats: 0,
+            });
+        }
+
+        plan
+    }
+
+    fn start_plan(
+        &mut self,
+        plan: Vec<PlaySegment>,
+        position: PlanPosition,
+        play_from: usize,
+        looped: bool,
+    ) -> Result<(), Error> {
+        // The old stream is gone, and its callback can no longer touch the
+        // playhead, before the new position is stored
+        self.retire_stream();
+        self.cursor.seek.take();
+        self.cursor.take_plan();
+        self.take_stream_error();
+        self.plan = Arc::new(plan);
+        self.cursor.store(position);
         self.playhead.store(play_from, Ordering::Relaxed);
 
-        let device = cpal::default_host()
-            .default_output_device()
-            .ok_or(NO_OUTPUT)?;
+        let device = output_device(self.device.as_deref())?;
 
         // It's clunky to have to call this twice, but easier than
         // maintaining device and stream config in the struct
         let stream_config = stream_config(&device, self.playback_rate)?;
 
         if stream_config.sample_rate().0 != self.playback_rate {
-            return Err(format!(
+            return Err(Error::Audio(format!(
                 "Failed to acquire stream config @ {}Hz",
                 self.playback_rate
-            ));
+            )));
         }
 
-        let loop_start = if looped { Some(self.loop_start) } else { None };
-        let channels = stream_config.channels();
-
-        let stream = Box::new(
-            device
-                .build_output_stream(
-                    &stream_config.into(),
-                    stream_callback(
-                        Arc::clone(&self.samples),
-                        Arc::clone(&self.playhead),
-                        loop_start,
-                        self.end,
-                        channels,
-                    ),
-                    move |_| {},
-                    None,
-                )
-                .map_err(|e| e.to_string())?,
-        );
+        let stream = match stream_config.sample_format() {
+            SampleFormat::I16 => {
+                self.build_stream::<i16>(&device, stream_config)
+            }
+            SampleFormat::U16 => {
+                self.build_stream::<u16>(&device, stream_config)
+            }
+            _ => self.build_stream::<f32>(&device, stream_config),
+        }?;
 
-        stream.play().map_err(|e| e.to_string())?;
+        stream.play().map_err(|e| Error::Audio(e.to_string()))?;
 
         self.state = if looped {
             PlayerState::PlayingLooped(stream)
         } else {
             PlayerState::Playing(stream)
         };
+        self.disconnected = false;
 
         Ok(())
     }
 
-    pub fn stop(&mut self) {
-        // Stream gets dropped if state was previously Playing or PlayingLooped
-        self.state = PlayerState::Stopped;
+    // Output stream of `S` samples playing the plan from the playhead
+    fn build_stream<S>(
+        &self,
+        device: &cpal::Device,
+        stream_config: SupportedStreamConfig,
+    ) -> Result<Box<dyn StreamTrait>, Error>
+    where
+        S: SizedSample + FromSample<f32> + 'static,
+    {
+        let channels = stream_config.channels();
+        let stream = device
+            .build_output_stream(
+                &stream_config.into(),
+                converting_callback::<S, _>(stream_callback(
+                    Arc::clone(&self.samples),
+                    Arc::clone(&self.playhead),
+                    Arc::clone(&self.history),
+                    StreamToken::current(&self.generation),
+                    Arc::clone(&self.plan),
+                    Arc::clone(&self.cursor),
+                    channels,
+                )),
+                stream_error_callback(
+                    Arc::clone(&self.stream_error),
+                    StreamToken::current(&self.generation),
+                ),
+                None,
+            )
+            .map_err(|e| Error::Audio(e.to_string()))?;
+
+        Ok(Box::new(stream))
+    }
+
+    /// Move the playhead to input-rate sample `position` without stopping.
+    /// A playing stream picks it up at its next buffer; paused or stopped,
+    /// playback resumes from there.  Positions past the end wrap into the
+    /// loop when looping, and otherwise clamp to where playback stops.
+    /// Playback plans are searched for the first segment holding the
+    /// position, which is an error if there is none.
+    pub fn seek(&mut self, position: usize) -> Result<(), Error> {
+        self.settle();
+
+        let looped = self.is_looping();
+        let position =
+            scale_index(self.input_rate, self.playback_rate, position)
+                .ok_or_else(|| {
+                    Error::InvalidArgument(String::from(
+                        "Bad playhead position",
+                    ))
+                })?;
+        let stop = self.stop_position(looped);
+        let position = if looped && position >= stop {
+            let loop_len = stop - self.playback_loop_start;
+            (position - self.playback_loop_start) % loop_len
+                + self.playback_loop_start
+        } else {
+            position.min(stop)
+        };
+
+        // Plans built by play have a segment up to the stop position, then
+        // with looping one over the loop, which an earlier position plays
+        // into as well.  Replaying a plan, the segment is played from the
+        // position to its end and the rest of the plan follows as before.
+        let segment = if self.custom_plan {
+            self.plan
+                .iter()
+                .position(|segment| segment.range.contains(&position))
+                .ok_or_else(|| {
+                    Error::InvalidArgument(String::from(
+                        "Position lies outside the playback plan",
+                    ))
+                })?
+        } else if looped && position >= self.playback_loop_start {
+            self.plan.len().saturating_sub(1)
+        } else {
+            0
+        };
+
+        match self.state {
+            PlayerState::Playing(_) | PlayerState::PlayingLooped(_) => {
+                self.cursor.seek.request(position, segment);
+            }
+            PlayerState::Paused(PlaybackState { looped, .. }) => {
+                self.cursor.store(PlanPosition { segment, pass: 0 });
+                self.state = PlayerState::Paused(PlaybackState {
+                    looped,
+                    playhead: position,
+                });
+            }
+            PlayerState::Stopped => {
+                self.custom_plan = false;
+                self.state = PlayerState::Paused(PlaybackState {
+                    looped: false,
+                    playhead: position,
+                });
+            }
+        }
+
+        self.playhead.store(position, Ordering::Relaxed);
+        Ok(())
+    }
+
+    /// Move the loop to input-rate samples `loop_start` (0 if none) to `end`
+    /// without stopping.  Playing the loop or the lead-in to it, playback
+    /// runs on to the new end and wraps to the new start from the next
+    /// buffer; playback already past the new end wraps at once.  Plans given
+    /// to play_plan are left as they are.  On error nothing changes.
+    pub fn set_loop(
+        &mut self,
+        loop_start: Option<usize>,
+        end: usize,
+    ) -> Result<(), Error> {
+        let start = loop_start.unwrap_or(0);
+
+        if end > self.input_duration {
+            return Err(Error::InvalidLoop(String::from(
+                "End beyond input buffer",
+            )));
+        }
+
+        if start >= end {
+            return Err(Error::InvalidLoop(String::from(
+                "Loop start is AT or AFTER end",
+            )));
+        }
+
+        let scale = |index, what| {
+            scale_index(self.input_rate, self.playback_rate, index)
+                .ok_or_else(|| too_large(what))
+        };
+        let playback_end = scale(end, "end")?.min(self.samples.len());
+        let playback_start = scale(start, "loop start")?;
+
+        if playback_start >= playback_end {
+            return Err(Error::InvalidLoop(String::from(
+                "Loop start is AT or AFTER end",
+            )));
+        }
+
+        self.playback_loop_start = playback_start;
+        self.playback_end = playback_end;
+        self.input_loop_start = loop_start;
+        self.input_end = end;
+
+        if self.custom_plan || self.plan.is_empty() {
+            return Ok(());
+        }
+
+        // Plans built by play keep their shape, each segment running to the
+        // new stop position and the looped one from the new start, so that
+        // the callback's place in the plan still means the same
+        let looped = self.is_looping();
+        let stop = self.stop_position(looped);
+        let last = self.plan.len() - 1;
+        let plan = self
+            .plan
+            .iter()
+            .enumerate()
+            .map(|(index, segment)| {
+                let start = if looped && index == last {
+                    self.playback_loop_start
+                } else {
+                    segment.range.start
+                };
+
+                PlaySegment {
+                    range: start..stop,
+                    repeats: segment.repeats,
+                }
+            })
+            .collect::<Vec<_>>();
+
+        self.plan = Arc::new(plan);
+        self.cursor.replace_plan(Arc::clone(&self.plan));
+        Ok(())
+    }
+
+    /// Play at `speed` times the original rate, from 1/8 to 8, which shifts
+    /// pitch with it.  The playhead and loop points stay in input-rate
+    /// samples.  Takes effect at the next buffer and lasts across streams.
+    pub fn set_speed(&mut self, speed: f64) -> Result<(), Error> {
+        check_speed(speed)?;
+        self.cursor.set_speed(speed);
+        Ok(())
+    }
+
+    /// Ratio of the playback speed to the original, 1 unless set
+    pub fn speed(&self) -> f64 {
+        self.cursor.speed()
+    }
+
+    /// Scale output by `volume`, from 0 to `MAX_VOLUME`.  Playing, the
+    /// change is ramped in over the next buffer so it doesn't click.
+    pub fn set_volume(&mut self, volume: f32) -> Result<(), Error> {
+        check_volume(volume)?;
+        self.cursor.set_volume(volume);
+        Ok(())
+    }
+
+    /// Gain applied to output, 1 unless set
+    pub fn volume(&self) -> f32 {
+        self.cursor.volume()
+    }
 
+    pub fn stop(&mut self) {
+        self.retire_stream();
         self.playhead.store(0, Ordering::Relaxed);
+        self.cursor.store(PlanPosition::default());
+    }
+
+    // Drop any stream, first bumping the generation so that a callback still
+    // in flight on a backend whose drop doesn't wait for it leaves the
+    // playhead alone
+    fn retire_stream(&mut self) {
+        if matches!(
+            self.state,
+            PlayerState::Playing(_) | PlayerState::PlayingLooped(_)
+        ) {
+            self.generation.fetch_add(1, Ordering::AcqRel);
+            self.state = PlayerState::Stopped;
+        }
     }
 
     pub fn pause(&mut self) {
+        self.settle();
+
         let looped = match self.state {
             PlayerState::Paused(_) => {
                 return;
@@ -177,19 +926,27 @@ impl Player {
             _ => false,
         };
 
-        // Stream gets dropped if state was previously Playing or PlayingLooped
         // Need to stop b/c we need the playhead location before setting Paused
-        self.state = PlayerState::Stopped;
+        self.retire_stream();
 
         let playhead = self.playhead.load(Ordering::Relaxed);
 
         self.state = PlayerState::Paused(PlaybackState { looped, playhead });
     }
 
-    pub fn resume(&mut self) -> Result<(), String> {
+    pub fn resume(&mut self) -> Result<(), Error> {
+        self.settle();
+
         match self.state {
             PlayerState::PlayingLooped(_) | PlayerState::Playing(_) => {}
             PlayerState::Stopped => self.play(0, false)?,
+            PlayerState::Paused(PlaybackState { playhead, looped })
+                if self.custom_plan =>
+            {
+                let plan = self.plan.to_vec();
+                let position = self.cursor.load();
+                self.start_plan(plan, position, playhead, looped)?;
+            }
             PlayerState::Paused(PlaybackState { playhead, looped }) => {
                 self.play_from_playback_position(playhead, looped)?;
             }
@@ -198,181 +955,1062 @@ impl Player {
         Ok(())
     }
 
+    /// Playhead in input-rate samples: the first that plays at or after the
+    /// playback position
     pub fn playhead(&self) -> usize {
         let playback_position = self.playhead.load(Ordering::Relaxed);
-        scale_index(self.playback_rate, self.input_rate, playback_position)
-            .unwrap()
+        playback_to_input(
+            playback_position,
+            self.playback_rate,
+            self.input_rate,
+        )
+        .unwrap()
+    }
+
+    /// Playhead in seconds into the input; `play_from_seconds` given this
+    /// resumes from the same sample
+    pub fn playhead_seconds(&self) -> f64 {
+        samples_to_seconds(self.playhead(), self.input_rate)
     }
 
     pub fn playback_rate(&self) -> u32 {
         self.playback_rate
     }
 
-    pub fn samples_remaining(&self) -> usize {
-        let playback_position = self.playhead.load(Ordering::Relaxed);
-        let playback_samples =
-            self.samples.len().saturating_sub(playback_position);
-        scale_index(self.playback_rate, self.input_rate, playback_samples)
-            .unwrap()
+    /// Name of the device given in the config, if any
+    pub fn device(&self) -> Option<&str> {
+        self.device.as_deref()
     }
 
-    pub fn state(&self) -> PlayerStateTag {
-        self.state.state_tag()
+    /// Output devices of the default host
+    pub fn devices() -> Result<Vec<DeviceInfo>, Error> {
+        let host = cpal::default_host();
+        let default_name = host
+            .default_output_device()
+            .and_then(|device| device.name().ok());
+        let devices = host
+            .output_devices()
+            .map_err(|e| Error::Audio(e.to_string()))?;
+
+        Ok(devices
+            .filter_map(|device| {
+                let name = device.name().ok()?;
+                let configs = device
+                    .supported_output_configs()
+                    .map(|configs| {
+                        configs
+                            .filter(|cfg| {
+                                OUTPUT_FORMATS.contains(&cfg.sample_format())
+                            })
+                            .collect::<Vec<_>>()
+                    })
+                    .unwrap_or_default();
+                let sample_rates = LISTED_RATES
+                    .into_iter()
+                    .filter(|&rate| {
+                        configs.iter().any(|cfg| {
+                            (cfg.min_sample_rate().0..=cfg.max_sample_rate().0)
+                                .contains(&rate)
+                        })
+                    })
+                    .collect();
+
+                Some(DeviceInfo {
+                    is_default: default_name.as_ref() == Some(&name),
+                    name,
+                    sample_rates,
+                })
+            })
+            .collect())
     }
-}
 
-fn scale_index(inrate: u32, outrate: u32, index: usize) -> Option<usize> {
-    u64::try_from(index)
-        .ok()
-        .and_then(|idx| idx.checked_mul(outrate.into()))
-        .and_then(|idx| (idx / u64::from(inrate)).try_into().ok())
-}
+    /// Sample rate of the samples the player was configured with
+    pub fn input_rate(&self) -> u32 {
+        self.input_rate
+    }
 
-fn resample(inrate: u32, outrate: u32, input_samples: &[f32]) -> Vec<f32> {
-    let sinc_len = 256usize;
-    let f_cutoff = 1f32 + 1f32 / sinc_len as f32;
-    /*
-    let f_cutoff = 0.95f32;
-    */
+    /// Configured loop in input-rate samples, if a loop start was given
+    pub fn loop_bounds_input(&self) -> Option<Range<usize>> {
+        self.input_loop_start.map(|start| start..self.input_end)
+    }
 
-    let config = SincInterpolationParameters {
-        sinc_len,
-        f_cutoff,
-        oversampling_factor: 128,
-        interpolation: SincInterpolationType::Cubic,
-        window: WindowFunction::Blackman,
-    };
+    /// Loop start in input-rate samples, if a loop start was given
+    pub fn loop_start(&self) -> Option<usize> {
+        self.input_loop_start
+    }
 
-    let mut interpolator = SincFixedIn::new(
-        outrate as f64 / inrate as f64,
-        1.0,
-        config,
-        input_samples.len(),
-        1,
-    )
-    .unwrap();
+    /// Configured end of playback, and of any loop, in input-rate samples
+    pub fn end(&self) -> usize {
+        self.input_end
+    }
 
-    interpolator.process(&[input_samples], None).unwrap()[0].clone()
-}
+    /// True when there is a loop and the playhead lies within it, whether or
+    /// not playback is looping
+    pub fn is_in_loop(&self) -> bool {
+        let position = self.playhead.load(Ordering::Relaxed);
+        self.input_loop_start.is_some()
+            && (self.playback_loop_start..self.playback_end).contains(&position)
+    }
 
-fn stream_config(
-    device: &cpal::Device,
-    inrate: u32,
-) -> Result<SupportedStreamConfig, String> {
-    let preferred_rate = if inrate % DVD_DIVISOR == 0 {
-        DVD_SAMPLE_RATE
-    } else {
-        CD_SAMPLE_RATE
-    };
+    /// Seek to the loop start, as `seek`; the playhead then reads as exactly
+    /// the loop start whenever the device rate is at least the input rate
+    pub fn skip_to_loop_start(&mut self) -> Result<(), Error> {
+        let loop_start =
+            self.input_loop_start.ok_or(Error::NoLoop("skip to"))?;
+        self.seek(loop_start)
+    }
 
-    let mut configs = device
-        .supported_output_configs()
-        .map_err(|e| e.to_string())?
-        .filter(|cfg| cfg.sample_format() == SampleFormat::F32)
-        .collect::<Vec<_>>();
+    /// Seek to `lead` input-rate samples before the loop end, as `seek`, so
+    /// that looped playback soon plays across the seam
+    pub fn skip_to_pre_seam(&mut self, lead: usize) -> Result<(), Error> {
+        if self.input_loop_start.is_none() {
+            return Err(Error::NoLoop("skip to"));
+        }
 
-    let configs_1_ch = configs
-        .iter()
-        .filter(|cfg| cfg.channels() == 1)
-        .copied()
-        .collect::<Vec<_>>();
-    let configs_2_ch = configs
-        .iter()
-        .filter(|cfg| cfg.channels() == 2)
-        .copied()
-        .collect::<Vec<_>>();
+        self.seek(self.input_end.saturating_sub(lead))
+    }
 
-    configs = if !configs_1_ch.is_empty() {
-        configs_1_ch
-    } else if !configs_2_ch.is_empty() {
-        configs_2_ch
-    } else {
-        configs
-    };
+    /// Number of resampled samples that exceeded full scale and were clipped,
+    /// as far as resampling has got; see `wait_for_resampling`
+    pub fn overshoot_count(&self) -> usize {
+        self.samples.overshoot.load(Ordering::Relaxed)
+    }
 
-    let config = configs
-        .iter()
-        .flat_map(|range| {
-            let mut cfg =
-                range.try_with_sample_rate(SampleRate(preferred_rate));
-
-            if cfg.is_none() {
-                if preferred_rate == DVD_SAMPLE_RATE {
-                    cfg =
-                        range.try_with_sample_rate(SampleRate(CD_SAMPLE_RATE));
-                } else {
-                    cfg =
-                        range.try_with_sample_rate(SampleRate(DVD_SAMPLE_RATE));
+    /// True once the samples have all been resampled for the device.  Until
+    /// then, playback reaching samples not yet resampled falls silent and
+    /// picks up as they arrive.
+    pub fn is_resampled(&self) -> bool {
+        self.samples.is_complete()
+    }
+
+    /// Block until the samples have all been resampled for the device, or
+    /// resampling failed, in which case `take_error` gives why
+    pub fn wait_for_resampling(&self) {
+        while !self.samples.is_complete()
+            && !self.samples.failed.load(Ordering::Acquire)
+        {
+            thread::sleep(RESAMPLE_POLL);
+        }
+    }
+
+    /// Length of the configured samples in input-rate samples
+    pub fn duration_input(&self) -> usize {
+        self.input_duration
+    }
+
+    /// Length of the configured samples in seconds
+    pub fn duration_seconds(&self) -> f64 {
+        samples_to_seconds(self.input_duration, self.input_rate)
+    }
+
+    /// True when playing looped, or paused out of looped playback
+    pub fn is_looping(&self) -> bool {
+        matches!(
+            self.state,
+            PlayerState::PlayingLooped(_)
+                | PlayerState::Paused(PlaybackState { looped: true, .. })
+        )
+    }
+
+    /// Samples left before playback ends, counting the passes still to be
+    /// made through each segment of the plan, or before the end of the pass
+    /// through a segment repeating forever.  Segments after one repeating
+    /// forever are never reached and aren't counted.  If not started, samples
+    /// left before non-looped playback ends.
+    pub fn samples_remaining(&self) -> usize {
+        let playback_position = self.playhead.load(Ordering::Relaxed);
+
+        let playback_samples = match self.state {
+            PlayerState::Stopped => {
+                self.stop_position(false).saturating_sub(playback_position)
+            }
+            _ => {
+                let position = self.cursor.load();
+                let mut remaining = 0usize;
+
+                for (index, segment) in
+                    self.plan.iter().enumerate().skip(position.segment)
+                {
+                    let len = segment.range.len();
+
+                    if index == position.segment {
+                        remaining =
+                            segment.range.end.saturating_sub(playback_position);
+
+                        if !segment.is_infinite() {
+                            let passes = segment
+                                .repeats
+                                .saturating_sub(position.pass)
+                                .saturating_sub(1);
+                            remaining = remaining.saturating_add(
+                                len.saturating_mul(passes as usize),
+                            );
+                        }
+                    } else if !segment.is_infinite() {
+                        remaining = remaining.saturating_add(
+                            len.saturating_mul(segment.repeats as usize),
+                        );
+                    }
+
+                    if segment.is_infinite() {
+                        break;
+                    }
                 }
+
+                remaining
+            }
+        };
+
+        scale_index(self.playback_rate, self.input_rate, playback_samples)
+            .unwrap()
+    }
+
+    /// True once every segment of the plan has been played, so that playback
+    /// has fallen silent
+    pub fn is_finished(&self) -> bool {
+        match self.state {
+            PlayerState::Playing(_) | PlayerState::PlayingLooped(_) => {
+                self.cursor.load().segment >= self.plan.len()
             }
+            _ => false,
+        }
+    }
+
+    /// State of the transport, or `Errored` once the stream has reported an
+    /// error that hasn't been taken.  Playback that has played the last of
+    /// its plan is stopped.
+    pub fn state(&self) -> PlayerStateTag {
+        if self.has_error() {
+            PlayerStateTag::Errored
+        } else if self.is_finished() {
+            PlayerStateTag::Stopped
+        } else {
+            self.state.state_tag()
+        }
+    }
+
+    /// A receiver sent `()` each time playback plays the last of its plan,
+    /// as non-looped playback does on reaching the end.  It is sent from the
+    /// audio callback as the final buffer is handed over.
+    pub fn finished_receiver(&self) -> Receiver<()> {
+        let (sender, receiver) = mpsc::channel();
+
+        if let Ok(mut senders) = self.cursor.finished.lock() {
+            senders.push(sender);
+        }
+
+        receiver
+    }
+
+    // Drop the stream of playback that has finished, so that the player is
+    // stopped as `state` says
+    fn settle(&mut self) {
+        if self.is_finished() {
+            self.retire_stream();
+        }
+    }
+
+    /// Error the stream reported while playing, such as its device going
+    /// away, if any.  Taking it drops the stream and leaves the player
+    /// stopped, or if the device went away, paused where it was until
+    /// `reopen`; starting playback afresh discards any not taken.
+    pub fn take_error(&mut self) -> Option<Error> {
+        let fault = self.take_stream_error()?;
+
+        if fault.device_lost {
+            self.pause();
+            self.disconnected = true;
+        } else {
+            self.retire_stream();
+        }
+
+        Some(fault.error)
+    }
+
+    /// True once an error taken from the stream was its device going away,
+    /// until the player is reopened
+    pub fn is_disconnected(&self) -> bool {
+        self.disconnected
+    }
+
+    /// Open the output device again, the one named in the config or else
+    /// whatever is now the default, as after it was unplugged.  If the
+    /// device plays at another rate, the samples are resampled to it, and
+    /// loop points, the plan, and the playhead are scaled to match.
+    /// Playback resumes where it was if it was playing or was cut off by the
+    /// device going away, and stays paused or stopped otherwise.
+    pub fn reopen(&mut self) -> Result<(), Error> {
+        let resume = self.disconnected
+            || matches!(
+                self.state(),
+                PlayerStateTag::Playing | PlayerStateTag::PlayingLooped
+            );
+
+        if resume {
+            self.pause();
+        }
+
+        let device = output_device(self.device.as_deref())?;
+        let playback_rate =
+            stream_config(&device, self.input_rate)?.sample_rate().0;
+
+        if playback_rate != self.playback_rate {
+            self.rescale(playback_rate)?;
+        }
+
+        self.disconnected = false;
+
+        if resume {
+            self.resume()?;
+        }
+
+        Ok(())
+    }
+
+    // Resample for another playback rate and scale every playback-rate
+    // position to it; the stream must be retired
+    fn rescale(&mut self, playback_rate: u32) -> Result<(), Error> {
+        let len = resampled_len(
+            self.input_samples.len(),
+            self.input_rate,
+            playback_rate,
+        );
+        let from_input = |index, what| {
+            scale_index(self.input_rate, playback_rate, index)
+                .map(|index| index.min(len))
+                .ok_or_else(|| too_large(what))
+        };
+        let end = from_input(self.input_end, "end")?;
+        let play_end = from_input(self.input_play_end, "end marker")?;
+        let loop_start =
+            from_input(self.input_loop_start.unwrap_or(0), "loop start")?;
+
+        if loop_start >= end {
+            return Err(Error::InvalidLoop(String::from(
+                "Loop start is AT or AFTER end",
+            )));
+        }
+
+        let old_rate = self.playback_rate;
+        let rescale = |index| {
+            scale_index(old_rate, playback_rate, index)
+                .map(|index| index.min(len))
+                .ok_or_else(|| too_large("segment"))
+        };
+        // Segments narrower than a frame at the new rate would floor to
+        // nothing, and an empty segment repeated forever never ends, so they
+        // keep a frame
+        let plan = self
+            .plan
+            .iter()
+            .map(|segment| {
+                let end = rescale(segment.range.end)?.max(1);
+                let start = rescale(segment.range.start)?.min(end - 1);
+
+                Ok(PlaySegment {
+                    range: start..end,
+                    repeats: segment.repeats,
+                })
+            })
+            .collect::<Result<Vec<_>, Error>>()?;
+        check_plan(&plan, len)?;
+        let playhead = rescale(self.playhead.load(Ordering::Relaxed))?;
+
+        let paused_playhead = match self.state {
+            PlayerState::Paused(state) => Some(rescale(state.playhead)?),
+            _ => None,
+        };
+
+        self.samples = start_resampling(
+            &self.input_samples,
+            self.input_rate,
+            playback_rate,
+            self.clip,
+            self.quality,
+            &self.stream_error,
+        )?;
+
+        if let (PlayerState::Paused(state), Some(playhead)) =
+            (&mut self.state, paused_playhead)
+        {
+            state.playhead = playhead;
+        }
+
+        self.playback_rate = playback_rate;
+        self.playback_loop_start = loop_start;
+        self.playback_end = end;
+        self.play_end = play_end;
+        self.plan = Arc::new(plan);
+        self.playhead.store(playhead, Ordering::Relaxed);
+        Ok(())
+    }
+
+    fn has_error(&self) -> bool {
+        self.stream_error
+            .lock()
+            .map(|slot| slot.is_some())
+            .unwrap_or(false)
+    }
+
+    fn take_stream_error(&self) -> Option<StreamFault> {
+        self.stream_error
+            .lock()
+            .ok()
+            .and_then(|mut slot| slot.take())
+    }
+
+    /// Playhead positions recorded by the audio callback, one per output
+    /// buffer, oldest first.  Holds up to the last 64 buffers across streams;
+    /// positions are in input-rate samples.
+    pub fn recent_positions(&self) -> Vec<PlayheadSample> {
+        self.history
+            .read()
+            .into_iter()
+            .filter_map(|(timestamp, offset)| {
+                playback_to_input(offset, self.playback_rate, self.input_rate)
+                    .map(|position| PlayheadSample {
+                        timestamp,
+                        position,
+                    })
+            })
+            .collect()
+    }
+
+    /// Highest amplitude, from 0 to 1, in the buffer last handed to the
+    /// output device; 0 unless playing
+    pub fn current_peak(&self) -> f32 {
+        if self.is_streaming() {
+            self.history.peak()
+        } else {
+            0.0
+        }
+    }
+
+    /// RMS level, from 0 to 1, of the buffer last handed to the output
+    /// device; 0 unless playing
+    pub fn current_rms(&self) -> f32 {
+        if self.is_streaming() {
+            self.history.rms()
+        } else {
+            0.0
+        }
+    }
+
+    // Whether a stream is playing something other than the silence after
+    // the plan
+    fn is_streaming(&self) -> bool {
+        matches!(
+            self.state(),
+            PlayerStateTag::Playing | PlayerStateTag::PlayingLooped
+        )
+    }
+
+    pub fn bounds(&self) -> PlaybackBounds {
+        self.bounds
+    }
+
+    /// Choose where non-looped playback stops; takes effect the next time
+    /// playback is started or resumed
+    pub fn set_bounds(&mut self, bounds: PlaybackBounds) {
+        self.bounds = bounds;
+    }
+
+    // Playback-rate position at which the stream wraps or runs out of samples
+    fn stop_position(&self, looped: bool) -> usize {
+        if looped || self.bounds == PlaybackBounds::ToEndMarker {
+            self.playback_end
+        } else {
+            self.play_end
+        }
+    }
+}
+
+/// Time in seconds of sample `index` at `rate`
+pub fn samples_to_seconds(index: usize, rate: u32) -> f64 {
+    index as f64 / f64::from(rate)
+}
+
+/// Sample at `rate` nearest `seconds` in, undoing `samples_to_seconds`, or
+/// `None` for a time that is negative, not finite or too large
+pub fn seconds_to_samples(seconds: f64, rate: u32) -> Option<usize> {
+    let index = (seconds * f64::from(rate)).round();
+
+    (index.is_finite() && index >= 0.0 && index < usize::MAX as f64)
+        .then_some(index as usize)
+}
 
-            cfg
+/// Sample at `playback_rate` that input-rate sample `index` plays at, as the
+/// player places loop points and seeks, or `None` if too large
+pub fn input_to_playback(
+    index: usize,
+    input_rate: u32,
+    playback_rate: u32,
+) -> Option<usize> {
+    scale_index(input_rate, playback_rate, index)
+}
+
+/// First input-rate sample that plays at or after sample `index` at
+/// `playback_rate`, or `None` if too large.  This undoes `input_to_playback`
+/// when the playback rate is at least the input rate, so a playhead seeked to
+/// a loop point reads back as it, and otherwise `input_to_playback` undoes
+/// this.
+pub fn playback_to_input(
+    index: usize,
+    playback_rate: u32,
+    input_rate: u32,
+) -> Option<usize> {
+    if input_rate == playback_rate {
+        return Some(index);
+    }
+
+    u64::try_from(index)
+        .ok()
+        .and_then(|idx| idx.checked_mul(input_rate.into()))
+        .map(|idx| idx.div_ceil(u64::from(playback_rate)))
+        .and_then(|idx| idx.try_into().ok())
+}
+
+// Index at `outrate` of `index` at `inrate`; unchanged when the rates match
+fn scale_index(inrate: u32, outrate: u32, index: usize) -> Option<usize> {
+    if inrate == outrate {
+        return Some(index);
+    }
+
+    u64::try_from(index)
+        .ok()
+        .and_then(|idx| idx.checked_mul(outrate.into()))
+        .and_then(|idx| (idx / u64::from(inrate)).try_into().ok())
+}
+
+// Buffer of `input` resampled from `inrate` to `outrate` and clipped.  Unless
+// the rates match, it is filled in the background a block at a time until
+// full or no longer shared, and a failure is left in `fault`.
+fn start_resampling(
+    input: &Arc<Vec<f32>>,
+    inrate: u32,
+    outrate: u32,
+    mode: ClipMode,
+    quality: ResampleQuality,
+    fault: &Arc<Mutex<Option<StreamFault>>>,
+) -> Result<Arc<SampleBuffer>, Error> {
+    let buffer = Arc::new(SampleBuffer::new(resampled_len(
+        input.len(),
+        inrate,
+        outrate,
+    )));
+
+    if inrate == outrate {
+        buffer.push_clipped(input, mode);
+        return Ok(buffer);
+    }
+
+    let (input, fault, worker_buffer) =
+        (Arc::clone(input), Arc::clone(fault), Arc::clone(&buffer));
+
+    thread::Builder::new()
+        .name(String::from("quadio-resampler"))
+        .spawn(move || {
+            let result = resample_blocks(
+                &input,
+                inrate,
+                outrate,
+                quality,
+                RESAMPLE_BLOCK,
+                |block| {
+                    worker_buffer.push_clipped(block, mode);
+                    Arc::strong_count(&worker_buffer) > 1
+                },
+            );
+
+            if let Err(error) = result {
+                worker_buffer.failed.store(true, Ordering::Release);
+
+                if let Ok(mut slot) = fault.lock() {
+                    slot.get_or_insert(StreamFault {
+                        error,
+                        device_lost: false,
+                    });
+                }
+            }
+        })
+        .map_err(|e| Error::Resample(e.to_string()))?;
+
+    Ok(buffer)
+}
+
+// Bring samples within full scale, returning how many were beyond it
+fn clip(samples: &mut [f32], mode: ClipMode) -> usize {
+    let mut overshoot = 0;
+
+    for sample in samples.iter_mut() {
+        if sample.abs() > 1.0 {
+            overshoot += 1;
+        }
+
+        *sample = match mode {
+            ClipMode::Hard => sample.clamp(-1.0, 1.0),
+            ClipMode::Soft if sample.abs() > SOFT_CLIP_KNEE => {
+                let over =
+                    (sample.abs() - SOFT_CLIP_KNEE) / (1.0 - SOFT_CLIP_KNEE);
+                let bent =
+                    SOFT_CLIP_KNEE + (1.0 - SOFT_CLIP_KNEE) * over.tanh();
+                bent.copysign(*sample)
+            }
+            ClipMode::Soft => *sample,
+        };
+    }
+
+    overshoot
+}
+
+// Output device of the default host with the given name, or its default
+fn output_device(name: Option<&str>) -> Result<cpal::Device, Error> {
+    let host = cpal::default_host();
+
+    match name {
+        None => host
+            .default_output_device()
+            .ok_or_else(|| Error::Audio(String::from(NO_OUTPUT))),
+        Some(name) => host
+            .output_devices()
+            .map_err(|e| Error::Audio(e.to_string()))?
+            .find(|device| device.name().is_ok_and(|found| found == name))
+            .ok_or_else(|| {
+                Error::Audio(format!("No output device named \"{}\"", name))
+            }),
+    }
+}
+
+fn stream_config(
+    device: &cpal::Device,
+    inrate: u32,
+) -> Result<SupportedStreamConfig, Error> {
+    let preferred_rate = if inrate.is_multiple_of(DVD_DIVISOR) {
+        DVD_SAMPLE_RATE
+    } else {
+        CD_SAMPLE_RATE
+    };
+
+    let supported = device
+        .supported_output_configs()
+        .map_err(|e| Error::Audio(e.to_string()))?
+        .collect::<Vec<_>>();
+
+    // Formats other than f32 only when the device offers no f32 at all
+    let mut configs = OUTPUT_FORMATS
+        .into_iter()
+        .map(|format| {
+            supported
+                .iter()
+                .filter(|cfg| cfg.sample_format() == format)
+                .copied()
+                .collect::<Vec<_>>()
         })
-        .next()
-        .ok_or("Could not find appropriate stream configuration")?;
+        .find(|configs| !configs.is_empty())
+        .unwrap_or_default();
+
+    let configs_1_ch = configs
+        .iter()
+        .filter(|cfg| cfg.channels() == 1)
+        .copied()
+        .collect::<Vec<_>>();
+    let configs_2_ch = configs
+        .iter()
+        .filter(|cfg| cfg.channels() == 2)
+        .copied()
+        .collect::<Vec<_>>();
+
+    configs = if !configs_1_ch.is_empty() {
+        configs_1_ch
+    } else if !configs_2_ch.is_empty() {
+        configs_2_ch
+    } else {
+        configs
+    };
+
+    let fallback_rate = if preferred_rate == DVD_SAMPLE_RATE {
+        CD_SAMPLE_RATE
+    } else {
+        DVD_SAMPLE_RATE
+    };
+
+    // The input's own rate spares resampling it
+    let config = [inrate, preferred_rate, fallback_rate]
+        .into_iter()
+        .find_map(|rate| {
+            configs
+                .iter()
+                .find_map(|range| range.try_with_sample_rate(SampleRate(rate)))
+        })
+        .ok_or_else(|| {
+            Error::Audio(String::from(
+                "Could not find appropriate stream configuration",
+            ))
+        })?;
 
     Ok(config)
 }
 
+// Generation a stream's callback was built for
+struct StreamToken {
+    generation: Arc<AtomicUsize>,
+    built_for: usize,
+}
+
+impl StreamToken {
+    fn current(generation: &Arc<AtomicUsize>) -> Self {
+        StreamToken {
+            generation: Arc::clone(generation),
+            built_for: generation.load(Ordering::Acquire),
+        }
+    }
+
+    fn is_retired(&self) -> bool {
+        self.generation.load(Ordering::Acquire) != self.built_for
+    }
+}
+
+// Segment of the plan being played and the passes made through it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+struct PlanPosition {
+    segment: usize,
+    pass: u32,
+}
+
+// PlanPosition shared with the callback, where it should jump to at its
+// next buffer, a plan to replace its own with there, the speed it steps
+// through samples at, as f64 bits, and who to tell when it plays the last of
+// the plan
+#[derive(Debug)]
+struct PlanCursor {
+    segment: AtomicUsize,
+    pass: AtomicUsize,
+    seek: SeekRequest,
+    new_plan: Mutex<Option<Arc<Vec<PlaySegment>>>>,
+    speed: AtomicU64,
+    volume: AtomicU32,
+    finished: Mutex<Vec<Sender<()>>>,
+}
+
+impl Default for PlanCursor {
+    fn default() -> Self {
+        PlanCursor {
+            segment: AtomicUsize::new(0),
+            pass: AtomicUsize::new(0),
+            seek: SeekRequest::default(),
+            new_plan: Mutex::new(None),
+            speed: AtomicU64::new(1f64.to_bits()),
+            volume: AtomicU32::new(1f32.to_bits()),
+            finished: Mutex::new(vec![]),
+        }
+    }
+}
+
+impl PlanCursor {
+    fn load(&self) -> PlanPosition {
+        PlanPosition {
+            segment: self.segment.load(Ordering::Relaxed),
+            pass: self.pass.load(Ordering::Relaxed) as u32,
+        }
+    }
+
+    fn store(&self, position: PlanPosition) {
+        self.segment.store(position.segment, Ordering::Relaxed);
+        self.pass.store(position.pass as usize, Ordering::Relaxed);
+    }
+
+    fn speed(&self) -> f64 {
+        f64::from_bits(self.speed.load(Ordering::Relaxed))
+    }
+
+    fn set_speed(&self, speed: f64) {
+        self.speed.store(speed.to_bits(), Ordering::Relaxed);
+    }
+
+    fn volume(&self) -> f32 {
+        f32::from_bits(self.volume.load(Ordering::Relaxed))
+    }
+
+    fn set_volume(&self, volume: f32) {
+        self.volume.store(volume.to_bits(), Ordering::Relaxed);
+    }
+
+    fn replace_plan(&self, plan: Arc<Vec<PlaySegment>>) {
+        if let Ok(mut new_plan) = self.new_plan.lock() {
+            *new_plan = Some(plan);
+        }
+    }
+
+    // Plan to play from the next buffer, if one was given and the player
+    // isn't giving another right now
+    fn take_plan(&self) -> Option<Arc<Vec<PlaySegment>>> {
+        self.new_plan
+            .try_lock()
+            .ok()
+            .and_then(|mut plan| plan.take())
+    }
+
+    // Send to every receiver still listening
+    fn notify_finished(&self) {
+        if let Ok(mut senders) = self.finished.lock() {
+            senders.retain(|sender| sender.send(()).is_ok());
+        }
+    }
+}
+
+// Playback-rate offset and plan segment for the callback to jump to, shared
+// with it.  The offset is the request, set last and taken first, and
+// NO_SEEK when there is none.
+#[derive(Debug)]
+struct SeekRequest {
+    offset: AtomicUsize,
+    segment: AtomicUsize,
+}
+
+const NO_SEEK: usize = usize::MAX;
+
+impl Default for SeekRequest {
+    fn default() -> Self {
+        SeekRequest {
+            offset: AtomicUsize::new(NO_SEEK),
+            segment: AtomicUsize::new(0),
+        }
+    }
+}
+
+impl SeekRequest {
+    fn request(&self, offset: usize, segment: usize) {
+        self.segment.store(segment, Ordering::Relaxed);
+        self.offset.store(offset, Ordering::Release);
+    }
+
+    // Offset and plan position requested, if any, clearing the request
+    fn take(&self) -> Option<(usize, PlanPosition)> {
+        let offset = self.offset.swap(NO_SEEK, Ordering::Acquire);
+
+        (offset != NO_SEEK).then(|| {
+            let segment = self.segment.load(Ordering::Relaxed);
+            (offset, PlanPosition { segment, pass: 0 })
+        })
+    }
+}
+
+fn check_speed(speed: f64) -> Result<(), Error> {
+    if (MIN_SPEED..=MAX_SPEED).contains(&speed) {
+        Ok(())
+    } else {
+        Err(Error::InvalidArgument(format!(
+            "Speed must be between {} and {}",
+            MIN_SPEED, MAX_SPEED,
+        )))
+    }
+}
+
+fn check_volume(volume: f32) -> Result<(), Error> {
+    if (0.0..=MAX_VOLUME).contains(&volume) {
+        Ok(())
+    } else {
+        Err(Error::InvalidArgument(format!(
+            "Volume must be between 0 and {}",
+            MAX_VOLUME,
+        )))
+    }
+}
+
+// Move on from the end of the segment being played: to its start for another
+// pass, or to the start of the next segment
+fn end_segment(
+    plan: &[PlaySegment],
+    position: &mut PlanPosition,
+    offset: &mut usize,
+) {
+    let segment = &plan[position.segment];
+    position.pass = position.pass.saturating_add(1);
+
+    if !segment.is_infinite() && position.pass >= segment.repeats {
+        *position = PlanPosition {
+            segment: position.segment + 1,
+            pass: 0,
+        };
+    }
+
+    if let Some(next) = plan.get(position.segment) {
+        *offset = next.range.start;
+    }
+}
+
+// Position scaled to the playback rate overflowing
+fn too_large(what: &str) -> Error {
+    Error::InvalidArgument(format!("Scaled {} too large", what))
+}
+
+fn check_plan(plan: &[PlaySegment], len: usize) -> Result<(), Error> {
+    if plan.is_empty() {
+        return Err(Error::InvalidArgument(String::from(
+            "Playback plan is empty",
+        )));
+    }
+
+    for segment in plan {
+        if segment.range.start >= segment.range.end {
+            return Err(Error::InvalidArgument(format!(
+                "Segment {}..{} is empty",
+                segment.range.start, segment.range.end,
+            )));
+        }
+
+        if segment.range.end > len {
+            return Err(Error::InvalidArgument(format!(
+                "Segment {}..{} ends beyond input buffer",
+                segment.range.start, segment.range.end,
+            )));
+        }
+    }
+
+    Ok(())
+}
+
 fn stream_callback<T>(
-    samples: Arc<Vec<f32>>,
+    samples: Arc<SampleBuffer>,
     playhead: Arc<AtomicUsize>,
-    loop_start: Option<usize>,
-    in_end: usize,
+    history: Arc<PlayheadHistory>,
+    token: StreamToken,
+    plan: Arc<Vec<PlaySegment>>,
+    cursor: Arc<PlanCursor>,
     channels: u16,
 ) -> impl FnMut(&mut [f32], &'_ T) {
+    let mut plan = plan;
     let mut offset = playhead.load(Ordering::Relaxed);
+    let mut position = cursor.load();
     let channels = usize::from(channels);
 
+    // How far past `offset` playback is, in samples, when not at full speed
+    let mut fraction = 0f64;
+
+    // Gain applied at the end of the last buffer, ramped toward the volume
+    let mut gain = cursor.volume();
+
     move |buf: &mut [f32], _: &'_ _| {
+        if token.is_retired() {
+            buf.fill(f32::EQUILIBRIUM);
+            return;
+        }
+
+        // Segments are replaced in place, so the position in the plan holds
+        if let Some(new_plan) = cursor.take_plan() {
+            plan = new_plan;
+        }
+
+        if let Some((seek_offset, seek_position)) = cursor.seek.take() {
+            offset = seek_offset;
+            position = seek_position;
+            fraction = 0.0;
+        }
+
+        let was_finished = position.segment >= plan.len();
+
         let sub_buf_len = buf.len() / channels;
+        let mut write_start = 0usize;
+        let speed = cursor.speed();
 
-        if let Some(loop_start) = loop_start {
-            let loop_len = in_end - loop_start;
+        // Playback catching up with resampling waits in silence for more
+        let ready = samples.ready();
 
-            let wrap = |off: usize| {
-                if off >= in_end {
-                    (off - loop_start) % loop_len + loop_start
-                } else {
-                    off
-                }
+        // Off full speed, samples are stepped through fractionally and
+        // interpolated linearly toward the one played next
+        while speed != 1.0 && write_start < sub_buf_len {
+            let Some(segment) = plan.get(position.segment) else {
+                break;
             };
 
-            let mut write_start = 0usize;
+            if offset >= segment.range.end {
+                end_segment(&plan, &mut position, &mut offset);
+                continue;
+            }
 
-            loop {
-                let write_count = (sub_buf_len - write_start)
-                    .min(in_end.saturating_sub(offset));
+            if offset >= ready {
+                break;
+            }
 
-                let write_end = write_start + write_count;
-                let read_end = offset + write_count;
+            let current = samples.get(offset);
+            let next_offset = if offset + 1 < segment.range.end {
+                Some(offset + 1)
+            } else {
+                let (mut next_position, mut next_offset) = (position, offset);
+                end_segment(&plan, &mut next_position, &mut next_offset);
+                (next_position.segment < plan.len()).then_some(next_offset)
+            };
+            let next = next_offset
+                .filter(|&next_offset| next_offset < ready)
+                .map_or(current, |next_offset| samples.get(next_offset));
 
-                buf[write_start..write_end]
-                    .copy_from_slice(&samples[offset..read_end]);
+            buf[write_start] = current + (next - current) * fraction as f32;
+            write_start += 1;
+            fraction += speed;
 
-                offset += write_count;
-                offset = wrap(offset);
-                write_start = write_end;
+            while fraction >= 1.0 && position.segment < plan.len() {
+                fraction -= 1.0;
+                offset += 1;
 
-                if write_start >= sub_buf_len {
-                    assert_eq!(write_end, sub_buf_len);
-                    break;
+                if offset >= plan[position.segment].range.end {
+                    end_segment(&plan, &mut position, &mut offset);
                 }
             }
-        } else if offset < in_end {
-            let sample_ct = in_end.saturating_sub(offset);
-            let write_count =
-                sample_ct.min(sub_buf_len).min(samples.len() - offset);
+        }
+
+        while let Some(segment) =
+            plan.get(position.segment).filter(|_| speed == 1.0)
+        {
+            // A playhead left at or past the end moves on before reading, so
+            // every pass through here either writes or breaks
+            if offset >= segment.range.end {
+                end_segment(&plan, &mut position, &mut offset);
+                continue;
+            }
+
+            let write_count = (sub_buf_len - write_start)
+                .min(segment.range.end.saturating_sub(offset))
+                .min(ready.saturating_sub(offset));
 
+            let write_end = write_start + write_count;
             let read_end = offset + write_count;
 
-            buf[..write_count].copy_from_slice(&samples[offset..read_end]);
-            buf[write_count..sub_buf_len].fill(f32::EQUILIBRIUM);
-            offset += sub_buf_len;
-        } else {
-            buf[..sub_buf_len].fill(f32::EQUILIBRIUM);
+            samples.read(offset, &mut buf[write_start..write_end]);
+
+            offset = read_end;
+            write_start = write_end;
+
+            if offset >= segment.range.end {
+                end_segment(&plan, &mut position, &mut offset);
+            } else if offset >= ready {
+                break;
+            }
+
+            if write_start >= sub_buf_len {
+                break;
+            }
+        }
+
+        buf[write_start..sub_buf_len].fill(f32::EQUILIBRIUM);
+
+        let volume = cursor.volume();
+
+        if volume != gain {
+            let step = (volume - gain) / sub_buf_len.max(1) as f32;
+
+            for (i, sample) in buf[..sub_buf_len].iter_mut().enumerate() {
+                *sample *= gain + step * (i + 1) as f32;
+            }
+
+            gain = volume;
+        } else if gain != 1.0 {
+            buf[..sub_buf_len]
+                .iter_mut()
+                .for_each(|sample| *sample *= gain);
         }
 
+        history.record_levels(&buf[..sub_buf_len]);
+
         // extend buffer by channel count
         if channels > 1 {
             let mut src_idx = sub_buf_len;
@@ -388,7 +2026,528 @@ fn stream_callback<T>(
         }
 
         offset = offset.min(samples.len());
+        cursor.store(position);
         playhead.store(offset, Ordering::Relaxed);
+        history.record(offset);
+
+        if !was_finished && position.segment >= plan.len() {
+            cursor.notify_finished();
+        }
+    }
+}
+
+// Callback writing `S` samples converted from those `callback` writes in f32
+fn converting_callback<S, T>(
+    mut callback: impl FnMut(&mut [f32], &T),
+) -> impl FnMut(&mut [S], &T)
+where
+    S: Sample + FromSample<f32>,
+{
+    let mut scratch = Vec::<f32>::new();
+
+    move |buf: &mut [S], info: &T| {
+        scratch.resize(buf.len(), f32::EQUILIBRIUM);
+        callback(&mut scratch, info);
+
+        for (out, &sample) in buf.iter_mut().zip(&scratch) {
+            *out = S::from_sample(sample);
+        }
+    }
+}
+
+// Error reported by a stream
+#[derive(Debug)]
+struct StreamFault {
+    error: Error,
+    device_lost: bool,
+}
+
+// Keep the first error a stream reports, unless it has been retired
+fn stream_error_callback(
+    slot: Arc<Mutex<Option<StreamFault>>>,
+    token: StreamToken,
+) -> impl FnMut(cpal::StreamError) {
+    move |e| {
+        if token.is_retired() {
+            return;
+        }
+
+        if let Ok(mut slot) = slot.lock() {
+            slot.get_or_insert(StreamFault {
+                device_lost: matches!(e, cpal::StreamError::DeviceNotAvailable),
+                error: Error::Audio(e.to_string()),
+            });
+        }
+    }
+}
+
+/// How `render_plan_with` hands buffers to the stream callback
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RenderOptions {
+    /// Frames in each buffer handed to the callback
+    pub buffer_frames: usize,
+
+    /// Channels each sample is copied to, as for an output device
+    pub channels: u16,
+
+    /// Ratio of the playback speed to the original, as `Player::set_speed`
+    /// takes
+    pub speed: f64,
+
+    /// Gain applied to output, as `Player::set_volume` takes
+    pub volume: f32,
+
+    /// Sample the first buffer starts from in place of the start of the first
+    /// segment, as for a playhead left at or past a segment's end
+    pub start: Option<usize>,
+}
+
+impl Default for RenderOptions {
+    fn default() -> Self {
+        RenderOptions {
+            buffer_frames: 512,
+            channels: 1,
+            speed: 1.0,
+            volume: 1.0,
+            start: None,
+        }
+    }
+}
+
+/// Play `plan` through the stream callback without an audio device, at the
+/// samples' own rate, and return the first `frames` samples.  The callback is
+/// handed buffers of `buffer_frames` frames at a time.
+pub fn render_plan(
+    samples: &[f32],
+    plan: &[PlaySegment],
+    frames: usize,
+    buffer_frames: usize,
+) -> Result<Vec<f32>, Error> {
+    let options = RenderOptions {
+        buffer_frames,
+        ..RenderOptions::default()
+    };
+
+    render_plan_with(samples, plan, frames, &options)
+}
+
+/// As `render_plan`, returning the first `frames` frames of exactly what the
+/// callback writes for an output device as `options` describes, with the
+/// channels of each frame interleaved
+pub fn render_plan_with(
+    samples: &[f32],
+    plan: &[PlaySegment],
+    frames: usize,
+    options: &RenderOptions,
+) -> Result<Vec<f32>, Error> {
+    check_plan(plan, samples.len())?;
+    check_speed(options.speed)?;
+    check_volume(options.volume)?;
+
+    if options.buffer_frames == 0 {
+        return Err(Error::InvalidArgument(String::from(
+            "Buffer size must be non-zero",
+        )));
+    }
+
+    if options.channels == 0 {
+        return Err(Error::InvalidArgument(String::from(
+            "Channel count must be non-zero",
+        )));
+    }
+
+    let cursor = Arc::new(PlanCursor::default());
+    cursor.set_speed(options.speed);
+    cursor.set_volume(options.volume);
+
+    let mut callback = stream_callback::<()>(
+        Arc::new(SampleBuffer::full(samples)),
+        Arc::new(AtomicUsize::new(
+            options.start.unwrap_or(plan[0].range.start),
+        )),
+        Arc::new(PlayheadHistory::new()),
+        StreamToken::current(&Arc::new(AtomicUsize::new(0))),
+        Arc::new(plan.to_vec()),
+        cursor,
+        options.channels,
+    );
+
+    let channels = usize::from(options.channels);
+    let mut rendered = vec![f32::NAN; frames * channels];
+
+    for buf in rendered.chunks_mut(options.buffer_frames * channels) {
+        callback(buf, &());
+    }
+
+    Ok(rendered)
+}
+
+const SOAK_MAX_FRAMES: usize = 4096;
+const SOAK_CHANNELS: u16 = 2;
+
+/// Result of `soak_loop`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SoakReport {
+    pub seed: u64,
+    pub callbacks: usize,
+    pub wraps: usize,
+
+    /// Times the stream was replaced, as by pausing and resuming, with the
+    /// retired callback then fired once more
+    pub restarts: usize,
+
+    /// First broken invariant, if any
+    pub failure: Option<String>,
+
+    /// Buffer length in frames passed to each callback, in order
+    pub buffer_sizes: Vec<usize>,
+}
+
+/// Drive the looped playback callback without an audio device until the
+/// playhead has wrapped `iterations` times, using buffer lengths drawn at
+/// random from `seed`.  After every callback the buffer is checked against
+/// the samples expected at that point in the loop, and the playhead against
+/// where it should have come to rest.  Now and then the stream is replaced
+/// by a fresh one picking up where it left off, and the retired callback is
+/// fired late to check that it leaves the playhead and history alone.
+pub fn soak_loop(
+    samples: &[f32],
+    loop_start: usize,
+    end: usize,
+    iterations: usize,
+    seed: u64,
+) -> Result<SoakReport, Error> {
+    if samples.is_empty() {
+        return Err(Error::InvalidArgument(String::from("No audio samples")));
+    }
+
+    if end > samples.len() {
+        return Err(Error::InvalidLoop(String::from(
+            "End beyond input buffer",
+        )));
+    }
+
+    if loop_start >= end {
+        return Err(Error::InvalidLoop(String::from(
+            "Loop start is AT or AFTER end",
+        )));
+    }
+
+    let channels = usize::from(SOAK_CHANNELS);
+    let buffer = Arc::new(SampleBuffer::full(samples));
+    let playhead = Arc::new(AtomicUsize::new(0));
+    let history = Arc::new(PlayheadHistory::new());
+    let generation = Arc::new(AtomicUsize::new(0));
+    let plan = Arc::new(vec![
+        PlaySegment {
+            range: 0..end,
+            repeats: 1,
+        },
+        PlaySegment {
+            range: loop_start..end,
+            repeats: 0,
+        },
+    ]);
+    let cursor = Arc::new(PlanCursor::default());
+    let new_callback = || {
+        stream_callback::<()>(
+            Arc::clone(&buffer),
+            Arc::clone(&playhead),
+            Arc::clone(&history),
+            StreamToken::current(&generation),
+            Arc::clone(&plan),
+            Arc::clone(&cursor),
+            SOAK_CHANNELS,
+        )
+    };
+    let mut callback = new_callback();
+
+    let mut rng = SplitMix64(seed);
+    let mut report = SoakReport {
+        seed,
+        callbacks: 0,
+        wraps: 0,
+        restarts: 0,
+        failure: None,
+        buffer_sizes: vec![],
+    };
+    let mut expected = 0usize;
+
+    while report.wraps < iterations && report.failure.is_none() {
+        let frames = rng.below(SOAK_MAX_FRAMES) + 1;
+        let mut buf = vec![f32::NAN; frames * channels];
+        report.buffer_sizes.push(frames);
+        report.callbacks += 1;
+
+        let result =
+            std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+                callback(&mut buf, &())
+            }));
+
+        if result.is_err() {
+            report.failure = Some(String::from("Callback panicked"));
+            break;
+        }
+
+        for (frame_idx, frame) in buf.chunks(channels).enumerate() {
+            if frame.iter().any(|s| s.is_nan()) {
+                report.failure =
+                    Some(format!("Frame {} left unwritten", frame_idx));
+            } else if frame.iter().any(|&s| s != samples[expected]) {
+                report.failure = Some(format!(
+                    "Frame {} does not hold sample {}",
+                    frame_idx, expected,
+                ));
+            }
+
+            if report.failure.is_some() {
+                break;
+            }
+
+            expected += 1;
+
+            if expected >= end {
+                expected = loop_start;
+                report.wraps += 1;
+            }
+        }
+
+        let position = playhead.load(Ordering::Relaxed);
+
+        if report.failure.is_none() && position != expected {
+            report.failure = Some(format!(
+                "Playhead at {}, expected {}",
+                position, expected,
+            ));
+        }
+
+        if report.failure.is_none() && rng.below(SOAK_RESTART_ODDS) == 0 {
+            generation.fetch_add(1, Ordering::AcqRel);
+            let mut retired = std::mem::replace(&mut callback, new_callback());
+            report.restarts += 1;
+
+            let writes = history.writes.load(Ordering::Acquire);
+            let mut buf =
+                vec![f32::NAN; (rng.below(SOAK_MAX_FRAMES) + 1) * channels];
+            retired(&mut buf, &());
+
+            if buf.iter().any(|&s| s != f32::EQUILIBRIUM) {
+                report.failure =
+                    Some(String::from("Retired callback wrote samples"));
+            } else if playhead.load(Ordering::Relaxed) != expected
+                || history.writes.load(Ordering::Acquire) != writes
+            {
+                report.failure =
+                    Some(String::from("Retired callback moved the playhead"));
+            }
+        }
+    }
+
+    Ok(report)
+}
+
+// Small, seedable generator for soak buffer lengths and dither; quality is
+// unimportant
+pub(crate) struct SplitMix64(pub(crate) u64);
+
+impl SplitMix64 {
+    pub(crate) fn below(&mut self, bound: usize) -> usize {
+        self.0 = self.0.wrapping_add(0x9e3779b97f4a7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+        z ^= z >> 31;
+        (z % bound as u64) as usize
+    }
+}
+
+/// Playhead position at a moment in time, as recorded by the audio callback
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PlayheadSample {
+    /// Time since the player was created
+    pub timestamp: Duration,
+
+    /// Position in input-rate samples
+    pub position: usize,
+}
+
+// Samples at the playback rate, filled from the front by one writer while
+// the callback reads those ready behind it.  Each sample is held as f32 bits.
+#[derive(Debug)]
+struct SampleBuffer {
+    samples: Box<[AtomicU32]>,
+    ready: AtomicUsize,
+    overshoot: AtomicUsize,
+    failed: AtomicBool,
+}
+
+impl SampleBuffer {
+    fn new(len: usize) -> Self {
+        SampleBuffer {
+            samples: (0..len).map(|_| AtomicU32::new(0)).collect(),
+            ready: AtomicUsize::new(0),
+            overshoot: AtomicUsize::new(0),
+            failed: AtomicBool::new(false),
+        }
+    }
+
+    fn full(samples: &[f32]) -> Self {
+        let buffer = SampleBuffer::new(samples.len());
+        buffer.push(samples);
+        buffer
+    }
+
+    fn len(&self) -> usize {
+        self.samples.len()
+    }
+
+    // Samples from the start that can be read
+    fn ready(&self) -> usize {
+        self.ready.load(Ordering::Acquire)
+    }
+
+    fn is_complete(&self) -> bool {
+        self.ready() == self.len()
+    }
+
+    fn get(&self, index: usize) -> f32 {
+        f32::from_bits(self.samples[index].load(Ordering::Relaxed))
+    }
+
+    fn read(&self, start: usize, out: &mut [f32]) {
+        for (out, sample) in out.iter_mut().zip(&self.samples[start..]) {
+            *out = f32::from_bits(sample.load(Ordering::Relaxed));
+        }
+    }
+
+    fn to_vec(&self) -> Vec<f32> {
+        let mut samples = vec![0.0; self.ready()];
+        self.read(0, &mut samples);
+        samples
+    }
+
+    // Append samples, as far as they fit; only one writer may push
+    fn push(&self, block: &[f32]) {
+        let start = self.ready.load(Ordering::Relaxed);
+        let block_len = block.len().min(self.len() - start);
+
+        for (sample, &value) in self.samples[start..].iter().zip(block) {
+            sample.store(value.to_bits(), Ordering::Relaxed);
+        }
+
+        self.ready.store(start + block_len, Ordering::Release);
+    }
+
+    // Clip and append samples, counting those beyond full scale
+    fn push_clipped(&self, block: &[f32], mode: ClipMode) {
+        let mut block = block.to_vec();
+        let overshoot = clip(&mut block, mode);
+        self.overshoot.fetch_add(overshoot, Ordering::Relaxed);
+        self.push(&block);
+    }
+}
+
+/// Fixed-size ring of (timestamp, playback offset) written by the audio
+/// callback and read from the UI thread.  Each slot is guarded by a sequence
+/// number in the manner of a seqlock: the writer never waits, and the reader
+/// discards any slot that was overwritten while it was being read.  The peak
+/// and RMS level of the latest buffer, as f32 bits, are kept alongside.
+#[derive(Debug)]
+pub struct PlayheadHistory {
+    epoch: Instant,
+    writes: AtomicUsize,
+    slots: [HistorySlot; HISTORY_LEN],
+    peak: AtomicU32,
+    rms: AtomicU32,
+}
+
+#[derive(Debug, Default)]
+struct HistorySlot {
+    // 2n + 1 while write n is in progress, 2n + 2 once complete
+    sequence: AtomicUsize,
+    micros: AtomicU64,
+    offset: AtomicUsize,
+}
+
+impl Default for PlayheadHistory {
+    fn default() -> Self {
+        PlayheadHistory::new()
+    }
+}
+
+impl PlayheadHistory {
+    pub fn new() -> Self {
+        PlayheadHistory {
+            epoch: Instant::now(),
+            writes: AtomicUsize::new(0),
+            slots: std::array::from_fn(|_| HistorySlot::default()),
+            peak: AtomicU32::new(0),
+            rms: AtomicU32::new(0),
+        }
+    }
+
+    fn record_levels(&self, samples: &[f32]) {
+        let mut peak = 0f32;
+        let mut power = 0f32;
+
+        for &sample in samples {
+            peak = peak.max(sample.abs());
+            power += sample * sample;
+        }
+
+        let rms = (power / samples.len().max(1) as f32).sqrt();
+        self.peak.store(peak.to_bits(), Ordering::Relaxed);
+        self.rms.store(rms.to_bits(), Ordering::Relaxed);
+    }
+
+    fn peak(&self) -> f32 {
+        f32::from_bits(self.peak.load(Ordering::Relaxed))
+    }
+
+    fn rms(&self) -> f32 {
+        f32::from_bits(self.rms.load(Ordering::Relaxed))
+    }
+
+    /// Record `offset` as of now.  Only one writer may record at a time; each
+    /// stream's callback is never run concurrently with itself, and a player
+    /// only has one stream at a time.
+    pub fn record(&self, offset: usize) {
+        let write = self.writes.load(Ordering::Relaxed);
+        let slot = &self.slots[write % HISTORY_LEN];
+        let micros =
+            u64::try_from(self.epoch.elapsed().as_micros()).unwrap_or(u64::MAX);
+
+        slot.sequence.store(write * 2 + 1, Ordering::Relaxed);
+        fence(Ordering::Release);
+        slot.micros.store(micros, Ordering::Relaxed);
+        slot.offset.store(offset, Ordering::Relaxed);
+        slot.sequence.store(write * 2 + 2, Ordering::Release);
+        self.writes.store(write + 1, Ordering::Release);
+    }
+
+    /// Recorded (time since creation, offset) pairs, oldest first, leaving out
+    /// any overwritten while being read
+    pub fn read(&self) -> Vec<(Duration, usize)> {
+        let writes = self.writes.load(Ordering::Acquire);
+        let oldest = writes.saturating_sub(HISTORY_LEN);
+
+        (oldest..writes)
+            .filter_map(|write| {
+                let slot = &self.slots[write % HISTORY_LEN];
+                let sequence = slot.sequence.load(Ordering::Acquire);
+                let micros = slot.micros.load(Ordering::Relaxed);
+                let offset = slot.offset.load(Ordering::Relaxed);
+                fence(Ordering::Acquire);
+
+                if sequence == write * 2 + 2
+                    && slot.sequence.load(Ordering::Relaxed) == sequence
+                {
+                    Some((Duration::from_micros(micros), offset))
+                } else {
+                    None
+                }
+            })
+            .collect()
     }
 }
 
@@ -445,6 +2604,9 @@ pub enum PlayerStateTag {
     Playing,
     PlayingLooped,
     Paused,
+
+    /// The stream failed; see `Player::take_error`
+    Errored,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -1,32 +1,297 @@
+use crate::dsp::{self, crossfade, BlendShape, ResampleQuality};
+use crate::player::SplitMix64;
+use crate::Error;
+use crate::Marker;
 use cuet::{ChunkWriter, CuePoint, LabeledText};
 use hound::{WavSpec, WavWriter};
-use std::fs::OpenOptions;
-use std::io::{BufWriter, Read, Seek, SeekFrom};
+use std::ffi::OsString;
+use std::fmt;
+use std::fs::{self, OpenOptions};
+use std::io::{BufWriter, Read, Seek, SeekFrom, Write};
 use std::ops::Range;
 use std::path::Path;
 
 // (Presumed) minimum audible frequency
 const MIN_FREQ: u32 = 50u32;
 
+// Shortest blend window chosen from the loop's content, in milliseconds
+const MIN_BLEND_MS: u32 = 5;
+
+// Length of the end of the loop analyzed for its lowest frequency, in
+// milliseconds
+const BLEND_ANALYSIS_MS: u32 = 250;
+
+// Longest and shortest frames analyzed when scoring the loop seam
+const SEAM_FRAME_MAX: usize = 1024;
+const SEAM_FRAME_MIN: usize = 64;
+
+// Magnitude of a full-scale sample, 0 dBFS
+const FULL_SCALE: f64 = 32768.0;
+
+// Seconds in each window the loudest RMS level is measured over, as for the
+// momentary loudness of ITU-R BS.1770, and the windows each window steps by
+const LOUDNESS_WINDOW_SECONDS: f64 = 0.4;
+const LOUDNESS_WINDOW_STEPS: usize = 4;
+
+// Loops shorter than this, in seconds, are linted as likely to buzz
+const MIN_LOOP_SECONDS: f64 = 0.02;
+
+// Loop points landing on samples louder than this share of full scale are
+// linted as likely to click
+const LOUD_LOOP_POINT: f64 = 0.1;
+
+// Sample rates Quake ports commonly play
+const QUAKE_RATES: [u32; 3] = [11025, 22050, 44100];
+
+// Seam artifact scores at or below this are given as this, e.g. for silence
+const SEAM_SCORE_FLOOR_DB: f64 = -120.0;
+
 #[derive(Debug, Clone, Copy, Eq, PartialEq)]
 pub enum SampleFmt {
     Unsigned8,
     Signed16,
 }
 
+impl SampleFmt {
+    /// Bits each sample is written with
+    pub fn bits_per_sample(self) -> u16 {
+        match self {
+            SampleFmt::Unsigned8 => 8,
+            SampleFmt::Signed16 => 16,
+        }
+    }
+}
+
+/// Change made to a project, as passed to observers registered with
+/// `Project::subscribe`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ChangeEvent {
+    LoopChanged,
+
+    /// Frames overwritten in place
+    SamplesChanged(Range<u32>),
+
+    /// Cue ID, label or chunk format of the loop, the markers kept besides
+    /// it, or the chunks carried through alongside the samples, changed
+    MarkersChanged,
+
+    /// Sample rate, channel count, sample format, or length changed
+    FormatChanged,
+}
+
+type Observer = Box<dyn Fn(&ChangeEvent)>;
+
+/// How much of the loop a blend would rewrite
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BlendImpact {
+    pub window_sz: u32,
+    pub loop_length: u32,
+}
+
+impl BlendImpact {
+    /// Share of the loop covered by the window
+    pub fn fraction(&self) -> f64 {
+        f64::from(self.window_sz) / f64::from(self.loop_length)
+    }
+}
+
+/// Samples a blend would write, as given by `Project::blend_preview`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BlendPreview {
+    /// Frames the blend rewrites, ending at the loop end
+    pub range: Range<u32>,
+
+    /// Replacement samples for `range`, interleaved by channel
+    pub samples: Vec<i16>,
+}
+
+/// Levels measured by `Project::loudness`, in dBFS, taking a full-scale
+/// square wave as 0 dBFS.  Silence measures negative infinity.  No frequency
+/// weighting is applied.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LoudnessStats {
+    pub peak_dbfs: f64,
+    pub rms_dbfs: f64,
+
+    /// Loudest RMS level over windows of 400 ms, each a quarter window on
+    /// from the last, or over all samples when there are fewer
+    pub max_window_rms_dbfs: f64,
+
+    /// RMS level over the loop, if there is one
+    pub loop_rms_dbfs: Option<f64>,
+}
+
+/// Window chosen by `Project::blend_default_window`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BlendWindow {
+    pub window_sz: u32,
+
+    /// Click-free window the loop or the lead before it left no room for
+    pub clamped_from: Option<u32>,
+}
+
+/// Gain applied by `Project::normalize_peak` or `normalize_rms`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Normalization {
+    pub gain_db: f64,
+
+    /// Samples pushed past full scale and clipped
+    pub clipped: usize,
+}
+
+/// How sharply the loop wraps, from `Project::seam_analysis`.  Steps are
+/// given in sample units, where full scale is 32768, and in dBFS of their
+/// magnitude.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SeamReport {
+    /// First sample of the loop less its last: the jump played at the wrap
+    pub amplitude_step: i32,
+    pub amplitude_step_dbfs: f64,
+
+    /// Slope leaving the loop start less the slope reaching the loop end
+    pub slope_step: i32,
+    pub slope_step_dbfs: f64,
+
+    /// Energy the spectrum across the seam holds beyond that of the frames
+    /// around it, as a share of theirs, and in dB as `seam_artifact_score`
+    pub spectral_excess: f64,
+    pub spectral_excess_db: f64,
+}
+
+/// Something `Project::lint` found that writes fine but may sound bad or
+/// confuse Quake
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Warning {
+    /// The loop is shorter than 20 ms
+    ShortLoop { frames: u32, sample_rate: u32 },
+
+    /// A loop point, "start" or "end", lands on a loud sample.  Holds the
+    /// amplitude relative to full scale and how far the nearest zero crossing
+    /// is, if there is one.
+    LoudLoopPoint {
+        point: &'static str,
+        amplitude: f64,
+        crossing_distance: Option<u32>,
+    },
+
+    /// The sample rate is not one Quake ports commonly play
+    UnusualSampleRate(u32),
+
+    /// Samples are written with 16 bits, which vanilla Quake doesn't play
+    SixteenBit,
+}
+
+impl fmt::Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Warning::ShortLoop {
+                frames,
+                sample_rate,
+            } => write!(
+                f,
+                "loop is only {} samples ({:.1} ms) long and may buzz",
+                frames,
+                f64::from(*frames) * 1000.0 / f64::from(*sample_rate),
+            ),
+            Warning::LoudLoopPoint {
+                point,
+                amplitude,
+                crossing_distance,
+            } => {
+                write!(
+                    f,
+                    "loop {} lands at amplitude {:.2}; consider blend (",
+                    point, amplitude,
+                )?;
+
+                match crossing_distance {
+                    Some(distance) => write!(
+                        f,
+                        "nearest zero crossing is {} samples away)",
+                        distance,
+                    ),
+                    None => write!(f, "no zero crossing found)"),
+                }
+            }
+            Warning::UnusualSampleRate(rate) => write!(
+                f,
+                "{} Hz is not one of the 11025, 22050, or 44100 Hz Quake \
+                ports commonly play",
+                rate,
+            ),
+            Warning::SixteenBit => {
+                f.write_str("vanilla Quake plays only 8-bit samples")
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct WriteOptions {
+    /// Flush the written file to disk before returning
+    pub sync: bool,
+
+    /// Encode the samples afresh even if they are unchanged since reading,
+    /// rather than copying the source's fmt and data chunks byte for byte
+    pub reencode: bool,
+}
+
+/// Chunks a loop is written to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LoopChunkFormat {
+    /// A cue point, with a labeled text giving the loop length when needed,
+    /// as Quake reads
+    #[default]
+    Cue,
+
+    /// The only sample loop of a smpl chunk, as many samplers and other
+    /// engines read
+    Smpl,
+
+    Both,
+}
+
 pub struct Project {
+    // Interleaved by channel; loop points are frame indices
     samples: Vec<i16>,
+    channels: u16,
     sample_rate: u32,
     sample_loop: Option<Range<u32>>,
     render_format: SampleFmt,
+    loop_cue_id: u32,
+    loop_label: Option<String>,
+    loop_chunk_format: LoopChunkFormat,
+    play_end: Option<u32>,
+    markers: Vec<Marker>,
+    extra_chunks: Vec<crate::RawChunk>,
+
+    // Chunks the samples were read from, until the samples or format change
+    source: Option<crate::SourceChunks>,
+    observers: Vec<Observer>,
 }
 
 impl Project {
+    /// Read the WAV at `path`; errors name the file
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, Error> {
+        let path = path.as_ref();
+
+        crate::QWaveReader::open(path).and_then(|reader| {
+            Project::from_reader(reader).map_err(|e| e.at_path(path))
+        })
+    }
+
     pub fn from_reader<R: Read + Seek>(
         mut reader: crate::QWaveReader<R>,
-    ) -> Result<Self, String> {
+    ) -> Result<Self, Error> {
         let (samples, metadata) =
-            { (reader.collect_samples()?, reader.metadata()) };
+            { (reader.collect_interleaved()?, reader.metadata()) };
+        let channels = metadata.channels;
+
+        if samples.len() / usize::from(channels) > crate::MAX_FRAMES as usize {
+            return Err(crate::reader::too_long(
+                samples.len() / usize::from(channels),
+            ));
+        }
 
         let sample_loop = metadata
             .loop_start
@@ -34,185 +299,1941 @@ impl Project {
                 if let Some(end) = metadata.end {
                     Ok(start..end)
                 } else {
-                    Ok(start..samples.len().try_into()?)
+                    Ok(start
+                        ..(samples.len() / usize::from(channels)).try_into()?)
                 }
             })
             .transpose()
-            .map_err(|e| e.to_string())?;
+            .map_err(|_| crate::reader::too_long(samples.len()))?;
 
-        let sample_fmt = if metadata.bits_per_sample == 8 {
-            SampleFmt::Unsigned8
-        } else if metadata.bits_per_sample == 16 {
-            SampleFmt::Signed16
-        } else {
-            return Err(String::from("beans"));
+        let sample_fmt = render_format_of(&metadata)?;
+
+        // Loops are written back to the chunks they were found in
+        let loop_chunk_format = match (metadata.loop_source, metadata.smpl_loop)
+        {
+            (Some(crate::LoopSource::Cue), true) => LoopChunkFormat::Both,
+            (Some(crate::LoopSource::Smpl), _) => LoopChunkFormat::Smpl,
+            _ => LoopChunkFormat::Cue,
         };
 
+        // Only 8- and 16-bit data holds the samples exactly as decoded, and
+        // data read with -repair may hold more than the samples decoded; in
+        // either case it can't stand in for them
+        let sample_bytes = usize::from(metadata.bits_per_sample / 8);
+        let exact = !metadata.float_samples
+            && matches!(metadata.bits_per_sample, 8 | 16);
+        let markers = reader.markers().to_vec();
+        let mut source = reader.into_source_chunks().ok();
+        let extra_chunks = source
+            .as_mut()
+            .map(|source| std::mem::take(&mut source.extra))
+            .unwrap_or_default();
+        let source = source.filter(|source| {
+            exact && source.data.len() == samples.len() * sample_bytes
+        });
+
         Ok(Project {
             samples,
+            channels,
             sample_rate: metadata.sample_rate,
             sample_loop,
             render_format: sample_fmt,
+            loop_cue_id: 0,
+            loop_label: None,
+            loop_chunk_format,
+            play_end: metadata.play_end,
+            markers,
+            extra_chunks,
+            source,
+            observers: vec![],
         })
     }
 
+    /// Call `observer` after every change made to the project
+    pub fn subscribe(&mut self, observer: impl Fn(&ChangeEvent) + 'static) {
+        self.observers.push(Box::new(observer));
+    }
+
+    fn notify(&self, event: ChangeEvent) {
+        for observer in &self.observers {
+            observer(&event);
+        }
+    }
+
     pub fn set_loop(&mut self, sample_loop: Option<Range<u32>>) {
-        self.sample_loop = sample_loop;
+        if self.sample_loop != sample_loop {
+            self.sample_loop = sample_loop;
+            self.notify(ChangeEvent::LoopChanged);
+        }
     }
 
-    pub fn sample_rate(&self) -> u32 {
-        self.sample_rate
+    /// Clamp the loop to the samples held by the project, removing it entirely
+    /// if it starts at or after the last sample
+    pub fn clamp_loop(&mut self) {
+        let len = self.sample_count();
+
+        let clamped = self
+            .sample_loop
+            .clone()
+            .map(|sample_loop| sample_loop.start..sample_loop.end.min(len))
+            .filter(|sample_loop| sample_loop.start < sample_loop.end);
+
+        self.set_loop(clamped);
+
+        if let Some(play_end) = self.play_end {
+            self.set_play_end(Some(play_end.min(len)));
+        }
+
+        self.markers.retain(|marker| marker.position <= len);
     }
 
-    pub fn sample_count(&self) -> u32 {
-        self.samples.len().try_into().unwrap()
+    /// Suggest loops of at least `min_length` frames, best first, whose start
+    /// and end lie within `search_range`, or anywhere if none is given.  The
+    /// first channel is searched coarsely in bins, then the best pairs are
+    /// refined sample by sample; see `LoopCandidate` for the score.  Silence
+    /// and audio too short for the search give no candidates.
+    pub fn find_loop(
+        &self,
+        min_length: u32,
+        search_range: Option<Range<u32>>,
+    ) -> Result<Vec<crate::LoopCandidate>, Error> {
+        let range = search_range.unwrap_or(0..self.sample_count());
+
+        if range.start >= range.end || range.end > self.sample_count() {
+            return Err(Error::InvalidArgument(String::from(
+                "Search range must be non-empty and within the file",
+            )));
+        }
+
+        let first_channel = self
+            .samples
+            .iter()
+            .step_by(self.channels.into())
+            .copied()
+            .collect::<Vec<_>>();
+
+        Ok(crate::autoloop::loop_candidates(
+            &first_channel,
+            min_length,
+            range,
+        ))
     }
 
-    pub fn blend(&mut self, window_sz: u32) -> Result<(), String> {
+    /// Move the loop start and end each to the nearest rising zero crossing
+    /// within `max_shift` frames, judged on the first channel, so that both
+    /// sides of the wrap pass upward through zero.  Returns the points moved
+    /// to; an endpoint with no crossing in reach is left where it is, and
+    /// given as none.
+    pub fn snap_loop_to_zero_crossings(
+        &mut self,
+        max_shift: u32,
+    ) -> Result<(Option<u32>, Option<u32>), Error> {
         self.validate()?;
 
-        if let Some(sample_loop) = &self.sample_loop {
-            let loop_width = sample_loop.end - sample_loop.start;
+        let sample_loop =
+            self.sample_loop.clone().ok_or(Error::NoLoop("snap"))?;
+        let first_channel = self
+            .samples
+            .iter()
+            .step_by(self.channels.into())
+            .copied()
+            .collect::<Vec<_>>();
+        let snap = |frame: u32| {
+            dsp::nearest_rising_zero_crossing(
+                &first_channel,
+                frame as usize,
+                max_shift as usize,
+            )
+            .map(|idx| idx as u32)
+        };
 
-            if loop_width == 0 {
-                return Err(String::from("Invalid loop"));
-            }
+        let (start, end) = (snap(sample_loop.start), snap(sample_loop.end));
+        let snapped =
+            start.unwrap_or(sample_loop.start)..end.unwrap_or(sample_loop.end);
 
-            if window_sz > sample_loop.start {
-                return Err(String::from(
-                    "Insufficient lead before loop for blend",
-                ));
-            }
+        if snapped.start >= snapped.end {
+            return Err(Error::InvalidLoop(String::from(
+                "Snapping the loop would leave it empty",
+            )));
+        }
 
-            if window_sz > loop_width {
-                return Err(String::from("Blend window longer than loop"));
-            }
+        self.set_loop(Some(snapped));
+        Ok((start, end))
+    }
 
-            let window_a_start =
-                sample_loop.start as usize - window_sz as usize;
-            let window_b_start = sample_loop.end as usize - window_sz as usize;
+    /// ID given to the loop's cue point on write, 0 unless set
+    pub fn loop_cue_id(&self) -> u32 {
+        self.loop_cue_id
+    }
 
-            for i in 0..window_sz as usize {
-                let weight = cube_step(i as f64 / f64::from(window_sz));
-                let sample_a = self.samples[i + window_a_start] as f64;
-                let sample_b = self.samples[i + window_b_start] as f64;
-                let new_sample = weight * sample_a + (1.0 - weight) * sample_b;
-                self.samples[i + window_b_start] = new_sample.round() as i16;
-            }
-        } else {
-            return Err(String::from("No loop to blend"));
+    pub fn set_loop_cue_id(&mut self, id: u32) {
+        if self.loop_cue_id != id {
+            self.loop_cue_id = id;
+            self.notify(ChangeEvent::MarkersChanged);
         }
+    }
 
-        Ok(())
+    /// Text of the loop's labeled text on write; none unless set
+    pub fn loop_label(&self) -> Option<&str> {
+        self.loop_label.as_deref()
     }
 
-    pub fn blend_default_window(&mut self) -> Result<(), String> {
-        let window_sz = self.sample_rate / MIN_FREQ;
-        self.blend(window_sz)
+    pub fn set_loop_label(&mut self, label: Option<String>) {
+        if self.loop_label != label {
+            self.loop_label = label;
+            self.notify(ChangeEvent::MarkersChanged);
+        }
     }
 
-    pub fn write_to(&self, outpath: &impl AsRef<Path>) -> Result<(), String> {
-        let outfile = OpenOptions::new()
-            .read(true)
-            .write(true)
-            .create(true)
-            .truncate(true)
-            .open(outpath)
-            .map_err(|e| e.to_string())?;
-        let mut writer = BufWriter::new(outfile);
+    /// Chunks the loop is written to: those it was read from, or cue points
+    /// unless set
+    pub fn loop_chunk_format(&self) -> LoopChunkFormat {
+        self.loop_chunk_format
+    }
 
-        let wave_spec = WavSpec {
-            channels: 1,
-            sample_format: hound::SampleFormat::Int,
-            sample_rate: self.sample_rate,
-            bits_per_sample: match self.render_format {
-                SampleFmt::Unsigned8 => 8,
-                SampleFmt::Signed16 => 16,
-            },
+    pub fn set_loop_chunk_format(&mut self, format: LoopChunkFormat) {
+        if self.loop_chunk_format != format {
+            self.loop_chunk_format = format;
+            self.notify(ChangeEvent::MarkersChanged);
+        }
+    }
+
+    /// Frame at which non-looped playback stops, short of the end of the file
+    pub fn play_end(&self) -> Option<u32> {
+        self.play_end
+    }
+
+    pub fn set_play_end(&mut self, play_end: Option<u32>) {
+        if self.play_end != play_end {
+            self.play_end = play_end;
+            self.notify(ChangeEvent::LoopChanged);
+        }
+    }
+
+    /// Cue points kept besides the loop's and the end marker's.  They are
+    /// written only along with a loop in a cue point, as otherwise Quake would
+    /// take the first of them for the loop start.
+    pub fn markers(&self) -> &[Marker] {
+        &self.markers
+    }
+
+    /// Add a marker at frame `position`, returning the cue point ID given it
+    pub fn add_marker(
+        &mut self,
+        position: u32,
+        label: impl Into<String>,
+    ) -> Result<u32, Error> {
+        if position > self.sample_count() {
+            return Err(Error::InvalidArgument(format!(
+                "Marker at sample {} lies past the {} samples in the file",
+                position,
+                self.sample_count(),
+            )));
+        }
+
+        let id = self.next_marker_id();
+        self.markers.push(Marker {
+            id,
+            position,
+            label: label.into(),
+            length: None,
+        });
+        self.notify(ChangeEvent::MarkersChanged);
+
+        Ok(id)
+    }
+
+    /// Remove the marker with cue point ID `id`, returning it
+    pub fn remove_marker(&mut self, id: u32) -> Option<Marker> {
+        let idx = self.markers.iter().position(|marker| marker.id == id)?;
+        let marker = self.markers.remove(idx);
+        self.notify(ChangeEvent::MarkersChanged);
+        Some(marker)
+    }
+
+    // Lowest cue point ID above those of the markers, the loop, and the end
+    // marker
+    fn next_marker_id(&self) -> u32 {
+        self.markers
+            .iter()
+            .map(|marker| marker.id)
+            .chain([self.loop_cue_id, self.loop_cue_id.wrapping_add(1)])
+            .max()
+            .map_or(0, |id| id.wrapping_add(1))
+    }
+
+    /// Chunks read from the source which the project doesn't interpret, such
+    /// as INFO lists and bext chunks, written back unchanged after the loop's
+    pub fn extra_chunks(&self) -> &[crate::RawChunk] {
+        &self.extra_chunks
+    }
+
+    pub fn set_extra_chunks(&mut self, chunks: Vec<crate::RawChunk>) {
+        if self.extra_chunks != chunks {
+            self.extra_chunks = chunks;
+            self.notify(ChangeEvent::MarkersChanged);
+        }
+    }
+
+    pub fn sample_loop(&self) -> Option<Range<u32>> {
+        self.sample_loop.clone()
+    }
+
+    /// Length of the loop in seconds
+    pub fn loop_duration_seconds(&self) -> Option<f64> {
+        self.sample_loop.as_ref().map(|sample_loop| {
+            f64::from(sample_loop.end - sample_loop.start)
+                / f64::from(self.sample_rate.max(1))
+        })
+    }
+
+    /// Metadata a reader would report for the project once written.  The
+    /// project is written to memory and read back, so this costs as much as
+    /// writing does, and fails where writing would.
+    pub fn metadata(&self) -> Result<crate::Metadata, Error> {
+        let mut wave = std::io::Cursor::new(vec![]);
+        self.write(&mut wave)?;
+        wave.set_position(0);
+        Ok(crate::QWaveReader::new(wave)?.metadata())
+    }
+
+    pub fn render_format(&self) -> SampleFmt {
+        self.render_format
+    }
+
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    /// Relabel the samples with a new rate without resampling, changing pitch
+    /// and speed.  Loop points stay on the same samples.
+    pub fn set_sample_rate_raw(&mut self, rate: u32) -> Result<(), Error> {
+        if rate == 0 {
+            return Err(Error::InvalidArgument(String::from(
+                "Sample rate must be non-zero",
+            )));
+        }
+
+        if rate != self.sample_rate {
+            self.sample_rate = rate;
+            self.source = None;
+            self.notify(ChangeEvent::FormatChanged);
+        }
+
+        Ok(())
+    }
+
+    /// Resample to a new rate, keeping pitch and speed.  The loop and end
+    /// marker move to the nearest frames at the same times.
+    pub fn resample(
+        &mut self,
+        rate: u32,
+        quality: ResampleQuality,
+    ) -> Result<(), Error> {
+        if rate == 0 {
+            return Err(Error::InvalidArgument(String::from(
+                "Sample rate must be non-zero",
+            )));
+        }
+
+        if rate == self.sample_rate {
+            return Ok(());
+        }
+
+        let channels = usize::from(self.channels);
+        let resampled = (0..channels)
+            .map(|channel| {
+                let input = self
+                    .samples
+                    .iter()
+                    .skip(channel)
+                    .step_by(channels)
+                    .map(|&s| f32::from(s) / 32768.0)
+                    .collect::<Vec<_>>();
+
+                dsp::resample(&input, self.sample_rate, rate, quality)
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let frames = resampled.first().map_or(0, Vec::len);
+        let frame_limit = u32::try_from(frames)
+            .map_err(|_| crate::reader::too_long(frames))?;
+
+        let inrate = u64::from(self.sample_rate);
+        let scale = |frame: u32| {
+            let scaled =
+                (u64::from(frame) * u64::from(rate) + inrate / 2) / inrate;
+            scaled.min(frame_limit.into()) as u32
         };
 
-        {
-            let mut wav_writer = WavWriter::new(&mut writer, wave_spec)
-                .map_err(|e| e.to_string())?;
+        self.samples = (0..frames)
+            .flat_map(|frame| {
+                resampled.iter().map(move |channel| {
+                    crate::reader::float_to_i16(channel[frame])
+                })
+            })
+            .collect();
+        self.sample_rate = rate;
+        self.sample_loop = self.sample_loop.clone().map(|sample_loop| {
+            scale(sample_loop.start)..scale(sample_loop.end)
+        });
+        self.play_end = self.play_end.map(scale);
 
-            let samples = self.samples.iter().map(match self.render_format {
-                SampleFmt::Unsigned8 => |&s| s >> 8,
-                SampleFmt::Signed16 => |&s| s,
-            });
+        for marker in &mut self.markers {
+            marker.position = scale(marker.position);
+        }
 
-            for s in samples {
-                wav_writer.write_sample(s).map_err(|e| e.to_string())?;
-            }
+        self.source = None;
+        self.notify(ChangeEvent::FormatChanged);
+
+        Ok(())
+    }
+
+    /// Set the format samples are encoded in.  Narrowing to 8 bits applies
+    /// triangular dither drawn from `dither_seed`, so quiet passages don't
+    /// turn into stepped distortion, and the same seed gives the same
+    /// samples.
+    pub fn set_render_format(&mut self, format: SampleFmt, dither_seed: u64) {
+        // The source's data can only be copied while it holds samples of the
+        // depth written
+        let source_bits = self
+            .source
+            .as_ref()
+            .and_then(|source| source.fmt.get(14..16))
+            .map(|bits| u16::from_le_bytes([bits[0], bits[1]]));
 
-            wav_writer.finalize().map_err(|e| e.to_string())?;
+        if source_bits != Some(format.bits_per_sample()) {
+            self.source = None;
         }
 
-        let mut outfile = writer.into_inner().map_err(|e| e.to_string())?;
+        if format == self.render_format {
+            return;
+        }
 
-        if let Some(sample_loop) = &self.sample_loop {
-            outfile
-                .seek(SeekFrom::Start(0))
-                .map_err(|e| e.to_string())?;
-
-            let mut chunk_writer =
-                ChunkWriter::new(outfile).map_err(|e| e.to_string())?;
-
-            let cue = [CuePoint::from_sample_offset(0, sample_loop.start)];
-            chunk_writer
-                .append_cue_chunk(&cue)
-                .map_err(|e| e.to_string())?;
-
-            if self
-                .samples
-                .len()
-                .try_into()
-                .map(|len: u32| len != sample_loop.end)
-                .unwrap_or(true)
-            {
-                let length = sample_loop
-                    .end
-                    .checked_sub(sample_loop.start)
-                    .ok_or("Loop ends before it begins")?;
+        if format == SampleFmt::Unsigned8 {
+            let mut rng = SplitMix64(dither_seed);
 
-                let labeled_text = [LabeledText::from_cue_length(0, length)];
-                chunk_writer
-                    .append_label_chunk(&labeled_text)
-                    .map_err(|e| e.to_string())?;
+            for s in &mut self.samples {
+                let noise = rng.below(256) as i32 + rng.below(256) as i32 - 255;
+                let rounded = (i32::from(*s) + noise + 128) >> 8;
+                *s = (rounded.clamp(-128, 127) << 8) as i16;
             }
         }
 
-        Ok(())
+        self.render_format = format;
+        self.source = None;
+        self.notify(ChangeEvent::FormatChanged);
     }
 
-    pub fn validate(&self) -> Result<(), String> {
-        let len: u32 = self
+    /// Drop the frames from `frame_count` on, clamping the loop and end
+    /// marker to those left
+    pub fn truncate(&mut self, frame_count: u32) {
+        if frame_count >= self.sample_count() {
+            return;
+        }
+
+        self.samples
+            .truncate(frame_count as usize * usize::from(self.channels));
+        self.source = None;
+        self.notify(ChangeEvent::FormatChanged);
+        self.clamp_loop();
+    }
+
+    /// Apply the gain that brings the highest sample to `target_dbfs`, at most
+    /// 0 dBFS.  Silence is left as it is.
+    pub fn normalize_peak(
+        &mut self,
+        target_dbfs: f64,
+    ) -> Result<Normalization, Error> {
+        if target_dbfs.is_nan() || target_dbfs > 0.0 {
+            return Err(Error::InvalidArgument(String::from(
+                "Peak target must be at most 0 dBFS",
+            )));
+        }
+
+        let peak = self
             .samples
-            .len()
-            .try_into()
-            .map_err(|_| "Too many samples")?;
+            .iter()
+            .map(|&s| f64::from(s).abs())
+            .fold(0.0, f64::max);
 
-        if len == 0 {
-            return Err(String::from("No audio samples"));
+        Ok(self.apply_gain(target_dbfs, peak / FULL_SCALE))
+    }
+
+    /// Apply the gain that brings the RMS level of all samples to
+    /// `target_dbfs`, taking a full-scale square wave as 0 dBFS.  Loud
+    /// targets may clip, which the result counts.  Silence is left as it is.
+    pub fn normalize_rms(
+        &mut self,
+        target_dbfs: f64,
+    ) -> Result<Normalization, Error> {
+        if !target_dbfs.is_finite() {
+            return Err(Error::InvalidArgument(String::from(
+                "RMS target must be a finite dBFS value",
+            )));
         }
 
-        if let Some(sample_loop) = &self.sample_loop {
-            if sample_loop.end > len {
-                return Err(String::from("Loop extends beyond file end"));
+        Ok(self.apply_gain(target_dbfs, rms_level(&self.samples)))
+    }
+
+    /// As `normalize_rms`, bringing the RMS level of the loop to
+    /// `target_dbfs`, or of all samples if there is no loop.  The gain is
+    /// applied to every sample.
+    pub fn normalize_loop_rms(
+        &mut self,
+        target_dbfs: f64,
+    ) -> Result<Normalization, Error> {
+        if !target_dbfs.is_finite() {
+            return Err(Error::InvalidArgument(String::from(
+                "RMS target must be a finite dBFS value",
+            )));
+        }
+
+        let level = rms_level(self.loop_samples());
+        Ok(self.apply_gain(target_dbfs, level))
+    }
+
+    /// Measure the peak and RMS levels of all samples, the loudest RMS level
+    /// over short windows, and the RMS level of the loop
+    pub fn loudness(&self) -> LoudnessStats {
+        let channels = usize::from(self.channels);
+        let peak = self
+            .samples
+            .iter()
+            .map(|&s| f64::from(s).abs())
+            .fold(0.0, f64::max);
+
+        let window = ((LOUDNESS_WINDOW_SECONDS * f64::from(self.sample_rate))
+            .round() as usize)
+            .max(1)
+            * channels;
+        let step =
+            (window / LOUDNESS_WINDOW_STEPS / channels).max(1) * channels;
+        let max_window_level = if self.samples.len() <= window {
+            rms_level(&self.samples)
+        } else {
+            (0..=self.samples.len() - window)
+                .step_by(step)
+                .map(|start| rms_level(&self.samples[start..start + window]))
+                .fold(0.0, f64::max)
+        };
+
+        LoudnessStats {
+            peak_dbfs: to_dbfs(peak / FULL_SCALE),
+            rms_dbfs: to_dbfs(rms_level(&self.samples)),
+            max_window_rms_dbfs: to_dbfs(max_window_level),
+            loop_rms_dbfs: self
+                .sample_loop
+                .is_some()
+                .then(|| to_dbfs(rms_level(self.loop_samples()))),
+        }
+    }
+
+    // Samples of every channel within the loop, or all of them without one
+    fn loop_samples(&self) -> &[i16] {
+        let channels = usize::from(self.channels);
+
+        match &self.sample_loop {
+            Some(sample_loop) => {
+                &self.samples[sample_loop.start as usize * channels
+                    ..sample_loop.end as usize * channels]
             }
+            None => &self.samples,
+        }
+    }
 
-            if sample_loop.end < sample_loop.start {
-                return Err(String::from("Loop ends before it begins"));
+    // Scale every sample so a `level` relative to full scale becomes
+    // `target_dbfs`, clamping to the range of an i16
+    fn apply_gain(&mut self, target_dbfs: f64, level: f64) -> Normalization {
+        if level == 0.0 {
+            return Normalization {
+                gain_db: 0.0,
+                clipped: 0,
+            };
+        }
+
+        let gain_db = target_dbfs - 20.0 * level.log10();
+        let gain = 10f64.powf(gain_db / 20.0);
+        let mut clipped = 0usize;
+
+        for s in &mut self.samples {
+            let scaled = (f64::from(*s) * gain).round();
+
+            if scaled.abs() > FULL_SCALE {
+                clipped += 1;
             }
 
-            if sample_loop.end == sample_loop.start {
-                return Err(String::from("Loop length is 0 samples"));
+            *s = scaled.clamp(f64::from(i16::MIN), f64::from(i16::MAX)) as i16;
+        }
+
+        self.source = None;
+        self.notify(ChangeEvent::SamplesChanged(0..self.sample_count()));
+
+        Normalization { gain_db, clipped }
+    }
+
+    /// Subtract from each channel the mean of its samples, rounded, returning
+    /// the offsets removed, one per channel.  Samples pushed past the range of
+    /// an i16 saturate, and a project already centered is left untouched.
+    pub fn remove_dc_offset(&mut self) -> Vec<i16> {
+        let channels = usize::from(self.channels);
+        let frames = self.samples.len() / channels.max(1);
+        let offsets = (0..channels)
+            .map(|channel| {
+                let sum = self
+                    .samples
+                    .iter()
+                    .skip(channel)
+                    .step_by(channels)
+                    .map(|&s| i64::from(s))
+                    .sum::<i64>();
+
+                (sum as f64 / frames.max(1) as f64).round() as i16
+            })
+            .collect::<Vec<_>>();
+
+        if offsets.iter().all(|&offset| offset == 0) {
+            return offsets;
+        }
+
+        for frame in self.samples.chunks_exact_mut(channels) {
+            for (s, &offset) in frame.iter_mut().zip(&offsets) {
+                *s = s.saturating_sub(offset);
             }
         }
 
+        self.source = None;
+        self.notify(ChangeEvent::SamplesChanged(0..self.sample_count()));
+
+        offsets
+    }
+
+    /// Bake the loop into the samples: the lead before it, then the loop
+    /// played `iterations` times, then with `include_tail` whatever follows
+    /// it.  The loop and end marker are removed, as the result plays through
+    /// once, and only markers before the loop are kept.
+    pub fn unroll(
+        &mut self,
+        iterations: u32,
+        include_tail: bool,
+    ) -> Result<(), Error> {
+        self.validate()?;
+
+        let sample_loop =
+            self.sample_loop.clone().ok_or(Error::NoLoop("unroll"))?;
+
+        if iterations == 0 {
+            return Err(Error::InvalidArgument(String::from(
+                "Loop must be played at least once",
+            )));
+        }
+
+        let tail = if include_tail {
+            self.sample_count() - sample_loop.end
+        } else {
+            0
+        };
+        let frames = u64::from(sample_loop.end - sample_loop.start)
+            * u64::from(iterations)
+            + u64::from(sample_loop.start)
+            + u64::from(tail);
+
+        if frames > u64::from(crate::MAX_FRAMES) {
+            return Err(crate::reader::too_long(frames));
+        }
+
+        let channels = usize::from(self.channels);
+        let frame_range = |range: Range<u32>| {
+            range.start as usize * channels..range.end as usize * channels
+        };
+        let mut samples = Vec::with_capacity(frames as usize * channels);
+        samples.extend_from_slice(
+            &self.samples[frame_range(0..sample_loop.start)],
+        );
+
+        for _ in 0..iterations {
+            samples.extend_from_slice(
+                &self.samples[frame_range(sample_loop.clone())],
+            );
+        }
+
+        samples.extend_from_slice(
+            &self.samples[frame_range(sample_loop.end..sample_loop.end + tail)],
+        );
+
+        self.samples = samples;
+        self.markers
+            .retain(|marker| marker.position < sample_loop.start);
+        self.source = None;
+        self.notify(ChangeEvent::FormatChanged);
+        self.set_play_end(None);
+        self.set_loop(None);
+
         Ok(())
     }
-}
 
-fn cube_step(t: f64) -> f64 {
-    t * t * (3.0 - 2.0 * t)
+    /// Insert `before` frames of silence at the start and `after` at the end.
+    /// The loop, end marker, and markers move with the frames they mark, so
+    /// padding after leaves them where they are.
+    pub fn pad(&mut self, before: u32, after: u32) -> Result<(), Error> {
+        let frames = u64::from(self.sample_count())
+            + u64::from(before)
+            + u64::from(after);
+
+        if frames > u64::from(crate::MAX_FRAMES) {
+            return Err(crate::reader::too_long(frames));
+        }
+
+        if before == 0 && after == 0 {
+            return Ok(());
+        }
+
+        let channels = usize::from(self.channels);
+        let mut samples = Vec::with_capacity(frames as usize * channels);
+        samples.resize(before as usize * channels, 0);
+        samples.extend_from_slice(&self.samples);
+        samples.resize(frames as usize * channels, 0);
+
+        let sample_loop = self.sample_loop.clone().map(|sample_loop| {
+            sample_loop.start + before..sample_loop.end + before
+        });
+        let play_end = self.play_end.map(|play_end| play_end + before);
+
+        for marker in &mut self.markers {
+            marker.position += before;
+        }
+
+        self.samples = samples;
+        self.source = None;
+        self.notify(ChangeEvent::FormatChanged);
+        self.set_loop(sample_loop);
+        self.set_play_end(play_end);
+
+        Ok(())
+    }
+
+    /// Add the samples of `other` after this project's, which must have the
+    /// same sample rate and channel count.  This project's loop is kept, or
+    /// failing that `other`'s is moved to where its frames now lie.  An end
+    /// marker is likewise taken from `other`, as this project's would cut off
+    /// the appended frames.  Markers of both are kept, those of `other` given
+    /// new IDs where they clash.
+    pub fn append(&mut self, other: &Project) -> Result<(), Error> {
+        if other.sample_rate != self.sample_rate {
+            return Err(Error::InvalidArgument(format!(
+                "Can't append audio at {} Hz to audio at {} Hz; resample \
+                one to match first",
+                other.sample_rate, self.sample_rate,
+            )));
+        }
+
+        if other.channels != self.channels {
+            return Err(Error::InvalidArgument(format!(
+                "Can't append {}-channel audio to {}-channel audio",
+                other.channels, self.channels,
+            )));
+        }
+
+        let offset = self.sample_count();
+        let frames = u64::from(offset) + u64::from(other.sample_count());
+
+        if frames > u64::from(crate::MAX_FRAMES) {
+            return Err(crate::reader::too_long(frames));
+        }
+
+        let shift =
+            |range: Range<u32>| range.start + offset..range.end + offset;
+        let sample_loop = self
+            .sample_loop
+            .clone()
+            .or_else(|| other.sample_loop.clone().map(shift));
+        let play_end = other.play_end.map(|play_end| play_end + offset);
+
+        for marker in &other.markers {
+            let id = if self.markers.iter().any(|kept| kept.id == marker.id) {
+                self.next_marker_id()
+            } else {
+                marker.id
+            };
+
+            self.markers.push(Marker {
+                id,
+                position: marker.position + offset,
+                label: marker.label.clone(),
+                length: marker.length,
+            });
+        }
+
+        self.samples.extend_from_slice(&other.samples);
+        self.source = None;
+        self.notify(ChangeEvent::FormatChanged);
+        self.set_play_end(play_end);
+        self.set_loop(sample_loop);
+
+        Ok(())
+    }
+
+    /// Append `other` as `append` does, then loop exactly its frames
+    pub fn append_as_loop(&mut self, other: &Project) -> Result<(), Error> {
+        if other.samples.is_empty() {
+            return Err(Error::InvalidArgument(String::from(
+                "No samples to loop in the appended audio",
+            )));
+        }
+
+        let start = self.sample_count();
+        self.append(other)?;
+        self.set_play_end(None);
+        self.set_loop(Some(start..self.sample_count()));
+
+        Ok(())
+    }
+
+    /// Compare with another project sample by sample, as `compare`
+    pub fn diff(&self, other: &Project) -> crate::CompareReport {
+        crate::compare(self, other)
+    }
+
+    /// A new project of only the frames in the loop, looped from its start to
+    /// its end.  The format, loop cue settings, extra chunks, and markers
+    /// within the loop carry over; the end marker is dropped.
+    pub fn extract_loop(&self) -> Result<Project, Error> {
+        self.validate()?;
+
+        let sample_loop =
+            self.sample_loop.clone().ok_or(Error::NoLoop("extract"))?;
+        let channels = usize::from(self.channels);
+        let samples = self.samples[sample_loop.start as usize * channels
+            ..sample_loop.end as usize * channels]
+            .to_vec();
+        let markers = self
+            .markers
+            .iter()
+            .filter(|marker| sample_loop.contains(&marker.position))
+            .map(|marker| crate::Marker {
+                position: marker.position - sample_loop.start,
+                ..marker.clone()
+            })
+            .collect();
+
+        Ok(Project {
+            samples,
+            channels: self.channels,
+            sample_rate: self.sample_rate,
+            sample_loop: Some(0..sample_loop.end - sample_loop.start),
+            render_format: self.render_format,
+            loop_cue_id: self.loop_cue_id,
+            loop_label: self.loop_label.clone(),
+            loop_chunk_format: self.loop_chunk_format,
+            play_end: None,
+            markers,
+            extra_chunks: self.extra_chunks.clone(),
+            source: None,
+            observers: vec![],
+        })
+    }
+
+    /// Crop to the frames in `range`.  The loop and end marker move with the
+    /// frames they mark and are clamped to those left; a loop wholly outside
+    /// the range is dropped, along with the end marker, as is an end marker
+    /// before it.  Markers outside the range are dropped.
+    pub fn trim(&mut self, range: Range<u32>) -> Result<(), Error> {
+        if range.end > self.sample_count() {
+            return Err(Error::InvalidArgument(format!(
+                "Trim ends at sample {}, past the {} samples in the file",
+                range.end,
+                self.sample_count(),
+            )));
+        }
+
+        if range.start >= range.end {
+            return Err(Error::InvalidArgument(String::from(
+                "Trim would leave no samples",
+            )));
+        }
+
+        if range == (0..self.sample_count()) {
+            return Ok(());
+        }
+
+        let channels = usize::from(self.channels);
+        self.samples.truncate(range.end as usize * channels);
+        self.samples.drain(..range.start as usize * channels);
+        self.source = None;
+        self.notify(ChangeEvent::FormatChanged);
+
+        let len = range.end - range.start;
+        let shift = |frame: u32| frame.saturating_sub(range.start).min(len);
+        let sample_loop = self
+            .sample_loop
+            .clone()
+            .map(|sample_loop| shift(sample_loop.start)..shift(sample_loop.end))
+            .filter(|sample_loop| sample_loop.start < sample_loop.end);
+
+        if sample_loop.is_none() {
+            self.set_play_end(None);
+        }
+
+        self.set_loop(sample_loop);
+
+        if let Some(play_end) = self.play_end {
+            self.set_play_end(Some(shift(play_end)).filter(|&end| end > 0));
+        }
+
+        self.markers.retain(|marker| {
+            (range.start..=range.end).contains(&marker.position)
+        });
+
+        for marker in &mut self.markers {
+            marker.position -= range.start;
+        }
+
+        Ok(())
+    }
+
+    /// Samples interleaved by channel
+    pub fn samples(&self) -> &[i16] {
+        &self.samples
+    }
+
+    /// Replace the samples with ones processed elsewhere, interleaved by
+    /// channel as `samples` gives them.  Given fewer frames, the loop end and
+    /// end marker are clamped to those left; a loop starting past them is an
+    /// error, and the project is left unchanged.
+    pub fn replace_samples(&mut self, samples: Vec<i16>) -> Result<(), Error> {
+        if !samples.len().is_multiple_of(self.channels.into()) {
+            return Err(Error::InvalidArgument(String::from(
+                "Sample count is not a multiple of the channel count",
+            )));
+        }
+
+        let frames = samples.len() / usize::from(self.channels);
+        let len = u32::try_from(frames)
+            .map_err(|_| crate::reader::too_long(frames))?;
+
+        if len == 0 {
+            return Err(Error::InvalidArgument(String::from(
+                "No audio samples",
+            )));
+        }
+
+        match &self.sample_loop {
+            Some(sample_loop) if sample_loop.start >= len => {
+                return Err(Error::InvalidLoop(format!(
+                    "Loop starts at sample {}, past the {} samples given",
+                    sample_loop.start, len,
+                )));
+            }
+            _ => {}
+        }
+
+        let event = if samples.len() == self.samples.len() {
+            ChangeEvent::SamplesChanged(0..len)
+        } else {
+            ChangeEvent::FormatChanged
+        };
+
+        self.samples = samples;
+        self.source = None;
+        self.notify(event);
+        self.clamp_loop();
+
+        Ok(())
+    }
+
+    pub fn channels(&self) -> u16 {
+        self.channels
+    }
+
+    /// Number of frames, i.e. samples per channel.  Never more than
+    /// `MAX_FRAMES`, as longer files are refused on read.
+    pub fn sample_count(&self) -> u32 {
+        self.frame_count().try_into().unwrap()
+    }
+
+    fn frame_count(&self) -> usize {
+        self.samples.len() / usize::from(self.channels)
+    }
+
+    pub fn blend(&mut self, window_sz: u32) -> Result<(), Error> {
+        self.blend_with_shape(window_sz, BlendShape::default())
+    }
+
+    /// Blend as with `blend`, weighting the fade by `shape` rather than
+    /// smoothstep.  `BlendShape::EqualPower` avoids the dip in level that
+    /// smoothstep leaves mid-window on sustained tones.
+    pub fn blend_with_shape(
+        &mut self,
+        window_sz: u32,
+        shape: BlendShape,
+    ) -> Result<(), Error> {
+        let sample_loop = self.blend_loop(window_sz)?;
+
+        self.fade_window(
+            sample_loop.end - window_sz,
+            sample_loop.start - window_sz,
+            sample_loop.end - window_sz,
+            window_sz,
+            shape,
+        )
+    }
+
+    /// Smooth the transition from the lead into the loop by fading the first
+    /// `window_sz` samples of the loop in from the `window_sz` samples of lead
+    /// just before it.  The lead and the rest of the loop, including the
+    /// samples before the loop end, are left untouched.
+    pub fn blend_entry(
+        &mut self,
+        window_sz: u32,
+        shape: BlendShape,
+    ) -> Result<(), Error> {
+        let sample_loop = self.blend_loop(window_sz)?;
+
+        self.fade_window(
+            sample_loop.start - window_sz,
+            sample_loop.start,
+            sample_loop.start,
+            window_sz,
+            shape,
+        )
+    }
+
+    /// Crossfade across both seams at once: the `window_sz` frames before the
+    /// loop end fade toward the material before the loop start, and the
+    /// `window_sz` frames after the loop start fade in from the material after
+    /// the loop end, meeting halfway at the wrap.  Needs `window_sz` frames
+    /// before the loop start and after the loop end, and a loop at least twice
+    /// the window.  The first entry into the loop passes through the middle of
+    /// the fade.
+    pub fn blend_symmetric(
+        &mut self,
+        window_sz: u32,
+        shape: BlendShape,
+    ) -> Result<(), Error> {
+        let sample_loop = self.blend_loop(window_sz)?;
+
+        let loop_width = sample_loop.end - sample_loop.start;
+
+        if u64::from(window_sz) * 2 > u64::from(loop_width) {
+            return Err(Error::InvalidArgument(format!(
+                "Symmetric blend window of {} samples is longer than half the \
+                {}-sample loop; blend over at most {} samples",
+                window_sz,
+                loop_width,
+                loop_width / 2,
+            )));
+        }
+
+        if u64::from(sample_loop.end) + u64::from(window_sz)
+            > u64::from(self.sample_count())
+        {
+            return Err(Error::InvalidArgument(format!(
+                "Insufficient tail after loop for symmetric blend; pad the end \
+                by at least {} samples",
+                sample_loop.end + window_sz - self.sample_count(),
+            )));
+        }
+
+        // One fade spanning the wrap, its first half written before the loop
+        // end and its second after the loop start
+        let faded = self.faded_window(
+            sample_loop.end - window_sz,
+            sample_loop.start - window_sz,
+            window_sz * 2,
+            shape,
+        )?;
+        let (before_end, after_start) =
+            faded.split_at(window_sz as usize * usize::from(self.channels));
+
+        self.write_window(sample_loop.end - window_sz, before_end);
+        self.write_window(sample_loop.start, after_start);
+
+        Ok(())
+    }
+
+    /// Measure how much of the loop blending over `window_sz` samples would
+    /// rewrite.  Fails as `blend` would if the window doesn't fit.
+    pub fn blend_impact(&self, window_sz: u32) -> Result<BlendImpact, Error> {
+        let sample_loop = self.blend_loop(window_sz)?;
+
+        Ok(BlendImpact {
+            window_sz,
+            loop_length: sample_loop.end - sample_loop.start,
+        })
+    }
+
+    // Loop to blend, provided the window fits both the loop and the lead
+    // before it
+    fn blend_loop(&self, window_sz: u32) -> Result<Range<u32>, Error> {
+        self.validate()?;
+
+        let sample_loop =
+            self.sample_loop.clone().ok_or(Error::NoLoop("blend"))?;
+        let loop_width = sample_loop.end - sample_loop.start;
+
+        if loop_width == 0 {
+            return Err(Error::InvalidLoop(String::from("Invalid loop")));
+        }
+
+        let max_window_sz = sample_loop.start.min(loop_width);
+
+        if window_sz > sample_loop.start && sample_loop.start == 0 {
+            return Err(Error::InvalidArgument(format!(
+                "Loop starts at sample 0, leaving no lead before it to blend \
+                from; pad the start by at least {} samples",
+                window_sz,
+            )));
+        }
+
+        if window_sz > sample_loop.start {
+            return Err(Error::InvalidArgument(format!(
+                "Blend window of {} samples is longer than the {} samples of \
+                lead before the loop; blend over at most {} samples, or pad \
+                the start by at least {} samples",
+                window_sz,
+                sample_loop.start,
+                max_window_sz,
+                window_sz - sample_loop.start,
+            )));
+        }
+
+        if window_sz > loop_width {
+            return Err(Error::InvalidArgument(format!(
+                "Blend window of {} samples is longer than the {}-sample loop; \
+                blend over at most {} samples",
+                window_sz, loop_width, max_window_sz,
+            )));
+        }
+
+        Ok(sample_loop)
+    }
+
+    /// The samples `blend` would write, without changing the project
+    pub fn blend_preview(&self, window_sz: u32) -> Result<BlendPreview, Error> {
+        let sample_loop = self.blend_loop(window_sz)?;
+        let from = sample_loop.end - window_sz;
+        let samples = self.faded_window(
+            from,
+            sample_loop.start - window_sz,
+            window_sz,
+            BlendShape::default(),
+        )?;
+
+        Ok(BlendPreview {
+            range: from..sample_loop.end,
+            samples,
+        })
+    }
+
+    // Fade the frames starting at `from` out and those starting at `to` in,
+    // overwriting the frames starting at `dest`
+    fn fade_window(
+        &mut self,
+        from: u32,
+        to: u32,
+        dest: u32,
+        window_sz: u32,
+        shape: BlendShape,
+    ) -> Result<(), Error> {
+        let faded = self.faded_window(from, to, window_sz, shape)?;
+        self.write_window(dest, &faded);
+
+        Ok(())
+    }
+
+    // Overwrite the frames starting at `from` with interleaved samples
+    fn write_window(&mut self, from: u32, samples: &[i16]) {
+        let channels = usize::from(self.channels);
+        let offset = from as usize * channels;
+        self.samples[offset..offset + samples.len()].copy_from_slice(samples);
+
+        let frames = u32::try_from(samples.len() / channels).unwrap();
+        self.source = None;
+        self.notify(ChangeEvent::SamplesChanged(from..from + frames));
+    }
+
+    // Interleaved samples of the window at `from` faded into the one at `to`
+    fn faded_window(
+        &self,
+        from: u32,
+        to: u32,
+        window_sz: u32,
+        shape: BlendShape,
+    ) -> Result<Vec<i16>, Error> {
+        let channels = usize::from(self.channels);
+        let window_sz = window_sz as usize;
+        let mut faded = vec![0i16; window_sz * channels];
+
+        for ch in 0..channels {
+            let channel_window = |start: u32| {
+                self.samples[start as usize * channels + ch..]
+                    .iter()
+                    .step_by(channels)
+                    .take(window_sz)
+                    .copied()
+                    .collect::<Vec<_>>()
+            };
+
+            let window_to = channel_window(to);
+            let window_from = channel_window(from);
+            let mut blended = vec![0i16; window_sz];
+            crossfade(&mut blended, &window_to, &window_from, shape)?;
+
+            for (i, sample) in blended.into_iter().enumerate() {
+                faded[i * channels + ch] = sample;
+            }
+        }
+
+        Ok(faded)
+    }
+
+    /// Blend as with `blend`, but shrink the window to the largest that fits
+    /// both the lead before the loop and the loop itself.  Returns the window
+    /// size actually used.
+    pub fn blend_clamped(&mut self, window_sz: u32) -> Result<u32, Error> {
+        let window_sz = self.clamp_blend_window(window_sz)?;
+        self.blend(window_sz)?;
+        Ok(window_sz)
+    }
+
+    /// Shrink a blend window to the largest that fits both the lead before
+    /// the loop and the loop itself
+    pub fn clamp_blend_window(&self, window_sz: u32) -> Result<u32, Error> {
+        Ok(window_sz.min(self.max_blend_window()?))
+    }
+
+    /// Largest blend window that fits both the lead before the loop and the
+    /// loop itself.  Fails if the loop starts at the first sample, leaving no
+    /// lead to blend from.
+    pub fn max_blend_window(&self) -> Result<u32, Error> {
+        self.validate()?;
+
+        let sample_loop =
+            self.sample_loop.as_ref().ok_or(Error::NoLoop("blend"))?;
+
+        if sample_loop.start == 0 {
+            return Err(Error::InvalidArgument(String::from(
+                "Loop starts at sample 0, leaving no lead before it to blend \
+                from; pad the start",
+            )));
+        }
+
+        Ok(sample_loop.start.min(sample_loop.end - sample_loop.start))
+    }
+
+    /// `click_free_blend_window`, kept to the most the loop and the lead
+    /// before it allow
+    pub fn default_blend_window(&self) -> u32 {
+        let window_sz = self.click_free_blend_window();
+
+        self.max_blend_window()
+            .map_or(window_sz, |max_window_sz| window_sz.min(max_window_sz))
+    }
+
+    /// Blend window expected to eliminate clicks: two periods of the lowest
+    /// significant frequency near the end of the loop, at least 5 ms.  Falls
+    /// back to a period of the lowest presumed audible frequency when there is
+    /// no loop to analyze or no frequency is found.  The loop and the lead
+    /// before it may not leave room for it.
+    pub fn click_free_blend_window(&self) -> u32 {
+        let fallback = self.sample_rate / MIN_FREQ;
+
+        let Some(sample_loop) =
+            self.sample_loop.clone().filter(|sample_loop| {
+                sample_loop.start < sample_loop.end
+                    && sample_loop.end <= self.sample_count()
+            })
+        else {
+            return fallback;
+        };
+
+        let analysis_len = (self.sample_rate / 1000 * BLEND_ANALYSIS_MS)
+            .min(sample_loop.end - sample_loop.start);
+        let channels = usize::from(self.channels);
+        let tail = self.samples[(sample_loop.end - analysis_len) as usize
+            * channels
+            ..sample_loop.end as usize * channels]
+            .iter()
+            .step_by(channels)
+            .copied()
+            .collect::<Vec<_>>();
+
+        crate::dsp::lowest_frequency(&tail, self.sample_rate)
+            .map(|freq| {
+                (2.0 * f64::from(self.sample_rate) / freq).round() as u32
+            })
+            .map(|window_sz| {
+                window_sz.max(self.sample_rate / 1000 * MIN_BLEND_MS)
+            })
+            .unwrap_or(fallback)
+    }
+
+    /// Blend over `default_blend_window`, returning the window used and, if
+    /// the loop or the lead before it left room for less, the click-free
+    /// window it was shrunk from
+    pub fn blend_default_window(&mut self) -> Result<BlendWindow, Error> {
+        let click_free = self.click_free_blend_window();
+        let window_sz = click_free.min(self.max_blend_window()?);
+        self.blend(window_sz)?;
+
+        Ok(BlendWindow {
+            window_sz,
+            clamped_from: (window_sz < click_free).then_some(click_free),
+        })
+    }
+
+    /// How much the spectrum straddling the loop seam holds beyond what the
+    /// frames around it do, in dB relative to their average energy.  The loop
+    /// is played through the wrap offline, and the power spectrum of a frame
+    /// centered on the seam is compared against that of frames one and two
+    /// frame lengths either side.  Clicks and mismatched endpoints spread
+    /// energy across the spectrum and score higher; seamless loops score near
+    /// the floor of -120 dB.
+    pub fn seam_artifact_score(&self) -> Result<f64, Error> {
+        Ok(self.seam_analysis()?.spectral_excess_db)
+    }
+
+    /// Measure the loop seam: the step in amplitude and in slope from the
+    /// end of the loop to its start, and the spectral excess scored by
+    /// `seam_artifact_score`.  Only the first channel is measured.
+    pub fn seam_analysis(&self) -> Result<SeamReport, Error> {
+        let spectral_excess = self.seam_spectral_excess()?;
+
+        // Scoring the spectrum checks the loop, and that it holds far more
+        // than the two samples either side of the seam measured here
+        let sample_loop =
+            self.sample_loop.clone().ok_or(Error::NoLoop("score"))?;
+        let channels = usize::from(self.channels);
+        let at =
+            |frame: u32| i32::from(self.samples[frame as usize * channels]);
+        let (start, end) = (sample_loop.start, sample_loop.end);
+
+        let amplitude_step = at(start) - at(end - 1);
+        let slope_step =
+            (at(start + 1) - at(start)) - (at(end - 1) - at(end - 2));
+        let step_dbfs = |step: i32| to_dbfs(f64::from(step).abs() / FULL_SCALE);
+        let floor = 10f64.powf(SEAM_SCORE_FLOOR_DB / 10.0);
+
+        Ok(SeamReport {
+            amplitude_step,
+            amplitude_step_dbfs: step_dbfs(amplitude_step),
+            slope_step,
+            slope_step_dbfs: step_dbfs(slope_step),
+            spectral_excess,
+            spectral_excess_db: 10.0 * spectral_excess.max(floor).log10(),
+        })
+    }
+
+    // Energy the spectrum across the seam holds beyond that of the frames
+    // around it, as a share of theirs, as `seam_artifact_score` describes
+    fn seam_spectral_excess(&self) -> Result<f64, Error> {
+        self.validate()?;
+
+        let sample_loop =
+            self.sample_loop.clone().ok_or(Error::NoLoop("score"))?;
+        let loop_len = (sample_loop.end - sample_loop.start) as usize;
+
+        // Largest power of two leaving room for two and a half frames either
+        // side of the seam
+        let frame_len = (loop_len * 2 / 5)
+            .checked_ilog2()
+            .map_or(0, |bits| 1usize << bits)
+            .min(SEAM_FRAME_MAX);
+
+        if frame_len < SEAM_FRAME_MIN {
+            return Err(Error::InvalidLoop(String::from(
+                "Loop too short to score its seam",
+            )));
+        }
+
+        let channels = usize::from(self.channels);
+        let samples = self
+            .samples
+            .iter()
+            .step_by(channels)
+            .map(|&s| f32::from(s) / -f32::from(i16::MIN))
+            .collect::<Vec<_>>();
+
+        let plan = [crate::PlaySegment {
+            range: sample_loop.start as usize..sample_loop.end as usize,
+            repeats: 0,
+        }];
+        let wrapped =
+            crate::render_plan(&samples, &plan, loop_len * 2, loop_len)?;
+
+        let spectrum = |center: usize| {
+            let frame = wrapped[center - frame_len / 2..center + frame_len / 2]
+                .iter()
+                .map(|&s| f64::from(s))
+                .collect::<Vec<_>>();
+            crate::dsp::power_spectrum(&frame)
+        };
+
+        let seam = spectrum(loop_len)?;
+        let neighbors = [
+            loop_len - 2 * frame_len,
+            loop_len - frame_len,
+            loop_len + frame_len,
+            loop_len + 2 * frame_len,
+        ]
+        .into_iter()
+        .map(spectrum)
+        .collect::<Result<Vec<_>, _>>()?;
+
+        let mut excess = 0f64;
+        let mut total = 0f64;
+
+        for (bin, &power) in seam.iter().enumerate() {
+            let average = neighbors.iter().map(|n| n[bin]).sum::<f64>()
+                / neighbors.len() as f64;
+            excess += (power - average).max(0.0);
+            total += average;
+        }
+
+        Ok(if total > 0.0 { excess / total } else { 0.0 })
+    }
+
+    pub fn write_to(&self, outpath: &impl AsRef<Path>) -> Result<(), Error> {
+        self.write_to_with(outpath, WriteOptions::default())
+    }
+
+    /// Write the project as a WAV.  Output depends only on the project and
+    /// options, so writing the same project twice gives identical bytes:
+    /// chunks always come in the order fmt, data, cue, LIST, smpl, then the
+    /// extra chunks in the order they were read, nothing written depends on
+    /// the time or environment, and every odd-sized chunk is followed by a
+    /// zero pad byte counted in the RIFF size but not the chunk's.  Which of
+    /// cue, LIST, and smpl are written depends on the loop chunk format.
+    /// Dither, when narrowing to 8 bits, is drawn from the seed given to
+    /// `set_render_format`, so it too is the same each time.
+    ///
+    /// An existing file, which may be the one the project was read from, is
+    /// only replaced once the new one is complete: it is written first to
+    /// `.<name>.tmp` beside it, then renamed over it, and left as it was if
+    /// writing fails.
+    pub fn write_to_with(
+        &self,
+        outpath: &impl AsRef<Path>,
+        options: WriteOptions,
+    ) -> Result<(), Error> {
+        self.validate()?;
+
+        let outpath = outpath.as_ref();
+        let existing = fs::metadata(outpath)
+            .ok()
+            .filter(|metadata| metadata.is_file())
+            .map(|_| fs::canonicalize(outpath))
+            .transpose()?;
+
+        let Some(target) = existing else {
+            return self.write_file(outpath, options);
+        };
+
+        let mut name = OsString::from(".");
+        name.push(target.file_name().unwrap_or_default());
+        name.push(".tmp");
+        let temp = target.with_file_name(name);
+
+        let written = self.write_file(&temp, options).and_then(|_| {
+            let permissions = fs::metadata(&target)?.permissions();
+            fs::set_permissions(&temp, permissions)?;
+            Ok(fs::rename(&temp, &target)?)
+        });
+
+        if written.is_err() {
+            let _ = fs::remove_file(&temp);
+        }
+
+        written
+    }
+
+    // Write a new file, or truncate and write over one
+    fn write_file(
+        &self,
+        outpath: &Path,
+        options: WriteOptions,
+    ) -> Result<(), Error> {
+        let mut outfile = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(outpath)?;
+
+        self.write_with(&mut outfile, options)?;
+
+        if options.sync {
+            outfile.sync_all()?;
+        }
+
+        Ok(())
+    }
+
+    pub fn write<W: Read + Write + Seek>(
+        &self,
+        writer: W,
+    ) -> Result<(), Error> {
+        self.write_with(writer, WriteOptions::default())
+    }
+
+    /// Write the project as a WAV from the start of `writer`, which should be
+    /// empty, as `write_to_with` writes a file.  Appending the loop's chunks
+    /// reads back the RIFF header, hence `Read`.  Only files can be synced, so
+    /// `options.sync` is left to the caller.
+    pub fn write_with<W: Read + Write + Seek>(
+        &self,
+        mut outfile: W,
+        options: WriteOptions,
+    ) -> Result<(), Error> {
+        self.validate()?;
+        outfile.seek(SeekFrom::Start(0))?;
+        let mut writer = BufWriter::new(&mut outfile);
+
+        match (&self.source, options.reencode) {
+            (Some(source), false) => {
+                write_source(&mut writer, source).map_err(Error::from)
+            }
+            _ => self.encode(&mut writer).map_err(Error::from),
+        }?;
+
+        writer.into_inner().map_err(|e| e.into_error())?;
+
+        if let Some(sample_loop) = &self.sample_loop {
+            outfile.seek(SeekFrom::Start(0))?;
+
+            if self.loop_chunk_format != LoopChunkFormat::Smpl {
+                // The end marker's cue follows the loop's, as Quake takes the
+                // first cue point for the loop start, and markers follow both
+                let end_cue_id = self.loop_cue_id.wrapping_add(1);
+                let markers = self.written_markers();
+                let mut cues = vec![CuePoint::from_sample_offset(
+                    self.loop_cue_id,
+                    sample_loop.start,
+                )];
+
+                if let Some(play_end) = self.play_end {
+                    cues.push(CuePoint::from_sample_offset(
+                        end_cue_id, play_end,
+                    ));
+                }
+
+                cues.extend(markers.iter().map(|(id, marker)| {
+                    CuePoint::from_sample_offset(*id, marker.position)
+                }));
+
+                ChunkWriter::new(&mut outfile)?.append_cue_chunk(&cues)?;
+
+                // The loop's length is always written, even when it runs to
+                // the end of the file, as other tools take a cue point without
+                // one for a marker
+                let length = sample_loop
+                    .end
+                    .checked_sub(sample_loop.start)
+                    .ok_or(Error::InvalidLoop(String::from(
+                        "Loop ends before it begins",
+                    )))?;
+
+                let mut labeled_text =
+                    LabeledText::from_cue_length(self.loop_cue_id, length);
+
+                if let Some(label) = &self.loop_label {
+                    labeled_text.text.clone_from(label);
+                }
+
+                let mut labeled_texts = vec![labeled_text];
+
+                if self.play_end.is_some() {
+                    let mut end_text =
+                        LabeledText::from_cue_length(end_cue_id, 0);
+                    end_text.text =
+                        String::from(crate::reader::END_MARKER_LABEL);
+                    labeled_texts.push(end_text);
+                }
+
+                let labels = markers
+                    .iter()
+                    .filter(|(_, marker)| !marker.label.is_empty())
+                    .map(|(id, marker)| (*id, &marker.label[..]))
+                    .collect::<Vec<_>>();
+
+                append_chunk(
+                    &mut outfile,
+                    b"LIST",
+                    &adtl_body(&labeled_texts, &labels),
+                )?;
+            }
+
+            if self.loop_chunk_format != LoopChunkFormat::Cue {
+                append_smpl_chunk(
+                    &mut outfile,
+                    self.sample_rate,
+                    self.loop_cue_id,
+                    sample_loop,
+                )?;
+            }
+        }
+
+        for chunk in &self.extra_chunks {
+            append_chunk(&mut outfile, &chunk.tag, &chunk.body)?;
+        }
+
+        Ok(outfile.flush()?)
+    }
+
+    // Write the samples as a new WAV holding only fmt and data chunks
+    fn encode<W: Write + Seek>(&self, mut writer: W) -> hound::Result<()> {
+        let wave_spec = WavSpec {
+            channels: self.channels,
+            sample_format: hound::SampleFormat::Int,
+            sample_rate: self.sample_rate,
+            bits_per_sample: self.render_format.bits_per_sample(),
+        };
+
+        let mut wav_writer = WavWriter::new(&mut writer, wave_spec)?;
+
+        let samples = self.samples.iter().map(match self.render_format {
+            SampleFmt::Unsigned8 => |&s| s >> 8,
+            SampleFmt::Signed16 => |&s| s,
+        });
+
+        for s in samples {
+            wav_writer.write_sample(s)?;
+        }
+
+        wav_writer.finalize()?;
+
+        // hound leaves an odd-sized data chunk unpadded, which chunks
+        // appended after it can't tolerate
+        let len = writer.seek(SeekFrom::End(0))?;
+
+        if len % 2 == 1 {
+            writer.write_all(&[0])?;
+            writer.seek(SeekFrom::Start(4))?;
+            writer.write_all(
+                &u32::try_from(len + 1 - 8).unwrap().to_le_bytes(),
+            )?;
+        }
+
+        Ok(())
+    }
+
+    // Markers with the cue point IDs they're written with.  Those whose IDs
+    // are taken by the loop or end marker are given IDs above all others.
+    fn written_markers(&self) -> Vec<(u32, &Marker)> {
+        let mut reserved = vec![self.loop_cue_id];
+
+        if self.play_end.is_some() {
+            reserved.push(self.loop_cue_id.wrapping_add(1));
+        }
+
+        let mut next_id = self.next_marker_id();
+
+        self.markers
+            .iter()
+            .map(|marker| {
+                if reserved.contains(&marker.id) {
+                    next_id = next_id.wrapping_add(1);
+                    (next_id.wrapping_sub(1), marker)
+                } else {
+                    (marker.id, marker)
+                }
+            })
+            .collect()
+    }
+
+    /// Find what would write fine but may sound bad or confuse Quake.  Loop
+    /// points are checked in the first channel.
+    pub fn lint(&self) -> Vec<Warning> {
+        let mut warnings = vec![];
+
+        if !QUAKE_RATES.contains(&self.sample_rate) {
+            warnings.push(Warning::UnusualSampleRate(self.sample_rate));
+        }
+
+        if self.render_format == SampleFmt::Signed16 {
+            warnings.push(Warning::SixteenBit);
+        }
+
+        let Some(sample_loop) = &self.sample_loop else {
+            return warnings;
+        };
+
+        let frames = sample_loop.end.saturating_sub(sample_loop.start);
+
+        if f64::from(frames) < MIN_LOOP_SECONDS * f64::from(self.sample_rate) {
+            warnings.push(Warning::ShortLoop {
+                frames,
+                sample_rate: self.sample_rate,
+            });
+        }
+
+        let first_channel = self
+            .samples
+            .iter()
+            .step_by(usize::from(self.channels).max(1))
+            .copied()
+            .collect::<Vec<_>>();
+        let points = [
+            ("start", sample_loop.start as usize),
+            ("end", (sample_loop.end as usize).saturating_sub(1)),
+        ];
+
+        for (point, position) in points {
+            let Some(&sample) = first_channel.get(position) else {
+                continue;
+            };
+            let amplitude = f64::from(sample) / FULL_SCALE;
+
+            if amplitude.abs() > LOUD_LOOP_POINT {
+                let crossing_distance = dsp::nearest_zero_crossing(
+                    &first_channel,
+                    position,
+                    first_channel.len(),
+                )
+                .map(|idx| idx.abs_diff(position) as u32);
+
+                warnings.push(Warning::LoudLoopPoint {
+                    point,
+                    amplitude,
+                    crossing_distance,
+                });
+            }
+        }
+
+        warnings
+    }
+
+    pub fn validate(&self) -> Result<(), Error> {
+        if self.channels == 0 {
+            return Err(Error::InvalidArgument(String::from(
+                "Channel count must be non-zero",
+            )));
+        }
+
+        if !self.samples.len().is_multiple_of(self.channels.into()) {
+            return Err(Error::InvalidArgument(String::from(
+                "Sample count is not a multiple of the channel count",
+            )));
+        }
+
+        let len: u32 = self
+            .frame_count()
+            .try_into()
+            .map_err(|_| crate::reader::too_long(self.frame_count()))?;
+
+        if len == 0 {
+            return Err(Error::InvalidArgument(String::from(
+                "No audio samples",
+            )));
+        }
+
+        if let Some(sample_loop) = &self.sample_loop {
+            if sample_loop.end > len {
+                return Err(Error::InvalidLoop(String::from(
+                    "Loop extends beyond file end",
+                )));
+            }
+
+            if sample_loop.end < sample_loop.start {
+                return Err(Error::InvalidLoop(String::from(
+                    "Loop ends before it begins",
+                )));
+            }
+
+            if sample_loop.end == sample_loop.start {
+                return Err(Error::InvalidLoop(String::from(
+                    "Loop length is 0 samples",
+                )));
+            }
+        }
+
+        if let Some(play_end) = self.play_end {
+            if self.sample_loop.is_none() {
+                return Err(Error::InvalidLoop(String::from(
+                    "End marker requires a loop",
+                )));
+            }
+
+            if play_end > len {
+                return Err(Error::InvalidLoop(String::from(
+                    "End marker beyond file end",
+                )));
+            }
+
+            if self.loop_chunk_format == LoopChunkFormat::Smpl {
+                return Err(Error::InvalidLoop(String::from(
+                    "End marker requires the loop in a cue chunk",
+                )));
+            }
+        }
+
+        if let Some(marker) =
+            self.markers.iter().find(|marker| marker.position > len)
+        {
+            return Err(Error::InvalidArgument(format!(
+                "Marker at sample {} lies past the end of the file",
+                marker.position,
+            )));
+        }
+
+        Ok(())
+    }
+}
+
+// Format samples read with `metadata` are written back in.  Float and wider
+// integer samples are converted to 16 bits on read, and re-encoded as such.
+pub(crate) fn render_format_of(
+    metadata: &crate::Metadata,
+) -> Result<SampleFmt, Error> {
+    match metadata.bits_per_sample {
+        8 if !metadata.float_samples => Ok(SampleFmt::Unsigned8),
+        16 | 24 | 32 => Ok(SampleFmt::Signed16),
+        bits => Err(Error::Unsupported(format!(
+            "Unsupported {}-bit samples",
+            bits
+        ))),
+    }
+}
+
+// Root mean square of samples relative to full scale, or 0 for none
+fn rms_level(samples: &[i16]) -> f64 {
+    let power = samples.iter().map(|&s| f64::from(s).powi(2)).sum::<f64>()
+        / samples.len().max(1) as f64;
+
+    power.sqrt() / FULL_SCALE
+}
+
+// Level relative to full scale in dBFS, negative infinity for silence
+fn to_dbfs(level: f64) -> f64 {
+    20.0 * level.log10()
+}
+
+// Append a smpl chunk holding the loop as its one forward sample loop
+fn append_smpl_chunk<F: Read + Write + Seek>(
+    file: &mut F,
+    sample_rate: u32,
+    cue_id: u32,
+    sample_loop: &Range<u32>,
+) -> std::io::Result<()> {
+    let sample_period_ns = 1_000_000_000u32.checked_div(sample_rate);
+    let fields = [
+        0,                             // Manufacturer
+        0,                             // Product
+        sample_period_ns.unwrap_or(0), // Sample period
+        60,                            // MIDI unity note, middle C
+        0,                             // MIDI pitch fraction
+        0,                             // SMPTE format
+        0,                             // SMPTE offset
+        1,                             // Sample loop count
+        0,                             // Sampler data size
+        cue_id,
+        0, // Loop type, forward
+        sample_loop.start,
+        sample_loop.end.saturating_sub(1), // Last frame of the loop
+        0,                                 // Fraction
+        0,                                 // Play count, forever
+    ];
+    let body = fields
+        .iter()
+        .flat_map(|field| field.to_le_bytes())
+        .collect::<Vec<_>>();
+
+    append_chunk(file, b"smpl", &body)
+}
+
+// Body of an adtl LIST chunk holding the labeled texts, then labels given as
+// cue point IDs and texts
+fn adtl_body(labeled_texts: &[LabeledText], labels: &[(u32, &str)]) -> Vec<u8> {
+    let mut body = b"adtl".to_vec();
+    let mut push_sub_chunk = |tag: &[u8; 4], sub_chunk: Vec<u8>| {
+        body.extend_from_slice(tag);
+        body.extend_from_slice(&(sub_chunk.len() as u32).to_le_bytes());
+        body.extend_from_slice(&sub_chunk);
+
+        if sub_chunk.len() % 2 == 1 {
+            body.push(0);
+        }
+    };
+
+    for labeled_text in labeled_texts {
+        push_sub_chunk(b"ltxt", labeled_text.as_bytes());
+    }
+
+    for (id, text) in labels {
+        let mut sub_chunk = id.to_le_bytes().to_vec();
+        sub_chunk.extend_from_slice(text.as_bytes());
+        sub_chunk.push(0);
+        push_sub_chunk(b"labl", sub_chunk);
+    }
+
+    body
+}
+
+// Append a chunk at the end of the RIFF, padded to an even length, and update
+// the RIFF size to match.  The cursor is left past the new chunk.
+fn append_chunk<F: Read + Write + Seek>(
+    file: &mut F,
+    tag: &[u8; 4],
+    body: &[u8],
+) -> std::io::Result<()> {
+    let too_large = || {
+        std::io::Error::other(format!(
+            "WAV too large for {} chunk",
+            String::from_utf8_lossy(tag).trim_end()
+        ))
+    };
+    let body_size = u32::try_from(body.len()).map_err(|_| too_large())?;
+
+    let mut riff_size = [0u8; 4];
+    file.seek(SeekFrom::Start(4))?;
+    file.read_exact(&mut riff_size)?;
+    let riff_size = u32::from_le_bytes(riff_size);
+    let new_size = riff_size
+        .checked_add(8)
+        .and_then(|size| size.checked_add(body_size))
+        .and_then(|size| size.checked_add(body_size % 2))
+        .ok_or_else(too_large)?;
+
+    file.seek(SeekFrom::Start(4))?;
+    file.write_all(&new_size.to_le_bytes())?;
+    file.seek(SeekFrom::Start(8 + u64::from(riff_size)))?;
+    file.write_all(tag)?;
+    file.write_all(&body_size.to_le_bytes())?;
+    file.write_all(body)?;
+
+    if body_size % 2 == 1 {
+        file.write_all(&[0])?;
+    }
+
+    Ok(())
+}
+
+// Write a WAV holding the source's fmt and data chunks unchanged
+fn write_source<W: Write>(
+    writer: &mut W,
+    source: &crate::SourceChunks,
+) -> std::io::Result<()> {
+    let chunk_len = |body: &[u8]| 8 + body.len() + body.len() % 2;
+    let riff_size = 4 + chunk_len(&source.fmt) + chunk_len(&source.data);
+    let size = |len: usize| {
+        u32::try_from(len).map(u32::to_le_bytes).map_err(|e| {
+            std::io::Error::new(std::io::ErrorKind::InvalidData, e)
+        })
+    };
+
+    writer.write_all(b"RIFF")?;
+    writer.write_all(&size(riff_size)?)?;
+    writer.write_all(b"WAVE")?;
+
+    for (tag, body) in [(b"fmt ", &source.fmt), (b"data", &source.data)] {
+        writer.write_all(tag)?;
+        writer.write_all(&size(body.len())?)?;
+        writer.write_all(body)?;
+
+        if body.len() % 2 == 1 {
+            writer.write_all(&[0])?;
+        }
+    }
+
+    Ok(())
 }
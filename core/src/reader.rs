@@ -1,131 +1,967 @@
+use crate::Error;
 use hound::SampleFormat;
-use std::io::{Read, Seek};
-use std::num::TryFromIntError;
+use std::fs::File;
+use std::io::{BufReader, Cursor, Read, Seek, SeekFrom};
+use std::ops::Range;
+use std::path::Path;
 
+/// Longest supported file in frames.  Cue points and labeled texts address
+/// frames with 32-bit offsets, so longer files couldn't carry a loop anyway.
+pub const MAX_FRAMES: u32 = u32::MAX;
+
+/// Most bytes buffered from a stream that can't seek, such as a pipe, before
+/// giving up on it.  Such streams are read whole into memory, and this keeps a
+/// runaway one from exhausting it.
+pub const MAX_UNSEEKABLE_BYTES: u64 = 1 << 30;
+
+// Container IDs of 64-bit WAV variants, which hold their true sizes in a ds64
+// chunk
+const RF64_MAGIC: [[u8; 4]; 2] = [*b"RF64", *b"BW64"];
+
+// Sizes of the smpl chunk's fixed fields and of each sample loop after them
+const SMPL_HEADER_SZ: u32 = 36;
+const SMPL_LOOP_SZ: u32 = 24;
+
+/// Text of the labeled text marking the end of playback, as opposed to a loop
+pub const END_MARKER_LABEL: &str = "END";
+
+/// Chunk a loop was read from
 #[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum LoopSource {
+    /// A cue point, with its length in a labeled text if there is one
+    Cue,
+
+    /// The first sample loop of a sampler chunk, read only when there are no
+    /// cue points
+    Smpl,
+}
+
+/// A cue point other than the loop's and the end marker's, with the text of
+/// its label, empty if it has none
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Marker {
+    pub id: u32,
+    pub position: u32,
+    pub label: String,
+
+    /// Length of the region the marker starts, as given by a labeled text.
+    /// Only read: markers are written with labels alone, since Quake takes
+    /// the first cue point with a labeled text as the loop.
+    pub length: Option<u32>,
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Default)]
 pub struct Metadata {
     pub sample_rate: u32,
+
+    /// Length in frames, each holding a sample per channel
     pub sample_count: u32,
+
+    /// Bytes of sample data present, whatever the data chunk header claims
+    pub data_bytes: u32,
+
     pub loop_start: Option<u32>,
     pub end: Option<u32>,
     pub bits_per_sample: u16,
+    pub channels: u16,
+
+    /// Whether the samples are stored as IEEE floats, which are converted to
+    /// 16 bits on read
+    pub float_samples: bool,
+
+    /// Where the loop was read from, if there is one
+    pub loop_source: Option<LoopSource>,
+
+    /// Whether there is a sample loop in a smpl chunk, read or not
+    pub smpl_loop: bool,
+
+    /// ID of the cue point the loop was read from
+    pub loop_cue_id: Option<u32>,
+
+    /// Where playback ends short of the end of the file, as marked by a cue
+    /// point labeled "END"
+    pub play_end: Option<u32>,
+
+    /// Set to the loop end given by the labeled text when it lies past the
+    /// last sample, in which case `end` is clamped to the samples present
+    pub loop_end_past_eof: Option<u32>,
+
+    /// Set when the sample count declared by the data chunk header disagrees
+    /// with the sample data actually present in the file, as
+    /// (declared, actual)
+    pub declared_vs_actual: Option<(u32, u32)>,
 }
 
 pub struct QWaveReader<R: Read> {
     reader: hound::WavReader<R>,
     loop_start: Option<u32>,
     loop_length: Option<u32>,
+    loop_source: Option<LoopSource>,
+    has_smpl_loop: bool,
+    loop_cue_id: Option<u32>,
+    loop_label: Option<String>,
+    play_end: Option<u32>,
+    markers: Vec<Marker>,
+    actual_sample_count: Option<u32>,
+    data_bytes: Option<u64>,
+    chunk_index: ChunkIndex,
 }
 
-impl<R: Read + Seek> QWaveReader<R> {
-    pub fn new(reader: R) -> Result<Self, String> {
-        let mut chunk_reader =
-            cuet::ChunkReader::new(reader).map_err(|e| e.to_string())?;
+impl QWaveReader<BufReader<File>> {
+    /// Open the WAV at `path`; errors name the file
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, Error> {
+        let path = path.as_ref();
 
-        let cue_chunk = chunk_reader
-            .read_next_chunk(Some(*b"cue "))
-            .map_err(|e| e.to_string())?;
+        File::open(path)
+            .map_err(Error::from)
+            .and_then(|file| QWaveReader::new(BufReader::new(file)))
+            .map_err(|e| e.at_path(path))
+    }
+}
+
+impl QWaveReader<Cursor<Vec<u8>>> {
+    /// Read a WAV from a stream that can't seek, such as a pipe, by first
+    /// buffering it whole with `buffer_unseekable`
+    pub fn from_unseekable(reader: impl Read) -> Result<Self, Error> {
+        QWaveReader::new(buffer_unseekable(reader)?)
+    }
+}
 
-        let loop_start = cue_chunk.and_then(|(_, bytes)| {
-            let pts = cuet::parse_cue_points(&bytes[..]);
+/// Read a stream whole into memory so it can be sought, as a WAV reader
+/// needs.  Streams longer than `MAX_UNSEEKABLE_BYTES` are an error.  Writers
+/// streaming a WAV can't go back to fill in its sizes, so RIFF and data sizes
+/// left as 0xFFFFFFFF are set to those of the bytes read.
+pub fn buffer_unseekable(reader: impl Read) -> Result<Cursor<Vec<u8>>, Error> {
+    let mut bytes = vec![];
+    reader
+        .take(MAX_UNSEEKABLE_BYTES + 1)
+        .read_to_end(&mut bytes)?;
 
-            if pts.is_empty() {
-                None
-            } else {
-                Some(pts[0].sample_offset)
+    if bytes.len() as u64 > MAX_UNSEEKABLE_BYTES {
+        return Err(Error::Unsupported(format!(
+            "Stream is longer than the {} MiB that can be buffered; write it \
+            to a file first",
+            MAX_UNSEEKABLE_BYTES >> 20,
+        )));
+    }
+
+    fill_streamed_sizes(&mut bytes);
+    Ok(Cursor::new(bytes))
+}
+
+// Replace placeholder RIFF and data chunk sizes with those of the bytes present
+fn fill_streamed_sizes(bytes: &mut [u8]) {
+    const UNKNOWN: [u8; 4] = u32::MAX.to_le_bytes();
+    let size_from = |start: usize, len: usize| {
+        u32::try_from(len.saturating_sub(start))
+            .unwrap_or(u32::MAX)
+            .to_le_bytes()
+    };
+
+    if bytes.len() < 12 {
+        return;
+    }
+
+    if bytes[4..8] == UNKNOWN {
+        let size = size_from(8, bytes.len());
+        bytes[4..8].copy_from_slice(&size);
+    }
+
+    let mut position = 12;
+
+    while position + 8 <= bytes.len() {
+        let size: [u8; 4] =
+            bytes[position + 4..position + 8].try_into().unwrap();
+
+        if &bytes[position..position + 4] == b"data" {
+            if size == UNKNOWN {
+                let size = size_from(position + 8, bytes.len());
+                bytes[position + 4..position + 8].copy_from_slice(&size);
             }
-        });
 
-        let loop_length = if loop_start.is_some() {
-            let list_chunk = chunk_reader
-                .read_next_chunk(Some(*b"LIST"))
-                .map_err(|e| e.to_string())?;
+            return;
+        }
+
+        let size = u32::from_le_bytes(size) as usize;
+        position += 8 + size + size % 2;
+    }
+}
+
+impl<R: Read + Seek> QWaveReader<R> {
+    pub fn new(mut reader: R) -> Result<Self, Error> {
+        let chunk_index = index_chunks(&mut reader)?;
 
-            list_chunk.and_then(|(_, bytes)| {
-                let labeled_texts =
-                    cuet::extract_labeled_text_from_list(&bytes);
-                labeled_texts.first().map(|ltxt| ltxt.sample_length)
+        if RF64_MAGIC.contains(&chunk_index.magic) {
+            return Err(Error::Unsupported(format!(
+                "{} (64-bit RIFF) files are unsupported; convert to a \
+                standard WAV first, e.g. with sox or ffmpeg",
+                String::from_utf8_lossy(&chunk_index.magic),
+            )));
+        }
+        let data_extent = chunk_index.measure_data(&mut reader)?;
+        let smpl_loop = chunk_index.read_smpl_loop(&mut reader)?;
+
+        let CueChunks {
+            cue_points,
+            labeled_texts,
+            labels,
+        } = chunk_index.read_cue_chunks(&mut reader)?;
+
+        let (end_texts, labeled_texts): (Vec<_>, Vec<_>) =
+            labeled_texts.into_iter().partition(|ltxt| {
+                ltxt.text.trim_end_matches('\0') == END_MARKER_LABEL
+            });
+
+        let (end_cues, cue_points): (Vec<_>, Vec<_>) =
+            cue_points.into_iter().partition(|cue| {
+                end_texts.iter().any(|ltxt| ltxt.cue_id == cue.id)
+            });
+
+        let play_end = end_cues.first().map(|cue| cue.sample_offset);
+        let loop_cue = pair_loop_cue(&cue_points, &labeled_texts);
+        let loop_cue_id = loop_cue.map(|(cue, _)| cue.id);
+        let mut loop_source = loop_cue.map(|_| LoopSource::Cue);
+        let mut loop_start = loop_cue.map(|(cue, _)| cue.sample_offset);
+        let mut loop_length = loop_cue.and_then(|(_, length)| length);
+        let has_smpl_loop = smpl_loop.is_some();
+
+        if let (None, Some(smpl_loop)) = (loop_cue, smpl_loop) {
+            let length = smpl_loop
+                .end
+                .checked_sub(smpl_loop.start)
+                .ok_or_else(|| {
+                    Error::InvalidLoop(format!(
+                        "smpl loop ends at sample {}, before it starts at {}",
+                        smpl_loop.end, smpl_loop.start,
+                    ))
+                })?;
+
+            loop_source = Some(LoopSource::Smpl);
+            loop_start = Some(smpl_loop.start);
+            loop_length = Some(length);
+        }
+        let loop_label = labeled_texts
+            .iter()
+            .find(|ltxt| Some(ltxt.cue_id) == loop_cue_id)
+            .map(|ltxt| String::from(ltxt.text.trim_end_matches('\0')))
+            .filter(|text| !text.is_empty());
+
+        // Markers take their text from a label, or failing that a labeled
+        // text
+        let markers = cue_points
+            .iter()
+            .filter(|cue| Some(cue.id) != loop_cue_id)
+            .map(|cue| {
+                let labeled_text =
+                    labeled_texts.iter().find(|ltxt| ltxt.cue_id == cue.id);
+                let label = labels
+                    .iter()
+                    .find(|(id, _)| *id == cue.id)
+                    .map(|(_, text)| text.clone())
+                    .or_else(|| labeled_text.map(|ltxt| ltxt.text.clone()))
+                    .unwrap_or_default();
+
+                Marker {
+                    id: cue.id,
+                    position: cue.sample_offset,
+                    label: String::from(label.trim_end_matches('\0')),
+                    length: labeled_text
+                        .map(|ltxt| ltxt.sample_length)
+                        .filter(|&length| length > 0),
+                }
             })
-        } else {
-            None
-        };
+            .collect();
+
+        let reader = hound::WavReader::new(reader)?;
 
-        let reader = hound::WavReader::new(
-            chunk_reader.restore_cursor().map_err(|e| e.to_string())?,
-        )
-        .map_err(|e| e.to_string())?;
+        let data_bytes =
+            data_extent.as_ref().map(|extent| extent.available_bytes);
+
+        let actual_sample_count = data_extent
+            .and_then(|extent| {
+                extent
+                    .available_bytes
+                    .checked_div(extent.block_align.into())
+            })
+            .map(|count| u32::try_from(count).map_err(|_| too_long(count)))
+            .transpose()?;
 
         Ok(QWaveReader {
             reader,
             loop_start,
             loop_length,
+            loop_source,
+            has_smpl_loop,
+            loop_cue_id,
+            loop_label,
+            play_end,
+            markers,
+            actual_sample_count,
+            data_bytes,
+            chunk_index,
+        })
+    }
+}
+
+pub(crate) fn too_long(frames: impl std::fmt::Display) -> Error {
+    Error::Unsupported(format!(
+        "File holds {} samples, beyond the maximum of {}",
+        frames, MAX_FRAMES
+    ))
+}
+
+/// Bodies of the fmt and data chunks exactly as stored in the source file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SourceChunks {
+    pub fmt: Vec<u8>,
+
+    /// Only the sample data actually present, which may be shorter or longer
+    /// than the data chunk header declares
+    pub data: Vec<u8>,
+
+    /// Chunks carried over by `Project::write_to`, in file order
+    pub extra: Vec<RawChunk>,
+}
+
+/// A chunk's tag and body, stored without interpreting it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RawChunk {
+    pub tag: [u8; 4],
+    pub body: Vec<u8>,
+}
+
+// Chunks not carried over, as they are rewritten from the project or hold
+// sample offsets or counts that would go stale
+const REGENERATED_TAGS: [&[u8; 4]; 6] =
+    [b"fmt ", b"data", b"fact", b"cue ", b"smpl", b"plst"];
+
+impl<R: Read + Seek> QWaveReader<R> {
+    /// Read back the raw fmt and data chunks, for writing them out unchanged,
+    /// and any other chunks that can be.  Of those, LIST chunks of associated
+    /// data are left out for naming cue points, as are chunks cut short by the
+    /// end of the file.
+    pub fn into_source_chunks(self) -> Result<SourceChunks, Error> {
+        let chunks = &self.chunk_index.chunks;
+        let missing = |tag: &[u8; 4]| {
+            Error::WavDecode(format!(
+                "No {} chunk",
+                String::from_utf8_lossy(tag).trim_end()
+            ))
+        };
+        let fmt = chunks
+            .iter()
+            .find(|chunk| chunk.tag == *b"fmt ")
+            .copied()
+            .ok_or_else(|| missing(b"fmt "))?;
+        let data = self.data_range().ok_or_else(|| missing(b"data"))?;
+        let wave_start = self.chunk_index.wave_start;
+        let mut reader = self.reader.into_inner();
+
+        let mut read_range = |start: u64, len: u64| {
+            let len = usize::try_from(len).map_err(|_| too_long(len))?;
+            let mut bytes = vec![0u8; len];
+            reader.seek(SeekFrom::Start(wave_start + start))?;
+            reader.read_exact(&mut bytes)?;
+            Ok::<_, Error>(bytes)
+        };
+
+        let fmt = read_range(fmt.body_start(), fmt.size.into())?;
+        let data = read_range(data.start, data.end - data.start)?;
+        let mut extra = vec![];
+
+        for chunk in &self.chunk_index.chunks {
+            let body_end = chunk.body_start() + u64::from(chunk.size);
+
+            if REGENERATED_TAGS.contains(&&chunk.tag)
+                || body_end > self.chunk_index.file_end
+            {
+                continue;
+            }
+
+            let body = read_range(chunk.body_start(), chunk.size.into())?;
+
+            if chunk.tag == *b"LIST" && body.starts_with(b"adtl") {
+                continue;
+            }
+
+            extra.push(RawChunk {
+                tag: chunk.tag,
+                body,
+            });
+        }
+
+        Ok(SourceChunks { fmt, data, extra })
+    }
+}
+
+// Choose the cue point marking the loop.  The first cue point named by a
+// labeled text is preferred, taking the loop length from that text, whatever
+// order the texts come in; the first point is used only when no labeled text
+// refers to any cue point.
+fn pair_loop_cue(
+    cue_points: &[cuet::CuePoint],
+    labeled_texts: &[cuet::LabeledText],
+) -> Option<(cuet::CuePoint, Option<u32>)> {
+    cue_points
+        .iter()
+        .find_map(|&cue| {
+            labeled_texts
+                .iter()
+                .find(|ltxt| ltxt.cue_id == cue.id)
+                .map(|ltxt| (cue, Some(ltxt.sample_length)))
+        })
+        .or_else(|| cue_points.first().map(|&cue| (cue, None)))
+}
+
+// Cue points and the texts attached to them
+struct CueChunks {
+    cue_points: Vec<cuet::CuePoint>,
+    labeled_texts: Vec<cuet::LabeledText>,
+
+    // Cue point IDs and texts of labels
+    labels: Vec<(u32, String)>,
+}
+
+// Cue point IDs and texts of the labels in the body of an adtl LIST chunk
+fn extract_labels_from_list(body: &[u8]) -> Vec<(u32, String)> {
+    let mut labels = vec![];
+    let mut rest = body.get(4..).unwrap_or_default();
+
+    while rest.len() >= 8 {
+        let size = u32::from_le_bytes([rest[4], rest[5], rest[6], rest[7]]);
+        let size = (size as usize).min(rest.len() - 8);
+        let sub_chunk = &rest[8..8 + size];
+
+        if rest.starts_with(b"labl") && size >= 4 {
+            let id = u32::from_le_bytes([
+                sub_chunk[0],
+                sub_chunk[1],
+                sub_chunk[2],
+                sub_chunk[3],
+            ]);
+            let text = sub_chunk[4..].split(|&b| b == 0).next().unwrap_or(&[]);
+            labels.push((id, String::from_utf8_lossy(text).into_owned()));
+        }
+
+        rest = rest.get(8 + size + size % 2..).unwrap_or_default();
+    }
+
+    labels
+}
+
+/// Location of a chunk within a WAV
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChunkEntry {
+    pub tag: [u8; 4],
+
+    /// Offset of the chunk header from the start of the WAV
+    pub offset: u64,
+
+    /// Size of the chunk body as declared by its header
+    pub size: u32,
+}
+
+impl ChunkEntry {
+    fn body_start(&self) -> u64 {
+        self.offset + 8
+    }
+
+    fn padded_end(&self) -> u64 {
+        self.body_start() + u64::from(self.size) + u64::from(self.size & 1)
+    }
+}
+
+struct ChunkIndex {
+    magic: [u8; 4],
+    chunks: Vec<ChunkEntry>,
+    riff_end: u64,
+    parsed_end: u64,
+    file_end: u64,
+    wave_start: u64,
+}
+
+struct DataExtent {
+    block_align: u16,
+    available_bytes: u64,
+}
+
+// Walk every chunk header in the stream, including any tacked on past the end
+// of the RIFF, stopping at the first bytes that don't look like a chunk.
+// Cursor is returned to its initial position.
+fn index_chunks<R: Read + Seek>(reader: &mut R) -> std::io::Result<ChunkIndex> {
+    let wave_start = reader.stream_position()?;
+    let file_end = reader.seek(SeekFrom::End(0))? - wave_start;
+    let mut riff_head = [0u8; 8];
+    reader.seek(SeekFrom::Start(wave_start))?;
+    reader.read_exact(&mut riff_head)?;
+    let riff_size = u32::from_le_bytes(riff_head[4..].try_into().unwrap());
+    let riff_end = 8 + u64::from(riff_size);
+    let mut chunks = vec![];
+    let mut position = 12u64;
+
+    while position + 8 <= file_end {
+        let mut head = [0u8; 8];
+        reader.seek(SeekFrom::Start(wave_start + position))?;
+        reader.read_exact(&mut head)?;
+
+        let tag: [u8; 4] = head[..4].try_into().unwrap();
+
+        if !tag.iter().all(|&b| b.is_ascii_graphic() || b == b' ') {
+            break;
+        }
+
+        let entry = ChunkEntry {
+            tag,
+            offset: position,
+            size: u32::from_le_bytes(head[4..].try_into().unwrap()),
+        };
+
+        chunks.push(entry);
+        position = entry.padded_end();
+    }
+
+    reader.seek(SeekFrom::Start(wave_start))?;
+
+    Ok(ChunkIndex {
+        magic: riff_head[..4].try_into().unwrap(),
+        chunks,
+        riff_end,
+        parsed_end: position,
+        file_end,
+        wave_start,
+    })
+}
+
+impl ChunkIndex {
+    // Cue points and labeled texts, wherever their chunks lie relative to the
+    // data and each other.  Only LIST chunks of associated data are read for
+    // labeled texts, so INFO lists are never taken for them.  Cursor is
+    // returned to its initial position.
+    fn read_cue_chunks<R: Read + Seek>(
+        &self,
+        reader: &mut R,
+    ) -> std::io::Result<CueChunks> {
+        let cue_points = match self.find(b"cue ") {
+            Some(cue) => {
+                let body = self.read_body(reader, cue)?;
+
+                if body.len() < 4 {
+                    vec![]
+                } else {
+                    cuet::parse_cue_points(&body)
+                }
+            }
+            None => vec![],
+        };
+
+        let mut labeled_texts = vec![];
+        let mut labels = vec![];
+
+        if !cue_points.is_empty() {
+            for list in self.chunks.iter().filter(|chunk| chunk.tag == *b"LIST")
+            {
+                let body = self.read_body(reader, list)?;
+
+                if body.starts_with(b"adtl") {
+                    labeled_texts
+                        .extend(cuet::extract_labeled_text_from_list(&body));
+                    labels.extend(extract_labels_from_list(&body));
+                }
+            }
+        }
+
+        reader.seek(SeekFrom::Start(self.wave_start))?;
+        Ok(CueChunks {
+            cue_points,
+            labeled_texts,
+            labels,
         })
     }
+
+    fn find(&self, tag: &[u8; 4]) -> Option<&ChunkEntry> {
+        self.chunks.iter().find(|chunk| chunk.tag == *tag)
+    }
+
+    // Body of a chunk, cut short where the file ends
+    fn read_body<R: Read + Seek>(
+        &self,
+        reader: &mut R,
+        chunk: &ChunkEntry,
+    ) -> std::io::Result<Vec<u8>> {
+        let len = u64::from(chunk.size)
+            .min(self.file_end.saturating_sub(chunk.body_start()));
+        let mut body = vec![0u8; len as usize];
+        reader.seek(SeekFrom::Start(self.wave_start + chunk.body_start()))?;
+        reader.read_exact(&mut body)?;
+        Ok(body)
+    }
+
+    // Frames covered by the first sample loop of the smpl chunk, if any.  The
+    // chunk gives the last frame of the loop, not the one after it.  Cursor is
+    // returned to its initial position.
+    fn read_smpl_loop<R: Read + Seek>(
+        &self,
+        reader: &mut R,
+    ) -> std::io::Result<Option<Range<u32>>> {
+        let Some(smpl) = self.find(b"smpl") else {
+            return Ok(None);
+        };
+
+        if smpl.size < SMPL_HEADER_SZ + SMPL_LOOP_SZ
+            || smpl.body_start() + u64::from(SMPL_HEADER_SZ + SMPL_LOOP_SZ)
+                > self.file_end
+        {
+            return Ok(None);
+        }
+
+        let mut body = [0u8; (SMPL_HEADER_SZ + SMPL_LOOP_SZ) as usize];
+        reader.seek(SeekFrom::Start(self.wave_start + smpl.body_start()))?;
+        reader.read_exact(&mut body)?;
+        reader.seek(SeekFrom::Start(self.wave_start))?;
+
+        let field = |offset: usize| {
+            u32::from_le_bytes(body[offset..offset + 4].try_into().unwrap())
+        };
+        let loop_count = field(28);
+        let loop_entry = SMPL_HEADER_SZ as usize;
+
+        if loop_count == 0 {
+            return Ok(None);
+        }
+
+        Ok(Some(
+            field(loop_entry + 8)..field(loop_entry + 12).saturating_add(1),
+        ))
+    }
+
+    // Find how many bytes of sample data are really present, as opposed to
+    // what the data chunk header claims.  The data chunk is considered
+    // over-declared if the file ends before the declared size, and
+    // under-declared if the RIFF header also ends with it while the file goes
+    // on with what reads as more audio, as when a writer never went back to
+    // fill in its sizes.  Bytes the RIFF does hold after the data chunk, such
+    // as padding, are not sample data.  Cursor is returned to its initial
+    // position.
+    fn measure_data<R: Read + Seek>(
+        &self,
+        reader: &mut R,
+    ) -> std::io::Result<Option<DataExtent>> {
+        let fmt = self.find(b"fmt ");
+        let data_idx =
+            self.chunks.iter().position(|chunk| chunk.tag == *b"data");
+
+        let (Some(fmt), Some(data_idx)) = (fmt, data_idx) else {
+            return Ok(None);
+        };
+
+        if fmt.size < 14 {
+            return Ok(None);
+        }
+
+        let mut fmt_head = [0u8; 14];
+        reader.seek(SeekFrom::Start(self.wave_start + fmt.body_start()))?;
+        reader.read_exact(&mut fmt_head)?;
+        reader.seek(SeekFrom::Start(self.wave_start))?;
+
+        let channels = u16::from_le_bytes([fmt_head[2], fmt_head[3]]);
+        let block_align = u16::from_le_bytes([fmt_head[12], fmt_head[13]]);
+        let data = self.chunks[data_idx];
+        let body_end = data.body_start() + u64::from(data.size);
+
+        let runs_to_file_end = body_end > self.file_end
+            || (data_idx + 1 == self.chunks.len()
+                && data.padded_end() >= self.riff_end
+                && self.continues_data(
+                    reader,
+                    &data,
+                    channels,
+                    block_align,
+                )?);
+
+        let available_bytes = if runs_to_file_end {
+            self.file_end - data.body_start()
+        } else {
+            u64::from(data.size)
+        };
+
+        Ok(Some(DataExtent {
+            block_align,
+            available_bytes,
+        }))
+    }
+
+    // Whether the bytes from the declared end of `data` to the end of the file
+    // read as more of its audio: a whole number of frames, the first of which
+    // carries on from the last declared frame, if any, without jumping by more
+    // than a quarter of full scale in any channel and without being zero bytes
+    // of padding.  Cursor is returned to its initial position.
+    fn continues_data<R: Read + Seek>(
+        &self,
+        reader: &mut R,
+        data: &ChunkEntry,
+        channels: u16,
+        block_align: u16,
+    ) -> std::io::Result<bool> {
+        let data_end = data.body_start() + u64::from(data.size);
+        let tail_len = self.file_end.saturating_sub(data_end);
+        let frame_len = usize::from(block_align);
+
+        if tail_len == 0
+            || channels == 0
+            || frame_len == 0
+            || !tail_len.is_multiple_of(block_align.into())
+            || !frame_len.is_multiple_of(channels.into())
+        {
+            return Ok(false);
+        }
+
+        if data.size < u32::from(block_align) {
+            return Ok(true);
+        }
+
+        let mut frames = vec![0u8; frame_len * 2];
+        reader.seek(SeekFrom::Start(
+            self.wave_start + data_end - u64::from(block_align),
+        ))?;
+        reader.read_exact(&mut frames)?;
+        reader.seek(SeekFrom::Start(self.wave_start))?;
+
+        // The last byte of each little-endian sample is its most significant;
+        // 8-bit samples are unsigned
+        let width = frame_len / usize::from(channels);
+        let (last, first) = frames.split_at(frame_len);
+        let top = |frame: &[u8], ch: usize| {
+            let byte = frame[ch * width + width - 1];
+            let byte = if width == 1 { byte ^ 0x80 } else { byte };
+            i16::from(byte as i8)
+        };
+
+        Ok(first.iter().any(|&b| b != 0)
+            && (0..usize::from(channels))
+                .all(|ch| top(last, ch).abs_diff(top(first, ch)) <= 32))
+    }
 }
 
 impl<R: Read> QWaveReader<R> {
+    /// All chunks found in the WAV, in file order
+    pub fn chunks(&self) -> &[ChunkEntry] {
+        &self.chunk_index.chunks
+    }
+
+    /// Byte range of the sample data actually present in the data chunk,
+    /// relative to the start of the WAV
+    pub fn data_range(&self) -> Option<Range<u64>> {
+        let data = self
+            .chunk_index
+            .chunks
+            .iter()
+            .find(|chunk| chunk.tag == *b"data")?;
+        let start = data.body_start();
+
+        Some(start..start + self.data_bytes?)
+    }
+
+    /// Chunks which `Project::write_to` will not carry over once the project's
+    /// extra chunks are cleared.  Only the first fmt and data chunks are kept,
+    /// and only the cue and LIST chunks read for the loop are regenerated;
+    /// anything else is dropped.
+    pub fn unmanaged_chunks(&self) -> Vec<ChunkEntry> {
+        let chunks = &self.chunk_index.chunks;
+        let first = |tag: &[u8; 4]| chunks.iter().position(|c| c.tag == *tag);
+        let cue = first(b"cue ");
+        let list = cue.and_then(|cue| {
+            chunks[cue..]
+                .iter()
+                .position(|c| c.tag == *b"LIST")
+                .map(|idx| idx + cue)
+        });
+        let managed = [first(b"fmt "), first(b"data"), cue, list];
+
+        chunks
+            .iter()
+            .enumerate()
+            .filter(|(idx, _)| !managed.contains(&Some(*idx)))
+            .map(|(_, &chunk)| chunk)
+            .collect()
+    }
+
+    /// Bytes at the end of the file that don't belong to any chunk
+    pub fn trailing_bytes(&self) -> u64 {
+        self.chunk_index
+            .file_end
+            .saturating_sub(self.chunk_index.parsed_end)
+    }
+
+    /// Cue points other than the loop's and the end marker's, in file order
+    pub fn markers(&self) -> &[Marker] {
+        &self.markers
+    }
+
+    /// Text of the loop's labeled text, if it has any
+    pub fn loop_label(&self) -> Option<&str> {
+        self.loop_label.as_deref()
+    }
+
     pub fn metadata(&self) -> Metadata {
         let sample_count = self.reader.duration();
 
-        let end = if let (Some(start), Some(length)) =
+        let readable_count =
+            sample_count.min(self.actual_sample_count.unwrap_or(u32::MAX));
+
+        let declared_end = if let (Some(start), Some(length)) =
             (self.loop_start, self.loop_length)
         {
-            start.checked_add(length)
+            Some(start.saturating_add(length))
         } else {
             None
         };
 
+        let loop_end_past_eof =
+            declared_end.filter(|&end| end > readable_count);
+        let end = declared_end.map(|end| end.min(readable_count));
+
+        let declared_vs_actual = self
+            .actual_sample_count
+            .filter(|&actual| actual != sample_count)
+            .map(|actual| (sample_count, actual));
+
+        let data_bytes = self
+            .data_bytes
+            .unwrap_or(
+                u64::from(sample_count)
+                    * u64::from(self.reader.spec().channels)
+                    * u64::from(self.reader.spec().bits_per_sample / 8),
+            )
+            .try_into()
+            .unwrap_or(u32::MAX);
+
         Metadata {
             sample_rate: self.reader.spec().sample_rate,
             sample_count,
+            data_bytes,
             loop_start: self.loop_start,
             end,
             bits_per_sample: self.reader.spec().bits_per_sample,
+            channels: self.reader.spec().channels,
+            loop_source: self.loop_source,
+            smpl_loop: self.has_smpl_loop,
+            float_samples: self.reader.spec().sample_format
+                == SampleFormat::Float,
+            loop_cue_id: self.loop_cue_id,
+            play_end: self.play_end,
+            loop_end_past_eof,
+            declared_vs_actual,
         }
     }
 
-    pub fn collect_samples(&mut self) -> Result<Vec<i16>, String> {
-        let mut error = Option::<String>::None;
+    pub fn collect_samples(&mut self) -> Result<Vec<i16>, Error> {
+        if self.reader.spec().channels != 1 {
+            return Err(Error::Unsupported(String::from("Too many channels")));
+        }
+
+        self.collect_interleaved()
+    }
+
+    /// Samples of every channel, interleaved frame by frame
+    pub fn collect_interleaved(&mut self) -> Result<Vec<i16>, Error> {
+        self.samples()?.collect()
+    }
+
+    /// Read interleaved samples in consecutive chunks of up to `chunk_len`, so
+    /// a file can be summarized without holding all of its samples at once
+    pub fn sample_chunks(
+        &mut self,
+        chunk_len: usize,
+    ) -> Result<impl Iterator<Item = Result<Vec<i16>, Error>> + '_, Error> {
+        if chunk_len == 0 {
+            return Err(Error::InvalidArgument(String::from(
+                "Chunk length must be positive",
+            )));
+        }
+
+        let mut samples = self.samples()?;
+        let mut failed = false;
+
+        Ok(std::iter::from_fn(move || {
+            if failed {
+                return None;
+            }
+
+            let chunk = samples
+                .by_ref()
+                .take(chunk_len)
+                .collect::<Result<Vec<_>, _>>();
+
+            match chunk {
+                Ok(chunk) if chunk.is_empty() => None,
+                Err(e) => {
+                    failed = true;
+                    Some(Err(e))
+                }
+                chunk => Some(chunk),
+            }
+        }))
+    }
+
+    // Interleaved samples converted to 16 bits, up to the end of the data
+    // actually present
+    fn samples(
+        &mut self,
+    ) -> Result<Box<dyn Iterator<Item = Result<i16, Error>> + '_>, Error> {
         let spec = self.reader.spec();
-        let duration = self
+        let frames = self
             .reader
             .duration()
-            .try_into()
-            .map_err(|e: TryFromIntError| e.to_string())?;
+            .min(self.actual_sample_count.unwrap_or(u32::MAX));
+        let frames = usize::try_from(frames).map_err(|_| too_long(frames))?;
+        let unsupported =
+            |message: &str| Err(Error::Unsupported(String::from(message)));
 
-        if spec.channels != 1 {
-            return Err("Too many channels".into());
+        if spec.channels == 0 {
+            return unsupported("No channels");
         }
 
-        if spec.sample_format != SampleFormat::Int {
-            return Err("Float samples are unsupported".into());
+        let samples = frames.saturating_mul(spec.channels.into());
+
+        if spec.sample_format == SampleFormat::Float {
+            if spec.bits_per_sample != 32 {
+                return unsupported("Float samples must be 32-bits");
+            }
+
+            return Ok(Box::new(
+                self.reader
+                    .samples::<f32>()
+                    .take(samples)
+                    .map(|s| s.map(float_to_i16).map_err(Error::from)),
+            ));
         }
 
-        let samp_to_i16 = if spec.bits_per_sample == 8 {
-            |s| s << 8
-        } else if spec.bits_per_sample == 16 {
-            |s| s
-        } else {
-            return Err("Samples must be 8- or 16-bits".into());
+        let samp_to_i16: fn(i32) -> i16 = match spec.bits_per_sample {
+            8 => |s| (s << 8) as i16,
+            16 => |s| s as i16,
+            24 => |s| (s >> 8) as i16,
+            32 => |s| (s >> 16) as i16,
+            _ => {
+                return unsupported("Samples must be 8-, 16-, 24-, or 32-bits")
+            }
         };
 
-        let samples = self
-            .reader
-            .samples::<i16>()
-            .take(duration)
-            .map_while(|s| match s {
-                Ok(s) => Some(samp_to_i16(s)),
-                Err(e) => {
-                    error = Some(e.to_string());
-                    None
-                }
-            })
-            .collect();
+        Ok(Box::new(
+            self.reader
+                .samples::<i32>()
+                .take(samples)
+                .map(move |s| s.map(samp_to_i16).map_err(Error::from)),
+        ))
+    }
+}
 
-        if let Some(e) = error {
-            Err(e)
-        } else {
-            Ok(samples)
-        }
+// Scale a float sample to 16 bits, clamping anything beyond full scale
+pub(crate) fn float_to_i16(s: f32) -> i16 {
+    let scaled = f64::from(s.clamp(-1.0, 1.0)) * 32768.0;
+
+    if scaled.is_nan() {
+        0
+    } else {
+        scaled.round().min(f64::from(i16::MAX)) as i16
     }
 }
@@ -0,0 +1,327 @@
+use crate::hash::{self, Digest};
+use crate::Project;
+use std::fmt::Write;
+use std::ops::Range;
+
+/// Extension of loop sidecar files
+pub const LOOP_SIDECAR_EXTENSION: &str = "loop";
+
+/// A WAV's loop kept in a small TOML file beside it, so loop points can be
+/// reviewed and versioned as text while the audio stays untouched
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LoopSidecar {
+    /// Digest of the source's sample data, as `hash::WaveDigest::data`
+    pub data_sha256: Digest,
+
+    pub sample_rate: u32,
+    pub sample_loop: Range<u32>,
+    pub label: Option<String>,
+}
+
+impl LoopSidecar {
+    /// Record the project's loop; `data_sha256` is the digest of the sample
+    /// data it was read from
+    pub fn from_project(
+        proj: &Project,
+        data_sha256: Digest,
+    ) -> Result<Self, String> {
+        Ok(LoopSidecar {
+            data_sha256,
+            sample_rate: proj.sample_rate(),
+            sample_loop: proj.sample_loop().ok_or("No loop to export")?,
+            label: proj.loop_label().map(String::from),
+        })
+    }
+
+    /// Write as TOML.  Times in seconds are included for the reader's sake
+    /// and ignored by `parse`.
+    pub fn to_toml(&self) -> String {
+        let seconds = |frames: u32| {
+            f64::from(frames) / f64::from(self.sample_rate.max(1))
+        };
+
+        let mut toml = String::from("# Loop points exported by QUADIO\n");
+        let _ = writeln!(
+            toml,
+            "source_sha256 = \"{}\"",
+            hash::to_hex(&self.data_sha256)
+        );
+        let _ = writeln!(toml, "sample_rate = {}", self.sample_rate);
+        let _ = writeln!(toml, "\n[loop]");
+        let _ = writeln!(toml, "start = {}", self.sample_loop.start);
+        let _ = writeln!(toml, "end = {}", self.sample_loop.end);
+        let _ = writeln!(
+            toml,
+            "start_seconds = {:.6}",
+            seconds(self.sample_loop.start)
+        );
+        let _ = writeln!(
+            toml,
+            "end_seconds = {:.6}",
+            seconds(self.sample_loop.end)
+        );
+
+        if let Some(label) = &self.label {
+            let _ = writeln!(toml, "label = {}", quote(label));
+        }
+
+        toml
+    }
+
+    /// Read the TOML written by `to_toml`
+    pub fn parse(toml: &str) -> Result<Self, String> {
+        let mut data_sha256 = None;
+        let mut sample_rate = None;
+        let mut start = None;
+        let mut end = None;
+        let mut label = None;
+        let mut table = String::new();
+
+        for (line_idx, line) in toml.lines().enumerate() {
+            let at_line = |e: String| format!("line {}: {}", line_idx + 1, e);
+            let line = line.trim();
+
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            if let Some(rest) = line.strip_prefix('[') {
+                let (name, rest) = rest
+                    .split_once(']')
+                    .ok_or_else(|| at_line(String::from("Unclosed table")))?;
+
+                if !is_blank(rest) {
+                    return Err(at_line(String::from(
+                        "Unexpected text after table",
+                    )));
+                }
+
+                table = String::from(name.trim());
+                continue;
+            }
+
+            let (key, value) = line
+                .split_once('=')
+                .ok_or_else(|| at_line(String::from("Expected key = value")))?;
+            let key = key.trim();
+            let value = Value::parse(value.trim()).map_err(at_line)?;
+
+            match (&table[..], key) {
+                ("", "source_sha256") => {
+                    let hex = value.string().map_err(at_line)?;
+                    data_sha256 =
+                        Some(hash::from_hex(&hex).ok_or_else(|| {
+                            at_line(String::from("Bad SHA-256 digest"))
+                        })?);
+                }
+                ("", "sample_rate") => {
+                    sample_rate = Some(value.integer().map_err(at_line)?)
+                }
+                ("loop", "start") => {
+                    start = Some(value.integer().map_err(at_line)?)
+                }
+                ("loop", "end") => {
+                    end = Some(value.integer().map_err(at_line)?)
+                }
+                ("loop", "label") => {
+                    label = Some(value.string().map_err(at_line)?)
+                }
+                ("loop", "start_seconds") | ("loop", "end_seconds") => {
+                    value.seconds().map_err(at_line)?;
+                }
+                _ => {
+                    let name = if table.is_empty() {
+                        String::from(key)
+                    } else {
+                        format!("{}.{}", table, key)
+                    };
+
+                    return Err(at_line(format!("Unknown key \"{}\"", name)));
+                }
+            }
+        }
+
+        let missing = |key| format!("Loop sidecar lacks \"{}\"", key);
+
+        Ok(LoopSidecar {
+            data_sha256: data_sha256.ok_or_else(|| missing("source_sha256"))?,
+            sample_rate: sample_rate.ok_or_else(|| missing("sample_rate"))?,
+            sample_loop: start.ok_or_else(|| missing("loop.start"))?
+                ..end.ok_or_else(|| missing("loop.end"))?,
+            label,
+        })
+    }
+
+    /// Check that the sidecar was exported from sample data with the given
+    /// digest
+    pub fn verify_source(&self, data_sha256: &Digest) -> Result<(), String> {
+        if &self.data_sha256 == data_sha256 {
+            Ok(())
+        } else {
+            Err(format!(
+                "Sample data hash {} doesn't match the loop sidecar's {}",
+                hash::to_hex(data_sha256),
+                hash::to_hex(&self.data_sha256),
+            ))
+        }
+    }
+
+    /// Set the project's loop and label to the sidecar's
+    pub fn apply(&self, proj: &mut Project) -> Result<(), String> {
+        if self.sample_loop.end > proj.sample_count() {
+            return Err(format!(
+                "Loop ends at sample {}, past the {} samples in the file",
+                self.sample_loop.end,
+                proj.sample_count(),
+            ));
+        }
+
+        proj.set_loop(Some(self.sample_loop.clone()));
+        proj.set_loop_label(self.label.clone());
+        Ok(proj.validate()?)
+    }
+}
+
+fn is_blank(rest: &str) -> bool {
+    let rest = rest.trim();
+    rest.is_empty() || rest.starts_with('#')
+}
+
+fn expect_blank(rest: &str) -> Result<(), String> {
+    if is_blank(rest) {
+        Ok(())
+    } else {
+        Err(format!("Unexpected \"{}\" after value", rest.trim()))
+    }
+}
+
+// Basic TOML string
+fn quote(text: &str) -> String {
+    let mut quoted = String::from("\"");
+
+    for c in text.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\t' => quoted.push_str("\\t"),
+            '\r' => quoted.push_str("\\r"),
+            c if c.is_control() => {
+                let _ = write!(quoted, "\\u{:04X}", u32::from(c));
+            }
+            c => quoted.push(c),
+        }
+    }
+
+    quoted.push('"');
+    quoted
+}
+
+// The subset of TOML values a sidecar holds
+enum Value {
+    Integer(u32),
+    Float(f64),
+    String(String),
+}
+
+impl Value {
+    fn parse(text: &str) -> Result<Self, String> {
+        if let Some(rest) = text.strip_prefix('"') {
+            let (string, rest) = parse_basic_string(rest)?;
+            expect_blank(rest)?;
+            Ok(Value::String(string))
+        } else if let Some(rest) = text.strip_prefix('\'') {
+            let (string, rest) =
+                rest.split_once('\'').ok_or("Unclosed string")?;
+            expect_blank(rest)?;
+            Ok(Value::String(String::from(string)))
+        } else {
+            let (number, rest) = text.split_at(
+                text.find(|c: char| c.is_whitespace() || c == '#')
+                    .unwrap_or(text.len()),
+            );
+            expect_blank(rest)?;
+            let digits = number.replace('_', "");
+
+            if digits.contains(['.', 'e', 'E']) && !digits.starts_with("0x") {
+                digits
+                    .parse::<f64>()
+                    .map(Value::Float)
+                    .map_err(|e| format!("Bad float \"{}\": {}", number, e))
+            } else {
+                digits
+                    .parse::<u32>()
+                    .map(Value::Integer)
+                    .map_err(|e| format!("Bad integer \"{}\": {}", number, e))
+            }
+        }
+    }
+
+    fn integer(self) -> Result<u32, String> {
+        match self {
+            Value::Integer(integer) => Ok(integer),
+            _ => Err(String::from("Expected an integer")),
+        }
+    }
+
+    fn seconds(self) -> Result<f64, String> {
+        match self {
+            Value::Integer(integer) => Ok(f64::from(integer)),
+            Value::Float(float) => Ok(float),
+            _ => Err(String::from("Expected a time in seconds")),
+        }
+    }
+
+    fn string(self) -> Result<String, String> {
+        match self {
+            Value::String(string) => Ok(string),
+            _ => Err(String::from("Expected a string")),
+        }
+    }
+}
+
+// Parse a double-quoted string whose opening quote has been consumed,
+// returning it and whatever follows the closing quote
+fn parse_basic_string(text: &str) -> Result<(String, &str), String> {
+    let mut string = String::new();
+    let mut chars = text.char_indices();
+
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '"' => return Ok((string, &text[i + 1..])),
+            '\\' => {
+                let escape = match chars.next().map(|(_, c)| c) {
+                    Some('"') => '"',
+                    Some('\\') => '\\',
+                    Some('n') => '\n',
+                    Some('t') => '\t',
+                    Some('r') => '\r',
+                    Some('b') => '\u{8}',
+                    Some('f') => '\u{c}',
+                    Some(len @ ('u' | 'U')) => {
+                        let len = if len == 'u' { 4 } else { 8 };
+                        let hex = chars
+                            .by_ref()
+                            .take(len)
+                            .map(|(_, c)| c)
+                            .collect::<String>();
+
+                        u32::from_str_radix(&hex, 16)
+                            .ok()
+                            .filter(|_| hex.len() == len)
+                            .and_then(char::from_u32)
+                            .ok_or_else(|| {
+                                format!("Bad unicode escape \"{}\"", hex)
+                            })?
+                    }
+                    _ => return Err(String::from("Bad escape in string")),
+                };
+
+                string.push(escape);
+            }
+            c => string.push(c),
+        }
+    }
+
+    Err(String::from("Unclosed string"))
+}
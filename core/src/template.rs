@@ -0,0 +1,256 @@
+use crate::{Metadata, Project, MAX_FRAMES};
+use std::fmt;
+use std::ops::Range;
+
+/// A time as written on the command line, resolved against a file's own rate
+/// and length
+#[derive(Debug, Clone, PartialEq)]
+pub enum TimeSpec {
+    Samples(u32),
+    Seconds(f64),
+    Millis(f64),
+
+    /// Percentage of the file's length
+    Percent(f64),
+
+    /// End of the file
+    Last,
+
+    /// The time given before the end of the file
+    BeforeEnd(Box<TimeSpec>),
+}
+
+impl TimeSpec {
+    /// Parse a sample count, or a time suffixed with "s", "ms", or "%", or
+    /// "LAST" or "END" for the end of the file.  Either of these may be
+    /// prefixed "END-" to count back from the end, or "START+" for symmetry,
+    /// which changes nothing.
+    pub fn parse(time_str: &str) -> Result<Self, String> {
+        if time_str == "LAST" || time_str == "END" {
+            Ok(TimeSpec::Last)
+        } else if let Some(offset) = time_str.strip_prefix("END-") {
+            Ok(TimeSpec::BeforeEnd(Box::new(TimeSpec::parse_offset(
+                offset,
+            )?)))
+        } else if let Some(offset) = time_str.strip_prefix("START+") {
+            TimeSpec::parse_offset(offset)
+        } else if let Some(stripped) = time_str.strip_suffix('%') {
+            stripped
+                .parse::<f64>()
+                .map(TimeSpec::Percent)
+                .or(Err(String::from("Failed to parse time in percent")))
+        } else if let Some(stripped) = time_str.strip_suffix("ms") {
+            stripped
+                .parse::<f64>()
+                .map(TimeSpec::Millis)
+                .or(Err(String::from("Failed to parse time in milliseconds")))
+        } else if let Some(stripped) = time_str.strip_suffix('s') {
+            stripped
+                .parse::<f64>()
+                .map(TimeSpec::Seconds)
+                .or(Err(String::from("Failed to parse time in seconds")))
+        } else {
+            time_str
+                .parse::<u32>()
+                .map(TimeSpec::Samples)
+                .map_err(|e| match e.kind() {
+                    std::num::IntErrorKind::PosOverflow => beyond_max(time_str),
+                    _ => String::from("Failed to parse time"),
+                })
+        }
+    }
+
+    // Time counted from the start or end of the file, which can't itself be
+    // relative to either
+    fn parse_offset(time_str: &str) -> Result<Self, String> {
+        match TimeSpec::parse(time_str)? {
+            TimeSpec::Last | TimeSpec::BeforeEnd(_) => Err(format!(
+                "Expected a length of time after START+ or END-, got \"{}\"",
+                time_str
+            )),
+            offset => Ok(offset),
+        }
+    }
+
+    /// Frame the time falls on in a file of `sample_count` frames
+    pub fn resolve(
+        &self,
+        sample_rate: u32,
+        sample_count: u32,
+    ) -> Result<u32, String> {
+        let frames = match *self {
+            TimeSpec::Samples(frames) => return Ok(frames),
+            TimeSpec::Last => return Ok(sample_count),
+            TimeSpec::BeforeEnd(ref offset) => {
+                let offset = offset.resolve(sample_rate, sample_count)?;
+
+                return sample_count.checked_sub(offset).ok_or_else(|| {
+                    format!(
+                        "Time \"{}\" is before the start of the {}-sample \
+                        file",
+                        self, sample_count,
+                    )
+                });
+            }
+            TimeSpec::Seconds(seconds) => seconds * f64::from(sample_rate),
+            TimeSpec::Millis(millis) => {
+                millis / 1000.0 * f64::from(sample_rate)
+            }
+            TimeSpec::Percent(percent) => {
+                percent / 100.0 * f64::from(sample_count)
+            }
+        }
+        .round();
+
+        if frames.is_nan() || frames < 0.0 {
+            Err(format!("Time \"{}\" is negative", self))
+        } else if frames > f64::from(MAX_FRAMES) {
+            Err(beyond_max(self))
+        } else {
+            Ok(frames as u32)
+        }
+    }
+}
+
+impl fmt::Display for TimeSpec {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TimeSpec::Samples(frames) => write!(f, "{}", frames),
+            TimeSpec::Seconds(seconds) => write!(f, "{}s", seconds),
+            TimeSpec::Millis(millis) => write!(f, "{}ms", millis),
+            TimeSpec::Percent(percent) => write!(f, "{}%", percent),
+            TimeSpec::Last => write!(f, "LAST"),
+            TimeSpec::BeforeEnd(offset) => write!(f, "END-{}", offset),
+        }
+    }
+}
+
+fn beyond_max(time: impl fmt::Display) -> String {
+    format!(
+        "Time \"{}\" is beyond the maximum of {} samples",
+        time, MAX_FRAMES,
+    )
+}
+
+/// Where a template puts the loop
+#[derive(Debug, Clone, PartialEq)]
+pub enum LoopPlacement {
+    /// Endpoints resolved against each file's own rate and length
+    Times { start: TimeSpec, end: TimeSpec },
+
+    /// Endpoints at the same fractions of each file's length, as copied from
+    /// a reference file
+    Proportional { start: f64, end: f64 },
+}
+
+/// Loop and blend settings shared by a batch of files
+#[derive(Debug, Clone, PartialEq)]
+pub struct LoopTemplate {
+    pub placement: LoopPlacement,
+
+    /// Blend window, if the loop is to be blended after placing it
+    pub blend: Option<TimeSpec>,
+}
+
+/// A template as resolved against one file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ResolvedTemplate {
+    pub sample_loop: Range<u32>,
+    pub blend: Option<u32>,
+}
+
+impl LoopTemplate {
+    /// Parse comma-separated settings, e.g. "start=25%,end=LAST,blend=40ms".
+    /// The start is required; the end defaults to "LAST".
+    pub fn parse(spec: &str) -> Result<Self, String> {
+        let mut start = None;
+        let mut end = TimeSpec::Last;
+        let mut blend = None;
+
+        for setting in spec.split(',').map(str::trim) {
+            let (key, value) = setting.split_once('=').ok_or_else(|| {
+                format!("Expected key=value in template, got \"{}\"", setting)
+            })?;
+            let time = TimeSpec::parse(value.trim())?;
+
+            match key.trim() {
+                "start" => start = Some(time),
+                "end" => end = time,
+                "blend" => blend = Some(time),
+                other => {
+                    return Err(format!("Unknown template key \"{}\"", other))
+                }
+            }
+        }
+
+        let start = start.ok_or("Template lacks a loop start")?;
+
+        Ok(LoopTemplate {
+            placement: LoopPlacement::Times { start, end },
+            blend,
+        })
+    }
+
+    /// Copy the loop of a reference file as fractions of its length
+    pub fn from_reference(metadata: &Metadata) -> Result<Self, String> {
+        let start = metadata.loop_start.ok_or("Reference file has no loop")?;
+        let end = metadata.end.unwrap_or(metadata.sample_count);
+        let length = f64::from(metadata.sample_count);
+
+        if metadata.sample_count == 0 {
+            return Err(String::from("Reference file has no samples"));
+        }
+
+        Ok(LoopTemplate {
+            placement: LoopPlacement::Proportional {
+                start: f64::from(start) / length,
+                end: f64::from(end) / length,
+            },
+            blend: None,
+        })
+    }
+
+    pub fn resolve(
+        &self,
+        sample_rate: u32,
+        sample_count: u32,
+    ) -> Result<ResolvedTemplate, String> {
+        let sample_loop = match self.placement {
+            LoopPlacement::Times { ref start, ref end } => {
+                start.resolve(sample_rate, sample_count)?
+                    ..end.resolve(sample_rate, sample_count)?
+            }
+            LoopPlacement::Proportional { start, end } => {
+                let scale = |fraction: f64| TimeSpec::Percent(fraction * 100.0);
+                scale(start).resolve(sample_rate, sample_count)?
+                    ..scale(end).resolve(sample_rate, sample_count)?
+            }
+        };
+
+        let blend = self
+            .blend
+            .as_ref()
+            .map(|blend| blend.resolve(sample_rate, sample_count))
+            .transpose()?;
+
+        Ok(ResolvedTemplate { sample_loop, blend })
+    }
+
+    /// Set the project's loop and blend it as the template says, returning
+    /// the settings used
+    pub fn apply(
+        &self,
+        proj: &mut Project,
+    ) -> Result<ResolvedTemplate, String> {
+        let resolved = self.resolve(proj.sample_rate(), proj.sample_count())?;
+
+        proj.set_loop(Some(resolved.sample_loop.clone()));
+        proj.validate()?;
+
+        if let Some(window_sz) = resolved.blend {
+            proj.blend(window_sz)?;
+        }
+
+        Ok(resolved)
+    }
+}
@@ -0,0 +1,416 @@
+use std::ops::Range;
+
+pub type Rgb = [u8; 3];
+
+// Samples at or beyond these count as clipped.  The positive limit is the top
+// step of 8-bit audio, which is widened to 16 bits on read and so never
+// reaches i16::MAX.
+const CLIP_HIGH: i16 = i16::MAX & !0xff;
+const CLIP_LOW: i16 = i16::MIN;
+
+/// Summary of a run of samples, as drawn in a single waveform column
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Bin {
+    pub min: i16,
+    pub max: i16,
+    pub rms: f32,
+
+    /// Number of samples at full scale, positive or negative
+    pub clipped: u32,
+}
+
+impl Bin {
+    /// Summarize samples; an empty slice gives a silent bin
+    pub fn from_samples(samples: &[i16]) -> Self {
+        if samples.is_empty() {
+            return Bin {
+                min: 0,
+                max: 0,
+                rms: 0.0,
+                clipped: 0,
+            };
+        }
+
+        let mut min = i16::MAX;
+        let mut max = i16::MIN;
+        let mut sum_sq = 0f64;
+        let mut clipped = 0u32;
+
+        for &s in samples {
+            min = min.min(s);
+            max = max.max(s);
+            sum_sq += f64::from(s) * f64::from(s);
+
+            if is_clipped(s) {
+                clipped = clipped.saturating_add(1);
+            }
+        }
+
+        Bin {
+            min,
+            max,
+            rms: (sum_sq / samples.len() as f64).sqrt() as f32,
+            clipped,
+        }
+    }
+}
+
+pub fn is_clipped(sample: i16) -> bool {
+    sample >= CLIP_HIGH || sample == CLIP_LOW
+}
+
+/// Split a range of samples into `count` consecutive, nearly equal sub-ranges.
+/// When there are fewer samples than sub-ranges, each sub-range holds the one
+/// sample nearest to it so that no column comes out empty.
+pub fn rebin_ranges(
+    range: Range<usize>,
+    count: usize,
+) -> impl Iterator<Item = Range<usize>> {
+    let len = range.len();
+
+    (0..count).map(move |i| {
+        let start = range.start + i * len / count;
+        let end = range.start + (i + 1) * len / count;
+
+        if end > start || len == 0 {
+            start..end
+        } else {
+            start..start + 1
+        }
+    })
+}
+
+/// Summarize a range of samples into `count` bins
+pub fn bin_samples(
+    samples: &[i16],
+    range: Range<usize>,
+    count: usize,
+) -> Vec<Bin> {
+    rebin_ranges(range, count)
+        .map(|sub_range| Bin::from_samples(&samples[sub_range]))
+        .collect()
+}
+
+/// Recompute the bins that `bin_samples` gave for `range` which hold any of
+/// the samples in `changed`, after those samples were edited, leaving every
+/// other bin as it is.  Returns the indices of the bins recomputed.
+pub fn update_bins(
+    bins: &mut [Bin],
+    samples: &[i16],
+    range: Range<usize>,
+    changed: Range<usize>,
+) -> Range<usize> {
+    let columns = columns_of(range.clone(), bins.len(), changed);
+    let sub_ranges = rebin_ranges(range, bins.len())
+        .skip(columns.start)
+        .take(columns.len());
+
+    for (bin, sub_range) in bins[columns.clone()].iter_mut().zip(sub_ranges) {
+        *bin = Bin::from_samples(&samples[sub_range]);
+    }
+
+    columns
+}
+
+/// Summarize a range of samples into `count` bins as `bin_samples` does, but
+/// from consecutive chunks of a sample stream starting at sample 0, so the
+/// whole file never has to be in memory.  Bins past the end of a short stream
+/// come out silent.
+pub fn bin_samples_streaming<C, E>(
+    chunks: impl IntoIterator<Item = Result<C, E>>,
+    range: Range<usize>,
+    count: usize,
+) -> Result<Vec<Bin>, E>
+where
+    C: AsRef<[i16]>,
+{
+    let sub_ranges = rebin_ranges(range.clone(), count).collect::<Vec<_>>();
+    let mut bins = vec![BinAccumulator::default(); count];
+    let mut first_bin = 0;
+    let mut offset = 0;
+
+    for chunk in chunks {
+        let chunk = chunk?;
+        let chunk = chunk.as_ref();
+
+        // Sub-ranges only overlap when a few samples are spread over many
+        // bins, and then always onto consecutive bins
+        for (index, &s) in (offset..).zip(chunk) {
+            while first_bin < count && sub_ranges[first_bin].end <= index {
+                first_bin += 1;
+            }
+
+            for (sub_range, bin) in
+                sub_ranges[first_bin..].iter().zip(&mut bins[first_bin..])
+            {
+                if sub_range.start > index {
+                    break;
+                }
+
+                bin.add(s);
+            }
+        }
+
+        offset += chunk.len();
+
+        if offset >= range.end {
+            break;
+        }
+    }
+
+    Ok(bins.iter().map(BinAccumulator::bin).collect())
+}
+
+// Running totals for a bin whose samples arrive a few at a time
+#[derive(Debug, Clone, Copy)]
+struct BinAccumulator {
+    min: i16,
+    max: i16,
+    sum_sq: f64,
+    len: usize,
+    clipped: u32,
+}
+
+impl Default for BinAccumulator {
+    fn default() -> Self {
+        BinAccumulator {
+            min: i16::MAX,
+            max: i16::MIN,
+            sum_sq: 0.0,
+            len: 0,
+            clipped: 0,
+        }
+    }
+}
+
+impl BinAccumulator {
+    fn add(&mut self, s: i16) {
+        self.min = self.min.min(s);
+        self.max = self.max.max(s);
+        self.sum_sq += f64::from(s) * f64::from(s);
+        self.len += 1;
+
+        if is_clipped(s) {
+            self.clipped = self.clipped.saturating_add(1);
+        }
+    }
+
+    fn bin(&self) -> Bin {
+        if self.len == 0 {
+            return Bin::from_samples(&[]);
+        }
+
+        Bin {
+            min: self.min,
+            max: self.max,
+            rms: (self.sum_sq / self.len as f64).sqrt() as f32,
+            clipped: self.clipped,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Theme {
+    pub background: Rgb,
+    pub peak: Rgb,
+    pub rms: Rgb,
+    pub marker: Rgb,
+
+    /// Color of the top or bottom pixel of columns whose samples reach full
+    /// scale; clipping is left unmarked when none
+    pub clip: Option<Rgb>,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Theme {
+            background: [0x30, 0x30, 0x30],
+            peak: [0xc0, 0xa0, 0x20],
+            rms: [0xf0, 0xe0, 0x80],
+            marker: [0xe0, 0x40, 0x40],
+            clip: None,
+        }
+    }
+}
+
+// Row of a `height`-pixel render at which a sample value is drawn
+fn sample_to_row(s: f64, height: usize) -> usize {
+    let scaled = (f64::from(i16::MAX) - s) / f64::from(u16::MAX);
+    (scaled * (height - 1) as f64)
+        .round()
+        .clamp(0.0, (height - 1) as f64) as usize
+}
+
+/// Render samples in `range` to a tightly packed 8-bit RGB buffer of
+/// `width` x `height` pixels.  Each column spans min to max of its bin in the
+/// peak color, overdrawn with +/- RMS in the RMS color.  Markers are sample
+/// positions drawn as full-height lines; those outside of `range` are skipped.
+/// With a clip color in the theme, clipped columns are marked at the top row,
+/// the bottom row, or both, depending on which extreme they reach.
+pub fn render_rgb(
+    samples: &[i16],
+    range: Range<usize>,
+    width: usize,
+    height: usize,
+    markers: &[usize],
+    theme: &Theme,
+) -> Vec<u8> {
+    let mut buffer = Vec::with_capacity(width * height * 3);
+
+    for _ in 0..width * height {
+        buffer.extend_from_slice(&theme.background);
+    }
+
+    if width == 0 || height == 0 {
+        return buffer;
+    }
+
+    let sample_to_row = |s: f64| sample_to_row(s, height);
+
+    let mut fill_column = |col: usize, rows: Range<usize>, color: &Rgb| {
+        for row in rows {
+            let idx = (row * width + col) * 3;
+            buffer[idx..idx + 3].copy_from_slice(color);
+        }
+    };
+
+    let sub_ranges = rebin_ranges(range.clone(), width).collect::<Vec<_>>();
+
+    for (col, sub_range) in sub_ranges.iter().enumerate() {
+        let bin = Bin::from_samples(&samples[sub_range.clone()]);
+        let top = sample_to_row(bin.max.into());
+        let bottom = sample_to_row(bin.min.into());
+        fill_column(col, top..bottom + 1, &theme.peak);
+
+        let rms_top = sample_to_row(f64::from(bin.rms).min(bin.max.into()));
+        let rms_bottom =
+            sample_to_row((-f64::from(bin.rms)).max(bin.min.into()));
+
+        if rms_top < rms_bottom {
+            fill_column(col, rms_top..rms_bottom + 1, &theme.rms);
+        }
+
+        if let (Some(clip), true) = (&theme.clip, bin.clipped > 0) {
+            if bin.max >= CLIP_HIGH {
+                fill_column(col, 0..1, clip);
+            }
+
+            if bin.min == CLIP_LOW {
+                fill_column(col, height - 1..height, clip);
+            }
+        }
+    }
+
+    for &marker in markers {
+        if !range.contains(&marker) {
+            continue;
+        }
+
+        let col = sub_ranges
+            .iter()
+            .position(|sub_range| sub_range.end > marker)
+            .unwrap_or(width - 1);
+
+        fill_column(col, 0..height, &theme.marker);
+    }
+
+    buffer
+}
+
+/// Alternative samples for part of a rendered range, such as a preview of an
+/// edit, drawn over the original
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Overlay<'a> {
+    /// Index of the first sample replaced
+    pub start: usize,
+    pub samples: &'a [i16],
+
+    /// Color of the replacement's min to max extent
+    pub color: Rgb,
+
+    /// Color replacing the background behind the replaced samples, if any
+    pub highlight: Option<Rgb>,
+}
+
+impl Overlay<'_> {
+    pub fn range(&self) -> Range<usize> {
+        self.start..self.start + self.samples.len()
+    }
+}
+
+/// Columns of a `width`-column render of `range` holding any of the samples
+/// in `sub_range`; empty if none do
+pub fn columns_of(
+    range: Range<usize>,
+    width: usize,
+    sub_range: Range<usize>,
+) -> Range<usize> {
+    let mut columns = rebin_ranges(range, width)
+        .enumerate()
+        .filter(|(_, bin)| {
+            bin.start < sub_range.end && sub_range.start < bin.end
+        })
+        .map(|(col, _)| col);
+
+    match columns.next() {
+        Some(first) => first..columns.last().unwrap_or(first) + 1,
+        None => 0..0,
+    }
+}
+
+/// Draw `overlay` onto a buffer from `render_rgb` with the same `range`,
+/// `width`, and `height`.  Each column holding replaced samples is summarized
+/// as `render_rgb` would, with the replacement standing in for the original
+/// samples it covers, and its min to max extent drawn in the overlay color.
+/// With a highlight, pixels of the theme background in those columns take the
+/// highlight color.
+pub fn render_overlay(
+    buffer: &mut [u8],
+    samples: &[i16],
+    range: Range<usize>,
+    width: usize,
+    height: usize,
+    overlay: &Overlay,
+    theme: &Theme,
+) {
+    if width == 0 || height == 0 {
+        return;
+    }
+
+    let sample_to_row = |s: f64| sample_to_row(s, height);
+
+    let overlay_range = overlay.range();
+    let columns = columns_of(range.clone(), width, overlay_range.clone());
+
+    for (col, bin_range) in rebin_ranges(range, width).enumerate() {
+        if !columns.contains(&col) {
+            continue;
+        }
+
+        let column = bin_range
+            .map(|idx| {
+                if overlay_range.contains(&idx) {
+                    overlay.samples[idx - overlay.start]
+                } else {
+                    samples[idx]
+                }
+            })
+            .collect::<Vec<_>>();
+        let bin = Bin::from_samples(&column);
+        let rows =
+            sample_to_row(bin.max.into())..sample_to_row(bin.min.into()) + 1;
+
+        for row in 0..height {
+            let idx = (row * width + col) * 3;
+            let pixel = &mut buffer[idx..idx + 3];
+
+            if rows.contains(&row) {
+                pixel.copy_from_slice(&overlay.color);
+            } else if let (Some(highlight), true) =
+                (&overlay.highlight, *pixel == theme.background)
+            {
+                pixel.copy_from_slice(highlight);
+            }
+        }
+    }
+}
@@ -0,0 +1,56 @@
+use quadio_core::{Project, QWaveReader};
+use std::io::Cursor;
+
+// Noise repeating every `period` frames at 11025 Hz, swelling and fading
+// over each period
+fn periodic_project(period: u32, periods: u32) -> Project {
+    let spec = hound::WavSpec {
+        channels: 1,
+        sample_rate: 11025,
+        bits_per_sample: 16,
+        sample_format: hound::SampleFormat::Int,
+    };
+
+    let mut wave = Cursor::new(vec![]);
+    let mut writer = hound::WavWriter::new(&mut wave, spec).unwrap();
+    let mut noise = 1u32;
+    let cycle = (0..period)
+        .map(|i| {
+            noise = noise.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+            let swell = (f64::from(i) / f64::from(period)
+                * std::f64::consts::TAU)
+                .sin()
+                + 1.2;
+            (f64::from(noise >> 20) - 2048.0) * 4.0 * swell
+        })
+        .collect::<Vec<_>>();
+
+    for _ in 0..periods {
+        for &sample in &cycle {
+            writer.write_sample(sample as i16).unwrap();
+        }
+    }
+
+    writer.finalize().unwrap();
+    wave.set_position(0);
+    Project::from_reader(QWaveReader::new(wave).unwrap()).unwrap()
+}
+
+#[test]
+fn loops_are_found_a_whole_number_of_periods_long() {
+    let project = periodic_project(5000, 4);
+    let candidates = project.find_loop(4000, None).unwrap();
+    let best = candidates.first().unwrap();
+
+    assert_eq!((best.end - best.start) % 5000, 0, "{:?}", best);
+    assert!(best.score > 0.99, "{:?}", best);
+    assert!(candidates.windows(2).all(|w| w[0].score >= w[1].score));
+
+    let within = project.find_loop(4000, Some(2000..16000)).unwrap();
+    assert!(within
+        .iter()
+        .all(|candidate| candidate.start >= 2000 && candidate.end <= 16000));
+
+    assert!(project.find_loop(4000, Some(0..30000)).is_err());
+    assert!(project.find_loop(30000, None).unwrap().is_empty());
+}
@@ -0,0 +1,182 @@
+use quadio_core::dsp::BlendShape;
+use quadio_core::{Project, QWaveReader};
+use std::io::Cursor;
+use std::ops::Range;
+
+const FRAMES: u32 = 22050;
+
+// A second of 16-bit, 441 Hz sine at 22050 Hz with the given loop
+fn project(sample_loop: Option<Range<u32>>) -> Project {
+    project_of(sample_loop, |i| sine(i, 0.0))
+}
+
+// 441 Hz sine at 22050 Hz, scaled to 16-bit, a cycle of 50 samples
+fn sine(i: u32, phase: f64) -> f64 {
+    let cycles = f64::from(i) * 441.0 / 22050.0 + phase;
+    (cycles * std::f64::consts::TAU).sin() * 16000.0
+}
+
+// A second of 16-bit samples at 22050 Hz with the given loop
+fn project_of(
+    sample_loop: Option<Range<u32>>,
+    sample: impl Fn(u32) -> f64,
+) -> Project {
+    let spec = hound::WavSpec {
+        channels: 1,
+        sample_rate: 22050,
+        bits_per_sample: 16,
+        sample_format: hound::SampleFormat::Int,
+    };
+
+    let mut bytes = Cursor::new(Vec::new());
+    let mut writer = hound::WavWriter::new(&mut bytes, spec).unwrap();
+
+    for i in 0..FRAMES {
+        writer.write_sample(sample(i) as i16).unwrap();
+    }
+
+    writer.finalize().unwrap();
+
+    let reader = QWaveReader::new(Cursor::new(bytes.into_inner())).unwrap();
+    let mut proj = Project::from_reader(reader).unwrap();
+    proj.set_loop(sample_loop);
+    proj
+}
+
+// Clamped blend of `window_sz` matches an ordinary blend of the window it
+// reports using
+fn assert_clamps_to(sample_loop: Range<u32>, window_sz: u32, expected: u32) {
+    let mut clamped = project(Some(sample_loop.clone()));
+    assert_eq!(clamped.blend_clamped(window_sz).unwrap(), expected);
+
+    let mut blended = project(Some(sample_loop));
+    blended.blend(expected).unwrap();
+    assert_eq!(clamped.samples(), blended.samples());
+}
+
+#[test]
+fn window_that_fits_is_kept() {
+    assert_clamps_to(5000..15000, 500, 500);
+}
+
+#[test]
+fn window_clamps_to_lead_at_file_start() {
+    assert_clamps_to(120..15000, 1000, 120);
+}
+
+#[test]
+fn window_clamps_to_loop_ending_at_file_end() {
+    assert_clamps_to(FRAMES - 300..FRAMES, 1000, 300);
+}
+
+#[test]
+fn window_clamps_to_whichever_edge_is_nearer() {
+    assert_clamps_to(200..FRAMES, FRAMES, 200);
+    assert_clamps_to(FRAMES - 700..FRAMES, FRAMES, 700);
+}
+
+#[test]
+fn loop_from_file_start_has_no_lead_to_blend() {
+    let mut proj = project(Some(0..FRAMES));
+    assert!(proj.blend_clamped(1000).is_err());
+    assert_eq!(proj.samples(), project(None).samples());
+}
+
+#[test]
+fn no_loop_is_an_error() {
+    assert!(project(None).blend_clamped(1000).is_err());
+}
+
+const ENTRY_LOOP: Range<u32> = 5000..15000;
+
+// Lead of sine spliced onto a loop a quarter cycle ahead of it.  The loop
+// wraps cleanly, being a whole number of cycles, but entering it from the lead
+// jumps.
+fn spliced() -> Project {
+    project_of(Some(ENTRY_LOOP), |i| {
+        sine(i, if ENTRY_LOOP.contains(&i) { 0.25 } else { 0.0 })
+    })
+}
+
+// Size of the step between two samples
+fn step(samples: &[i16], from: u32, to: u32) -> i32 {
+    (i32::from(samples[to as usize]) - i32::from(samples[from as usize])).abs()
+}
+
+fn entry_step(proj: &Project) -> i32 {
+    step(proj.samples(), ENTRY_LOOP.start - 1, ENTRY_LOOP.start)
+}
+
+fn wrap_step(proj: &Project) -> i32 {
+    step(proj.samples(), ENTRY_LOOP.end - 1, ENTRY_LOOP.start)
+}
+
+// Largest step anywhere in an unbroken 441 Hz sine
+fn natural_step() -> i32 {
+    let proj = project(None);
+    (1..FRAMES)
+        .map(|i| step(proj.samples(), i - 1, i))
+        .max()
+        .unwrap()
+}
+
+#[test]
+fn entry_blend_smooths_the_entry_into_the_loop() {
+    let mut proj = spliced();
+    assert!(entry_step(&proj) > natural_step() * 4);
+    assert!(wrap_step(&proj) <= natural_step());
+
+    proj.blend_entry(500, BlendShape::default()).unwrap();
+    assert!(entry_step(&proj) <= natural_step());
+
+    // Every step into, through and out of the blended window is smooth
+    let window = ENTRY_LOOP.start - 1..ENTRY_LOOP.start + 501;
+    for i in window.clone().skip(1) {
+        let window_step = step(proj.samples(), i - 1, i);
+        assert!(
+            window_step <= natural_step() * 2,
+            "step of {window_step} at {i}"
+        );
+    }
+}
+
+#[test]
+fn entry_blend_leaves_the_wrap_material_untouched() {
+    let original = spliced();
+    let mut proj = spliced();
+    proj.blend_entry(500, BlendShape::default()).unwrap();
+
+    // Only the first window of the loop is rewritten, so the lead and the
+    // samples leading into the wrap are as they were
+    let start = ENTRY_LOOP.start as usize;
+    assert_eq!(proj.samples()[..start], original.samples()[..start]);
+    assert_eq!(
+        proj.samples()[start + 500..],
+        original.samples()[start + 500..]
+    );
+    assert_ne!(proj.samples(), original.samples());
+}
+
+#[test]
+fn entry_and_wrap_blends_together_smooth_both_seams() {
+    let mut proj = spliced();
+    proj.blend_entry(500, BlendShape::default()).unwrap();
+    proj.blend(500).unwrap();
+
+    assert!(entry_step(&proj) <= natural_step());
+    assert!(wrap_step(&proj) <= natural_step());
+}
+
+#[test]
+fn entry_blend_needs_room_for_the_window() {
+    let mut proj = project(Some(100..15000));
+    assert!(proj.blend_entry(200, BlendShape::default()).is_err());
+
+    let mut proj = project(Some(5000..5100));
+    assert!(proj.blend_entry(200, BlendShape::default()).is_err());
+
+    assert!(project(None)
+        .blend_entry(200, BlendShape::default())
+        .is_err());
+    assert_eq!(proj.samples(), project(None).samples());
+}
@@ -0,0 +1,109 @@
+use quadio_core::{Project, QWaveReader};
+use std::fs;
+use std::io::Cursor;
+use std::path::PathBuf;
+
+const FRAMES: i16 = 2000;
+
+// 16-bit WAV of `channels` channels, each a ramp offset by its index so
+// channels can't be mistaken for one another
+fn wav(channels: u16) -> Vec<u8> {
+    let spec = hound::WavSpec {
+        channels,
+        sample_rate: 22050,
+        bits_per_sample: 16,
+        sample_format: hound::SampleFormat::Int,
+    };
+
+    let mut bytes = Cursor::new(Vec::new());
+    let mut writer = hound::WavWriter::new(&mut bytes, spec).unwrap();
+
+    for frame in 0..FRAMES {
+        for ch in 0..channels as i16 {
+            writer.write_sample(frame * 8 - ch * 10000).unwrap();
+        }
+    }
+
+    writer.finalize().unwrap();
+    bytes.into_inner()
+}
+
+fn project(bytes: Vec<u8>) -> Project {
+    Project::from_reader(QWaveReader::new(Cursor::new(bytes)).unwrap()).unwrap()
+}
+
+fn temp_path(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!(
+        "quadio-channels-{}-{}",
+        std::process::id(),
+        name
+    ))
+}
+
+// Project written to a file and read back
+fn round_trip(proj: &Project, name: &str) -> (Vec<u8>, Project) {
+    let path = temp_path(name);
+    proj.write_to(&path).unwrap();
+    let bytes = fs::read(&path).unwrap();
+    fs::remove_file(&path).unwrap();
+    (bytes.clone(), project(bytes))
+}
+
+#[test]
+fn stereo_is_read_interleaved() {
+    let proj = project(wav(2));
+
+    assert_eq!(proj.channels(), 2);
+    assert_eq!(proj.sample_count(), FRAMES as u32);
+    assert_eq!(proj.samples().len(), FRAMES as usize * 2);
+    assert_eq!(&proj.samples()[..4], &[0, -10000, 8, -9992]);
+}
+
+#[test]
+fn stereo_round_trips_with_loop() {
+    let mut proj = project(wav(2));
+    proj.set_loop(Some(500..1500));
+
+    let (bytes, reread) = round_trip(&proj, "stereo.wav");
+    let metadata = QWaveReader::new(Cursor::new(bytes)).unwrap().metadata();
+
+    assert_eq!(metadata.channels, 2);
+    assert_eq!(metadata.sample_count, FRAMES as u32);
+    assert_eq!(metadata.loop_start, Some(500));
+    assert_eq!(metadata.end, Some(1500));
+    assert_eq!(reread.channels(), 2);
+    assert_eq!(reread.samples(), proj.samples());
+
+    let (_, reread_again) = round_trip(&reread, "stereo-again.wav");
+    assert_eq!(reread_again.samples(), proj.samples());
+}
+
+#[test]
+fn mono_round_trip_is_bit_identical() {
+    let original = wav(1);
+    let (bytes, reread) = round_trip(&project(original.clone()), "mono.wav");
+
+    assert_eq!(reread.channels(), 1);
+    assert_eq!(bytes, original);
+}
+
+#[test]
+fn blend_keeps_channels_apart() {
+    let mut proj = project(wav(2));
+    proj.set_loop(Some(500..1500));
+    proj.blend(100).unwrap();
+
+    // Each channel's ramp is blended on its own, so every right sample stays
+    // 10000 below the left one
+    for frame in proj.samples().chunks_exact(2) {
+        assert_eq!(i32::from(frame[0]) - i32::from(frame[1]), 10000);
+    }
+
+    assert_ne!(proj.samples(), project(wav(2)).samples());
+}
+
+#[test]
+fn mono_only_readers_refuse_stereo() {
+    let mut reader = QWaveReader::new(Cursor::new(wav(2))).unwrap();
+    assert!(reader.collect_samples().is_err());
+}
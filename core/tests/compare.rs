@@ -0,0 +1,76 @@
+use quadio_core::{compare_readers, CompareOptions, Project, QWaveReader};
+use std::io::Cursor;
+
+// Three seconds of 16-bit ramp at 44.1 kHz, longer than a chunk compared at
+// once, looped from 1000 to 100000
+fn ramp_wave(loop_end: u32) -> Vec<u8> {
+    let spec = hound::WavSpec {
+        channels: 1,
+        sample_rate: 44100,
+        bits_per_sample: 16,
+        sample_format: hound::SampleFormat::Int,
+    };
+
+    let mut wave = Cursor::new(vec![]);
+    let mut writer = hound::WavWriter::new(&mut wave, spec).unwrap();
+
+    for i in 0..132_300 {
+        writer.write_sample((i % 1000) as i16 * 30).unwrap();
+    }
+
+    writer.finalize().unwrap();
+    wave.set_position(0);
+
+    let mut proj =
+        Project::from_reader(QWaveReader::new(wave).unwrap()).unwrap();
+    proj.set_loop(Some(1000..loop_end));
+
+    let mut wave = Cursor::new(vec![]);
+    proj.write(&mut wave).unwrap();
+    wave.into_inner()
+}
+
+fn project(wave: &[u8]) -> Project {
+    Project::from_reader(QWaveReader::new(Cursor::new(wave)).unwrap()).unwrap()
+}
+
+#[test]
+fn streamed_comparison_matches_in_memory() {
+    let a = ramp_wave(100_000);
+    let mut edited = project(&a);
+    let mut samples = edited.samples().to_vec();
+
+    // Either side of the chunk boundary at 65536
+    samples[65_000] += 7;
+    samples[99_999] -= 3;
+    edited.replace_samples(samples).unwrap();
+    let mut b = Cursor::new(vec![]);
+    edited.write(&mut b).unwrap();
+    let b = b.into_inner();
+
+    let streamed = compare_readers(
+        &mut QWaveReader::new(Cursor::new(&a)).unwrap(),
+        &mut QWaveReader::new(Cursor::new(&b)).unwrap(),
+        CompareOptions::default(),
+    )
+    .unwrap();
+    assert_eq!(streamed, project(&a).diff(&project(&b)));
+    assert_eq!(streamed.first_difference, Some(65_000));
+    assert_eq!(streamed.last_difference, Some(99_999));
+    assert_eq!(streamed.differing_samples, 2);
+    assert_eq!(streamed.max_difference, 7);
+    assert!(!streamed.is_identical());
+    assert!(streamed.matches_within(7));
+    assert!(streamed.differences_within(65_000..100_000));
+    assert!(!streamed.differences_within(66_000..100_000));
+
+    let moved = ramp_wave(90_000);
+    let report = project(&a).diff(&project(&moved));
+    assert_eq!(
+        report.loops,
+        Some((Some(1000..100_000), Some(1000..90_000)))
+    );
+    assert_eq!(report.first_difference, None);
+    assert!(report.differences_within(0..0));
+    assert!(!report.matches_within(u32::MAX));
+}
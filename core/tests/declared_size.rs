@@ -0,0 +1,103 @@
+use quadio_core::QWaveReader;
+use std::io::Cursor;
+
+const SAMPLES: u32 = 1000;
+
+// 16-bit mono WAV holding `SAMPLES` samples, with the given sizes written into
+// the RIFF and data chunk headers, followed by `tail`
+fn wav(riff_size: u32, data_size: u32, tail: &[u8]) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    bytes.extend(b"RIFF");
+    bytes.extend(riff_size.to_le_bytes());
+    bytes.extend(b"WAVEfmt ");
+    bytes.extend(16u32.to_le_bytes());
+    bytes.extend(1u16.to_le_bytes());
+    bytes.extend(1u16.to_le_bytes());
+    bytes.extend(22050u32.to_le_bytes());
+    bytes.extend(44100u32.to_le_bytes());
+    bytes.extend(2u16.to_le_bytes());
+    bytes.extend(16u16.to_le_bytes());
+    bytes.extend(b"data");
+    bytes.extend(data_size.to_le_bytes());
+
+    for i in 0..SAMPLES {
+        bytes.extend(((i % 200) as i16 * 100 - 9950).to_le_bytes());
+    }
+
+    bytes.extend(tail);
+    bytes
+}
+
+fn declared_vs_actual(bytes: Vec<u8>) -> Option<(u32, u32)> {
+    QWaveReader::new(Cursor::new(bytes))
+        .unwrap()
+        .metadata()
+        .declared_vs_actual
+}
+
+#[test]
+fn matching_sizes_are_not_reported() {
+    let bytes = wav(36 + SAMPLES * 2, SAMPLES * 2, &[]);
+    assert_eq!(declared_vs_actual(bytes), None);
+}
+
+#[test]
+fn truncated_data_is_over_declared() {
+    let bytes = wav(36 + SAMPLES * 4, SAMPLES * 4, &[]);
+    assert_eq!(declared_vs_actual(bytes), Some((SAMPLES * 2, SAMPLES)));
+}
+
+#[test]
+fn sizes_never_filled_in_are_under_declared() {
+    let bytes = wav(36, 0, &[]);
+    assert_eq!(declared_vs_actual(bytes), Some((0, SAMPLES)));
+
+    let bytes = wav(36 + SAMPLES, SAMPLES, &[]);
+    assert_eq!(declared_vs_actual(bytes), Some((SAMPLES / 2, SAMPLES)));
+}
+
+#[test]
+fn padding_within_the_riff_is_not_sample_data() {
+    let tail = [0u8; 64];
+    let bytes = wav(36 + SAMPLES * 2 + 64, SAMPLES * 2, &tail);
+    assert_eq!(declared_vs_actual(bytes), None);
+}
+
+#[test]
+fn junk_within_the_riff_is_not_sample_data() {
+    let tail = b"\x01\xff-- trailing junk ---\x00\x00";
+    let riff_size = 36 + SAMPLES * 2 + tail.len() as u32;
+    let bytes = wav(riff_size, SAMPLES * 2, tail);
+    assert_eq!(declared_vs_actual(bytes), None);
+}
+
+#[test]
+fn chunks_after_the_data_are_not_sample_data() {
+    let mut tail = Vec::new();
+    tail.extend(b"LIST");
+    tail.extend(4u32.to_le_bytes());
+    tail.extend(b"INFO");
+
+    let riff_size = 36 + SAMPLES * 2 + tail.len() as u32;
+    let bytes = wav(riff_size, SAMPLES * 2, &tail);
+    assert_eq!(declared_vs_actual(bytes), None);
+}
+
+#[test]
+fn junk_after_the_riff_is_not_sample_data() {
+    let tail = b"junk appended by another tool\n";
+    let bytes = wav(36 + SAMPLES * 2, SAMPLES * 2, tail);
+    assert_eq!(declared_vs_actual(bytes), None);
+
+    // A whole number of frames, but nothing like the audio before it
+    let tail = b"junk appended by another tool!!\n";
+    let bytes = wav(36 + SAMPLES * 2, SAMPLES * 2, tail);
+    assert_eq!(declared_vs_actual(bytes), None);
+}
+
+#[test]
+fn padding_after_the_riff_is_not_sample_data() {
+    let tail = [0u8; 512];
+    let bytes = wav(36 + SAMPLES * 2, SAMPLES * 2, &tail);
+    assert_eq!(declared_vs_actual(bytes), None);
+}
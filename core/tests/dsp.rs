@@ -0,0 +1,76 @@
+use quadio_core::dsp::{crossfade, BlendShape};
+
+const SHAPES: [BlendShape; 3] = [
+    BlendShape::Smooth,
+    BlendShape::Linear,
+    BlendShape::EqualPower,
+];
+
+fn fade(a: &[i16], b: &[i16], shape: BlendShape) -> (Vec<i16>, usize) {
+    let mut dst = vec![0; a.len()];
+    let clipped = crossfade(&mut dst, a, b, shape).unwrap();
+    (dst, clipped)
+}
+
+#[test]
+fn fade_starts_on_b_and_ends_near_a() {
+    let a = [10000; 100];
+    let b = [-10000; 100];
+
+    for shape in SHAPES {
+        let (dst, clipped) = fade(&a, &b, shape);
+
+        assert_eq!(clipped, 0);
+        assert_eq!(dst[0], -10000, "{shape:?}");
+        assert!(dst[99] > 9700, "{shape:?} ends at {}", dst[99]);
+        assert!(dst.windows(2).all(|pair| pair[0] <= pair[1]), "{shape:?}");
+    }
+}
+
+#[test]
+fn smooth_and_linear_weights_sum_to_one() {
+    let a = [12345; 64];
+
+    for shape in [BlendShape::Smooth, BlendShape::Linear] {
+        assert_eq!(fade(&a, &a, shape), (a.to_vec(), 0), "{shape:?}");
+    }
+}
+
+#[test]
+fn equal_power_weights_keep_power() {
+    let full = [20000; 64];
+    let silent = [0; 64];
+    let (rising, _) = fade(&full, &silent, BlendShape::EqualPower);
+    let (falling, _) = fade(&silent, &full, BlendShape::EqualPower);
+
+    for (&rise, &fall) in rising.iter().zip(&falling) {
+        let power = f64::from(rise).powi(2) + f64::from(fall).powi(2);
+        let expected = 20000f64.powi(2);
+        assert!((power - expected).abs() / expected < 1e-3, "{rise} {fall}");
+    }
+}
+
+#[test]
+fn equal_power_clips_loud_correlated_signals() {
+    let loud = [30000; 64];
+    let (dst, clipped) = fade(&loud, &loud, BlendShape::EqualPower);
+
+    assert!(clipped > 0);
+    assert!(dst.contains(&i16::MAX));
+    assert_eq!(dst[0], 30000);
+}
+
+#[test]
+fn zero_length_window_does_nothing() {
+    for shape in SHAPES {
+        assert_eq!(crossfade(&mut [], &[], &[], shape).unwrap(), 0);
+    }
+}
+
+#[test]
+fn mismatched_lengths_are_rejected() {
+    let mut dst = [0; 4];
+    assert!(crossfade(&mut dst, &[1; 4], &[1; 3], BlendShape::Linear).is_err());
+    assert!(crossfade(&mut dst, &[1; 5], &[1; 4], BlendShape::Linear).is_err());
+    assert_eq!(dst, [0; 4]);
+}
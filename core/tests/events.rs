@@ -0,0 +1,170 @@
+use quadio_core::dsp::BlendShape;
+use quadio_core::{
+    ChangeEvent, LoopChunkFormat, Project, QWaveReader, RawChunk,
+};
+use std::cell::RefCell;
+use std::io::Cursor;
+use std::rc::Rc;
+
+// A second of 16-bit ramp at 11025 Hz, looped from 1000 to 9000, with every
+// event it reports collected from then on
+fn observed_project() -> (Project, Rc<RefCell<Vec<ChangeEvent>>>) {
+    let spec = hound::WavSpec {
+        channels: 1,
+        sample_rate: 11025,
+        bits_per_sample: 16,
+        sample_format: hound::SampleFormat::Int,
+    };
+
+    let mut wave = Cursor::new(vec![]);
+    let mut writer = hound::WavWriter::new(&mut wave, spec).unwrap();
+
+    for i in 0..11025 {
+        writer.write_sample((i % 200) as i16 * 100).unwrap();
+    }
+
+    writer.finalize().unwrap();
+    wave.set_position(0);
+
+    let mut proj =
+        Project::from_reader(QWaveReader::new(wave).unwrap()).unwrap();
+    proj.set_loop(Some(1000..9000));
+
+    let events = Rc::new(RefCell::new(vec![]));
+    let seen = Rc::clone(&events);
+    proj.subscribe(move |event| seen.borrow_mut().push(event.clone()));
+
+    (proj, events)
+}
+
+#[test]
+fn set_loop_reports_only_actual_changes() {
+    let (mut proj, events) = observed_project();
+
+    proj.set_loop(Some(1000..9000));
+    assert_eq!(events.take(), []);
+
+    proj.set_loop(Some(2000..9000));
+    proj.set_loop(None);
+    assert_eq!(
+        events.take(),
+        [ChangeEvent::LoopChanged, ChangeEvent::LoopChanged]
+    );
+
+    // Clamping a loop that already fits changes nothing
+    proj.set_loop(Some(2000..9000));
+    events.take();
+    proj.clamp_loop();
+    assert_eq!(events.take(), []);
+}
+
+#[test]
+fn blend_reports_the_frames_it_rewrote() {
+    let (mut proj, events) = observed_project();
+
+    proj.blend(300).unwrap();
+    assert_eq!(events.take(), [ChangeEvent::SamplesChanged(8700..9000)]);
+
+    proj.blend_entry(300, BlendShape::default()).unwrap();
+    assert_eq!(events.take(), [ChangeEvent::SamplesChanged(1000..1300)]);
+
+    // Nothing is reported when nothing was rewritten
+    assert!(proj.blend(2000).is_err());
+    assert_eq!(events.take(), []);
+}
+
+#[test]
+fn cue_id_and_label_report_marker_changes() {
+    let (mut proj, events) = observed_project();
+
+    proj.set_loop_cue_id(7);
+    proj.set_loop_label(Some(String::from("sustain")));
+    assert_eq!(
+        events.take(),
+        [ChangeEvent::MarkersChanged, ChangeEvent::MarkersChanged]
+    );
+
+    proj.set_loop_cue_id(7);
+    proj.set_loop_label(Some(String::from("sustain")));
+    assert_eq!(events.take(), []);
+
+    proj.set_loop_label(None);
+    assert_eq!(events.take(), [ChangeEvent::MarkersChanged]);
+}
+
+#[test]
+fn extra_chunks_report_marker_changes() {
+    let (mut proj, events) = observed_project();
+    let chunks = vec![RawChunk {
+        tag: *b"bext",
+        body: vec![0; 8],
+    }];
+
+    proj.set_extra_chunks(chunks.clone());
+    proj.set_extra_chunks(chunks);
+    assert_eq!(events.take(), [ChangeEvent::MarkersChanged]);
+
+    proj.set_extra_chunks(vec![]);
+    assert_eq!(events.take(), [ChangeEvent::MarkersChanged]);
+}
+
+#[test]
+fn retagging_the_rate_reports_a_format_change() {
+    let (mut proj, events) = observed_project();
+
+    proj.set_sample_rate_raw(11025).unwrap();
+    assert_eq!(events.take(), []);
+
+    proj.set_sample_rate_raw(22050).unwrap();
+    assert!(proj.set_sample_rate_raw(0).is_err());
+    assert_eq!(events.take(), [ChangeEvent::FormatChanged]);
+}
+
+#[test]
+fn loop_chunk_format_reports_marker_changes() {
+    let (mut proj, events) = observed_project();
+
+    proj.set_loop_chunk_format(LoopChunkFormat::Cue);
+    assert_eq!(events.take(), []);
+
+    proj.set_loop_chunk_format(LoopChunkFormat::Both);
+    proj.set_loop_chunk_format(LoopChunkFormat::Both);
+    assert_eq!(events.take(), [ChangeEvent::MarkersChanged]);
+}
+
+#[test]
+fn trim_reports_the_format_and_any_loop_change() {
+    let (mut proj, events) = observed_project();
+
+    proj.trim(0..11025).unwrap();
+    assert!(proj.trim(0..20000).is_err());
+    assert_eq!(events.take(), []);
+
+    // The loop sits on the same frames, so only the length changes
+    proj.trim(0..10000).unwrap();
+    assert_eq!(events.take(), [ChangeEvent::FormatChanged]);
+
+    proj.trim(500..10000).unwrap();
+    assert_eq!(proj.sample_loop(), Some(500..8500));
+    assert_eq!(
+        events.take(),
+        [ChangeEvent::FormatChanged, ChangeEvent::LoopChanged]
+    );
+}
+
+#[test]
+fn adding_and_removing_markers_report_marker_changes() {
+    let (mut proj, events) = observed_project();
+
+    assert!(proj.add_marker(20000, "past the end").is_err());
+    assert_eq!(events.take(), []);
+
+    let id = proj.add_marker(500, "hit").unwrap();
+    assert_eq!(events.take(), [ChangeEvent::MarkersChanged]);
+
+    assert!(proj.remove_marker(id + 1).is_none());
+    assert_eq!(events.take(), []);
+
+    assert!(proj.remove_marker(id).is_some());
+    assert_eq!(events.take(), [ChangeEvent::MarkersChanged]);
+}
@@ -0,0 +1,231 @@
+#![cfg(feature = "ffi")]
+
+use quadio_core::ffi::*;
+use std::ffi::{CStr, CString};
+use std::io::Cursor;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::ptr;
+
+const RATE: u32 = 22050;
+
+// Half a second of 16-bit, 441 Hz sine
+fn sine_wave() -> Vec<u8> {
+    let spec = hound::WavSpec {
+        channels: 1,
+        sample_rate: RATE,
+        bits_per_sample: 16,
+        sample_format: hound::SampleFormat::Int,
+    };
+
+    let mut wave = Cursor::new(vec![]);
+    let mut writer = hound::WavWriter::new(&mut wave, spec).unwrap();
+
+    for i in 0..RATE / 2 {
+        let phase = f64::from(i) * 441.0 / f64::from(RATE);
+        let sample = (phase * std::f64::consts::TAU).sin() * 16000.0;
+        writer.write_sample(sample as i16).unwrap();
+    }
+
+    writer.finalize().unwrap();
+    wave.into_inner()
+}
+
+fn temp_path(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!(
+        "quadio-ffi-{}-{}",
+        std::process::id(),
+        name
+    ))
+}
+
+fn last_error() -> String {
+    let message = quadio_last_error_message();
+    assert!(!message.is_null());
+    unsafe { CStr::from_ptr(message) }
+        .to_string_lossy()
+        .into_owned()
+}
+
+#[test]
+fn projects_round_trip_through_the_c_abi() {
+    let wave = sine_wave();
+    let outpath = temp_path("round-trip.wav");
+    let c_outpath = CString::new(outpath.to_str().unwrap()).unwrap();
+    let mut project = ptr::null_mut();
+    let mut metadata = QuadioMetadata::default();
+
+    unsafe {
+        assert_eq!(
+            quadio_project_from_memory(wave.as_ptr(), wave.len(), &mut project),
+            QUADIO_OK
+        );
+        assert_eq!(quadio_project_metadata(project, &mut metadata), QUADIO_OK);
+        assert_eq!(
+            metadata,
+            QuadioMetadata {
+                sample_rate: RATE,
+                channels: 1,
+                sample_count: RATE / 2,
+                ..QuadioMetadata::default()
+            }
+        );
+
+        assert_eq!(quadio_project_blend(project, 100), QUADIO_ERR_LOOP);
+        assert_eq!(last_error(), "No loop to blend");
+
+        assert_eq!(
+            quadio_project_set_loop(project, 1000, RATE),
+            QUADIO_ERR_LOOP
+        );
+        assert_eq!(quadio_project_metadata(project, &mut metadata), QUADIO_OK);
+        assert_eq!(metadata.has_loop, 0);
+
+        assert_eq!(quadio_project_set_loop(project, 1000, 6000), QUADIO_OK);
+        assert_eq!(quadio_project_blend(project, 2000), QUADIO_ERR_INVALID);
+        assert!(last_error().contains("pad the start by at least 1000"));
+        assert_eq!(quadio_project_blend(project, 500), QUADIO_OK);
+        assert_eq!(
+            quadio_project_write(project, c_outpath.as_ptr()),
+            QUADIO_OK
+        );
+        quadio_project_free(project);
+
+        let mut written = ptr::null_mut();
+        assert_eq!(
+            quadio_project_open(c_outpath.as_ptr(), &mut written),
+            QUADIO_OK
+        );
+        assert_eq!(quadio_project_metadata(written, &mut metadata), QUADIO_OK);
+        assert_eq!(
+            (metadata.has_loop, metadata.loop_start, metadata.loop_end),
+            (1, 1000, 6000)
+        );
+
+        assert_eq!(quadio_project_clear_loop(written), QUADIO_OK);
+        assert_eq!(quadio_project_metadata(written, &mut metadata), QUADIO_OK);
+        assert_eq!(metadata.has_loop, 0);
+        quadio_project_free(written);
+    }
+
+    std::fs::remove_file(outpath).unwrap();
+}
+
+#[test]
+fn bad_arguments_fail_with_a_message() {
+    let missing = CString::new("no such file.wav").unwrap();
+    let not_a_wave = b"RIFF, but not really";
+    let mut project = ptr::null_mut();
+
+    unsafe {
+        assert_eq!(
+            quadio_project_open(missing.as_ptr(), &mut project),
+            QUADIO_ERR_IO
+        );
+        assert!(last_error().contains("no such file.wav"));
+        assert_eq!(
+            quadio_project_from_memory(
+                not_a_wave.as_ptr(),
+                not_a_wave.len(),
+                &mut project
+            ),
+            QUADIO_ERR_DECODE
+        );
+        assert!(project.is_null());
+
+        assert_eq!(
+            quadio_project_open(ptr::null(), &mut project),
+            QUADIO_ERR_ARGUMENT
+        );
+        assert_eq!(last_error(), "Path is null");
+        assert_eq!(
+            quadio_project_metadata(ptr::null(), ptr::null_mut()),
+            QUADIO_ERR_ARGUMENT
+        );
+        assert_eq!(
+            quadio_project_blend(ptr::null_mut(), 1),
+            QUADIO_ERR_ARGUMENT
+        );
+        quadio_project_free(ptr::null_mut());
+    }
+}
+
+// Build the static library as the README does, in a target directory of its
+// own so as not to wait on the one running this test.  Returns it with the
+// native libraries it needs linked alongside, as rustc prints them.
+fn static_library() -> (PathBuf, Vec<String>) {
+    let target_dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join("ffi");
+    let output = Command::new(env!("CARGO"))
+        .current_dir(env!("CARGO_MANIFEST_DIR"))
+        .args(["rustc", "--lib", "--features", "ffi"])
+        .args(["--crate-type", "staticlib", "--target-dir"])
+        .arg(&target_dir)
+        .args(["--", "--print", "native-static-libs"])
+        .output()
+        .unwrap();
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(output.status.success(), "{}", stderr);
+
+    let native_libs = stderr
+        .lines()
+        .find_map(|line| line.split_once("native-static-libs: "))
+        .map(|(_, libs)| libs.split_whitespace().map(String::from).collect())
+        .expect("rustc printed no native libraries");
+
+    (target_dir.join("debug/libquadio_core.a"), native_libs)
+}
+
+// Build the C smoke test against the static library, where there is a C
+// compiler and the native libraries to do so
+#[cfg(unix)]
+#[test]
+fn c_smoke_test_links_and_runs() {
+    let manifest_dir = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let smoke = temp_path("smoke");
+    let inpath = temp_path("smoke-in.wav");
+    let outpath = temp_path("smoke-out.wav");
+
+    if Command::new("cc").arg("--version").output().is_err() {
+        eprintln!("No C compiler; skipping the C smoke test");
+        return;
+    }
+
+    let (library, native_libs) = static_library();
+
+    let output = Command::new("cc")
+        .arg("-Wall")
+        .arg("-I")
+        .arg(manifest_dir.join("include"))
+        .arg(manifest_dir.join("tests/ffi/smoke.c"))
+        .arg(&library)
+        .args(&native_libs)
+        .arg("-o")
+        .arg(&smoke)
+        .output()
+        .unwrap();
+    let stderr = String::from_utf8_lossy(&output.stderr);
+
+    // Missing development libraries, such as ALSA's, are the system's lack
+    if !output.status.success() && stderr.contains("cannot find -l") {
+        eprintln!("Native libraries missing; skipping the C smoke test");
+        eprintln!("{}", stderr);
+        return;
+    }
+
+    assert!(output.status.success(), "{}", stderr);
+    std::fs::write(&inpath, sine_wave()).unwrap();
+
+    let output = Command::new(&smoke).arg(&inpath).arg(&outpath).output();
+
+    for path in [&smoke, &inpath, &outpath] {
+        let _ = std::fs::remove_file(path);
+    }
+
+    let output = output.unwrap();
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "ok\n");
+}
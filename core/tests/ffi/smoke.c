@@ -0,0 +1,68 @@
+/*
+ * Loops, blends and writes the WAV named by the first argument to the path
+ * given by the second through the C ABI, exiting non-zero on any surprise.
+ */
+
+#include <stdio.h>
+#include <string.h>
+
+#include "quadio.h"
+
+#define CHECK(call) \
+    do { \
+        int code = (call); \
+        if (code != QUADIO_OK) { \
+            fprintf(stderr, "%s failed (%d): %s\n", #call, code, \
+                    quadio_last_error_message()); \
+            return 1; \
+        } \
+    } while (0)
+
+int main(int argc, char **argv)
+{
+    QuadioProject *project = NULL;
+    QuadioProject *written = NULL;
+    QuadioMetadata metadata;
+
+    if (argc != 3) {
+        fprintf(stderr, "Usage: smoke <input> <output>\n");
+        return 2;
+    }
+
+    if (quadio_project_open("no such file.wav", &project) != QUADIO_ERR_IO
+            || quadio_last_error_message() == NULL) {
+        fprintf(stderr, "Opening a missing file didn't fail as expected\n");
+        return 1;
+    }
+
+    CHECK(quadio_project_open(argv[1], &project));
+    CHECK(quadio_project_metadata(project, &metadata));
+
+    if (metadata.has_loop || metadata.sample_count < 4000) {
+        fprintf(stderr, "Unexpected input metadata\n");
+        return 1;
+    }
+
+    if (quadio_project_set_loop(project, 3000, 2000) != QUADIO_ERR_LOOP) {
+        fprintf(stderr, "A backwards loop wasn't refused\n");
+        return 1;
+    }
+
+    CHECK(quadio_project_set_loop(project, 1000, 4000));
+    CHECK(quadio_project_blend(project, 500));
+    CHECK(quadio_project_write(project, argv[2]));
+    quadio_project_free(project);
+
+    CHECK(quadio_project_open(argv[2], &written));
+    CHECK(quadio_project_metadata(written, &metadata));
+    quadio_project_free(written);
+
+    if (!metadata.has_loop || metadata.loop_start != 1000
+            || metadata.loop_end != 4000) {
+        fprintf(stderr, "Loop wasn't written\n");
+        return 1;
+    }
+
+    printf("ok\n");
+    return 0;
+}
@@ -0,0 +1,152 @@
+use quadio_core::{Error, Project, QWaveReader, SampleFmt};
+use std::io::{Cursor, ErrorKind};
+use std::ops::Range;
+
+const SAMPLES: [f32; 9] = [0.0, 0.5, -0.5, 1.0, -1.0, 1.5, -7.0, 1e-6, -0.25];
+
+fn wave<S: hound::Sample + Copy>(
+    bits_per_sample: u16,
+    sample_format: hound::SampleFormat,
+    samples: &[S],
+) -> Cursor<Vec<u8>> {
+    let spec = hound::WavSpec {
+        channels: 1,
+        sample_rate: 48000,
+        bits_per_sample,
+        sample_format,
+    };
+
+    let mut wave = Cursor::new(vec![]);
+    let mut writer = hound::WavWriter::new(&mut wave, spec).unwrap();
+
+    for &s in samples {
+        writer.write_sample(s).unwrap();
+    }
+
+    writer.finalize().unwrap();
+    wave.set_position(0);
+    wave
+}
+
+fn float_wave(samples: &[f32]) -> Cursor<Vec<u8>> {
+    wave(32, hound::SampleFormat::Float, samples)
+}
+
+#[test]
+fn float_samples_are_scaled_and_clamped() {
+    let mut reader = QWaveReader::new(float_wave(&SAMPLES)).unwrap();
+    let metadata = reader.metadata();
+
+    assert_eq!(metadata.bits_per_sample, 32);
+    assert!(metadata.float_samples);
+    assert_eq!(
+        reader.collect_samples().unwrap(),
+        [0, 16384, -16384, 32767, -32768, 32767, -32768, 0, -8192]
+    );
+}
+
+#[test]
+fn float_projects_encode_as_16_bits() {
+    let reader = QWaveReader::new(float_wave(&SAMPLES)).unwrap();
+    let proj = Project::from_reader(reader).unwrap();
+
+    assert_eq!(proj.render_format(), SampleFmt::Signed16);
+    assert_eq!(proj.sample_count(), SAMPLES.len() as u32);
+}
+
+#[test]
+fn wide_integer_samples_keep_their_top_16_bits() {
+    // Full scale both ways, quiet material a few 16-bit steps from zero, and
+    // values just under a step, which truncate toward negative infinity
+    let samples_24 = [
+        8_388_607,
+        -8_388_608,
+        256,
+        -256,
+        3 * 256 + 17,
+        -(3 * 256),
+        255,
+        -1,
+    ];
+    let expected = [32767, -32768, 1, -1, 3, -3, 0, -1];
+
+    for (bits, scale) in [(24, 1), (32, 256)] {
+        let samples = samples_24.map(|s: i32| s * scale + (scale - 1));
+        let mut reader =
+            QWaveReader::new(wave(bits, hound::SampleFormat::Int, &samples))
+                .unwrap();
+
+        assert_eq!(reader.metadata().bits_per_sample, bits);
+        assert!(!reader.metadata().float_samples);
+        assert_eq!(reader.collect_samples().unwrap(), expected, "{}-bit", bits);
+
+        let proj = Project::from_reader(reader).unwrap();
+        assert_eq!(proj.render_format(), SampleFmt::Signed16);
+    }
+}
+
+#[test]
+fn converted_samples_are_written_as_16_bits() {
+    let waves = [
+        ("float", float_wave(&SAMPLES)),
+        (
+            "24-bit",
+            wave(24, hound::SampleFormat::Int, &[0x7fff00, -256, 0, 512]),
+        ),
+        (
+            "32-bit",
+            wave(32, hound::SampleFormat::Int, &[i32::MAX, -65536]),
+        ),
+    ];
+
+    for (name, wave) in waves {
+        let proj =
+            Project::from_reader(QWaveReader::new(wave).unwrap()).unwrap();
+        let path = std::env::temp_dir().join(format!(
+            "quadio-formats-{}-{}.wav",
+            std::process::id(),
+            name
+        ));
+        proj.write_to(&path).unwrap();
+
+        let reader = hound::WavReader::open(&path).unwrap();
+        let spec = reader.spec();
+        let written = reader
+            .into_samples::<i16>()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(spec.bits_per_sample, 16, "{}", name);
+        assert_eq!(spec.sample_format, hound::SampleFormat::Int, "{}", name);
+        assert_eq!(written, proj.samples(), "{}", name);
+    }
+}
+
+#[test]
+fn errors_say_what_went_wrong() {
+    let reader =
+        QWaveReader::new(wave(16, hound::SampleFormat::Int, &[0i16; 100]))
+            .unwrap();
+    let mut proj = Project::from_reader(reader).unwrap();
+
+    let e = proj.blend(10).unwrap_err();
+    assert!(matches!(e, Error::NoLoop("blend")), "{e}");
+    assert_eq!(e.to_string(), "No loop to blend");
+
+    proj.set_loop(Some(Range { start: 50, end: 40 }));
+    assert!(matches!(proj.validate(), Err(Error::InvalidLoop(_))));
+
+    // MPEG layer 3 in a WAV
+    let mut mp3 = wave(16, hound::SampleFormat::Int, &[0i16; 100]).into_inner();
+    mp3[20..22].copy_from_slice(&0x55u16.to_le_bytes());
+    let e = QWaveReader::new(Cursor::new(mp3)).err().unwrap();
+    assert!(matches!(e, Error::Unsupported(_)), "{e:?}");
+}
+
+#[test]
+fn open_errors_name_the_file() {
+    let e = Project::open("/nonexistent/loop.wav").err().unwrap();
+    assert!(matches!(&e, Error::Io(io) if io.kind() == ErrorKind::NotFound));
+    assert!(e.to_string().starts_with("/nonexistent/loop.wav: "), "{e}");
+}
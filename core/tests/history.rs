@@ -0,0 +1,89 @@
+use quadio_core::PlayheadHistory;
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+
+const WRITES: usize = 200_000;
+const HISTORY_LEN: usize = 64;
+
+#[test]
+fn reads_racing_writes_stay_in_order() {
+    let history = Arc::new(PlayheadHistory::new());
+    let done = Arc::new(AtomicBool::new(false));
+
+    let writer = {
+        let history = Arc::clone(&history);
+        let done = Arc::clone(&done);
+
+        thread::spawn(move || {
+            for offset in 0..WRITES {
+                history.record(offset);
+            }
+
+            done.store(true, Ordering::Release);
+        })
+    };
+
+    let reader = {
+        let history = Arc::clone(&history);
+        let done = Arc::clone(&done);
+
+        thread::spawn(move || {
+            let mut seen = BTreeMap::new();
+
+            while !done.load(Ordering::Acquire) {
+                let entries = history.read();
+                assert!(entries.len() <= HISTORY_LEN);
+
+                for pair in entries.windows(2) {
+                    assert!(pair[0].0 <= pair[1].0, "{pair:?}");
+                    assert!(pair[0].1 < pair[1].1, "{pair:?}");
+                }
+
+                // Every offset is only ever read with the time it was
+                // written at, never a torn mix of two writes
+                for (timestamp, offset) in entries {
+                    assert!(offset < WRITES);
+                    assert_eq!(
+                        *seen.entry(offset).or_insert(timestamp),
+                        timestamp
+                    );
+                }
+            }
+
+            seen
+        })
+    };
+
+    writer.join().unwrap();
+    let seen = reader.join().unwrap();
+
+    // Times only move forward from one write to the next
+    let timestamps = seen.values().collect::<Vec<_>>();
+    assert!(timestamps.windows(2).all(|pair| pair[0] <= pair[1]));
+
+    // Once the writer is done, the last writes are all there
+    let offsets = history
+        .read()
+        .into_iter()
+        .map(|(_, offset)| offset)
+        .collect::<Vec<_>>();
+    assert_eq!(offsets, (WRITES - HISTORY_LEN..WRITES).collect::<Vec<_>>());
+}
+
+#[test]
+fn partial_history_holds_every_write() {
+    let history = PlayheadHistory::new();
+
+    for offset in [10, 20, 30] {
+        history.record(offset);
+    }
+
+    let offsets = history
+        .read()
+        .into_iter()
+        .map(|(_, offset)| offset)
+        .collect::<Vec<_>>();
+    assert_eq!(offsets, [10, 20, 30]);
+}
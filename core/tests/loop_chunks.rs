@@ -0,0 +1,399 @@
+use quadio_core::{
+    Error, LoopChunkFormat, LoopSource, Project, QWaveReader, SampleFmt,
+};
+use std::io::Cursor;
+
+// 16-bit mono WAV of `frames` silent samples followed by the given chunks
+fn wave(frames: u32, chunks: &[(&[u8; 4], Vec<u8>)]) -> Cursor<Vec<u8>> {
+    let mut wave = b"RIFF\0\0\0\0WAVEfmt ".to_vec();
+    wave.extend(16u32.to_le_bytes());
+    wave.extend(1u16.to_le_bytes());
+    wave.extend(1u16.to_le_bytes());
+    wave.extend(22050u32.to_le_bytes());
+    wave.extend(44100u32.to_le_bytes());
+    wave.extend(2u16.to_le_bytes());
+    wave.extend(16u16.to_le_bytes());
+    wave.extend(b"data");
+    wave.extend((frames * 2).to_le_bytes());
+    wave.extend(vec![0; frames as usize * 2]);
+
+    for (tag, body) in chunks {
+        wave.extend(*tag);
+        wave.extend(u32::try_from(body.len()).unwrap().to_le_bytes());
+        wave.extend(body);
+    }
+
+    let riff_size = u32::try_from(wave.len() - 8).unwrap();
+    wave[4..8].copy_from_slice(&riff_size.to_le_bytes());
+    Cursor::new(wave)
+}
+
+// smpl chunk with one forward loop over frames `start` through `last`
+fn smpl(start: u32, last: u32) -> (&'static [u8; 4], Vec<u8>) {
+    let mut body = vec![0u8; 28];
+    body.extend(1u32.to_le_bytes());
+    body.extend(0u32.to_le_bytes());
+    body.extend(7u32.to_le_bytes());
+    body.extend(0u32.to_le_bytes());
+    body.extend(start.to_le_bytes());
+    body.extend(last.to_le_bytes());
+    body.extend(0u32.to_le_bytes());
+    body.extend(0u32.to_le_bytes());
+    (b"smpl", body)
+}
+
+// cue chunk of (ID, offset) points
+fn cues(points: &[(u32, u32)]) -> (&'static [u8; 4], Vec<u8>) {
+    let mut body = u32::try_from(points.len()).unwrap().to_le_bytes().to_vec();
+
+    for &(id, offset) in points {
+        body.extend(id.to_le_bytes());
+        body.extend(0u32.to_le_bytes());
+        body.extend(b"data");
+        body.extend(0u32.to_le_bytes());
+        body.extend(0u32.to_le_bytes());
+        body.extend(offset.to_le_bytes());
+    }
+
+    (b"cue ", body)
+}
+
+fn cue(offset: u32) -> (&'static [u8; 4], Vec<u8>) {
+    cues(&[(3, offset)])
+}
+
+// LIST chunk of the given form holding labeled texts of (cue ID, length)
+fn labeled_texts(
+    form: &[u8; 4],
+    texts: &[(u32, u32)],
+) -> (&'static [u8; 4], Vec<u8>) {
+    let mut body = form.to_vec();
+
+    for &(cue_id, length) in texts {
+        body.extend(b"ltxt");
+        body.extend(20u32.to_le_bytes());
+        body.extend(cue_id.to_le_bytes());
+        body.extend(length.to_le_bytes());
+        body.extend(b"rgn ");
+        body.extend([0; 8]);
+    }
+
+    (b"LIST", body)
+}
+
+// adtl LIST chunk holding labels of (cue ID, text)
+fn labels(texts: &[(u32, &str)]) -> (&'static [u8; 4], Vec<u8>) {
+    let mut body = b"adtl".to_vec();
+
+    for &(cue_id, text) in texts {
+        let size = 4 + text.len() + 1;
+        body.extend(b"labl");
+        body.extend(u32::try_from(size).unwrap().to_le_bytes());
+        body.extend(cue_id.to_le_bytes());
+        body.extend(text.as_bytes());
+        body.push(0);
+
+        if size % 2 == 1 {
+            body.push(0);
+        }
+    }
+
+    (b"LIST", body)
+}
+
+fn list(form: &[u8; 4], length: u32) -> (&'static [u8; 4], Vec<u8>) {
+    labeled_texts(form, &[(3, length)])
+}
+
+#[test]
+fn smpl_loop_is_read_without_cue_points() {
+    let reader = QWaveReader::new(wave(1000, &[smpl(100, 499)])).unwrap();
+    let metadata = reader.metadata();
+
+    assert_eq!(metadata.loop_source, Some(LoopSource::Smpl));
+    assert_eq!(metadata.loop_start, Some(100));
+    assert_eq!(metadata.end, Some(500));
+    assert_eq!(metadata.loop_cue_id, None);
+
+    let proj = Project::from_reader(reader).unwrap();
+    assert_eq!(proj.sample_loop(), Some(100..500));
+}
+
+#[test]
+fn cue_points_take_precedence() {
+    let metadata = QWaveReader::new(wave(1000, &[cue(250), smpl(100, 499)]))
+        .unwrap()
+        .metadata();
+
+    assert_eq!(metadata.loop_source, Some(LoopSource::Cue));
+    assert_eq!(metadata.loop_start, Some(250));
+    assert_eq!(metadata.end, None);
+}
+
+#[test]
+fn bad_smpl_loops_are_clamped_or_rejected() {
+    let metadata = QWaveReader::new(wave(1000, &[smpl(100, 4999)]))
+        .unwrap()
+        .metadata();
+    assert_eq!(metadata.end, Some(1000));
+    assert_eq!(metadata.loop_end_past_eof, Some(5000));
+
+    let e = QWaveReader::new(wave(1000, &[smpl(500, 99)]))
+        .err()
+        .unwrap();
+    assert!(matches!(e, Error::InvalidLoop(_)), "{e}");
+    assert!(e
+        .to_string()
+        .contains("ends at sample 100, before it starts"));
+
+    // No loops, or too short to hold one
+    let mut empty = smpl(100, 499);
+    empty.1[28] = 0;
+    let mut short = smpl(100, 499);
+    short.1.truncate(40);
+
+    for chunk in [empty, short] {
+        let metadata =
+            QWaveReader::new(wave(1000, &[chunk])).unwrap().metadata();
+        assert_eq!(metadata.loop_source, None);
+        assert_eq!(metadata.loop_start, None);
+    }
+}
+
+#[test]
+fn cue_chunks_are_found_in_any_order() {
+    let orders = [
+        vec![cue(250), list(b"INFO", 100), list(b"adtl", 500)],
+        vec![list(b"adtl", 500), cue(250)],
+        vec![
+            list(b"INFO", 100),
+            smpl(10, 19),
+            list(b"adtl", 500),
+            cue(250),
+        ],
+    ];
+
+    for chunks in orders {
+        let metadata =
+            QWaveReader::new(wave(1000, &chunks)).unwrap().metadata();
+
+        assert_eq!(metadata.loop_source, Some(LoopSource::Cue));
+        assert_eq!(metadata.loop_start, Some(250));
+        assert_eq!(metadata.end, Some(750));
+    }
+
+    // A labeled text in an INFO list isn't taken for the loop length
+    let metadata =
+        QWaveReader::new(wave(1000, &[cue(250), list(b"INFO", 100)]))
+            .unwrap()
+            .metadata();
+    assert_eq!(metadata.loop_start, Some(250));
+    assert_eq!(metadata.end, None);
+}
+
+#[test]
+fn loop_length_is_the_first_cue_points() {
+    // The loop's labeled text after another cue's
+    let chunks = [
+        list(b"INFO", 100),
+        cues(&[(1, 250), (2, 800)]),
+        labeled_texts(b"adtl", &[(2, 100), (1, 500)]),
+    ];
+    let metadata = QWaveReader::new(wave(1000, &chunks)).unwrap().metadata();
+
+    assert_eq!(metadata.loop_cue_id, Some(1));
+    assert_eq!(metadata.loop_start, Some(250));
+    assert_eq!(metadata.end, Some(750));
+
+    // Labeled texts for cues that don't exist are passed over
+    let chunks = [cue(250), labeled_texts(b"adtl", &[(9, 100), (3, 500)])];
+    let metadata = QWaveReader::new(wave(1000, &chunks)).unwrap().metadata();
+    assert_eq!(metadata.end, Some(750));
+}
+
+#[test]
+fn projects_write_to_memory() {
+    let mut proj =
+        Project::from_reader(QWaveReader::new(wave(1000, &[])).unwrap())
+            .unwrap();
+    proj.set_loop(Some(100..600));
+    proj.set_play_end(Some(800));
+
+    let mut written = Cursor::new(vec![]);
+    proj.write(&mut written).unwrap();
+    written.set_position(0);
+
+    let metadata = QWaveReader::new(written).unwrap().metadata();
+    assert_eq!(metadata.sample_count, 1000);
+    assert_eq!((metadata.loop_start, metadata.end), (Some(100), Some(600)));
+    assert_eq!(metadata.play_end, Some(800));
+}
+
+#[test]
+fn project_metadata_matches_the_written_file() {
+    let mut proj =
+        Project::from_reader(QWaveReader::new(wave(1000, &[])).unwrap())
+            .unwrap();
+    proj.set_loop(Some(100..600));
+    proj.set_loop_chunk_format(LoopChunkFormat::Both);
+    assert_eq!(proj.loop_duration_seconds(), Some(500.0 / 22050.0));
+
+    let mut written = Cursor::new(vec![]);
+    proj.write(&mut written).unwrap();
+    written.set_position(0);
+
+    let metadata = QWaveReader::new(written).unwrap().metadata();
+    assert_eq!(proj.metadata().unwrap(), metadata);
+    assert!(metadata.smpl_loop);
+}
+
+#[test]
+fn markers_survive_writing() {
+    let source = wave(
+        1000,
+        &[
+            cues(&[(0, 100), (1, 300), (2, 700)]),
+            labels(&[(1, "verse"), (2, "thunder")]),
+        ],
+    );
+    let reader = QWaveReader::new(source).unwrap();
+    let positions = reader
+        .markers()
+        .iter()
+        .map(|marker| (marker.id, marker.position, &marker.label[..]))
+        .collect::<Vec<_>>();
+    assert_eq!(positions, [(1, 300, "verse"), (2, 700, "thunder")]);
+
+    let mut proj = Project::from_reader(reader).unwrap();
+    assert_eq!(proj.sample_loop(), Some(100..1000));
+
+    // The end marker's cue ID is taken, so the marker holding it moves
+    proj.set_play_end(Some(900));
+    proj.remove_marker(2).unwrap();
+    let id = proj.add_marker(500, "drone").unwrap();
+
+    let mut written = Cursor::new(vec![]);
+    proj.write(&mut written).unwrap();
+    written.set_position(0);
+
+    let reader = QWaveReader::new(written).unwrap();
+    let metadata = reader.metadata();
+    assert_eq!((metadata.loop_start, metadata.end), (Some(100), Some(1000)));
+    assert_eq!(metadata.play_end, Some(900));
+
+    let positions = reader
+        .markers()
+        .iter()
+        .map(|marker| (marker.position, &marker.label[..]))
+        .collect::<Vec<_>>();
+    assert_eq!(positions, [(300, "verse"), (500, "drone")]);
+    assert_eq!(reader.markers()[1].id, id);
+    assert!(reader.markers()[0].id > 1);
+}
+
+#[test]
+fn markers_take_region_lengths_from_labeled_texts() {
+    let chunks = [
+        cues(&[(1, 250), (2, 800), (3, 900)]),
+        labeled_texts(b"adtl", &[(1, 500), (2, 100), (3, 0)]),
+    ];
+    let reader = QWaveReader::new(wave(1000, &chunks)).unwrap();
+    let lengths = reader
+        .markers()
+        .iter()
+        .map(|marker| (marker.id, marker.length))
+        .collect::<Vec<_>>();
+
+    assert_eq!(reader.metadata().loop_cue_id, Some(1));
+    assert_eq!(lengths, [(2, Some(100)), (3, None)]);
+}
+
+#[test]
+fn loops_to_the_end_of_the_file_keep_their_length() {
+    let mut proj =
+        Project::from_reader(QWaveReader::new(wave(1000, &[])).unwrap())
+            .unwrap();
+    proj.set_loop(Some(0..1000));
+
+    let mut written = Cursor::new(vec![]);
+    proj.write(&mut written).unwrap();
+    assert!(written.get_ref().windows(4).any(|tag| tag == b"ltxt"));
+    written.set_position(0);
+
+    let metadata = QWaveReader::new(written).unwrap().metadata();
+    assert_eq!(proj.metadata().unwrap(), metadata);
+    assert_eq!((metadata.loop_start, metadata.end), (Some(0), Some(1000)));
+    assert_eq!(metadata.play_end, None);
+}
+
+#[test]
+fn writes_are_byte_identical_in_a_fixed_chunk_order() {
+    // Extra chunks ahead of the loop's, and an odd-sized one last
+    let chunks = [
+        list(b"INFO", 100),
+        (b"bext", vec![7; 8]),
+        cue(250),
+        list(b"adtl", 500),
+        (b"iXML", vec![1; 5]),
+    ];
+    let write = |proj: &Project| {
+        let mut written = Cursor::new(vec![]);
+        proj.write(&mut written).unwrap();
+        written.into_inner()
+    };
+    let open = |wave: Cursor<Vec<u8>>| {
+        Project::from_reader(QWaveReader::new(wave).unwrap()).unwrap()
+    };
+
+    let proj = open(wave(1000, &chunks));
+    let written = write(&proj);
+    assert_eq!(write(&open(wave(1000, &chunks))), written);
+
+    let reader = QWaveReader::new(Cursor::new(written.clone())).unwrap();
+    let tags = reader
+        .chunks()
+        .iter()
+        .map(|chunk| &chunk.tag)
+        .collect::<Vec<_>>();
+    assert_eq!(
+        tags,
+        [b"fmt ", b"data", b"cue ", b"LIST", b"LIST", b"bext", b"iXML"]
+    );
+
+    // The odd-sized chunk is padded, and the RIFF size counts the pad byte
+    assert_eq!(written.len() % 2, 0);
+    assert_eq!(&written[written.len() - 6..], [1, 1, 1, 1, 1, 0]);
+    assert_eq!(
+        u32::from_le_bytes(written[4..8].try_into().unwrap()) as usize,
+        written.len() - 8
+    );
+
+    // Reading the output back and writing it again changes nothing
+    let reread = open(Cursor::new(written.clone()));
+    assert!(proj.diff(&reread).is_identical());
+    assert_eq!(write(&reread), written);
+}
+
+#[test]
+fn dither_is_the_same_for_the_same_seed() {
+    // A ramp, so that samples fall between 8-bit steps
+    let mut wave = wave(1000, &[cue(250)]);
+
+    for (i, sample) in wave.get_mut()[44..2044].chunks_mut(2).enumerate() {
+        sample.copy_from_slice(&(i as i16 * 30).to_le_bytes());
+    }
+
+    let write = |seed| {
+        let mut proj =
+            Project::from_reader(QWaveReader::new(wave.clone()).unwrap())
+                .unwrap();
+        proj.set_render_format(SampleFmt::Unsigned8, seed);
+
+        let mut written = Cursor::new(vec![]);
+        proj.write(&mut written).unwrap();
+        written.into_inner()
+    };
+
+    assert_eq!(write(1), write(1));
+    assert_ne!(write(1), write(2));
+}
@@ -0,0 +1,103 @@
+use quadio_core::{columns_of, render_overlay, render_rgb, Overlay, Theme};
+
+const OVERLAY_COLOR: [u8; 3] = [0x20, 0xc0, 0xf0];
+
+fn samples() -> Vec<i16> {
+    (0..3000)
+        .map(|i| ((i as f64 / 37.0).sin() * 12000.0) as i16)
+        .collect()
+}
+
+// Columns whose pixels differ between two renders
+fn changed_columns(a: &[u8], b: &[u8], width: usize) -> Vec<usize> {
+    let mut columns = a
+        .chunks(3)
+        .zip(b.chunks(3))
+        .enumerate()
+        .filter(|(_, (a, b))| a != b)
+        .map(|(pixel, _)| pixel % width)
+        .collect::<Vec<_>>();
+    columns.sort();
+    columns.dedup();
+    columns
+}
+
+#[test]
+fn overlay_columns_match_the_render() {
+    let samples = samples();
+    let theme = Theme::default();
+    let flat = vec![0i16; 250];
+    let overlay = Overlay {
+        start: 1500,
+        samples: &flat,
+        color: OVERLAY_COLOR,
+        highlight: Some([0x50, 0x50, 0x80]),
+    };
+
+    // Whole file, zoomed in around the overlay, and more columns than samples
+    for (range, width) in [(0..3000, 100), (1400..1900, 250), (1740..1760, 60)]
+    {
+        let height = 40;
+        let base =
+            render_rgb(&samples, range.clone(), width, height, &[], &theme);
+        let mut drawn = base.clone();
+        render_overlay(
+            &mut drawn,
+            &samples,
+            range.clone(),
+            width,
+            height,
+            &overlay,
+            &theme,
+        );
+
+        let expected = columns_of(range.clone(), width, overlay.range());
+        assert!(!expected.is_empty(), "{range:?} at {width}");
+        assert_eq!(
+            changed_columns(&base, &drawn, width),
+            expected.collect::<Vec<_>>(),
+            "{range:?} at {width}",
+        );
+    }
+}
+
+#[test]
+fn overlay_of_original_samples_only_recolors() {
+    let samples = samples();
+    let theme = Theme::default();
+    let overlay = Overlay {
+        start: 700,
+        samples: &samples[700..1300],
+        color: theme.peak,
+        highlight: None,
+    };
+
+    // Redrawing the same extents in the peak color leaves the RMS band to
+    // be painted over, and nothing outside the overlay's columns
+    for width in [30, 300, 1000] {
+        let base = render_rgb(&samples, 0..3000, width, 20, &[], &theme);
+        let mut drawn = base.clone();
+        render_overlay(
+            &mut drawn,
+            &samples,
+            0..3000,
+            width,
+            20,
+            &overlay,
+            &theme,
+        );
+
+        let columns = columns_of(0..3000, width, overlay.range());
+        for col in changed_columns(&base, &drawn, width) {
+            assert!(columns.contains(&col), "column {col} at {width}");
+        }
+    }
+}
+
+#[test]
+fn columns_of_ranges_outside_the_render_are_empty() {
+    assert!(columns_of(100..200, 10, 300..400).is_empty());
+    assert_eq!(columns_of(0..100, 10, 0..100), 0..10);
+    assert_eq!(columns_of(0..100, 10, 15..16), 1..2);
+    assert_eq!(columns_of(0..100, 10, 19..21), 1..3);
+}
@@ -0,0 +1,184 @@
+use quadio_core::{
+    check_loop, Metadata, Project, QWaveReader, SampleFmt, TargetProfile,
+    Transform,
+};
+use std::io::Cursor;
+use std::path::Path;
+
+// A second of 16-bit, 441 Hz sine at `rate`, looped over its middle half
+fn sine_project(rate: u32) -> Project {
+    let spec = hound::WavSpec {
+        channels: 1,
+        sample_rate: rate,
+        bits_per_sample: 16,
+        sample_format: hound::SampleFormat::Int,
+    };
+
+    let mut wave = Cursor::new(vec![]);
+    let mut writer = hound::WavWriter::new(&mut wave, spec).unwrap();
+
+    for i in 0..rate {
+        let phase = f64::from(i) * 441.0 / f64::from(rate);
+        let sample = (phase * std::f64::consts::TAU).sin() * 16000.0;
+        writer.write_sample(sample as i16).unwrap();
+    }
+
+    writer.finalize().unwrap();
+    wave.set_position(0);
+
+    let mut project =
+        Project::from_reader(QWaveReader::new(wave).unwrap()).unwrap();
+    project.set_loop(Some(rate / 4..rate * 3 / 4));
+    project.set_play_end(Some(rate * 7 / 8));
+    project
+}
+
+fn profile(name: &str) -> &'static TargetProfile {
+    TargetProfile::find(name).unwrap()
+}
+
+#[test]
+fn rates_and_formats_come_from_the_profile() {
+    let quake1 = profile("quake1");
+    let quakespasm = profile("quakespasm");
+
+    assert_eq!(quake1.package_rate(11025), 11025);
+    assert_eq!(quake1.package_rate(16000), 22050);
+    assert_eq!(quake1.package_rate(44100), 22050);
+    assert_eq!(quakespasm.package_rate(8000), 11025);
+    assert_eq!(quakespasm.package_rate(32000), 44100);
+    assert_eq!(quakespasm.package_rate(96000), 48000);
+
+    assert_eq!(
+        quake1.package_format(SampleFmt::Signed16),
+        SampleFmt::Unsigned8
+    );
+    assert_eq!(
+        quakespasm.package_format(SampleFmt::Signed16),
+        SampleFmt::Signed16
+    );
+    assert!(TargetProfile::find("doom").is_none());
+}
+
+#[test]
+fn quake1_resamples_narrows_and_truncates() {
+    let mut proj = sine_project(44100);
+    let transforms = profile("quake1").apply(&mut proj, 7).unwrap();
+
+    assert_eq!(
+        transforms,
+        [
+            Transform::Resampled {
+                from: 44100,
+                to: 22050
+            },
+            Transform::Reformatted {
+                from: SampleFmt::Signed16,
+                to: SampleFmt::Unsigned8
+            },
+            Transform::Truncated {
+                from: 22050,
+                to: 16538
+            },
+        ]
+    );
+    assert_eq!(proj.sample_rate(), 22050);
+    assert_eq!(proj.render_format(), SampleFmt::Unsigned8);
+    assert_eq!(proj.sample_loop(), Some(5513..16538));
+    assert_eq!(proj.sample_count(), 16538);
+    assert_eq!(proj.play_end(), None);
+    assert!(proj.samples().iter().all(|&s| s & 0xff == 0));
+}
+
+#[test]
+fn quakespasm_leaves_playable_files_alone() {
+    let mut proj = sine_project(22050);
+    let samples = proj.samples().to_vec();
+
+    assert_eq!(profile("quakespasm").apply(&mut proj, 7), Ok(vec![]));
+    assert_eq!(proj.samples(), samples);
+    assert_eq!(proj.play_end(), Some(19293));
+}
+
+#[test]
+fn dither_is_seeded() {
+    let narrowed = |seed| {
+        let mut proj = sine_project(11025);
+        proj.set_render_format(SampleFmt::Unsigned8, seed);
+        proj.samples().to_vec()
+    };
+
+    assert_eq!(narrowed(1), narrowed(1));
+    assert_ne!(narrowed(1), narrowed(2));
+
+    // Dither stays within a step of the nearest 8-bit value
+    let original = sine_project(11025);
+    for (&dithered, &s) in narrowed(1).iter().zip(original.samples()) {
+        assert!((i32::from(dithered) - i32::from(s)).abs() < 512);
+    }
+}
+
+#[test]
+fn paths_mirror_the_game_directories() {
+    let quake1 = profile("quake1");
+    let path = |input: &str| quake1.package_path(Path::new(input)).unwrap();
+
+    assert_eq!(
+        path("assets/sound/ambience/wind.wav"),
+        Path::new("sound/ambience/wind.wav")
+    );
+    assert_eq!(
+        path("mod/music/track02.wav"),
+        Path::new("music/track02.wav")
+    );
+    assert_eq!(path("/tmp/loose.wav"), Path::new("sound/loose.wav"));
+    assert_eq!(path("sound"), Path::new("sound/sound"));
+}
+
+#[test]
+fn compatibility_checks_fail_one_by_one() {
+    let quake1 = profile("quake1");
+    let quakespasm = profile("quakespasm");
+    let fte = profile("fte");
+    let metadata = sine_project(22050).metadata().unwrap();
+    let failed = |profile: &TargetProfile, metadata: &Metadata| {
+        profile
+            .check_compatibility(metadata)
+            .into_iter()
+            .filter(|(_, result)| result.is_err())
+            .map(|(name, _)| name)
+            .collect::<Vec<_>>()
+    };
+
+    assert_eq!(failed(quake1, &metadata), ["sample format"]);
+    assert_eq!(failed(quakespasm, &metadata), Vec::<&str>::new());
+
+    let mut stereo = metadata;
+    stereo.channels = 2;
+    assert!(quakespasm.check_channels(&stereo).is_err());
+    assert!(fte.check_channels(&stereo).is_ok());
+
+    let mut float = metadata;
+    float.float_samples = true;
+    float.bits_per_sample = 32;
+    assert_eq!(
+        fte.check_format(&float),
+        Err(String::from(
+            "32-bit float samples; fte reads 16-bit or 8-bit"
+        ))
+    );
+
+    let mut fast = metadata;
+    fast.sample_rate = 44100;
+    assert!(quake1.check_sample_rate(&fast).is_err());
+    assert!(quakespasm.check_sample_rate(&fast).is_ok());
+
+    let mut unlooped = metadata;
+    unlooped.loop_start = None;
+    assert_eq!(check_loop(&unlooped), Err(String::from("No loop")));
+
+    let mut huge = metadata;
+    huge.data_bytes = quakespasm.max_data_bytes + 1;
+    assert_eq!(failed(quakespasm, &huge), ["size"]);
+    assert!(fte.check_size(&huge).is_ok());
+}
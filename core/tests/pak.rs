@@ -0,0 +1,114 @@
+use quadio_core::{Error, PakReader, PakWriter};
+use std::io::Cursor;
+
+fn archive(files: &[(&str, &[u8])]) -> Vec<u8> {
+    let mut writer = PakWriter::new();
+
+    for (name, bytes) in files {
+        writer.replace(name, bytes.to_vec()).unwrap();
+    }
+
+    let mut bytes = vec![];
+    writer.write(&mut bytes).unwrap();
+    bytes
+}
+
+fn decode_error(bytes: Vec<u8>) -> String {
+    match PakReader::new(Cursor::new(bytes)) {
+        Err(Error::PakDecode(message)) => message,
+        Err(e) => panic!("unexpected error {}", e),
+        Ok(_) => panic!("malformed archive was read"),
+    }
+}
+
+#[test]
+fn entries_survive_writing() {
+    let bytes = archive(&[("sound/a.wav", b"first"), ("maps/b.bsp", b"")]);
+    let mut reader = PakReader::new(Cursor::new(bytes)).unwrap();
+
+    let names = reader
+        .entries()
+        .iter()
+        .map(|entry| (&entry.name[..], entry.offset, entry.length))
+        .collect::<Vec<_>>();
+    assert_eq!(names, [("sound/a.wav", 12, 5), ("maps/b.bsp", 17, 0)]);
+    assert_eq!(reader.read_entry("sound/a.wav").unwrap(), b"first");
+    assert_eq!(reader.read_entry("maps/b.bsp").unwrap(), b"");
+    assert!(reader.read_entry("sound/A.wav").is_err());
+}
+
+#[test]
+fn replacing_an_entry_keeps_the_others() {
+    let bytes = archive(&[("a", b"one"), ("b", b"two"), ("c", b"three")]);
+    let mut reader = PakReader::new(Cursor::new(bytes)).unwrap();
+    let mut writer = PakWriter::from_reader(&mut reader).unwrap();
+    writer.replace("b", b"replaced".to_vec()).unwrap();
+    writer.replace("d", b"added".to_vec()).unwrap();
+
+    let mut bytes = vec![];
+    writer.write(&mut bytes).unwrap();
+    let mut reader = PakReader::new(Cursor::new(bytes)).unwrap();
+
+    for (name, expected) in [
+        ("a", "one"),
+        ("b", "replaced"),
+        ("c", "three"),
+        ("d", "added"),
+    ] {
+        assert_eq!(reader.read_entry(name).unwrap(), expected.as_bytes());
+    }
+
+    assert!(writer.replace(&"x".repeat(56), vec![]).is_err());
+    assert!(writer.replace("", vec![]).is_err());
+}
+
+#[test]
+fn entries_of_the_same_name_keep_their_own_bytes() {
+    // Renaming the second entry in the directory to the first's name
+    let mut bytes = archive(&[("a", b"first"), ("b", b"second")]);
+    let second_name = bytes.len() - 64;
+    bytes[second_name] = b'a';
+
+    let mut reader = PakReader::new(Cursor::new(bytes)).unwrap();
+    let writer = PakWriter::from_reader(&mut reader).unwrap();
+
+    let mut bytes = vec![];
+    writer.write(&mut bytes).unwrap();
+    let mut reader = PakReader::new(Cursor::new(&bytes)).unwrap();
+    let entries = reader.entries().to_vec();
+
+    assert_eq!(entries.len(), 2);
+    assert_eq!(entries[1].name, "a");
+    let second = entries[1].offset as usize..;
+    assert_eq!(&bytes[second][..entries[1].length as usize], b"second");
+    assert_eq!(reader.read_entry("a").unwrap(), b"first");
+}
+
+#[test]
+fn malformed_directories_are_rejected() {
+    let good = archive(&[("sound/a.wav", b"samples")]);
+    let dir_at = good.len() - 64;
+
+    assert!(decode_error(b"PACK".to_vec()).contains("header"));
+    assert!(decode_error(b"WAD2\0\0\0\0\0\0\0\0".to_vec()).contains("pak"));
+
+    let mut bytes = good.clone();
+    bytes[8..12].copy_from_slice(&63u32.to_le_bytes());
+    assert!(decode_error(bytes).contains("whole number"));
+
+    let mut bytes = good.clone();
+    bytes[4..8].copy_from_slice(&1000u32.to_le_bytes());
+    assert!(decode_error(bytes).contains("past the end"));
+
+    let mut bytes = good.clone();
+    bytes[dir_at + 56..dir_at + 60].copy_from_slice(&80u32.to_le_bytes());
+    assert!(decode_error(bytes).contains("sound/a.wav at 80..87"));
+
+    let mut bytes = good.clone();
+    bytes[dir_at + 60..dir_at + 64].copy_from_slice(&(-1i32).to_le_bytes());
+    assert!(decode_error(bytes).contains("negative"));
+
+    let mut bytes = good;
+    bytes[dir_at..dir_at + 56].fill(b'x');
+    assert!(decode_error(bytes).contains("not terminated"));
+}
@@ -0,0 +1,260 @@
+use quadio_core::{render_plan, render_plan_with, PlaySegment, RenderOptions};
+
+// Each sample holds its own index, so rendered output names its source
+fn ramp(len: usize) -> Vec<f32> {
+    (0..len).map(|i| i as f32).collect()
+}
+
+fn segment(range: std::ops::Range<usize>, repeats: u32) -> PlaySegment {
+    PlaySegment { range, repeats }
+}
+
+fn at_speed(speed: f64, buffer_frames: usize) -> RenderOptions {
+    RenderOptions {
+        buffer_frames,
+        speed,
+        ..RenderOptions::default()
+    }
+}
+
+// Expected output of a plan, built naively
+fn expected(plan: &[PlaySegment], frames: usize) -> Vec<f32> {
+    let mut out = vec![];
+
+    'plan: for segment in plan {
+        let mut pass = 0;
+
+        while segment.is_infinite() || pass < segment.repeats {
+            for i in segment.range.clone() {
+                if out.len() == frames {
+                    break 'plan;
+                }
+
+                out.push(i as f32);
+            }
+
+            pass += 1;
+        }
+    }
+
+    out.resize(frames, 0.0);
+    out
+}
+
+#[test]
+fn segment_boundaries_land_exactly() {
+    let samples = ramp(1000);
+    let plan = [
+        segment(0..300, 1),
+        segment(300..420, 2),
+        segment(600..900, 0),
+    ];
+
+    for buffer_frames in [1, 7, 64, 120, 4096] {
+        let rendered =
+            render_plan(&samples, &plan, 5000, buffer_frames).unwrap();
+        assert_eq!(rendered, expected(&plan, 5000), "{buffer_frames}");
+    }
+}
+
+#[test]
+fn finite_plans_end_in_silence() {
+    let samples = ramp(1000);
+    let plan = [segment(900..1000, 3), segment(10..20, 1)];
+
+    let rendered = render_plan(&samples, &plan, 600, 33).unwrap();
+    assert_eq!(rendered, expected(&plan, 600));
+    assert!(rendered[310..].iter().all(|&s| s == 0.0));
+}
+
+#[test]
+fn segments_after_an_infinite_one_are_unreachable() {
+    let samples = ramp(100);
+    let plan = [segment(50..60, 0), segment(0..10, 1)];
+
+    let rendered = render_plan(&samples, &plan, 1000, 17).unwrap();
+    assert!(rendered.iter().all(|&s| (50.0..60.0).contains(&s)));
+}
+
+#[test]
+fn bad_plans_are_rejected() {
+    let samples = ramp(100);
+
+    assert!(render_plan(&samples, &[], 10, 10).is_err());
+    assert!(render_plan(&samples, &[segment(10..10, 0)], 10, 10).is_err());
+    assert!(render_plan(&samples, &[segment(10..101, 1)], 10, 10).is_err());
+}
+
+#[test]
+fn speed_steps_fractionally_across_the_wrap() {
+    let samples = ramp(1000);
+    let plan = [segment(600..610, 0)];
+
+    let half =
+        render_plan_with(&samples, &plan, 40, &at_speed(0.5, 7)).unwrap();
+    let first_pass = (0..19).map(|i| 600.0 + i as f32 / 2.0);
+    assert!(half[..19].iter().copied().eq(first_pass));
+
+    // Halfway from the loop end back to its start, then its start again
+    assert_eq!(half[19], 604.5);
+    assert_eq!(half[20], 600.0);
+
+    let double =
+        render_plan_with(&samples, &plan, 10, &at_speed(2.0, 3)).unwrap();
+    assert_eq!(
+        double,
+        [
+            600.0, 602.0, 604.0, 606.0, 608.0, 600.0, 602.0, 604.0, 606.0,
+            608.0
+        ]
+    );
+
+    assert!(render_plan_with(&samples, &plan, 10, &at_speed(0.0, 10)).is_err());
+    assert!(
+        render_plan_with(&samples, &plan, 10, &at_speed(100.0, 10)).is_err()
+    );
+}
+
+// Intro up to the loop end, then the loop forever, as looped play builds it
+fn looped(loop_range: std::ops::Range<usize>) -> [PlaySegment; 2] {
+    [segment(0..loop_range.end, 1), segment(loop_range, 0)]
+}
+
+#[test]
+fn loops_wrap_across_buffer_boundaries() {
+    let samples = ramp(100);
+    let plan = looped(40..90);
+
+    // Buffers of 64 end 26 samples into the second pass
+    let rendered = render_plan(&samples, &plan, 200, 64).unwrap();
+    assert_eq!(rendered, expected(&plan, 200));
+    assert_eq!(rendered[89..92], [89.0, 40.0, 41.0]);
+}
+
+#[test]
+fn short_loops_wrap_many_times_per_buffer() {
+    let samples = ramp(100);
+    let plan = looped(50..53);
+
+    let rendered = render_plan(&samples, &plan, 1024, 512).unwrap();
+    assert_eq!(rendered, expected(&plan, 1024));
+    assert!(rendered[50..].chunks(3).all(|pass| pass[0] == 50.0));
+}
+
+#[test]
+fn buffers_ending_exactly_at_the_loop_end_wrap_in_the_next() {
+    let samples = ramp(100);
+    let plan = looped(20..60);
+
+    // The first buffer leaves the playhead at the loop end itself
+    let rendered = render_plan(&samples, &plan, 120, 60).unwrap();
+    assert_eq!(rendered, expected(&plan, 120));
+    assert_eq!(rendered[59..61], [59.0, 20.0]);
+}
+
+#[test]
+fn frames_are_copied_to_every_channel() {
+    let samples = ramp(100);
+    let plan = looped(10..30);
+    let options = RenderOptions {
+        buffer_frames: 7,
+        channels: 2,
+        ..RenderOptions::default()
+    };
+
+    let rendered = render_plan_with(&samples, &plan, 80, &options).unwrap();
+    let mono = expected(&plan, 80);
+    assert_eq!(rendered.len(), 160);
+
+    for (frame, &sample) in rendered.chunks(2).zip(&mono) {
+        assert_eq!(frame, [sample, sample]);
+    }
+
+    let options = RenderOptions {
+        channels: 0,
+        ..options
+    };
+    assert!(render_plan_with(&samples, &plan, 80, &options).is_err());
+}
+
+#[test]
+fn volume_scales_the_output() {
+    let samples = ramp(100);
+    let plan = [segment(0..100, 1)];
+    let options = RenderOptions {
+        volume: 0.5,
+        ..at_speed(1.0, 16)
+    };
+
+    let rendered = render_plan_with(&samples, &plan, 100, &options).unwrap();
+    let halved = expected(&plan, 100)
+        .iter()
+        .map(|s| s * 0.5)
+        .collect::<Vec<_>>();
+    assert_eq!(rendered, halved);
+
+    for volume in [-0.1, 2.5, f32::NAN] {
+        let options = RenderOptions {
+            volume,
+            ..RenderOptions::default()
+        };
+        assert!(render_plan_with(&samples, &plan, 10, &options).is_err());
+    }
+}
+
+// Options starting the playhead at `start` rather than the first segment
+fn starting_at(start: usize, buffer_frames: usize) -> RenderOptions {
+    RenderOptions {
+        start: Some(start),
+        ..at_speed(1.0, buffer_frames)
+    }
+}
+
+#[test]
+fn playheads_at_or_past_the_loop_end_wrap_to_its_start() {
+    let samples = ramp(100);
+    let plan = [segment(40..90, 0)];
+
+    for start in [90, 95, 1000] {
+        let rendered =
+            render_plan_with(&samples, &plan, 300, &starting_at(start, 64))
+                .unwrap();
+        assert_eq!(rendered, expected(&plan, 300));
+    }
+
+    // Past the intro, playback goes straight on to the loop
+    let plan = looped(40..90);
+    let rendered =
+        render_plan_with(&samples, &plan, 300, &starting_at(90, 64)).unwrap();
+    assert_eq!(rendered, expected(&plan[1..], 300));
+
+    let options = RenderOptions {
+        start: Some(95),
+        ..at_speed(0.5, 7)
+    };
+    let rendered = render_plan_with(&samples, &plan, 4, &options).unwrap();
+    assert_eq!(rendered, [40.0, 40.5, 41.0, 41.5]);
+}
+
+#[test]
+fn loops_shorter_than_a_buffer_wrap_from_past_their_end() {
+    let samples = ramp(100);
+    let plan = [segment(50..53, 0)];
+
+    let rendered =
+        render_plan_with(&samples, &plan, 1024, &starting_at(99, 512)).unwrap();
+    assert_eq!(rendered, expected(&plan, 1024));
+}
+
+#[test]
+fn finite_plans_started_past_their_end_are_silent() {
+    let samples = ramp(100);
+    let plan = [segment(0..50, 1)];
+
+    for start in [50, 70] {
+        let rendered =
+            render_plan_with(&samples, &plan, 100, &starting_at(start, 16))
+                .unwrap();
+        assert_eq!(rendered, vec![0.0; 100]);
+    }
+}
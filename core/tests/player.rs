@@ -0,0 +1,168 @@
+use quadio_core::dsp::ResampleQuality;
+use quadio_core::{
+    OutputLevel, PlaybackBounds, Player, PlayerConfig, PlayerStateTag,
+    TransportState,
+};
+
+const LEN: usize = 4410;
+
+fn config(sample_rate: u32, loop_start: Option<usize>) -> PlayerConfig {
+    PlayerConfig {
+        samples: (0..LEN).map(|i| (i as f32 * 0.05).sin() * 0.5).collect(),
+        sample_rate,
+        loop_start,
+        end: loop_start.map(|_| 3000),
+        play_end: None,
+        bounds: PlaybackBounds::ToEndMarker,
+        level: OutputLevel::default(),
+        device: None,
+        quality: ResampleQuality::default(),
+    }
+}
+
+#[test]
+fn getters_report_input_rate_values_when_resampled() {
+    for (input_rate, playback_rate) in [(22050, 48000), (48000, 22050)] {
+        let player = Player::with_playback_rate(
+            &config(input_rate, Some(1000)),
+            playback_rate,
+        )
+        .unwrap();
+
+        assert_eq!(player.input_rate(), input_rate);
+        assert_eq!(player.playback_rate(), playback_rate);
+        assert_eq!(player.loop_bounds_input(), Some(1000..3000));
+        assert_eq!(player.loop_start(), Some(1000));
+        assert_eq!(player.end(), 3000);
+        assert_eq!(player.duration_input(), LEN);
+        assert_eq!(player.playhead(), 0);
+        assert_eq!(player.state(), PlayerStateTag::Stopped);
+        assert!(!player.is_looping());
+
+        // Playback stops at the end, which only lands on an input-rate
+        // sample to within one when scaled there and back
+        let remaining = player.samples_remaining();
+        assert!(remaining.abs_diff(3000) <= 1, "{remaining} remaining");
+    }
+}
+
+#[test]
+fn getters_without_loop_cover_whole_input() {
+    let player =
+        Player::with_playback_rate(&config(22050, None), 44100).unwrap();
+
+    assert_eq!(player.loop_bounds_input(), None);
+    assert_eq!(player.end(), LEN);
+    assert_eq!(player.duration_input(), LEN);
+}
+
+#[test]
+fn zero_rates_are_rejected() {
+    assert!(Player::with_playback_rate(&config(0, None), 44100).is_err());
+    assert!(Player::with_playback_rate(&config(22050, None), 0).is_err());
+}
+
+#[test]
+fn bounds_choose_where_non_looped_playback_stops() {
+    let mut player =
+        Player::with_playback_rate(&config(22050, Some(1000)), 44100).unwrap();
+
+    let remaining = player.samples_remaining();
+    assert!(remaining.abs_diff(3000) <= 1, "{remaining} remaining");
+
+    // Full runs on past the loop end to about the end of the samples
+    player.set_bounds(PlaybackBounds::Full);
+    assert_eq!(player.bounds(), PlaybackBounds::Full);
+    let remaining = player.samples_remaining();
+    assert!(remaining > 4000, "{remaining} remaining");
+
+    player.set_bounds(PlaybackBounds::ToEndMarker);
+    let remaining = player.samples_remaining();
+    assert!(remaining.abs_diff(3000) <= 1, "{remaining} remaining");
+}
+
+#[test]
+fn bounds_default_to_full() {
+    assert_eq!(PlaybackBounds::default(), PlaybackBounds::Full);
+
+    let config = PlayerConfig {
+        bounds: PlaybackBounds::default(),
+        ..config(22050, Some(1000))
+    };
+    let player = Player::with_playback_rate(&config, 22050).unwrap();
+    assert!(player.samples_remaining() > 4000);
+}
+
+#[test]
+fn transport_round_trips_through_text() {
+    let states = [
+        TransportState::default(),
+        TransportState {
+            position: 4410,
+            looped: true,
+            playing: false,
+        },
+        TransportState {
+            position: usize::MAX,
+            looped: false,
+            playing: true,
+        },
+    ];
+
+    for state in states {
+        assert_eq!(TransportState::parse(&state.to_string()), Ok(state));
+    }
+
+    assert_eq!(
+        TransportState::parse(" looped = true "),
+        Ok(TransportState {
+            looped: true,
+            ..TransportState::default()
+        })
+    );
+
+    for bad in ["position=-1", "looped=yes", "volume=3", "playing"] {
+        assert!(TransportState::parse(bad).is_err(), "{bad}");
+    }
+}
+
+#[test]
+fn restore_carries_position_across_playback_rates() {
+    let config = config(22050, Some(1000));
+    let mut player = Player::with_playback_rate(&config, 44100).unwrap();
+    let paused = TransportState {
+        position: 2000,
+        looped: true,
+        playing: false,
+    };
+
+    player.restore(&paused).unwrap();
+    assert_eq!(player.state(), PlayerStateTag::Paused);
+    assert_eq!(player.snapshot(), paused);
+
+    // Carried as text to a player on a device running at another rate
+    let text = player.snapshot().to_string();
+    let mut player = Player::with_playback_rate(&config, 48000).unwrap();
+    player
+        .restore(&TransportState::parse(&text).unwrap())
+        .unwrap();
+
+    let restored = player.snapshot();
+    assert!(restored.position.abs_diff(2000) <= 1, "{restored:?}");
+    assert!(restored.looped && !restored.playing);
+
+    // Looped positions past the end wrap into the loop; others are refused
+    let past_end = TransportState {
+        position: 3500,
+        ..paused
+    };
+    player.restore(&past_end).unwrap();
+    assert!(player.snapshot().position.abs_diff(1500) <= 1);
+
+    let past_end = TransportState {
+        position: LEN + 10,
+        looped: false,
+        playing: false,
+    };
+    assert!(player.restore(&past_end).is_err());
+}
@@ -0,0 +1,81 @@
+use quadio_core::dsp::{resample, ResampleQuality};
+
+const QUALITIES: [ResampleQuality; 3] = [
+    ResampleQuality::High,
+    ResampleQuality::Medium,
+    ResampleQuality::Fast,
+];
+
+const RATIOS: [(u32, u32); 4] = [
+    (44100, 48000),
+    (22050, 44100),
+    (11025, 48000),
+    (48000, 22050),
+];
+
+#[test]
+fn output_length_is_exact() {
+    for (inrate, outrate) in RATIOS {
+        for len in [1, 2, 999, 4097] {
+            let expected = (len as f64 * f64::from(outrate) / f64::from(inrate))
+                .round() as usize;
+
+            for quality in QUALITIES {
+                let output =
+                    resample(&vec![0.25; len], inrate, outrate, quality)
+                        .unwrap();
+                assert_eq!(output.len(), expected, "{inrate}->{outrate} {len}");
+            }
+
+            // Indices scaled as the player scales loop points stay in bounds
+            let last =
+                (len - 1) as u64 * u64::from(outrate) / u64::from(inrate);
+            assert!((last as usize) < expected.max(1));
+        }
+    }
+}
+
+#[test]
+fn output_is_aligned_with_input() {
+    for (inrate, outrate, quality) in
+        RATIOS.into_iter().flat_map(|(inrate, outrate)| {
+            QUALITIES.map(|quality| (inrate, outrate, quality))
+        })
+    {
+        for position in [0, 10000, 19999] {
+            let mut input = vec![0f32; 20000];
+            input[position] = 1.0;
+
+            let output = resample(&input, inrate, outrate, quality).unwrap();
+            let peak = (0..output.len())
+                .max_by(|&a, &b| output[a].total_cmp(&output[b]))
+                .unwrap();
+            let expected =
+                position as f64 * f64::from(outrate) / f64::from(inrate);
+
+            let tolerance = (f64::from(outrate) / f64::from(inrate)).max(2.0);
+            assert!(
+                (peak as f64 - expected).abs() <= tolerance,
+                "{inrate}->{outrate} {quality:?}: {position} landed at {peak}",
+            );
+        }
+    }
+}
+
+#[test]
+fn fast_resampling_draws_lines_between_samples() {
+    let input = (0..100).map(|i| i as f32 / 100.0).collect::<Vec<_>>();
+    let output = resample(&input, 22050, 44100, ResampleQuality::Fast).unwrap();
+
+    // Every input sample, and halfway to the next between each pair
+    for (i, pair) in output[..198].chunks(2).enumerate() {
+        assert_eq!(pair[0], input[i]);
+        assert!((pair[1] - (input[i] + input[i + 1]) / 2.0).abs() < 1e-6);
+    }
+}
+
+#[test]
+fn zero_rate_fails() {
+    assert!(resample(&[0.0], 0, 44100, ResampleQuality::High).is_err());
+    assert!(resample(&[0.0], 44100, 0, ResampleQuality::High).is_err());
+}
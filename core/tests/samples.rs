@@ -0,0 +1,287 @@
+use quadio_core::{
+    ChangeEvent, Error, PlayerConfig, Project, QWaveReader, Warning,
+};
+use std::cell::RefCell;
+use std::io::Cursor;
+use std::rc::Rc;
+
+// A second of 16-bit ramp at 11025 Hz, looped from 1000 to the end
+fn looped_project() -> Project {
+    let spec = hound::WavSpec {
+        channels: 1,
+        sample_rate: 11025,
+        bits_per_sample: 16,
+        sample_format: hound::SampleFormat::Int,
+    };
+
+    let mut wave = Cursor::new(vec![]);
+    let mut writer = hound::WavWriter::new(&mut wave, spec).unwrap();
+
+    for i in 0..11025 {
+        writer.write_sample((i % 200) as i16 * 100).unwrap();
+    }
+
+    writer.finalize().unwrap();
+    wave.set_position(0);
+
+    let mut proj =
+        Project::from_reader(QWaveReader::new(wave).unwrap()).unwrap();
+    proj.set_loop(Some(1000..11025));
+    proj.set_play_end(Some(9000));
+    proj
+}
+
+#[test]
+fn replaced_samples_keep_the_loop_in_bounds() {
+    let mut proj = looped_project();
+    let events = Rc::new(RefCell::new(vec![]));
+    let seen = Rc::clone(&events);
+    proj.subscribe(move |event| seen.borrow_mut().push(event.clone()));
+
+    let halved = proj.samples().iter().map(|&s| s / 2).collect::<Vec<_>>();
+    proj.replace_samples(halved.clone()).unwrap();
+    assert_eq!(proj.samples(), &halved[..]);
+    assert_eq!(proj.sample_loop(), Some(1000..11025));
+    assert_eq!(events.take(), [ChangeEvent::SamplesChanged(0..11025)]);
+
+    proj.replace_samples(halved[..5000].to_vec()).unwrap();
+    assert_eq!(proj.sample_count(), 5000);
+    assert_eq!(proj.sample_loop(), Some(1000..5000));
+    assert_eq!(proj.play_end(), Some(5000));
+    proj.validate().unwrap();
+
+    for samples in [vec![0; 1000], vec![]] {
+        let e = proj.replace_samples(samples).unwrap_err();
+        assert!(
+            matches!(e, Error::InvalidLoop(_) | Error::InvalidArgument(_)),
+            "{}",
+            e
+        );
+        assert_eq!(proj.sample_count(), 5000);
+    }
+}
+
+#[test]
+fn trimming_moves_the_loop_with_its_frames() {
+    let mut proj = looped_project();
+    let samples = proj.samples().to_vec();
+    proj.trim(500..10000).unwrap();
+    assert_eq!(proj.samples(), &samples[500..10000]);
+    assert_eq!(proj.sample_loop(), Some(500..9500));
+    assert_eq!(proj.play_end(), Some(8500));
+    proj.validate().unwrap();
+
+    proj.trim(2000..3000).unwrap();
+    assert_eq!(proj.sample_loop(), Some(0..1000));
+    assert_eq!(proj.play_end(), Some(1000));
+
+    let mut proj = looped_project();
+    proj.trim(0..800).unwrap();
+    assert_eq!(proj.sample_loop(), None);
+    assert_eq!(proj.play_end(), None);
+
+    for range in [100..100, 0..20000] {
+        assert!(matches!(proj.trim(range), Err(Error::InvalidArgument(_))));
+        assert_eq!(proj.sample_count(), 800);
+    }
+}
+
+#[test]
+fn extracting_keeps_only_the_loop() {
+    let mut proj = looped_project();
+    proj.add_marker(500, "lead").unwrap();
+    let id = proj.add_marker(2000, "gust").unwrap();
+
+    let extracted = proj.extract_loop().unwrap();
+    assert_eq!(extracted.samples(), &proj.samples()[1000..11025]);
+    assert_eq!(extracted.sample_loop(), Some(0..10025));
+    assert_eq!(extracted.play_end(), None);
+    assert_eq!(extracted.sample_rate(), proj.sample_rate());
+    assert_eq!(extracted.markers().len(), 1);
+    assert_eq!(extracted.markers()[0].id, id);
+    assert_eq!(extracted.markers()[0].position, 1000);
+    extracted.validate().unwrap();
+
+    // The original is left as it was
+    assert_eq!(proj.sample_count(), 11025);
+    assert_eq!(proj.sample_loop(), Some(1000..11025));
+
+    let mut unlooped = looped_project();
+    unlooped.set_play_end(None);
+    unlooped.set_loop(None);
+    assert!(matches!(unlooped.extract_loop(), Err(Error::NoLoop(_))));
+}
+
+#[test]
+fn normalizing_reaches_the_target_level() {
+    let db = |level: f64| 20.0 * (level / 32768.0).log10();
+
+    let mut proj = looped_project();
+    let normalization = proj.normalize_peak(-1.0).unwrap();
+    let peak = proj.samples().iter().map(|&s| f64::from(s).abs());
+    assert!((db(peak.fold(0.0, f64::max)) + 1.0).abs() < 0.01);
+    assert_eq!(normalization.clipped, 0);
+
+    let normalization = proj.normalize_rms(-18.0).unwrap();
+    let power = proj.samples().iter().map(|&s| f64::from(s).powi(2));
+    let rms = (power.sum::<f64>() / f64::from(proj.sample_count())).sqrt();
+    assert!((db(rms) + 18.0).abs() < 0.01);
+    assert!(normalization.gain_db < 0.0);
+    assert_eq!(normalization.clipped, 0);
+
+    // The ramp peaks about 5 dB above its RMS level
+    assert!(proj.normalize_rms(-2.0).unwrap().clipped > 0);
+    assert!(proj.normalize_peak(1.0).is_err());
+
+    let mut silence = looped_project();
+    silence.replace_samples(vec![0; 11025]).unwrap();
+    let normalization = silence.normalize_rms(-18.0).unwrap();
+    assert_eq!(normalization.gain_db, 0.0);
+    assert!(silence.samples().iter().all(|&s| s == 0));
+}
+
+#[test]
+fn loudness_measures_sines_in_dbfs() {
+    // 441 Hz has 25 samples a cycle at 11025 Hz, so both halves hold whole
+    // cycles: a half-scale sine, then a quarter-scale one looped
+    let samples = (0..11025)
+        .map(|i| {
+            let amplitude = if i < 5500 { 16384.0 } else { 8192.0 };
+            let phase = f64::from(i) * 441.0 / 11025.0;
+            ((phase * std::f64::consts::TAU).sin() * amplitude).round() as i16
+        })
+        .collect::<Vec<_>>();
+    let close = |dbfs: f64, expected: f64| (dbfs - expected).abs() < 0.05;
+
+    let mut proj = looped_project();
+    proj.replace_samples(samples).unwrap();
+    proj.set_loop(Some(5500..11025));
+
+    let stats = proj.loudness();
+    assert!(close(stats.peak_dbfs, -6.02), "{:?}", stats);
+    assert!(close(stats.max_window_rms_dbfs, -9.03), "{:?}", stats);
+    assert!(close(stats.loop_rms_dbfs.unwrap(), -15.05), "{:?}", stats);
+
+    // Mean power of the two halves, weighted by their lengths
+    let power = (5500.0 * 0.125 + 5525.0 * 0.03125) / 11025.0;
+    assert!(
+        close(stats.rms_dbfs, 10.0 * f64::log10(power)),
+        "{:?}",
+        stats
+    );
+
+    let normalization = proj.normalize_loop_rms(-12.0).unwrap();
+    assert!(close(normalization.gain_db, 3.05), "{:?}", normalization);
+    assert!(close(proj.loudness().loop_rms_dbfs.unwrap(), -12.0));
+    assert!(proj.normalize_loop_rms(f64::INFINITY).is_err());
+
+    proj.set_loop(None);
+    assert_eq!(proj.loudness().loop_rms_dbfs, None);
+
+    proj.replace_samples(vec![0; 100]).unwrap();
+    let stats = proj.loudness();
+    assert_eq!(stats.peak_dbfs, f64::NEG_INFINITY);
+    assert_eq!(stats.rms_dbfs, f64::NEG_INFINITY);
+    assert_eq!(stats.max_window_rms_dbfs, f64::NEG_INFINITY);
+}
+
+#[test]
+fn removing_dc_offset_centers_the_samples() {
+    let mut proj = looped_project();
+    let offsets = proj.remove_dc_offset();
+    assert_eq!(offsets.len(), 1);
+    assert!((9900..=10000).contains(&offsets[0]), "{:?}", offsets);
+
+    let sum = proj.samples().iter().map(|&s| i64::from(s)).sum::<i64>();
+    assert!(sum.abs() <= proj.samples().len() as i64 / 2);
+
+    // Centered already, so the samples come back as they were
+    let centered = proj.samples().to_vec();
+    assert_eq!(proj.remove_dc_offset(), vec![0]);
+    assert_eq!(proj.samples(), &centered[..]);
+}
+
+#[test]
+fn appending_keeps_or_moves_the_loop() {
+    let intro = || {
+        let mut intro = looped_project();
+        intro.set_loop(None);
+        intro
+    };
+    let body = looped_project();
+
+    let mut joined = intro();
+    joined.append(&body).unwrap();
+    assert_eq!(joined.sample_count(), 22050);
+    assert_eq!(&joined.samples()[11025..], body.samples());
+    assert_eq!(joined.sample_loop(), Some(12025..22050));
+    assert_eq!(joined.play_end(), Some(20025));
+
+    let mut joined = intro();
+    joined.append_as_loop(&body).unwrap();
+    assert_eq!(joined.sample_loop(), Some(11025..22050));
+    assert_eq!(joined.play_end(), None);
+
+    let mut other_rate = looped_project();
+    other_rate.set_sample_rate_raw(22050).unwrap();
+    let mut joined = intro();
+    assert!(joined.append(&other_rate).is_err());
+    assert_eq!(joined.sample_count(), 11025);
+}
+
+#[test]
+fn padding_moves_the_loop_only_when_padding_before() {
+    let mut proj = looped_project();
+    proj.pad(0, 500).unwrap();
+    assert_eq!(proj.sample_count(), 11525);
+    assert_eq!(proj.sample_loop(), Some(1000..11025));
+    assert!(proj.samples()[11025..].iter().all(|&s| s == 0));
+
+    proj.pad(250, 0).unwrap();
+    assert_eq!(proj.sample_count(), 11775);
+    assert_eq!(proj.sample_loop(), Some(1250..11275));
+    assert_eq!(proj.play_end(), Some(9250));
+    assert!(proj.samples()[..250].iter().all(|&s| s == 0));
+    assert_eq!(proj.samples()[251], 100);
+
+    assert!(proj.pad(u32::MAX, 0).is_err());
+}
+
+#[test]
+fn lint_warns_of_short_loud_loops() {
+    let mut proj = looped_project();
+    assert_eq!(proj.lint(), vec![Warning::SixteenBit]);
+
+    proj.set_loop(Some(1150..1160));
+    let warnings = proj.lint();
+    assert!(warnings.contains(&Warning::ShortLoop {
+        frames: 10,
+        sample_rate: 11025,
+    }));
+    assert!(warnings.iter().any(|warning| matches!(
+        warning,
+        Warning::LoudLoopPoint { point: "start", .. }
+    )));
+}
+
+#[test]
+fn player_config_follows_unwritten_edits() {
+    let mut proj = looped_project();
+    proj.set_loop(Some(2000..8000));
+
+    let mut samples = proj.samples().to_vec();
+    samples[0] = i16::MIN;
+    samples[1] = i16::MAX;
+    proj.replace_samples(samples).unwrap();
+
+    let config = PlayerConfig::from_project(&proj);
+    assert_eq!(config.sample_rate, 11025);
+    assert_eq!(config.samples.len(), 11025);
+    assert_eq!((config.loop_start, config.end), (Some(2000), Some(8000)));
+    assert_eq!(config.play_end, Some(9000));
+
+    // Full scale negative is exactly -1, and nothing lies beyond it
+    assert_eq!(config.samples[0], -1.0);
+    assert!(config.samples[1] < 1.0);
+    assert!(config.samples.iter().all(|s| (-1.0..1.0).contains(s)));
+}
@@ -0,0 +1,268 @@
+use quadio_core::dsp::BlendShape;
+use quadio_core::{Project, QWaveReader};
+use std::io::Cursor;
+
+const RATE: u32 = 22050;
+
+// A second of 16-bit, 441 Hz sine looped from 1000 to `end`
+fn sine_project(end: u32) -> Project {
+    let spec = hound::WavSpec {
+        channels: 1,
+        sample_rate: RATE,
+        bits_per_sample: 16,
+        sample_format: hound::SampleFormat::Int,
+    };
+
+    let mut wave = Cursor::new(vec![]);
+    let mut writer = hound::WavWriter::new(&mut wave, spec).unwrap();
+
+    for i in 0..RATE {
+        let phase = f64::from(i) * 441.0 / f64::from(RATE);
+        let sample = (phase * std::f64::consts::TAU).sin() * 16000.0;
+        writer.write_sample(sample as i16).unwrap();
+    }
+
+    writer.finalize().unwrap();
+    wave.set_position(0);
+
+    let mut project =
+        Project::from_reader(QWaveReader::new(wave).unwrap()).unwrap();
+    project.set_loop(Some(1000..end));
+    project
+}
+
+#[test]
+fn hard_cuts_score_worse_than_blended_and_seamless_loops() {
+    // 50 samples per cycle, so 6000 is in phase with 1000 and 6025 is half a
+    // cycle out
+    let seamless = sine_project(6000).seam_artifact_score().unwrap();
+    let cut = sine_project(6025).seam_artifact_score().unwrap();
+
+    let mut blended = sine_project(6025);
+    blended.blend(500).unwrap();
+    let blended = blended.seam_artifact_score().unwrap();
+
+    assert!(cut > blended, "cut {cut} dB, blended {blended} dB");
+    assert!(blended > seamless, "blended {blended}, seamless {seamless}");
+    assert!(cut > -20.0, "cut {cut} dB");
+    assert!(seamless < -40.0, "seamless {seamless} dB");
+}
+
+#[test]
+fn seam_analysis_measures_steps_across_the_wrap() {
+    // In phase, the wrap continues the sine as one more sample would
+    let seamless = sine_project(6000).seam_analysis().unwrap();
+    assert!(seamless.amplitude_step.abs() < 2100, "{:?}", seamless);
+    assert!(seamless.slope_step.abs() < 50, "{:?}", seamless);
+    assert!(seamless.slope_step_dbfs < -50.0, "{:?}", seamless);
+    assert!(seamless.spectral_excess_db < -40.0, "{:?}", seamless);
+
+    // A quarter cycle out, the wrap drops from the crest to zero
+    let crest = sine_project(6012).seam_analysis().unwrap();
+    assert!(crest.amplitude_step < -15000, "{:?}", crest);
+    assert!(crest.amplitude_step_dbfs > -7.0, "{:?}", crest);
+    assert!(crest.spectral_excess_db > -20.0, "{:?}", crest);
+
+    // Half a cycle out, the wrap reverses the slope at a zero crossing
+    let cut = sine_project(6025).seam_analysis().unwrap();
+    assert!(cut.slope_step > 3900, "{:?}", cut);
+    assert!(cut.slope_step_dbfs > -20.0, "{:?}", cut);
+    assert_eq!(
+        sine_project(6025).seam_artifact_score().unwrap(),
+        cut.spectral_excess_db,
+    );
+
+    let mut blended = sine_project(6025);
+    blended.blend(500).unwrap();
+    let blended = blended.seam_analysis().unwrap();
+    assert!(
+        blended.slope_step.abs() < cut.slope_step / 4,
+        "{:?}",
+        blended
+    );
+    assert!(
+        blended.spectral_excess < cut.spectral_excess,
+        "{:?}",
+        blended
+    );
+}
+
+#[test]
+fn short_or_missing_loops_are_not_scored() {
+    let mut project = sine_project(1100);
+    assert!(project.seam_artifact_score().is_err());
+    assert!(project.seam_analysis().is_err());
+
+    project.set_loop(None);
+    assert!(project.seam_artifact_score().is_err());
+    assert!(project.seam_analysis().is_err());
+}
+
+#[test]
+fn blend_windows_fit_the_lead_and_the_loop() {
+    let mut project = sine_project(6025);
+    assert_eq!(project.max_blend_window().unwrap(), 1000);
+    assert_eq!(project.clamp_blend_window(2000).unwrap(), 1000);
+    assert_eq!(
+        project.default_blend_window(),
+        project.click_free_blend_window()
+    );
+    assert!(project.blend_impact(1000).is_ok());
+
+    let e = project.blend_impact(1001).unwrap_err().to_string();
+    assert!(e.contains("1001 samples is longer than the 1000 samples of lead"));
+    assert!(e.contains("at most 1000 samples"));
+    assert!(e.contains("pad the start by at least 1 samples"));
+
+    // The loop is as long as the lead, so both bound the window exactly
+    project.set_loop(Some(1000..2000));
+    assert_eq!(project.max_blend_window().unwrap(), 1000);
+    assert!(project.blend_impact(1000).is_ok());
+    assert!(project.blend_impact(1001).is_err());
+
+    project.set_loop(Some(1000..1050));
+    let click_free = project.click_free_blend_window();
+    assert!(click_free > 50);
+    assert_eq!(project.default_blend_window(), 50);
+
+    let e = project.blend_impact(51).unwrap_err().to_string();
+    assert!(e.contains("51 samples is longer than the 50-sample loop"));
+
+    let window = project.blend_default_window().unwrap();
+    assert_eq!(window.window_sz, 50);
+    assert_eq!(window.clamped_from, Some(click_free));
+}
+
+#[test]
+fn loops_from_the_first_sample_leave_nothing_to_blend() {
+    let mut project = sine_project(6025);
+    project.set_loop(Some(0..6025));
+
+    assert!(project.max_blend_window().is_err());
+    assert!(project.clamp_blend_window(100).is_err());
+    assert!(project.blend_default_window().is_err());
+
+    let e = project.blend_impact(100).unwrap_err().to_string();
+    assert!(e.contains("Loop starts at sample 0"));
+    assert!(e.contains("pad the start by at least 100 samples"));
+}
+
+#[test]
+fn blend_preview_matches_blend() {
+    let project = sine_project(6025);
+    let preview = project.blend_preview(500).unwrap();
+    assert_eq!(preview.range, 5525..6025);
+
+    let mut blended = sine_project(6025);
+    blended.blend(500).unwrap();
+    assert_eq!(preview.samples, &blended.samples()[5525..6025]);
+    assert_eq!(project.samples()[..5525], blended.samples()[..5525]);
+    assert_ne!(project.samples()[5525..6025], blended.samples()[5525..6025]);
+}
+
+#[test]
+fn equal_power_blends_keep_the_level() {
+    // 12 samples past a whole number of cycles, so the windows faded together
+    // are nearly a quarter cycle apart and add in power
+    let level_db = |shape| {
+        let mut project = sine_project(6012);
+        project.blend_with_shape(500, shape).unwrap();
+
+        project.samples()[5512..6012]
+            .chunks(50)
+            .map(|cycle| {
+                let power =
+                    cycle.iter().map(|&s| f64::from(s).powi(2)).sum::<f64>()
+                        / cycle.len() as f64;
+                10.0 * (power / (16000f64.powi(2) / 2.0)).log10()
+            })
+            .collect::<Vec<_>>()
+    };
+
+    let equal_power = level_db(BlendShape::EqualPower);
+    assert!(
+        equal_power.iter().all(|db| db.abs() < 0.5),
+        "{equal_power:?}"
+    );
+
+    let smooth = level_db(BlendShape::Smooth);
+    assert!(smooth.iter().any(|&db| db < -2.0), "{smooth:?}");
+}
+
+#[test]
+fn symmetric_blends_meet_at_the_wrap() {
+    let original = sine_project(6025);
+    let mut blended = sine_project(6025);
+    blended.blend_symmetric(500, BlendShape::Smooth).unwrap();
+
+    let (before, after) = (original.samples(), blended.samples());
+    assert_eq!(before[..1000], after[..1000]);
+    assert_eq!(before[1500..5525], after[1500..5525]);
+    assert_eq!(before[6025..], after[6025..]);
+
+    let cut = original.seam_artifact_score().unwrap();
+    let symmetric = blended.seam_artifact_score().unwrap();
+    assert!(
+        cut > symmetric + 10.0,
+        "cut {cut} dB, symmetric {symmetric} dB"
+    );
+
+    let mut project = sine_project(6025);
+    assert!(project.blend_symmetric(2600, BlendShape::Smooth).is_err());
+
+    project.set_loop(Some(1000..RATE));
+    assert!(project.blend_symmetric(500, BlendShape::Smooth).is_err());
+}
+
+#[test]
+fn unrolled_seams_are_as_smooth_as_the_blend() {
+    // Largest second difference around where the first repeat begins
+    let seam_jump = |mut project: Project| {
+        project.unroll(3, true).unwrap();
+        assert_eq!(project.sample_count(), 1000 + 3 * 5025 + (RATE - 6025));
+        assert_eq!(project.sample_loop(), None);
+
+        project.samples()[6020..6030]
+            .windows(3)
+            .map(|w| {
+                (i32::from(w[0]) - 2 * i32::from(w[1]) + i32::from(w[2])).abs()
+            })
+            .max()
+            .unwrap()
+    };
+
+    let mut blended = sine_project(6025);
+    blended.blend(500).unwrap();
+
+    let cut = seam_jump(sine_project(6025));
+    let smooth = seam_jump(blended);
+    assert!(cut > 1000, "cut {cut}");
+    assert!(smooth < 400, "blended {smooth}");
+
+    let mut project = sine_project(6025);
+    assert!(project.unroll(0, false).is_err());
+    assert!(matches!(
+        project.unroll(u32::MAX, false),
+        Err(quadio_core::Error::Unsupported(_))
+    ));
+    assert_eq!(project.sample_count(), RATE);
+}
+
+#[test]
+fn loops_snap_to_rising_zero_crossings() {
+    // Rising crossings every 50 samples from 0; 6025 is on a falling one
+    let mut project = sine_project(6025);
+    project.set_loop(Some(1010..6025));
+    assert_eq!(
+        project.snap_loop_to_zero_crossings(30).unwrap(),
+        (Some(1000), Some(6000))
+    );
+    assert_eq!(project.sample_loop(), Some(1000..6000));
+
+    project.set_loop(Some(1020..6025));
+    assert_eq!(
+        project.snap_loop_to_zero_crossings(5).unwrap(),
+        (None, None)
+    );
+    assert_eq!(project.sample_loop(), Some(1020..6025));
+}
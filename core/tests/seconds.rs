@@ -0,0 +1,71 @@
+use quadio_core::{
+    input_to_playback, playback_to_input, samples_to_seconds,
+    seconds_to_samples,
+};
+
+const RATES: [u32; 5] = [8000, 11025, 22050, 44100, 48000];
+
+#[test]
+fn samples_survive_a_trip_through_seconds() {
+    for rate in RATES {
+        for index in (0..100_000).chain([1 << 24, u32::MAX as usize]) {
+            let seconds = samples_to_seconds(index, rate);
+            assert_eq!(
+                seconds_to_samples(seconds, rate),
+                Some(index),
+                "{rate}"
+            );
+        }
+    }
+}
+
+#[test]
+fn seconds_round_to_the_nearest_sample() {
+    assert_eq!(seconds_to_samples(0.1, 11025), Some(1103));
+    assert_eq!(seconds_to_samples(1.0 / 3.0, 11025), Some(3675));
+    assert_eq!(seconds_to_samples(0.00004, 11025), Some(0));
+    assert_eq!(seconds_to_samples(0.00005, 11025), Some(1));
+    assert_eq!(samples_to_seconds(11025, 11025), 1.0);
+}
+
+#[test]
+fn bad_times_have_no_sample() {
+    for seconds in [-0.5, f64::NAN, f64::INFINITY, f64::MAX] {
+        assert_eq!(seconds_to_samples(seconds, 11025), None, "{seconds}");
+    }
+
+    assert_eq!(seconds_to_samples(-0.00001, 11025), Some(0));
+}
+
+#[test]
+fn loop_points_survive_a_trip_through_the_playback_rate() {
+    for input_rate in RATES {
+        for playback_rate in RATES {
+            for index in (0..20_000).chain([1 << 24]) {
+                let playback =
+                    input_to_playback(index, input_rate, playback_rate)
+                        .unwrap();
+                let input =
+                    playback_to_input(playback, playback_rate, input_rate)
+                        .unwrap();
+
+                // Skipping to a loop start reads it back exactly, unless the
+                // device plays fewer samples than there are to place
+                if playback_rate >= input_rate {
+                    assert_eq!(input, index, "{input_rate} {playback_rate}");
+                } else {
+                    assert!(input <= index, "{input_rate} {playback_rate}");
+                    assert_eq!(
+                        input_to_playback(input, input_rate, playback_rate),
+                        Some(playback),
+                    );
+                }
+            }
+        }
+    }
+
+    // A loop at 1000 in a 22050 Hz file starts 2176.87 samples in at 48 kHz
+    assert_eq!(input_to_playback(1000, 22050, 48000), Some(2176));
+    assert_eq!(playback_to_input(2176, 48000, 22050), Some(1000));
+    assert_eq!(playback_to_input(usize::MAX, 22050, 48000), None);
+}
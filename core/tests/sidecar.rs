@@ -0,0 +1,105 @@
+use quadio_core::{hash, LoopSidecar, Project, QWaveReader};
+use std::io::Cursor;
+
+fn project(sample_count: u32) -> Project {
+    let spec = hound::WavSpec {
+        channels: 1,
+        sample_rate: 11025,
+        bits_per_sample: 16,
+        sample_format: hound::SampleFormat::Int,
+    };
+
+    let mut wave = Cursor::new(vec![]);
+    let mut writer = hound::WavWriter::new(&mut wave, spec).unwrap();
+
+    for i in 0..sample_count {
+        writer.write_sample((i % 200) as i16 * 100).unwrap();
+    }
+
+    writer.finalize().unwrap();
+    wave.set_position(0);
+    Project::from_reader(QWaveReader::new(wave).unwrap()).unwrap()
+}
+
+fn sidecar(label: Option<&str>) -> LoopSidecar {
+    LoopSidecar {
+        data_sha256: hash::Sha256::new().finish(),
+        sample_rate: 11025,
+        sample_loop: 1102..4410,
+        label: label.map(String::from),
+    }
+}
+
+#[test]
+fn toml_round_trips() {
+    for label in [None, Some("loop"), Some("say \"hi\"\\\n\tthere\u{1}é")] {
+        let sidecar = sidecar(label);
+        let toml = sidecar.to_toml();
+
+        assert!(toml.contains("start_seconds = 0.099955"), "{}", toml);
+        assert_eq!(LoopSidecar::parse(&toml), Ok(sidecar));
+    }
+}
+
+#[test]
+fn parses_hand_edited_toml() {
+    let parsed = LoopSidecar::parse(
+        "# edited\n\
+        source_sha256 = 'E3B0C44298FC1C149AFBF4C8996FB92427AE41E4649B934CA495991B7852B855'\n\
+        sample_rate = 11_025 # Hz\n\
+        \n\
+        [ loop ]\n\
+        end = 4410\n\
+        start = 1102\n\
+        start_seconds = 12.5\n",
+    );
+
+    assert_eq!(parsed, Ok(sidecar(None)));
+}
+
+#[test]
+fn bad_toml_is_rejected() {
+    let toml = sidecar(None).to_toml();
+
+    for (bad, message) in [
+        (
+            toml.replace("start = 1102", "start = -1"),
+            "line 6: Bad integer",
+        ),
+        (
+            toml.replace("end = 4410", "ending = 4410"),
+            "\"loop.ending\"",
+        ),
+        (toml.replace("[loop]", "[loop"), "line 5: Unclosed table"),
+        (toml.replace("e3b0", "e3b"), "Bad SHA-256 digest"),
+        (
+            toml.replace("sample_rate = 11025", "sample_rate = \"x\""),
+            "integer",
+        ),
+        (toml.replace("start = 1102\n", ""), "lacks \"loop.start\""),
+    ] {
+        let e = LoopSidecar::parse(&bad).unwrap_err();
+        assert!(e.contains(message), "{:?} lacks {:?}", e, message);
+    }
+}
+
+#[test]
+fn applies_only_to_matching_data_within_the_file() {
+    let sidecar = sidecar(Some("verse"));
+    let mut proj = project(8000);
+
+    assert!(sidecar.verify_source(&sidecar.data_sha256).is_ok());
+    assert!(sidecar.verify_source(&[0; 32]).is_err());
+
+    sidecar.apply(&mut proj).unwrap();
+    assert_eq!(proj.sample_loop(), Some(1102..4410));
+    assert_eq!(proj.loop_label(), Some("verse"));
+    assert_eq!(
+        LoopSidecar::from_project(&proj, sidecar.data_sha256),
+        Ok(sidecar.clone())
+    );
+
+    let mut short = project(4000);
+    assert!(sidecar.apply(&mut short).is_err());
+    assert_eq!(short.sample_loop(), None);
+}
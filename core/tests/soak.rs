@@ -0,0 +1,12 @@
+use quadio_core::soak_loop;
+
+#[test]
+fn retired_callbacks_leave_the_playhead_alone() {
+    let samples = (0..5000).map(|i| i as f32 / 5000.0).collect::<Vec<_>>();
+
+    for seed in 0..8 {
+        let report = soak_loop(&samples, 1200, 4100, 20, seed).unwrap();
+        assert_eq!(report.failure, None, "seed {seed}");
+        assert!(report.restarts > 0, "seed {seed}");
+    }
+}
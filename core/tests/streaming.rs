@@ -0,0 +1,132 @@
+use quadio_core::{
+    bin_samples, bin_samples_streaming, update_bins, Bin, QWaveReader,
+};
+use std::io::Cursor;
+
+// Ten seconds of 8- or 16-bit noisy, clipping sine at 44.1 kHz
+fn medium_wave(bits_per_sample: u16) -> Vec<u8> {
+    let spec = hound::WavSpec {
+        channels: 1,
+        sample_rate: 44100,
+        bits_per_sample,
+        sample_format: hound::SampleFormat::Int,
+    };
+
+    let mut wave = Cursor::new(vec![]);
+    let mut writer = hound::WavWriter::new(&mut wave, spec).unwrap();
+    let mut noise = 1u32;
+
+    for i in 0..441_000 {
+        noise = noise.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+        let sine = (f64::from(i) / 50.0).sin() * 40000.0;
+        let sample = sine + f64::from(noise >> 20) - 2048.0;
+
+        if bits_per_sample == 8 {
+            writer.write_sample((sample / 256.0).clamp(-128.0, 127.0) as i8)
+        } else {
+            writer.write_sample(sample.clamp(-32768.0, 32767.0) as i16)
+        }
+        .unwrap();
+    }
+
+    writer.finalize().unwrap();
+    wave.into_inner()
+}
+
+#[test]
+fn streamed_bins_match_in_memory_bins() {
+    for bits_per_sample in [8, 16] {
+        let wave = medium_wave(bits_per_sample);
+        let samples = QWaveReader::new(Cursor::new(&wave))
+            .unwrap()
+            .collect_samples()
+            .unwrap();
+        let len = samples.len();
+
+        // Whole file, a zoomed range, and more bins than samples; chunk sizes
+        // that do and don't line up with the bins
+        for (range, count) in
+            [(0..len, 1000), (123_456..234_567, 777), (5000..5010, 64)]
+        {
+            for chunk_len in [1, 4096, 65536, len] {
+                if chunk_len == 1 && range.end > 10_000 {
+                    continue;
+                }
+
+                let mut reader = QWaveReader::new(Cursor::new(&wave)).unwrap();
+                let streamed = bin_samples_streaming(
+                    reader.sample_chunks(chunk_len).unwrap(),
+                    range.clone(),
+                    count,
+                )
+                .unwrap();
+
+                assert_eq!(
+                    streamed,
+                    bin_samples(&samples, range.clone(), count),
+                    "{}-bit, {:?} in {} bins, chunks of {}",
+                    bits_per_sample,
+                    range,
+                    count,
+                    chunk_len,
+                );
+            }
+        }
+    }
+}
+
+#[test]
+fn chunks_cover_only_the_data_present() {
+    let mut wave = medium_wave(16);
+
+    // Cut the data chunk short without fixing its declared size
+    wave.truncate(wave.len() - 1000);
+
+    let mut reader = QWaveReader::new(Cursor::new(&wave)).unwrap();
+    let chunks = reader
+        .sample_chunks(4096)
+        .unwrap()
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+
+    assert!(chunks.iter().rev().skip(1).all(|chunk| chunk.len() == 4096));
+    assert_eq!(chunks.iter().map(Vec::len).sum::<usize>(), 441_000 - 500);
+}
+
+#[test]
+fn updated_bins_match_a_rebuild() {
+    let mut samples = (0..20_000)
+        .map(|i| ((f64::from(i) / 37.0).sin() * 12000.0) as i16)
+        .collect::<Vec<_>>();
+    let untouched = Bin {
+        min: 1,
+        max: 1,
+        rms: 1.0,
+        clipped: 1,
+    };
+
+    for (range, count) in
+        [(0..20_000, 2048), (0..20_000, 64), (9000..9200, 500)]
+    {
+        let mut bins = vec![untouched; count];
+        let edit = 9050..9150;
+
+        for s in &mut samples[edit.clone()] {
+            *s /= 2;
+        }
+
+        let columns =
+            update_bins(&mut bins, &samples, range.clone(), edit.clone());
+        let rebuilt = bin_samples(&samples, range.clone(), count);
+
+        assert!(!columns.is_empty(), "{range:?} at {count}");
+        assert_eq!(bins[columns.clone()], rebuilt[columns.clone()]);
+
+        // Bins holding none of the edit are left alone
+        for (col, bin) in bins.iter().enumerate() {
+            if !columns.contains(&col) {
+                assert_eq!(*bin, untouched, "{range:?} at {count}");
+            }
+        }
+    }
+}
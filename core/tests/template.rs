@@ -0,0 +1,90 @@
+use quadio_core::{LoopPlacement, LoopTemplate, Metadata, TimeSpec};
+
+const RATE: u32 = 22050;
+
+#[test]
+fn times_resolve_against_each_file() {
+    let template =
+        LoopTemplate::parse("start=25%,end=LAST,blend=40ms").unwrap();
+
+    for sample_count in [RATE, 3 * RATE, 1001] {
+        let resolved = template.resolve(RATE, sample_count).unwrap();
+        let quarter = (f64::from(sample_count) / 4.0).round() as u32;
+        assert_eq!(resolved.sample_loop, quarter..sample_count);
+        assert_eq!(resolved.blend, Some(882));
+    }
+
+    let template = LoopTemplate::parse("start=0.5s, end=20000").unwrap();
+    let resolved = template.resolve(RATE, RATE).unwrap();
+    assert_eq!(resolved.sample_loop, 11025..20000);
+    assert_eq!(resolved.blend, None);
+}
+
+#[test]
+fn bad_templates_are_rejected() {
+    for spec in ["end=LAST", "start", "start=1,fade=2", "start=x%", ""] {
+        assert!(LoopTemplate::parse(spec).is_err(), "{spec}");
+    }
+
+    let template = LoopTemplate::parse("start=-5%").unwrap();
+    let e = template.resolve(RATE, RATE).unwrap_err();
+    assert!(e.contains("\"-5%\" is negative"), "{e}");
+}
+
+#[test]
+fn reference_loop_is_copied_proportionally() {
+    let reference = Metadata {
+        sample_rate: RATE,
+        sample_count: 40000,
+        loop_start: Some(10000),
+        end: Some(30000),
+        ..Default::default()
+    };
+
+    let template = LoopTemplate::from_reference(&reference).unwrap();
+    assert_eq!(
+        template.placement,
+        LoopPlacement::Proportional {
+            start: 0.25,
+            end: 0.75
+        }
+    );
+
+    let resolved = template.resolve(RATE, 40000).unwrap();
+    assert_eq!(resolved.sample_loop, 10000..30000);
+
+    let resolved = template.resolve(RATE, 1002).unwrap();
+    assert_eq!(resolved.sample_loop, 251..752);
+
+    let unlooped = Metadata {
+        loop_start: None,
+        ..reference
+    };
+    assert!(LoopTemplate::from_reference(&unlooped).is_err());
+}
+
+#[test]
+fn time_specs_round_trip_through_display() {
+    for time in ["1234", "0.5s", "111.1ms", "25%", "LAST", "END-1.5s"] {
+        assert_eq!(TimeSpec::parse(time).unwrap().to_string(), time);
+    }
+}
+
+#[test]
+fn times_count_from_either_end() {
+    let resolve = |time| TimeSpec::parse(time)?.resolve(RATE, 3 * RATE);
+
+    assert_eq!(resolve("END"), Ok(3 * RATE));
+    assert_eq!(resolve("END-0.5s"), Ok(3 * RATE - RATE / 2));
+    assert_eq!(resolve("END-20000"), Ok(3 * RATE - 20000));
+    assert_eq!(resolve("END-10%"), Ok(3 * RATE - 6615));
+    assert_eq!(resolve("START+1s"), Ok(RATE));
+    assert_eq!(resolve("END-0"), Ok(3 * RATE));
+
+    let e = resolve("END-10s").unwrap_err();
+    assert!(e.contains("\"END-10s\" is before the start"), "{e}");
+
+    for time in ["END-LAST", "START+END", "END-END-1s", "END+1s", "END-"] {
+        assert!(TimeSpec::parse(time).is_err(), "{time}");
+    }
+}